@@ -0,0 +1,55 @@
+//! Measures the cost of checking a jwt on each request, with the keys and settings loaded once
+//! into a [`JwtContext`], against reloading them from the configuration and key files on every
+//! call as was done before.
+//!
+//! Run with `cargo bench --bench jwt`.
+
+use actix_web_demo::infra::{
+    configuration::load_configuration,
+    security::jwt::{decode_jwt, JwtContext},
+};
+use chrono::{Duration, Utc};
+use criterion::{criterion_group, criterion_main, Criterion};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+
+/// Sign a token the way `/token` does, without needing a database.
+fn token() -> String {
+    let settings = load_configuration().unwrap().security;
+    let key = settings
+        .jwt_keys
+        .iter()
+        .find(|key| key.private_key.is_some())
+        .unwrap();
+    let pem = std::fs::read(key.private_key.as_ref().unwrap()).unwrap();
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some(key.kid.clone());
+    let claims = serde_json::json!({
+        "sub": 1,
+        "exp": (Utc::now() + Duration::hours(1)).timestamp(),
+        "roles": ["User"],
+        "permissions": ["accounts:read"],
+        "jti": uuid::Uuid::new_v4(),
+    });
+    jsonwebtoken::encode(&header, &claims, &EncodingKey::from_ec_pem(&pem).unwrap()).unwrap()
+}
+
+fn decode(c: &mut Criterion) {
+    let token = token();
+    let mut group = c.benchmark_group("decode_jwt");
+
+    let context = JwtContext::new(load_configuration().unwrap().security).unwrap();
+    group.bench_function("cached", |b| {
+        b.iter(|| decode_jwt(&context, &token).unwrap())
+    });
+    group.bench_function("reloaded", |b| {
+        b.iter(|| {
+            let context = JwtContext::new(load_configuration().unwrap().security).unwrap();
+            decode_jwt(&context, &token).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,3 @@
+CREATE TYPE ACCOUNT_STATUS AS ENUM ('Open', 'Closed');
+
+ALTER TABLE accounts ADD COLUMN status ACCOUNT_STATUS NOT NULL DEFAULT 'Open';
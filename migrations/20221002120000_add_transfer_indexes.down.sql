@@ -0,0 +1,3 @@
+DROP INDEX transfers_created_at_idx;
+DROP INDEX transfers_to_account_idx;
+DROP INDEX transfers_from_account_idx;
@@ -0,0 +1,3 @@
+CREATE INDEX transfers_from_account_idx ON transfers(from_account);
+CREATE INDEX transfers_to_account_idx ON transfers(to_account);
+CREATE INDEX transfers_created_at_idx ON transfers(created_at);
@@ -0,0 +1,11 @@
+CREATE TABLE idempotency_keys (
+    user_id INT NOT NULL REFERENCES users(id),
+    idempotency_key TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    response_code INT,
+    response_content_type TEXT,
+    response_body BYTEA,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at timestamptz NOT NULL,
+    PRIMARY KEY (user_id, idempotency_key)
+);
@@ -0,0 +1,3 @@
+DROP TABLE ledger_entries;
+DROP FUNCTION check_journal_balanced;
+DROP TYPE LEDGER_ENTRY_KIND;
@@ -0,0 +1,39 @@
+CREATE TYPE LEDGER_ENTRY_KIND AS ENUM ('Opening', 'Deposit', 'Withdrawal', 'Transfer');
+
+-- Each movement of money is a journal of entries that sum to zero.
+-- Entries without an account are money entering or leaving the system.
+CREATE TABLE ledger_entries (
+    id SERIAL PRIMARY KEY,
+    journal_id UUID NOT NULL,
+    account_id INT REFERENCES accounts(id),
+    amount BIGINT NOT NULL,
+    kind LEDGER_ENTRY_KIND NOT NULL,
+    transfer_id INT REFERENCES transfers(id),
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX ledger_entries_journal_id_idx ON ledger_entries(journal_id);
+CREATE INDEX ledger_entries_account_id_idx ON ledger_entries(account_id);
+
+CREATE FUNCTION check_journal_balanced() RETURNS TRIGGER AS $$
+BEGIN
+    IF (SELECT SUM(amount) FROM ledger_entries WHERE journal_id = NEW.journal_id) <> 0 THEN
+        RAISE EXCEPTION 'journal % is not balanced', NEW.journal_id
+            USING ERRCODE = 'check_violation';
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE CONSTRAINT TRIGGER ledger_entries_balanced
+    AFTER INSERT ON ledger_entries
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION check_journal_balanced();
+
+-- Open the ledger with the existing balances
+INSERT INTO ledger_entries (journal_id, account_id, amount, kind)
+SELECT journal_id, account_id, amount, 'Opening'
+FROM (
+    SELECT md5('opening' || id)::UUID AS journal_id, id AS account_id, balance AS amount FROM accounts WHERE balance <> 0
+    UNION ALL
+    SELECT md5('opening' || id)::UUID, NULL, -balance FROM accounts WHERE balance <> 0
+) opening;
@@ -0,0 +1,16 @@
+CREATE OR REPLACE FUNCTION check_journal_balanced() RETURNS TRIGGER AS $$
+BEGIN
+    IF (SELECT SUM(amount) FROM ledger_entries WHERE journal_id = NEW.journal_id) <> 0 THEN
+        RAISE EXCEPTION 'journal % is not balanced', NEW.journal_id
+            USING ERRCODE = 'check_violation';
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE ledger_entries DROP COLUMN currency;
+ALTER TABLE transfers DROP COLUMN exchange_rate;
+ALTER TABLE transfers DROP COLUMN to_amount;
+DROP TABLE fx_rates;
+ALTER TABLE accounts DROP COLUMN currency;
+DROP TABLE currencies;
@@ -0,0 +1,47 @@
+CREATE TABLE currencies (
+    code TEXT PRIMARY KEY CHECK (code ~ '^[A-Z]{3}$'),
+    minor_units SMALLINT NOT NULL CHECK (minor_units BETWEEN 0 AND 4)
+);
+
+INSERT INTO currencies (code, minor_units) VALUES
+    ('NOK', 2), ('SEK', 2), ('DKK', 2), ('EUR', 2), ('USD', 2),
+    ('GBP', 2), ('CHF', 2), ('JPY', 0), ('KWD', 3);
+
+ALTER TABLE accounts ADD COLUMN currency TEXT NOT NULL DEFAULT 'NOK' REFERENCES currencies(code);
+
+-- One major unit of the base currency is worth `rate` major units of the quote currency
+CREATE TABLE fx_rates (
+    base_currency TEXT NOT NULL REFERENCES currencies(code),
+    quote_currency TEXT NOT NULL REFERENCES currencies(code),
+    rate NUMERIC(20, 10) NOT NULL CHECK (rate > 0),
+    updated_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_by INT REFERENCES users(id),
+    PRIMARY KEY (base_currency, quote_currency),
+    CHECK (base_currency <> quote_currency)
+);
+
+-- The amount received in the destination currency, and the rate used to convert it
+ALTER TABLE transfers ADD COLUMN to_amount BIGINT;
+UPDATE transfers SET to_amount = amount;
+ALTER TABLE transfers ALTER COLUMN to_amount SET NOT NULL;
+ALTER TABLE transfers ADD COLUMN exchange_rate NUMERIC(20, 10);
+
+ALTER TABLE ledger_entries ADD COLUMN currency TEXT REFERENCES currencies(code);
+UPDATE ledger_entries SET currency = 'NOK';
+ALTER TABLE ledger_entries ALTER COLUMN currency SET NOT NULL;
+
+-- Journals must balance in each currency separately
+CREATE OR REPLACE FUNCTION check_journal_balanced() RETURNS TRIGGER AS $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM ledger_entries
+        WHERE journal_id = NEW.journal_id
+        GROUP BY currency
+        HAVING SUM(amount) <> 0
+    ) THEN
+        RAISE EXCEPTION 'journal % is not balanced', NEW.journal_id
+            USING ERRCODE = 'check_violation';
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,5 @@
+DROP TABLE scheduled_transfer_runs;
+DROP TABLE scheduled_transfers;
+DROP TYPE SCHEDULED_TRANSFER_RUN_STATUS;
+DROP TYPE SCHEDULED_TRANSFER_STATUS;
+DROP TYPE TRANSFER_RECURRENCE;
@@ -0,0 +1,35 @@
+CREATE TYPE TRANSFER_RECURRENCE AS ENUM ('Daily', 'Weekly', 'Monthly');
+CREATE TYPE SCHEDULED_TRANSFER_STATUS AS ENUM ('Active', 'Completed', 'Failed', 'Cancelled');
+CREATE TYPE SCHEDULED_TRANSFER_RUN_STATUS AS ENUM ('Succeeded', 'Failed');
+
+-- A transfer to execute once at `start_at`, or repeatedly until `end_at`
+CREATE TABLE scheduled_transfers (
+    id SERIAL PRIMARY KEY,
+    owner_id INT NOT NULL REFERENCES users(id),
+    from_account INT NOT NULL REFERENCES accounts(id),
+    to_account INT NOT NULL REFERENCES accounts(id),
+    amount BIGINT NOT NULL CHECK (amount > 0),
+    recurrence TRANSFER_RECURRENCE,
+    start_at timestamptz NOT NULL,
+    end_at timestamptz CHECK (end_at >= start_at),
+    next_run_at timestamptz NOT NULL,
+    -- The number of occurrences that have been run or skipped
+    occurrences INT NOT NULL DEFAULT 0,
+    status SCHEDULED_TRANSFER_STATUS NOT NULL DEFAULT 'Active',
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX scheduled_transfers_owner_id_idx ON scheduled_transfers(owner_id);
+CREATE INDEX scheduled_transfers_due_idx ON scheduled_transfers(next_run_at) WHERE status = 'Active';
+
+-- The outcome of each attempt to execute a scheduled transfer
+CREATE TABLE scheduled_transfer_runs (
+    id SERIAL PRIMARY KEY,
+    scheduled_transfer_id INT NOT NULL REFERENCES scheduled_transfers(id),
+    scheduled_for timestamptz NOT NULL,
+    status SCHEDULED_TRANSFER_RUN_STATUS NOT NULL,
+    transfer_id INT REFERENCES transfers(id),
+    error TEXT,
+    attempts INT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX scheduled_transfer_runs_scheduled_transfer_id_idx ON scheduled_transfer_runs(scheduled_transfer_id);
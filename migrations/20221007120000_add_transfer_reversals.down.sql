@@ -0,0 +1,2 @@
+ALTER TABLE transfers DROP COLUMN reversed_by;
+ALTER TABLE transfers DROP COLUMN reversal_of;
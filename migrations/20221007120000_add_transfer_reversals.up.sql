@@ -0,0 +1,4 @@
+-- A reversal is a compensating transfer linked to the transfer it undoes.
+-- The unique constraints make sure a transfer is reversed at most once.
+ALTER TABLE transfers ADD COLUMN reversal_of INT UNIQUE REFERENCES transfers(id);
+ALTER TABLE transfers ADD COLUMN reversed_by INT UNIQUE REFERENCES transfers(id);
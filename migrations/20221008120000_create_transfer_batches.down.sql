@@ -0,0 +1,5 @@
+DROP TABLE transfer_batch_items;
+DROP TABLE transfer_batches;
+DROP TYPE TRANSFER_BATCH_ITEM_STATUS;
+DROP TYPE TRANSFER_BATCH_STATUS;
+DROP TYPE TRANSFER_BATCH_MODE;
@@ -0,0 +1,25 @@
+CREATE TYPE TRANSFER_BATCH_MODE AS ENUM ('Atomic', 'BestEffort');
+CREATE TYPE TRANSFER_BATCH_STATUS AS ENUM ('Succeeded', 'PartiallySucceeded', 'Failed');
+CREATE TYPE TRANSFER_BATCH_ITEM_STATUS AS ENUM ('Succeeded', 'Failed', 'NotExecuted');
+
+CREATE TABLE transfer_batches (
+    id SERIAL PRIMARY KEY,
+    owner_id INT NOT NULL REFERENCES users(id),
+    mode TRANSFER_BATCH_MODE NOT NULL,
+    status TRANSFER_BATCH_STATUS NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX transfer_batches_owner_id_idx ON transfer_batches(owner_id);
+
+CREATE TABLE transfer_batch_items (
+    id SERIAL PRIMARY KEY,
+    batch_id INT NOT NULL REFERENCES transfer_batches(id),
+    position INT NOT NULL,
+    from_account INT NOT NULL,
+    to_account INT NOT NULL,
+    amount BIGINT NOT NULL,
+    status TRANSFER_BATCH_ITEM_STATUS NOT NULL,
+    transfer_id INT REFERENCES transfers(id),
+    error TEXT,
+    UNIQUE (batch_id, position)
+);
@@ -0,0 +1,6 @@
+DROP INDEX ledger_entries_created_at_idx;
+DROP TABLE user_spending_limits;
+ALTER TABLE accounts DROP CONSTRAINT accounts_overdraft_check;
+ALTER TABLE accounts DROP COLUMN monthly_limit;
+ALTER TABLE accounts DROP COLUMN daily_limit;
+ALTER TABLE accounts DROP COLUMN overdraft_limit;
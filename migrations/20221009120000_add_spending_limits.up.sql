@@ -0,0 +1,16 @@
+-- How far below zero an account may go, and how much may leave it per day and month
+ALTER TABLE accounts ADD COLUMN overdraft_limit BIGINT NOT NULL DEFAULT 0 CHECK (overdraft_limit >= 0);
+ALTER TABLE accounts ADD COLUMN daily_limit BIGINT CHECK (daily_limit >= 0);
+ALTER TABLE accounts ADD COLUMN monthly_limit BIGINT CHECK (monthly_limit >= 0);
+ALTER TABLE accounts ADD CONSTRAINT accounts_overdraft_check CHECK (balance >= -overdraft_limit);
+
+-- How much may leave all of a user's accounts in a currency per day and month
+CREATE TABLE user_spending_limits (
+    user_id INT NOT NULL REFERENCES users(id),
+    currency TEXT NOT NULL REFERENCES currencies(code),
+    daily_limit BIGINT CHECK (daily_limit >= 0),
+    monthly_limit BIGINT CHECK (monthly_limit >= 0),
+    PRIMARY KEY (user_id, currency)
+);
+
+CREATE INDEX ledger_entries_created_at_idx ON ledger_entries(created_at);
@@ -0,0 +1,4 @@
+DROP TABLE imported_payments;
+DROP TABLE payment_imports;
+DROP TYPE IMPORTED_PAYMENT_STATUS;
+DROP TYPE PAYMENT_IMPORT_STATUS;
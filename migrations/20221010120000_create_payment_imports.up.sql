@@ -0,0 +1,34 @@
+CREATE TYPE PAYMENT_IMPORT_STATUS AS ENUM ('Received', 'Accepted', 'PartiallyAccepted', 'Rejected');
+CREATE TYPE IMPORTED_PAYMENT_STATUS AS ENUM ('Pending', 'Accepted', 'Rejected');
+
+CREATE TABLE payment_imports (
+    id SERIAL PRIMARY KEY,
+    owner_id INT NOT NULL REFERENCES users(id),
+    message_id TEXT NOT NULL,
+    status PAYMENT_IMPORT_STATUS NOT NULL DEFAULT 'Received',
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    processed_at timestamptz,
+    UNIQUE (owner_id, message_id)
+);
+CREATE INDEX payment_imports_received_idx ON payment_imports(id) WHERE status = 'Received';
+
+CREATE TABLE imported_payments (
+    id SERIAL PRIMARY KEY,
+    import_id INT NOT NULL REFERENCES payment_imports(id),
+    position INT NOT NULL,
+    payment_info_id TEXT NOT NULL,
+    end_to_end_id TEXT NOT NULL,
+    debtor_account TEXT NOT NULL,
+    creditor_account TEXT NOT NULL,
+    instructed_amount NUMERIC NOT NULL,
+    currency TEXT NOT NULL,
+    -- Set once the payment has been validated and can be executed
+    from_account INT,
+    to_account INT,
+    amount BIGINT,
+    status IMPORTED_PAYMENT_STATUS NOT NULL,
+    reason_code TEXT,
+    reason TEXT,
+    transfer_id INT REFERENCES transfers(id),
+    UNIQUE (import_id, position)
+);
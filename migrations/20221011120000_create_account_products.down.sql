@@ -0,0 +1,13 @@
+DROP TABLE monthly_postings;
+DROP TABLE interest_accrual_runs;
+DROP TABLE interest_accruals;
+
+-- Enum values cannot be dropped, so the type is recreated without them
+DELETE FROM ledger_entries WHERE kind IN ('Interest', 'Fee');
+ALTER TYPE LEDGER_ENTRY_KIND RENAME TO LEDGER_ENTRY_KIND_OLD;
+CREATE TYPE LEDGER_ENTRY_KIND AS ENUM ('Opening', 'Deposit', 'Withdrawal', 'Transfer');
+ALTER TABLE ledger_entries ALTER COLUMN kind TYPE LEDGER_ENTRY_KIND USING kind::TEXT::LEDGER_ENTRY_KIND;
+DROP TYPE LEDGER_ENTRY_KIND_OLD;
+
+ALTER TABLE accounts DROP COLUMN product_id;
+DROP TABLE account_products;
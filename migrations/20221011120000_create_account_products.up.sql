@@ -0,0 +1,45 @@
+-- An account product, such as a savings or checking account.
+-- The interest rate is a yearly rate, e.g. 0.025 for 2.5 %.
+-- The monthly fee is in the minor unit of each account's currency.
+CREATE TABLE account_products (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    interest_rate NUMERIC(9, 6) NOT NULL DEFAULT 0 CHECK (interest_rate >= 0),
+    monthly_fee BIGINT NOT NULL DEFAULT 0 CHECK (monthly_fee >= 0),
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE accounts ADD COLUMN product_id INT REFERENCES account_products(id);
+
+ALTER TYPE LEDGER_ENTRY_KIND ADD VALUE 'Interest';
+ALTER TYPE LEDGER_ENTRY_KIND ADD VALUE 'Fee';
+
+-- Interest earned by an account on a single day, in fractions of the minor unit.
+-- Accruals are only booked to the ledger when the month is posted.
+CREATE TABLE interest_accruals (
+    account_id INT NOT NULL REFERENCES accounts(id),
+    accrual_date DATE NOT NULL,
+    product_id INT NOT NULL REFERENCES account_products(id),
+    balance BIGINT NOT NULL,
+    interest_rate NUMERIC(9, 6) NOT NULL,
+    amount NUMERIC(28, 8) NOT NULL,
+    PRIMARY KEY (account_id, accrual_date)
+);
+
+-- The days interest has been accrued for, so that missed days can be caught up
+CREATE TABLE interest_accrual_runs (
+    accrual_date DATE PRIMARY KEY,
+    accounts INT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- The interest and fee booked for an account at the end of a month.
+-- The primary key makes sure a month is posted at most once.
+CREATE TABLE monthly_postings (
+    account_id INT NOT NULL REFERENCES accounts(id),
+    month DATE NOT NULL CHECK (EXTRACT(DAY FROM month) = 1),
+    interest BIGINT NOT NULL CHECK (interest >= 0),
+    fee BIGINT NOT NULL CHECK (fee >= 0),
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, month)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE accounts DROP CONSTRAINT accounts_overdraft_check;
+ALTER TABLE accounts ADD CONSTRAINT accounts_overdraft_check CHECK (balance >= -overdraft_limit);
+ALTER TABLE accounts DROP COLUMN held;
+DROP TABLE holds;
+DROP TYPE HOLD_STATUS;
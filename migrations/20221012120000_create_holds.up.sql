@@ -0,0 +1,20 @@
+CREATE TYPE HOLD_STATUS AS ENUM ('Active', 'Captured', 'Released', 'Expired');
+
+-- Money reserved on an account without moving it, until it is captured or released
+CREATE TABLE holds (
+    id SERIAL PRIMARY KEY,
+    account_id INT NOT NULL REFERENCES accounts(id),
+    amount BIGINT NOT NULL CHECK (amount > 0),
+    captured_amount BIGINT CHECK (captured_amount > 0 AND captured_amount <= amount),
+    status HOLD_STATUS NOT NULL DEFAULT 'Active',
+    expires_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    closed_at timestamptz
+);
+CREATE INDEX holds_account_id_idx ON holds(account_id);
+CREATE INDEX holds_active_expires_at_idx ON holds(expires_at) WHERE status = 'Active';
+
+-- The sum of the active holds of an account, which is no longer available
+ALTER TABLE accounts ADD COLUMN held BIGINT NOT NULL DEFAULT 0 CHECK (held >= 0);
+ALTER TABLE accounts DROP CONSTRAINT accounts_overdraft_check;
+ALTER TABLE accounts ADD CONSTRAINT accounts_overdraft_check CHECK (balance - held >= -overdraft_limit);
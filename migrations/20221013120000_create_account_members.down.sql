@@ -0,0 +1,2 @@
+DROP TABLE account_members;
+DROP TYPE ACCOUNT_ROLE;
@@ -0,0 +1,17 @@
+CREATE TYPE ACCOUNT_ROLE AS ENUM ('Owner', 'CoOwner', 'Viewer');
+
+-- The users with access to an account, and what they may do with it
+CREATE TABLE account_members (
+    account_id INT NOT NULL REFERENCES accounts(id),
+    user_id INT NOT NULL REFERENCES users(id),
+    role ACCOUNT_ROLE NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, user_id)
+);
+CREATE INDEX account_members_user_id_idx ON account_members(user_id);
+
+-- Every account keeps exactly one owner, the user in accounts.owner_id
+CREATE UNIQUE INDEX account_members_owner_idx ON account_members(account_id) WHERE role = 'Owner';
+
+INSERT INTO account_members (account_id, user_id, role)
+SELECT id, owner_id, 'Owner' FROM accounts;
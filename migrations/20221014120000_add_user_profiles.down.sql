@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN deleted_at;
+ALTER TABLE users DROP COLUMN email;
+ALTER TABLE users DROP COLUMN full_name;
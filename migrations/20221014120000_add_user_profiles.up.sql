@@ -0,0 +1,6 @@
+-- Profile details that users maintain themselves
+ALTER TABLE users ADD COLUMN full_name TEXT;
+ALTER TABLE users ADD COLUMN email TEXT UNIQUE;
+
+-- Deleted users are anonymised but kept, so that their ledger history stays intact
+ALTER TABLE users ADD COLUMN deleted_at timestamptz;
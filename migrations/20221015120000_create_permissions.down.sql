@@ -0,0 +1,4 @@
+ALTER TABLE user_role DROP CONSTRAINT user_role_user_id_role_id_key;
+DROP TABLE role_permissions;
+DROP TABLE permissions;
+DROP TABLE role_hierarchy;
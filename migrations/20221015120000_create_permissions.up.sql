@@ -0,0 +1,37 @@
+-- Roles that a role implies, such as Admin implying User
+CREATE TABLE role_hierarchy (
+    role_id INT NOT NULL REFERENCES role(id),
+    implied_role_id INT NOT NULL REFERENCES role(id),
+    PRIMARY KEY (role_id, implied_role_id),
+    CHECK (role_id <> implied_role_id)
+);
+INSERT INTO role_hierarchy (role_id, implied_role_id) VALUES (2, 1);
+
+-- Fine-grained permissions such as transfers:create
+CREATE TABLE permissions (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE CHECK (name ~ '^[a-z_]+(:[a-z_]+)+$'),
+    description TEXT NOT NULL DEFAULT '',
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE role_permissions (
+    role_id INT NOT NULL REFERENCES role(id),
+    permission_id INT NOT NULL REFERENCES permissions(id) ON DELETE CASCADE,
+    PRIMARY KEY (role_id, permission_id)
+);
+
+INSERT INTO permissions (name, description) VALUES
+    ('accounts:read', 'Read accounts the user is a member of'),
+    ('transfers:read', 'Read transfers to or from accounts the user is a member of'),
+    ('transfers:create', 'Move money out of accounts the user can transact on'),
+    ('roles:manage', 'Grant and revoke roles and permissions');
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT 1, id FROM permissions WHERE name IN ('accounts:read', 'transfers:read', 'transfers:create');
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT 2, id FROM permissions WHERE name = 'roles:manage';
+
+-- A role can only be granted to a user once
+DELETE FROM user_role a USING user_role b
+WHERE a.user_id = b.user_id AND a.role_id = b.role_id AND a.id > b.id;
+ALTER TABLE user_role ADD CONSTRAINT user_role_user_id_role_id_key UNIQUE (user_id, role_id);
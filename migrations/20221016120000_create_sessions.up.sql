@@ -0,0 +1,28 @@
+-- A sign-in, which lasts until it is revoked or its refresh token expires
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id),
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at timestamptz
+);
+CREATE INDEX sessions_user_id_idx ON sessions(user_id);
+
+-- Refresh tokens are stored as SHA-256 hashes, and each can only be used once
+CREATE TABLE refresh_tokens (
+    token_hash TEXT PRIMARY KEY,
+    session_id UUID NOT NULL REFERENCES sessions(id),
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX refresh_tokens_session_id_idx ON refresh_tokens(session_id);
+
+-- The access tokens issued in each session, so that they can be revoked before they expire
+CREATE TABLE access_tokens (
+    jti UUID PRIMARY KEY,
+    session_id UUID NOT NULL REFERENCES sessions(id),
+    expires_at timestamptz NOT NULL,
+    revoked_at timestamptz
+);
+CREATE INDEX access_tokens_session_id_idx ON access_tokens(session_id);
+CREATE INDEX access_tokens_revoked_idx ON access_tokens(expires_at) WHERE revoked_at IS NOT NULL;
@@ -0,0 +1,2 @@
+DROP TABLE jwt_keys;
+DROP TYPE JWT_KEY_STATUS;
@@ -0,0 +1,15 @@
+CREATE TYPE JWT_KEY_STATUS AS ENUM ('Active', 'Verifying', 'Retired');
+
+-- The keys that jwts are signed and verified with, identified by the `kid` in the jwt header
+CREATE TABLE jwt_keys (
+    kid TEXT PRIMARY KEY,
+    -- PEM encoded, and only needed for keys that sign
+    private_key TEXT,
+    public_key TEXT NOT NULL,
+    status JWT_KEY_STATUS NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    retires_at timestamptz
+);
+
+-- Only one key signs new tokens
+CREATE UNIQUE INDEX jwt_keys_active_idx ON jwt_keys(status) WHERE status = 'Active';
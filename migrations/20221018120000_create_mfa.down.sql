@@ -0,0 +1,4 @@
+DROP TABLE mfa_challenges;
+DROP TABLE recovery_codes;
+DROP TABLE totp_secrets;
+ALTER TABLE sessions DROP COLUMN amr;
@@ -0,0 +1,32 @@
+-- How the user authenticated when the session started, as `amr` values of RFC 8176
+ALTER TABLE sessions ADD COLUMN amr TEXT[] NOT NULL DEFAULT '{pwd}';
+
+-- RFC 6238 secrets, which only count as a second factor once confirmed with a code
+CREATE TABLE totp_secrets (
+    user_id INT PRIMARY KEY REFERENCES users(id),
+    -- Base32 encoded, as shown to the user
+    secret TEXT NOT NULL,
+    confirmed_at timestamptz,
+    -- The last time step a code was accepted for, so that codes cannot be replayed
+    last_used_step BIGINT,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Single-use codes for when the authenticator is lost, stored as SHA-256 hashes
+CREATE TABLE recovery_codes (
+    code_hash TEXT PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id),
+    used_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX recovery_codes_user_id_idx ON recovery_codes(user_id);
+
+-- Sign-ins waiting for the second factor
+CREATE TABLE mfa_challenges (
+    id UUID PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id),
+    expires_at timestamptz NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    completed_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
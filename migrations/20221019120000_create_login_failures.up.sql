@@ -0,0 +1,13 @@
+CREATE TYPE LOGIN_SCOPE AS ENUM ('Username', 'Address');
+
+-- Failed sign-ins, counted both for the username tried and for the address they came from.
+-- Usernames that do not exist are counted too, so that they behave like those that do.
+CREATE TABLE login_failures (
+    scope LOGIN_SCOPE NOT NULL,
+    key TEXT NOT NULL,
+    failures INT NOT NULL,
+    last_failure_at timestamptz NOT NULL,
+    -- No sign-ins are tried before this time, whether because of backoff or a lockout
+    blocked_until timestamptz NOT NULL,
+    PRIMARY KEY (scope, key)
+);
@@ -0,0 +1,16 @@
+-- Keys that scripts sign in with instead of passwords, stored as SHA-256 hashes
+CREATE TABLE api_keys (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id),
+    name TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    -- The start of the key, so that users can tell their keys apart
+    prefix TEXT NOT NULL,
+    -- The permissions the key is limited to
+    scopes TEXT[] NOT NULL,
+    expires_at timestamptz,
+    last_used_at timestamptz,
+    revoked_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX api_keys_user_id_idx ON api_keys(user_id);
@@ -0,0 +1,32 @@
+-- Applications that get tokens through OAuth2
+CREATE TABLE oauth_clients (
+    id SERIAL PRIMARY KEY,
+    client_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    -- A SHA-256 hash, or NULL for public clients such as browser apps
+    secret_hash TEXT,
+    -- Where authorization codes may be sent, if the client uses them
+    redirect_uris TEXT[] NOT NULL,
+    -- The permissions that tokens of the client are limited to
+    scopes TEXT[] NOT NULL,
+    -- The user that the client credentials grant acts for, if any
+    user_id INT REFERENCES users(id),
+    revoked_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Authorization codes waiting to be exchanged for a token, stored as SHA-256 hashes
+CREATE TABLE oauth_authorization_codes (
+    code_hash TEXT PRIMARY KEY,
+    client_id INT NOT NULL REFERENCES oauth_clients(id),
+    user_id INT NOT NULL REFERENCES users(id),
+    redirect_uri TEXT NOT NULL,
+    scopes TEXT[] NOT NULL,
+    -- The S256 PKCE challenge that the code verifier must match
+    code_challenge TEXT NOT NULL,
+    -- How the user authenticated when they consented
+    amr TEXT[] NOT NULL,
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
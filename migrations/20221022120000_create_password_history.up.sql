@@ -0,0 +1,8 @@
+-- The hashes of the passwords that users had before, so that they cannot change back to them
+CREATE TABLE password_history (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id),
+    password TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX password_history_user_id_idx ON password_history(user_id);
@@ -0,0 +1,2 @@
+-- Expired keys are deleted by a periodic worker
+CREATE INDEX idempotency_keys_expires_at_idx ON idempotency_keys(expires_at);
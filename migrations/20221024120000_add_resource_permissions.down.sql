@@ -0,0 +1,5 @@
+DELETE FROM permissions
+WHERE name IN (
+    'accounts:write', 'deposits:create', 'withdrawals:create', 'holds:read', 'holds:write',
+    'currencies:read'
+);
@@ -0,0 +1,15 @@
+-- Permissions for the rest of the resource endpoints, so that roles, API keys and OAuth clients
+-- can be limited to them
+INSERT INTO permissions (name, description) VALUES
+    ('accounts:write', 'Open, rename and close accounts the user can manage, and manage their members'),
+    ('deposits:create', 'Pay money into accounts the user can transact on'),
+    ('withdrawals:create', 'Take money out of accounts the user can transact on'),
+    ('holds:read', 'Read holds on accounts the user is a member of'),
+    ('holds:write', 'Place, capture and release holds on accounts the user can transact on'),
+    ('currencies:read', 'Read currencies and exchange rates');
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT 1, id FROM permissions
+WHERE name IN (
+    'accounts:write', 'deposits:create', 'withdrawals:create', 'holds:read', 'holds:write',
+    'currencies:read'
+);
@@ -0,0 +1,4 @@
+-- Whether the authorization request named the redirect URI, in which case the token request
+-- must name the same one. Codes from before are treated as if it was named.
+ALTER TABLE oauth_authorization_codes ADD COLUMN redirect_uri_given BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE oauth_authorization_codes ALTER COLUMN redirect_uri_given DROP DEFAULT;
@@ -0,0 +1,7 @@
+DELETE FROM access_tokens WHERE session_id IS NULL;
+DROP INDEX access_tokens_user_id_idx;
+ALTER TABLE access_tokens DROP CONSTRAINT access_tokens_issued_check;
+ALTER TABLE access_tokens DROP COLUMN code_hash;
+ALTER TABLE access_tokens DROP COLUMN client_id;
+ALTER TABLE access_tokens ALTER COLUMN session_id SET NOT NULL;
+ALTER TABLE access_tokens DROP COLUMN user_id;
@@ -0,0 +1,13 @@
+-- Access tokens issued to OAuth clients have no session, but are revoked with the sessions of
+-- their user like any other
+ALTER TABLE access_tokens ADD COLUMN user_id INT REFERENCES users(id);
+UPDATE access_tokens a SET user_id = s.user_id FROM sessions s WHERE s.id = a.session_id;
+ALTER TABLE access_tokens ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE access_tokens ALTER COLUMN session_id DROP NOT NULL;
+ALTER TABLE access_tokens ADD COLUMN client_id INT REFERENCES oauth_clients(id);
+-- The authorization code the token was exchanged for, so that reusing the code revokes it
+ALTER TABLE access_tokens
+    ADD COLUMN code_hash TEXT REFERENCES oauth_authorization_codes(code_hash);
+ALTER TABLE access_tokens
+    ADD CONSTRAINT access_tokens_issued_check CHECK (session_id IS NOT NULL OR client_id IS NOT NULL);
+CREATE INDEX access_tokens_user_id_idx ON access_tokens(user_id);
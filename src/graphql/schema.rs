@@ -31,7 +31,7 @@ struct NewHuman {
 
 /// The root of all queries.
 #[derive(Clone, Debug)]
-pub struct QueryRoot(DbPool);
+pub struct QueryRoot(#[allow(dead_code)] DbPool);
 
 #[juniper::graphql_object]
 impl QueryRoot {
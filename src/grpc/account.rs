@@ -1,6 +1,7 @@
 //! Account service.
 
-use crate::infra::error::DbError;
+use crate::infra::error::{AppError, DbError};
+use crate::infra::security::jwt::{Claims, Role};
 use crate::model::account_model::Account;
 use crate::repository::account_repository;
 use crate::DbPool;
@@ -21,6 +22,7 @@ impl From<Account> for AccountResponse {
             name: account.name,
             balance: account.balance,
             owner_id: account.owner_id,
+            currency: account.currency.into(),
         }
     }
 }
@@ -44,12 +46,26 @@ impl AccountService for AccountServiceImpl {
         &self,
         req: Request<AccountRequest>,
     ) -> Result<Response<AccountResponse>, Status> {
+        // The claims come from the token that `jwt_interceptor` checked, not from the caller
+        let claims = req
+            .extensions()
+            .get::<Claims>()
+            .cloned()
+            .ok_or(AppError::AuthenticationError)?;
         let req = req.into_inner();
 
+        // Same rules as the REST API: users can only read their own accounts, unless they are
+        // admins, and scoped tokens need the permission
+        if !claims.has_permission("accounts:read")
+            || (req.user_id != claims.id() && !claims.has_role(&Role::Admin))
+        {
+            return Err(AppError::AuthorizationError.into());
+        }
+
         // Start transaction
         let mut tx = self.db.begin().await.map_err(DbError::from)?;
 
-        // Fetch account
+        // Fetch account, which the user must be a member of
         let account =
             account_repository::fetch_account(&mut tx, req.user_id, req.account_id).await?;
 
@@ -67,18 +83,41 @@ mod tests {
             generated::{account_service_server::AccountService, AccountRequest, AccountResponse},
             AccountServiceImpl,
         },
+        infra::security::jwt::Claims,
         DbPool,
     };
     use tonic::{Code, Request};
 
+    /// A request for an account on behalf of `user_id`, as `jwt_interceptor` passes it on.
+    fn request(
+        user_id: i32,
+        account_id: i32,
+        sub: i32,
+        permissions: &[&str],
+    ) -> Request<AccountRequest> {
+        let claims: Claims = serde_json::from_value(serde_json::json!({
+            "sub": sub,
+            "exp": usize::MAX,
+            "roles": [],
+            "permissions": permissions,
+            "jti": uuid::Uuid::new_v4(),
+        }))
+        .unwrap();
+        let mut request = Request::new(AccountRequest {
+            user_id,
+            account_id,
+        });
+        request.extensions_mut().insert(claims);
+        request
+    }
+
     #[sqlx::test]
     async fn get_existing_account_works(db: DbPool) {
         let service = AccountServiceImpl::new(db);
-        let request = AccountRequest {
-            user_id: 1,
-            account_id: 1,
-        };
-        let response = service.get_account(Request::new(request)).await.unwrap();
+        let response = service
+            .get_account(request(1, 1, 1, &["accounts:read"]))
+            .await
+            .unwrap();
         let account_response = response.into_inner();
         assert_eq!(
             AccountResponse {
@@ -86,20 +125,65 @@ mod tests {
                 name: "acc1".into(),
                 balance: 100,
                 owner_id: 1,
+                currency: "NOK".into(),
             },
             account_response
         );
     }
 
+    #[sqlx::test]
+    async fn members_can_get_shared_account(db: DbPool) {
+        let service = AccountServiceImpl::new(db.clone());
+        let status = service
+            .get_account(request(2, 1, 2, &["accounts:read"]))
+            .await
+            .unwrap_err();
+        assert_eq!(Code::NotFound, status.code());
+
+        sqlx::query!(
+            "INSERT INTO account_members (account_id, user_id, role) VALUES (1, 2, 'Viewer')"
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let response = service
+            .get_account(request(2, 1, 2, &["accounts:read"]))
+            .await
+            .unwrap();
+        assert_eq!(1, response.into_inner().owner_id);
+    }
+
+    #[sqlx::test]
+    async fn users_cannot_get_accounts_for_others(db: DbPool) {
+        let service = AccountServiceImpl::new(db);
+        let status = service
+            .get_account(request(1, 1, 2, &["accounts:read"]))
+            .await
+            .unwrap_err();
+        assert_eq!(Code::PermissionDenied, status.code());
+
+        // Scoped tokens need the permission
+        let status = service
+            .get_account(request(1, 1, 1, &["transfers:read"]))
+            .await
+            .unwrap_err();
+        assert_eq!(Code::PermissionDenied, status.code());
+
+        let status = service
+            .get_account(Request::new(AccountRequest {
+                user_id: 1,
+                account_id: 1,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(Code::Unauthenticated, status.code());
+    }
+
     #[sqlx::test]
     async fn get_non_existing_account_fails(db: DbPool) {
         let service = AccountServiceImpl::new(db);
-        let request = AccountRequest {
-            user_id: 0,
-            account_id: 0,
-        };
         let status = service
-            .get_account(Request::new(request))
+            .get_account(request(1, 0, 1, &["accounts:read"]))
             .await
             .err()
             .unwrap();
@@ -2,7 +2,7 @@
 
 use serde::Deserialize;
 
-use super::error::AppError;
+use super::{error::AppError, security::keys::KeyStatus};
 
 /// Application settings.
 #[derive(Clone, Debug, Deserialize)]
@@ -17,6 +17,20 @@ pub struct Settings {
     pub database: DatabaseSettings,
     /// Logging settings.
     pub logging: LoggingSettings,
+    /// Idempotency settings.
+    pub idempotency: IdempotencySettings,
+    /// Background worker settings.
+    pub scheduler: SchedulerSettings,
+    /// User management settings.
+    pub users: UserSettings,
+    /// Multi-factor authentication settings.
+    pub mfa: MfaSettings,
+    /// Settings for protecting sign-ins against password guessing.
+    pub login: LoginSettings,
+    /// OAuth2 authorization server settings.
+    pub oauth: OAuthSettings,
+    /// The policy that new passwords must follow.
+    pub passwords: PasswordSettings,
 }
 
 /// Application settings.
@@ -48,18 +62,35 @@ pub struct SecuritySettings {
     pub tls_certificate: String,
     /// SSL private key.
     pub tls_private_key: String,
-    /// JWT private key.
-    pub jwt_private_key: String,
-    /// JWT public key.
-    pub jwt_public_key: String,
+    /// The keys that JWTs are signed and verified with.
+    pub jwt_keys: Vec<JwtKeySettings>,
+    /// How long the previous key is still accepted after the keys are rotated. This should be
+    /// at least [`SecuritySettings::jwt_minutes_to_live`].
+    pub jwt_key_overlap_minutes: i64,
     /// JWT public key.
     pub jwt_minutes_to_live: i64,
+    /// How long a refresh token can be exchanged for new tokens.
+    pub refresh_token_days_to_live: i64,
     /// Signing private key.
     pub signing_private_key: String,
     /// Signing public key.
     pub signing_public_key: String,
 }
 
+/// A JWT key, read from PEM files.
+#[derive(Clone, Debug, Deserialize)]
+pub struct JwtKeySettings {
+    /// The key id, sent as `kid` in the header of JWTs.
+    pub kid: String,
+    /// Private key, only needed for the key that signs.
+    #[serde(default)]
+    pub private_key: Option<String>,
+    /// Public key.
+    pub public_key: String,
+    /// Whether the key signs new tokens, only verifies them, or is no longer used.
+    pub status: KeyStatus,
+}
+
 /// Database settings.
 #[derive(Clone, Debug, Deserialize)]
 pub struct DatabaseSettings {
@@ -100,6 +131,97 @@ pub struct LoggingSettings {
     pub opentelemetry: bool,
 }
 
+/// Idempotency settings.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct IdempotencySettings {
+    /// How long an idempotency key is remembered.
+    pub key_minutes_to_live: i64,
+}
+
+/// Settings for the background workers, such as the one that executes scheduled transfers.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct SchedulerSettings {
+    /// How often to look for due transfers, received payment files, interest to accrue,
+    /// or expired holds.
+    pub poll_seconds: u64,
+    /// How many times to try a transfer that fails with a transient error.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry. Doubled for each attempt.
+    pub retry_millis: u64,
+}
+
+/// User management settings.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct UserSettings {
+    /// Whether anyone can register a user without being signed in.
+    pub self_registration: bool,
+}
+
+/// Multi-factor authentication settings.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MfaSettings {
+    /// The issuer shown in authenticator apps.
+    pub issuer: String,
+    /// How long a user has to give the second factor after their password.
+    pub challenge_minutes_to_live: i64,
+    /// How many second factors can be tried for each sign-in.
+    pub max_attempts: i32,
+    /// Transfers of more than these amounts require a token from a sign-in with a second
+    /// factor. Transfers in currencies that are not listed always require one.
+    pub transfer_thresholds: Vec<TransferThreshold>,
+}
+
+/// The largest amount in a currency that can be transferred without a second factor.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransferThreshold {
+    /// The ISO 4217 code of the currency.
+    pub currency: String,
+    /// The amount in the minor unit of the currency.
+    pub amount: i64,
+}
+
+/// Settings for protecting sign-ins against password guessing.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct LoginSettings {
+    /// How long failed sign-ins are remembered after the last one.
+    pub failure_window_minutes: i64,
+    /// How many times a username can fail before each failure makes the next attempt wait.
+    pub username_free_attempts: i32,
+    /// How many times an address can fail before each failure makes the next attempt wait.
+    pub address_free_attempts: i32,
+    /// The wait after the first failure that is not free. Doubled for each failure after it.
+    pub backoff_base_seconds: i64,
+    /// The longest wait between attempts, unless the username is locked.
+    pub backoff_max_seconds: i64,
+    /// How many times a username can fail before it is locked.
+    pub lockout_threshold: i32,
+    /// How long a username stays locked, unless an admin unlocks it.
+    pub lockout_minutes: i64,
+}
+
+/// OAuth2 authorization server settings.
+#[derive(Clone, Debug, Deserialize)]
+pub struct OAuthSettings {
+    /// The public URL of the server, which the endpoints in the discovery document start with.
+    pub issuer: String,
+    /// How long an authorization code can be exchanged for a token.
+    pub code_minutes_to_live: i64,
+}
+
+/// The policy that new passwords must follow.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PasswordSettings {
+    /// The least number of characters of a password.
+    pub min_length: usize,
+    /// The most number of characters of a password.
+    pub max_length: usize,
+    /// A file of breached passwords that cannot be used, one on each line.
+    #[serde(default)]
+    pub blocklist_file: Option<String>,
+    /// How many of their last passwords, including the current one, users cannot change to.
+    pub history_size: usize,
+}
+
 /// Retrieve [`Settings`] from the default configuration file.
 #[tracing::instrument]
 pub fn load_configuration() -> Result<Settings, AppError> {
@@ -111,6 +233,17 @@ pub fn load_configuration() -> Result<Settings, AppError> {
     Ok(settings)
 }
 
+impl MfaSettings {
+    /// The largest amount in minor units of `currency` that can be transferred without a second
+    /// factor, if there is one.
+    pub fn transfer_threshold(&self, currency: &str) -> Option<i64> {
+        self.transfer_thresholds
+            .iter()
+            .find(|threshold| threshold.currency == currency)
+            .map(|threshold| threshold.amount)
+    }
+}
+
 impl DatabaseSettings {
     /// Constructs a connection string from the [`DatabaseSettings`].
     pub fn connection_string(&self) -> String {
@@ -1,5 +1,6 @@
 //! Application wide errors.
 
+use crate::model::{money::MoneyError, payment_import_model::Pain001Error};
 use actix_http::{body::BoxBody, StatusCode};
 use actix_web::ResponseError;
 use config::ConfigError;
@@ -48,6 +49,13 @@ impl ResponseError for AppError {
     }
 }
 
+impl AppError {
+    /// Whether the operation may succeed if it is retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, AppError::DbError(e) if e.is_transient())
+    }
+}
+
 impl From<AppError> for Status {
     fn from(e: AppError) -> Self {
         let message = format!("{}", e);
@@ -77,6 +85,18 @@ pub enum ServiceError {
     ValidationError(String),
 }
 
+impl From<MoneyError> for ServiceError {
+    fn from(e: MoneyError) -> Self {
+        ServiceError::ValidationError(e.to_string())
+    }
+}
+
+impl From<Pain001Error> for ServiceError {
+    fn from(e: Pain001Error) -> Self {
+        ServiceError::ValidationError(e.to_string())
+    }
+}
+
 impl ResponseError for ServiceError {
     fn status_code(&self) -> StatusCode {
         match self {
@@ -112,6 +132,19 @@ pub enum DbError {
     Other(sqlx::Error),
 }
 
+impl DbError {
+    /// Whether the operation may succeed if it is retried,
+    /// such as after a lost connection, a deadlock or a serialization failure.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            DbError::ConnectionError => true,
+            DbError::PgDatabaseError(e) => matches!(e.code(), "40001" | "40P01"),
+            DbError::Other(sqlx::Error::PoolTimedOut) => true,
+            _ => false,
+        }
+    }
+}
+
 impl ResponseError for DbError {
     fn status_code(&self) -> actix_http::StatusCode {
         match self {
@@ -119,7 +152,10 @@ impl ResponseError for DbError {
             DbError::Conflict => StatusCode::CONFLICT,
             DbError::ConnectionError => StatusCode::INTERNAL_SERVER_ERROR,
             DbError::PgDatabaseError(e) => match e.code() {
-                "23514" => StatusCode::BAD_REQUEST,
+                // check_violation and numeric_value_out_of_range
+                "23514" | "22003" => StatusCode::BAD_REQUEST,
+                // unique_violation
+                "23505" => StatusCode::CONFLICT,
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             },
             DbError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -1,7 +1,14 @@
 //! Middleware adding authentication data to requests.
 
 use crate::{
-    infra::security::{headers::Auth, jwt::Claims},
+    infra::{
+        configuration::LoginSettings,
+        security::{
+            api_key,
+            headers::Auth,
+            jwt::{Claims, JwtContext},
+        },
+    },
     security::jwt,
     DbPool,
 };
@@ -27,6 +34,12 @@ async fn authenticate(req: &ServiceRequest) -> Result<Claims, HttpResponse> {
     let db = req
         .app_data::<Data<DbPool>>()
         .ok_or_else(|| HttpResponse::InternalServerError().finish())?;
+    let context = req
+        .app_data::<Data<JwtContext>>()
+        .ok_or_else(|| HttpResponse::InternalServerError().finish())?;
+    let login = req
+        .app_data::<Data<LoginSettings>>()
+        .ok_or_else(|| HttpResponse::InternalServerError().finish())?;
     // Extract header
     let auth_header = req
         .headers()
@@ -43,13 +56,24 @@ async fn authenticate(req: &ServiceRequest) -> Result<Claims, HttpResponse> {
     // Handle the auth methods
     let claims = match auth {
         Auth::Basic(basic_auth) => {
-            jwt::create_claims(db, basic_auth.username(), basic_auth.password())
-                .await
-                .map_err(|e| e.error_response())?
+            let address = req.peer_addr().map(|addr| addr.ip());
+            jwt::create_claims(
+                db,
+                context,
+                login,
+                basic_auth.username(),
+                basic_auth.password(),
+                address,
+            )
+            .await
+            .map_err(|e| e.error_response())?
         }
         Auth::Bearer(bearer_auth) => {
-            jwt::decode_jwt(bearer_auth.token()).map_err(|e| e.error_response())?
+            jwt::decode_jwt(context, bearer_auth.token()).map_err(|e| e.error_response())?
         }
+        Auth::ApiKey(api_key_auth) => api_key::create_claims(db, context, api_key_auth.key())
+            .await
+            .map_err(|e| e.error_response())?,
     };
 
     Ok(claims)
@@ -73,6 +97,7 @@ where
             let resp = if let Ok(claims) = authenticate(&req).await {
                 let span = tracing::info_span!("principal", principal = claims.id());
                 req.attach(claims.roles().to_vec());
+                req.attach(claims.permissions().to_vec());
                 req.extensions_mut().insert(claims);
                 svc.call(req).instrument(span).await?
             } else {
@@ -0,0 +1,208 @@
+//! Middleware for safely retrying requests with an `Idempotency-Key` header.
+//!
+//! The first request with a key is executed and its response is stored.
+//! Repeating the request with the same key replays the stored response,
+//! while reusing the key for a different request is rejected.
+
+use super::{digest_filter::clone_request_body, request_logger::clone_response_body};
+use crate::{
+    infra::{configuration::IdempotencySettings, security::jwt::Claims},
+    repository::idempotency_repository::{self, IdempotencyRecord},
+    DbPool,
+};
+use actix_http::{
+    body::{BoxBody, MessageBody},
+    header::CONTENT_TYPE,
+    HttpMessage, StatusCode,
+};
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    web::Data,
+    HttpResponse, ResponseError,
+};
+use chrono::{Duration, Utc};
+use futures::future::{LocalBoxFuture, Ready};
+use std::rc::Rc;
+
+/// The header containing the client's idempotency key.
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+/// The header set on responses that were replayed.
+pub const IDEMPOTENT_REPLAYED: &str = "Idempotent-Replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// A service for replaying responses to repeated requests.
+#[derive(Debug)]
+pub struct IdempotencyService<S> {
+    service: Rc<S>,
+}
+
+/// Computes a hash identifying the request.
+fn request_hash(req: &ServiceRequest, body: &[u8]) -> String {
+    let mut hasher = openssl::sha::Sha256::new();
+    hasher.update(req.method().as_str().as_bytes());
+    hasher.update(b" ");
+    hasher.update(req.uri().path().as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    base64::encode(hasher.finish())
+}
+
+/// Responds to a request whose key has already been used.
+fn respond_to_repeat(record: IdempotencyRecord, request_hash: &str) -> HttpResponse {
+    if record.request_hash != request_hash {
+        return HttpResponse::UnprocessableEntity()
+            .body("Idempotency key was already used for a different request");
+    }
+    let status = record
+        .response_code
+        .and_then(|code| StatusCode::from_u16(code as u16).ok());
+    match status {
+        Some(status) => {
+            let mut response = HttpResponse::build(status);
+            response.insert_header((IDEMPOTENT_REPLAYED, "true"));
+            if let Some(content_type) = record.response_content_type {
+                response.insert_header((CONTENT_TYPE, content_type));
+            }
+            response.body(record.response_body.unwrap_or_default())
+        }
+        None => HttpResponse::Conflict().body("A request with this idempotency key is in progress"),
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotencyService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = S::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let svc = self.service.clone();
+        Box::pin(async move {
+            // Requests without a key are executed as usual
+            let key = match req.headers().get(IDEMPOTENCY_KEY) {
+                Some(key) => key
+                    .to_str()
+                    .ok()
+                    .filter(|k| !k.is_empty() && k.len() <= MAX_KEY_LENGTH)
+                    .map(str::to_string),
+                None => return Ok(svc.call(req).await?.map_into_boxed_body()),
+            };
+            let key = match key {
+                Some(key) => key,
+                None => {
+                    let response = HttpResponse::BadRequest().body("Invalid idempotency key");
+                    return Ok(req.into_response(response));
+                }
+            };
+
+            // Keys are scoped to the user
+            let user_id = req.extensions().get::<Claims>().map(Claims::id);
+            let user_id = match user_id {
+                Some(user_id) => user_id,
+                None => return Ok(req.into_response(HttpResponse::Unauthorized())),
+            };
+
+            let db = req
+                .app_data::<Data<DbPool>>()
+                .expect("no db available")
+                .clone();
+            let settings = req
+                .app_data::<Data<IdempotencySettings>>()
+                .expect("no idempotency settings available")
+                .clone();
+
+            let body = clone_request_body(&mut req).await;
+            let hash = request_hash(&req, &body);
+            let expires_at = Utc::now() + Duration::minutes(settings.key_minutes_to_live);
+
+            // Try to claim the key, or replay the response of whoever did
+            let claimed = match idempotency_repository::try_insert_key(
+                db.get_ref(),
+                user_id,
+                &key,
+                &hash,
+                expires_at,
+            )
+            .await
+            {
+                Ok(claimed) => claimed,
+                Err(e) => return Ok(req.into_response(e.error_response())),
+            };
+            if !claimed {
+                tracing::debug!("Idempotency key `{}` has already been used", key);
+                let response =
+                    match idempotency_repository::fetch_key(db.get_ref(), user_id, &key).await {
+                        Ok(record) => respond_to_repeat(record, &hash),
+                        Err(e) => e.error_response(),
+                    };
+                return Ok(req.into_response(response));
+            }
+
+            // Release the key if the request could not be handled, so that it can be retried
+            let resp = match svc.call(req).await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    idempotency_repository::delete_key(db.get_ref(), user_id, &key)
+                        .await
+                        .unwrap_or_else(|e| tracing::warn!("Failed to release key: {}", e));
+                    return Err(e);
+                }
+            };
+            let (body, resp) = clone_response_body(resp).await;
+
+            let stored = if resp.status().is_server_error() {
+                idempotency_repository::delete_key(db.get_ref(), user_id, &key).await
+            } else {
+                let content_type = resp
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|h| h.to_str().ok());
+                idempotency_repository::store_response(
+                    db.get_ref(),
+                    user_id,
+                    &key,
+                    resp.status().as_u16() as i32,
+                    content_type,
+                    &body,
+                )
+                .await
+            };
+            stored.unwrap_or_else(|e| tracing::warn!("Failed to store idempotent response: {}", e));
+
+            Ok(resp)
+        })
+    }
+}
+
+/// Middleware for replaying responses to requests with an `Idempotency-Key` header.
+///
+/// Routes opt in by wrapping themselves, e.g. `#[actix_web::post("/path", wrap = "Idempotency")]`.
+#[derive(Clone, Copy, Debug)]
+pub struct Idempotency;
+
+impl<S, B> Transform<S, ServiceRequest> for Idempotency
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = S::Error;
+    type InitError = ();
+    type Transform = IdempotencyService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        futures::future::ready(Ok(IdempotencyService {
+            service: Rc::new(service),
+        }))
+    }
+}
@@ -4,6 +4,7 @@ pub mod authentication_filter;
 pub mod authenticator;
 pub mod digest_filter;
 pub mod header_setter;
+pub mod idempotency;
 pub mod request_logger;
 pub mod signature_filter;
 
@@ -11,5 +12,6 @@ pub use authentication_filter::AuthenticationFilter;
 pub use authenticator::Authenticator;
 pub use digest_filter::DigestFilter;
 pub use header_setter::HeaderSetter;
+pub use idempotency::Idempotency;
 pub use request_logger::RequestLogger;
 pub use signature_filter::SignatureFilter;
@@ -40,8 +40,12 @@ where
     fn call(&self, mut req: ServiceRequest) -> Self::Future {
         let svc = self.service.clone();
         Box::pin(async move {
-            let db = req.app_data::<Data<DbPool>>().expect("no db available");
-            let mut conn = db.acquire().await.unwrap();
+            // Only borrow a connection once the response is ready, so that slow
+            // requests do not starve the handlers of connections
+            let db = req
+                .app_data::<Data<DbPool>>()
+                .expect("no db available")
+                .clone();
 
             // Log request information
             let body = clone_request_body(&mut req).await;
@@ -74,7 +78,7 @@ where
             let request = request.build().unwrap();
 
             // Store request information
-            request_repository::store_request(&mut conn, &request)
+            request_repository::store_request(db.get_ref(), &request)
                 .await
                 .unwrap();
 
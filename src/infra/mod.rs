@@ -7,3 +7,4 @@ pub mod logging;
 pub mod middleware;
 pub mod security;
 pub mod validation;
+pub mod xml;
@@ -0,0 +1,81 @@
+//! Personal API keys, which let scripts act for a user without their password.
+//!
+//! A key is a random token with a recognizable prefix, sent in an `Authorization: ApiKey <key>`
+//! header. Only its hash is stored, together with its first characters so that users can tell
+//! their keys apart. The claims of a key have no roles, and only those permissions of the user
+//! that are among the scopes of the key.
+
+use crate::{
+    infra::{
+        error::{AppError, DbError},
+        security::jwt::{self, Claims, JwtContext},
+    },
+    repository::{api_key_repository, user_repository},
+    DbPool,
+};
+use actix_http::StatusCode;
+
+/// The start of every key, so that leaked keys are easy to find.
+const KEY_PREFIX: &str = "ak_";
+/// The number of random bytes in a key.
+const KEY_BYTES: usize = 32;
+/// The number of characters of a key that are stored, including [`KEY_PREFIX`].
+const STORED_PREFIX_LEN: usize = 11;
+
+/// Create a random key, and return it together with its stored prefix.
+pub(crate) fn generate_key() -> Result<(String, String), AppError> {
+    let mut bytes = [0; KEY_BYTES];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| {
+        AppError::CustomError(
+            format!("failed to generate key: {}", e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
+    let key = format!(
+        "{}{}",
+        KEY_PREFIX,
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    );
+    let prefix = key[..STORED_PREFIX_LEN].to_string();
+    Ok((key, prefix))
+}
+
+/// Hash a key for storage.
+pub(crate) fn hash_key(key: &str) -> String {
+    base64::encode(openssl::sha::sha256(key.as_bytes()))
+}
+
+/// Look up a key that is neither revoked nor expired, and return the claims of its user limited
+/// to its scopes.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn create_claims(
+    db: &DbPool,
+    jwt: &JwtContext,
+    key: &str,
+) -> Result<Claims, AppError> {
+    let api_key = match api_key_repository::use_api_key(db, &hash_key(key)).await {
+        Err(DbError::NotFound) => return Err(AppError::AuthenticationError),
+        api_key => api_key?,
+    };
+    let user = match user_repository::fetch_user_by_id(db, &api_key.user_id).await {
+        Err(DbError::NotFound) => return Err(AppError::AuthenticationError),
+        user => user?,
+    };
+    jwt::load_scoped_claims(db, jwt, user.id, &user.name, &api_key.scopes, vec![], None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_random_and_stored_hashed() {
+        let (key, prefix) = generate_key().unwrap();
+        assert_eq!(46, key.len());
+        assert!(key.starts_with(&prefix));
+        assert!(prefix.starts_with(KEY_PREFIX));
+        assert_ne!(key, generate_key().unwrap().0);
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_ne!(key, hash_key(&key));
+    }
+}
@@ -4,6 +4,7 @@
 pub(crate) enum Auth {
     Basic(BasicAuth),
     Bearer(BearerAuth),
+    ApiKey(ApiKeyAuth),
 }
 
 impl Auth {
@@ -12,6 +13,7 @@ impl Auth {
         let auth = match prefix {
             "Bearer" => Auth::Bearer(BearerAuth::from_header(rest)?),
             "Basic" => Auth::Basic(BasicAuth::from_header(rest)?),
+            "ApiKey" => Auth::ApiKey(ApiKeyAuth::from_header(rest)?),
             _ => return None,
         };
         Some(auth)
@@ -62,3 +64,21 @@ impl BearerAuth {
         self.token.as_ref()
     }
 }
+
+/// Personal API key authentication.
+pub(crate) struct ApiKeyAuth {
+    key: String,
+}
+
+impl ApiKeyAuth {
+    pub(crate) fn from_header(header: &str) -> Option<Self> {
+        let api_key_auth = Self {
+            key: header.to_owned(),
+        };
+        Some(api_key_auth)
+    }
+
+    pub(crate) fn key(&self) -> &str {
+        self.key.as_ref()
+    }
+}
@@ -1,20 +1,38 @@
 //! Types and functions for setting up application security.
 
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-};
-
-use actix_http::{HttpMessage, StatusCode};
-use actix_web::{dev::ServiceRequest, Error};
+use actix_http::HttpMessage;
+use actix_web::{dev::ServiceRequest, web, Error};
 use actix_web_grants::permissions::AttachPermissions;
 use actix_web_httpauth::extractors::bearer::BearerAuth;
-use chrono::{Duration, Utc};
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Validation};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use jsonwebtoken::{Algorithm, Validation};
 use serde::{Deserialize, Serialize};
+use std::{
+    net::IpAddr,
+    sync::{Arc, RwLock},
+};
 use tonic::{Request, Status};
+use uuid::Uuid;
 
-use crate::{infra::error::AppError, repository::user_repository, DbPool};
+use crate::{
+    infra::{
+        configuration::{LoginSettings, SecuritySettings},
+        error::AppError,
+        security::{
+            keys::{self, KeyRing},
+            lockout, revocation,
+        },
+    },
+    repository::{mfa_repository, user_repository},
+    DbPool,
+};
+
+/// The `amr` value for a password.
+pub const AMR_PASSWORD: &str = "pwd";
+/// The `amr` value for a one-time password.
+pub const AMR_OTP: &str = "otp";
+/// The `amr` value for any sign-in with more than one factor.
+pub const AMR_MFA: &str = "mfa";
 
 /// The data stored in the jwt
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -22,6 +40,15 @@ pub struct Claims {
     sub: i32,
     exp: usize,
     roles: Vec<Role>,
+    #[serde(default)]
+    permissions: Vec<String>,
+    jti: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sid: Option<Uuid>,
+    #[serde(default)]
+    amr: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
 }
 
 impl Claims {
@@ -37,6 +64,40 @@ impl Claims {
     pub fn has_role(&self, role: &Role) -> bool {
         self.roles.contains(role)
     }
+    /// Returns the permissions granted by the roles stored in the claim.
+    pub fn permissions(&self) -> &[String] {
+        &self.permissions
+    }
+    /// Check if the claim contains a specific permission.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+    /// Returns the unique id of the token.
+    pub fn jti(&self) -> Uuid {
+        self.jti
+    }
+    /// Returns the session the token was issued in, if any.
+    pub fn session_id(&self) -> Option<Uuid> {
+        self.sid
+    }
+    /// Returns how the user authenticated, as `amr` values of RFC 8176 such as `pwd` and `otp`.
+    pub fn amr(&self) -> &[String] {
+        &self.amr
+    }
+    /// Check whether the user gave a second factor when signing in.
+    pub fn has_mfa(&self) -> bool {
+        self.amr.iter().any(|method| method == AMR_MFA)
+    }
+    /// Returns the OAuth client the token was issued to, if any.
+    pub fn client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+    /// Returns when the token expires.
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.exp as i64, 0)
+            .single()
+            .unwrap_or_else(Utc::now)
+    }
 }
 
 /// The possible roles used in the application.
@@ -49,102 +110,151 @@ pub enum Role {
     Admin,
 }
 
-fn file_to_bytes(path: &str) -> Result<Vec<u8>, AppError> {
-    let mut buf = Vec::new();
-    let file = File::open(path).map_err(|_| {
-        AppError::CustomError(
-            format!("failed to open {}", path),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        )
-    })?;
-    let mut file = BufReader::new(file);
-    file.read_to_end(&mut buf).map_err(|_| {
-        AppError::CustomError(
-            format!("failed to read {}", path),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        )
-    })?;
-    Ok(buf)
+impl sqlx::postgres::PgHasArrayType for Role {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_role_name")
+    }
+}
+
+/// The settings and keys needed to issue and check jwts. They are loaded once at startup and
+/// shared by the servers and workers, so that nothing is read from disk per request.
+#[derive(Clone, Debug)]
+pub struct JwtContext {
+    settings: Arc<SecuritySettings>,
+    keys: Arc<RwLock<Arc<KeyRing>>>,
+}
+
+impl JwtContext {
+    /// Load the keys in the settings.
+    pub fn new(settings: SecuritySettings) -> Result<Self, AppError> {
+        let keys = KeyRing::new(&keys::configured_keys(&settings)?)?;
+        Ok(Self {
+            settings: Arc::new(settings),
+            keys: Arc::new(RwLock::new(Arc::new(keys))),
+        })
+    }
+
+    /// Returns the security settings.
+    pub fn settings(&self) -> &SecuritySettings {
+        &self.settings
+    }
+
+    /// Returns the keys in use.
+    pub fn keys(&self) -> Arc<KeyRing> {
+        self.keys.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the keys in use, such as after a rotation.
+    pub(crate) fn set_keys(&self, keys: KeyRing) {
+        *self.keys.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(keys);
+    }
 }
 
-/// Authenticate a user and return their claims.
+/// Authenticate a user signing in from `address` and return their claims, outside of any
+/// session.
 pub(crate) async fn create_claims(
     conn: &DbPool,
+    jwt: &JwtContext,
+    login: &LoginSettings,
     username: &str,
     password: &str,
+    address: Option<IpAddr>,
 ) -> Result<Claims, AppError> {
-    let user_id = user_repository::authenticate(conn, username, password)
-        .await?
-        .ok_or(AppError::AuthenticationError)?;
+    let user_id = lockout::authenticate(conn, login, username, password, address).await?;
+    // A password alone is not enough for users with a second factor
+    if mfa_repository::is_enrolled(conn, user_id).await? {
+        return Err(AppError::AuthenticationError);
+    }
+    let amr = vec![AMR_PASSWORD.to_string()];
+    load_claims(conn, jwt, user_id, username, None, amr).await
+}
 
+/// Load the current roles and permissions of a user into new claims with a fresh `jti`.
+pub(crate) async fn load_claims(
+    conn: &DbPool,
+    jwt: &JwtContext,
+    user_id: i32,
+    username: &str,
+    session_id: Option<Uuid>,
+    amr: Vec<String>,
+) -> Result<Claims, AppError> {
     // Fetch user roles
     let roles = user_repository::fetch_roles(conn, username).await?;
-
-    // Load config
-    let config = crate::configuration::load_configuration()?;
+    let permissions = user_repository::fetch_permissions(conn, username).await?;
 
     // Set claims
-    let in_one_minute = Utc::now() + Duration::minutes(config.security.jwt_minutes_to_live);
-    let exp = in_one_minute.naive_utc().timestamp();
+    let expires_at = Utc::now() + Duration::minutes(jwt.settings().jwt_minutes_to_live);
+    let exp = expires_at.naive_utc().timestamp();
     let claims = Claims {
         sub: user_id,
         exp: exp as usize,
         roles,
+        permissions,
+        jti: Uuid::new_v4(),
+        sid: session_id,
+        amr,
+        client_id: None,
     };
 
     Ok(claims)
 }
 
-/// Create a jwt for the provided user.
-#[tracing::instrument(skip_all, fields(username = username))]
-pub async fn encode_jwt(conn: &DbPool, username: &str, password: &str) -> Result<String, AppError> {
-    // Authenticate user
-    let claims = create_claims(conn, username, password).await?;
-
-    // Load config
-    let config = crate::configuration::load_configuration()?;
-
-    // Read secret from config
-    let private_key_path = config.security.jwt_private_key;
-    let private_key = file_to_bytes(&private_key_path)?;
-    let encoding_key = EncodingKey::from_ec_pem(&private_key).map_err(|e| {
-        AppError::CustomError(
-            format!("failed to create encoding key: {}", e),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        )
-    })?;
+/// Load the claims of a user acting through an API key or an OAuth client. They have no
+/// roles, and only those permissions of the user that are among `scopes`, so they reach the
+/// resource handlers their scopes allow but never the account management of the user.
+pub(crate) async fn load_scoped_claims(
+    conn: &DbPool,
+    jwt: &JwtContext,
+    user_id: i32,
+    username: &str,
+    scopes: &[String],
+    amr: Vec<String>,
+    client_id: Option<String>,
+) -> Result<Claims, AppError> {
+    let mut claims = load_claims(conn, jwt, user_id, username, None, amr).await?;
+    claims.roles.clear();
+    claims
+        .permissions
+        .retain(|permission| scopes.contains(permission));
+    claims.client_id = client_id;
+    Ok(claims)
+}
+
+/// Sign claims into a jwt with the active key, whose id goes in the `kid` header.
+pub fn encode_jwt(jwt: &JwtContext, claims: &Claims) -> Result<String, AppError> {
+    let keys = jwt.keys();
+    let (kid, encoding_key) = keys.signing_key()?;
 
     // Create jwt
-    let token = jsonwebtoken::encode(
-        &jsonwebtoken::Header::new(Algorithm::ES256),
-        &claims,
-        &encoding_key,
-    )
-    .map_err(|_| AppError::AuthenticationError)?;
+    let mut header = jsonwebtoken::Header::new(Algorithm::ES256);
+    header.kid = Some(kid.to_string());
+    let token = jsonwebtoken::encode(&header, claims, encoding_key)
+        .map_err(|_| AppError::AuthenticationError)?;
 
     Ok(token)
 }
 
-/// Decode a jwt into its claims.
-pub fn decode_jwt(token: &str) -> Result<Claims, AppError> {
-    // Read secret from config
-    let config = crate::configuration::load_configuration()?;
-    let public_key_path = config.security.jwt_public_key;
-    let public_key = file_to_bytes(&public_key_path)?;
-    let decoding_key = DecodingKey::from_ec_pem(&public_key).map_err(|_| {
-        AppError::CustomError(
-            "failed to create decoding key".to_string(),
-            StatusCode::INTERNAL_SERVER_ERROR,
-        )
-    })?;
+/// Decode a jwt into its claims, rejecting tokens that have been revoked or that were signed
+/// with a key that is no longer accepted.
+pub fn decode_jwt(jwt: &JwtContext, token: &str) -> Result<Claims, AppError> {
+    let header = jsonwebtoken::decode_header(token).map_err(|_| AppError::AuthenticationError)?;
+    let keys = jwt.keys();
+    let decoding_key = keys
+        .decoding_key(header.kid.as_deref(), Utc::now())
+        .ok_or(AppError::AuthenticationError)?;
 
     let decoded =
-        jsonwebtoken::decode::<Claims>(token, &decoding_key, &Validation::new(Algorithm::ES256))
+        jsonwebtoken::decode::<Claims>(token, decoding_key, &Validation::new(Algorithm::ES256))
             .map_err(|_| AppError::AuthenticationError)?;
+    if revocation::is_revoked(&decoded.claims.jti) {
+        tracing::debug!("Token {} has been revoked", decoded.claims.jti);
+        return Err(AppError::AuthenticationError);
+    }
     Ok(decoded.claims)
 }
 
-/// A validator for [`actix_web_httpauth::middleware::HttpAuthentication`] that gets roles from a JWT.
+/// A validator for [`actix_web_httpauth::middleware::HttpAuthentication`] that gets roles and
+/// permissions from a JWT, using the [`JwtContext`] in the app data.
 ///
 /// # Examples
 ///
@@ -158,9 +268,14 @@ pub async fn validate_jwt(
     credentials: BearerAuth,
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
     let token = credentials.token();
-    if let Ok(claims) = decode_jwt(token) {
+    let claims = req
+        .app_data::<web::Data<JwtContext>>()
+        .ok_or(AppError::AuthenticationError)
+        .and_then(|jwt| decode_jwt(jwt, token));
+    if let Ok(claims) = claims {
         tracing::debug!("Found claims: {:?}", claims);
         req.attach(claims.roles().to_vec());
+        req.attach(claims.permissions().to_vec());
         req.extensions_mut().insert(claims);
         Ok(req)
     } else {
@@ -169,7 +284,8 @@ pub async fn validate_jwt(
 }
 
 /// Check that the incoming gRPC request contains a valid jwt.
-pub fn jwt_interceptor(mut request: Request<()>) -> Result<Request<()>, Status> {
+#[allow(clippy::result_large_err)]
+pub fn jwt_interceptor(jwt: &JwtContext, mut request: Request<()>) -> Result<Request<()>, Status> {
     tracing::debug!("Checking JWT");
 
     // Get authorization header
@@ -190,7 +306,7 @@ pub fn jwt_interceptor(mut request: Request<()>) -> Result<Request<()>, Status>
 
     match token_type {
         "Bearer" => {
-            let claims = decode_jwt(token)?;
+            let claims = decode_jwt(jwt, token)?;
             tracing::debug!("Found claims: {:?}", claims);
             request.extensions_mut().insert(claims);
             Ok(request)
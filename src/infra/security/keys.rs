@@ -0,0 +1,376 @@
+//! The keys that jwts are signed and verified with.
+//!
+//! Keys are identified by a `kid`, which [`encode_jwt`](super::jwt::encode_jwt) puts in the
+//! header of each token. One key is active and signs new tokens. When the keys are rotated, the
+//! previous key keeps verifying tokens for [`SecuritySettings::jwt_key_overlap_minutes`], so
+//! that the tokens it signed stay valid until they expire, and is retired after that.
+//!
+//! The keys are stored in the database, so that all instances share them and rotated keys
+//! survive restarts. The keys in the configuration are imported on startup. Since
+//! [`decode_jwt`](super::jwt::decode_jwt) cannot ask the database, it uses a [`KeyRing`] kept
+//! in the [`JwtContext`], which [`run_jwt_keys`](crate::worker::jwt_keys::run_jwt_keys)
+//! reloads.
+
+use crate::{
+    infra::{
+        configuration::SecuritySettings,
+        error::{AppError, DbError},
+        security::jwt::JwtContext,
+    },
+    repository::jwt_key_repository,
+    DbPool,
+};
+use actix_http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use openssl::{
+    bn::{BigNum, BigNumContext},
+    ec::{EcGroup, EcKey},
+    nid::Nid,
+    pkey::PKey,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+};
+use uuid::Uuid;
+
+/// The size in bytes of a P-256 coordinate.
+const COORDINATE_BYTES: i32 = 32;
+
+/// What a key is used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "jwt_key_status")]
+pub enum KeyStatus {
+    /// Signs new tokens, and verifies them.
+    Active,
+    /// Only verifies tokens, until it retires.
+    Verifying,
+    /// No longer used.
+    Retired,
+}
+
+/// A stored key.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JwtKey {
+    /// The key id.
+    pub kid: String,
+    /// The PEM encoded private key, if the key can sign.
+    #[serde(skip)]
+    pub private_key: Option<String>,
+    /// The PEM encoded public key.
+    pub public_key: String,
+    /// What the key is used for.
+    pub status: KeyStatus,
+    /// When the key was stored.
+    pub created_at: DateTime<Utc>,
+    /// When a verifying key stops being accepted.
+    pub retires_at: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtKey")
+            .field("kid", &self.kid)
+            .field("status", &self.status)
+            .field("created_at", &self.created_at)
+            .field("retires_at", &self.retires_at)
+            .finish()
+    }
+}
+
+/// A public key in the JSON Web Key format of RFC 7517.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwk {
+    /// The key type, always `EC`.
+    pub kty: String,
+    /// The curve, always `P-256`.
+    pub crv: String,
+    /// The base64url encoded x coordinate.
+    pub x: String,
+    /// The base64url encoded y coordinate.
+    pub y: String,
+    /// The key id.
+    pub kid: String,
+    /// What the key is for, always `sig`.
+    #[serde(rename = "use")]
+    pub usage: String,
+    /// The algorithm, always `ES256`.
+    pub alg: String,
+}
+
+/// A set of public keys, as served from `/.well-known/jwks.json`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JwkSet {
+    /// The keys that tokens may currently be signed with.
+    pub keys: Vec<Jwk>,
+}
+
+/// A key parsed for use.
+struct LoadedKey {
+    kid: String,
+    status: KeyStatus,
+    retires_at: Option<DateTime<Utc>>,
+    encoding: Option<EncodingKey>,
+    decoding: DecodingKey,
+    jwk: Jwk,
+}
+
+impl LoadedKey {
+    /// Whether tokens signed with the key are accepted at `now`.
+    fn verifies(&self, now: DateTime<Utc>) -> bool {
+        match self.status {
+            KeyStatus::Active => true,
+            KeyStatus::Verifying => self.retires_at.is_none_or(|at| at > now),
+            KeyStatus::Retired => false,
+        }
+    }
+}
+
+/// The keys in use, parsed and ready for signing and verifying.
+pub struct KeyRing {
+    keys: Vec<LoadedKey>,
+}
+
+impl std::fmt::Debug for KeyRing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.keys.iter().map(|key| (&key.kid, key.status)))
+            .finish()
+    }
+}
+
+impl KeyRing {
+    /// Parse keys. Retired keys are left out.
+    pub fn new(keys: &[JwtKey]) -> Result<Self, AppError> {
+        let keys = keys
+            .iter()
+            .filter(|key| key.status != KeyStatus::Retired)
+            .map(|key| {
+                let encoding = match (&key.private_key, key.status) {
+                    (Some(pem), KeyStatus::Active) => {
+                        Some(EncodingKey::from_ec_pem(pem.as_bytes()).map_err(key_error)?)
+                    }
+                    _ => None,
+                };
+                Ok(LoadedKey {
+                    kid: key.kid.clone(),
+                    status: key.status,
+                    retires_at: key.retires_at,
+                    encoding,
+                    decoding: DecodingKey::from_ec_pem(key.public_key.as_bytes())
+                        .map_err(key_error)?,
+                    jwk: to_jwk(&key.kid, &key.public_key)?,
+                })
+            })
+            .collect::<Result<_, AppError>>()?;
+        Ok(Self { keys })
+    }
+
+    /// Returns the id and the private key of the active key.
+    pub fn signing_key(&self) -> Result<(&str, &EncodingKey), AppError> {
+        self.keys
+            .iter()
+            .find(|key| key.status == KeyStatus::Active)
+            .and_then(|key| Some((key.kid.as_str(), key.encoding.as_ref()?)))
+            .ok_or_else(|| key_error("no active key with a private key"))
+    }
+
+    /// Returns the public key to verify a token signed with the key `kid`, or with the active key
+    /// for tokens without a `kid`.
+    pub fn decoding_key(&self, kid: Option<&str>, now: DateTime<Utc>) -> Option<&DecodingKey> {
+        self.keys
+            .iter()
+            .filter(|key| key.verifies(now))
+            .find(|key| match kid {
+                Some(kid) => key.kid == kid,
+                None => key.status == KeyStatus::Active,
+            })
+            .map(|key| &key.decoding)
+    }
+
+    /// Returns the public keys that tokens are accepted from at `now`.
+    pub fn jwks(&self, now: DateTime<Utc>) -> JwkSet {
+        JwkSet {
+            keys: self
+                .keys
+                .iter()
+                .filter(|key| key.verifies(now))
+                .map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Read the keys in the configuration.
+pub fn configured_keys(settings: &SecuritySettings) -> Result<Vec<JwtKey>, AppError> {
+    settings
+        .jwt_keys
+        .iter()
+        .map(|key| {
+            Ok(JwtKey {
+                kid: key.kid.clone(),
+                private_key: key.private_key.as_deref().map(read_pem).transpose()?,
+                public_key: read_pem(&key.public_key)?,
+                status: key.status,
+                created_at: Utc::now(),
+                retires_at: None,
+            })
+        })
+        .collect()
+}
+
+/// Store the keys in the configuration that are not stored yet, and load the stored keys.
+#[tracing::instrument(skip_all)]
+pub async fn initialize_keys(db: &DbPool, jwt: &JwtContext) -> Result<(), AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    for key in configured_keys(jwt.settings())? {
+        jwt_key_repository::import_key(&mut tx, &key).await?;
+    }
+    tx.commit().await.map_err(DbError::from)?;
+    sync_keys(db, jwt, Utc::now()).await
+}
+
+/// Retire the keys whose overlap ended before `now`, and load the stored keys.
+#[tracing::instrument(skip(db, jwt))]
+pub async fn sync_keys(db: &DbPool, jwt: &JwtContext, now: DateTime<Utc>) -> Result<(), AppError> {
+    let retired = jwt_key_repository::retire_expired_keys(db, now).await?;
+    if retired > 0 {
+        tracing::info!("Retired {} jwt keys", retired);
+    }
+    let keys = jwt_key_repository::fetch_keys(db).await?;
+    jwt.set_keys(KeyRing::new(&keys)?);
+    Ok(())
+}
+
+/// Generate a new active key. The previous active key keeps verifying tokens for
+/// [`SecuritySettings::jwt_key_overlap_minutes`].
+#[tracing::instrument(skip_all)]
+pub async fn rotate_keys(db: &DbPool, jwt: &JwtContext) -> Result<JwtKey, AppError> {
+    let (private_key, public_key) = generate_key()?;
+    let now = Utc::now();
+    let new_key = JwtKey {
+        kid: Uuid::new_v4().to_string(),
+        private_key: Some(private_key),
+        public_key,
+        status: KeyStatus::Active,
+        created_at: now,
+        retires_at: None,
+    };
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    // The configured keys may not have been stored by this instance
+    for key in configured_keys(jwt.settings())? {
+        jwt_key_repository::import_key(&mut tx, &key).await?;
+    }
+    let retires_at = now + Duration::minutes(jwt.settings().jwt_key_overlap_minutes);
+    jwt_key_repository::demote_active_key(&mut tx, retires_at).await?;
+    let key = jwt_key_repository::insert_key(&mut tx, &new_key).await?;
+    tx.commit().await.map_err(DbError::from)?;
+
+    sync_keys(db, jwt, now).await?;
+    Ok(key)
+}
+
+/// Generate a P-256 key pair, PEM encoded.
+fn generate_key() -> Result<(String, String), AppError> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).map_err(key_error)?;
+    let key = PKey::from_ec_key(EcKey::generate(&group).map_err(key_error)?).map_err(key_error)?;
+    let private_key = key.private_key_to_pem_pkcs8().map_err(key_error)?;
+    let public_key = key.public_key_to_pem().map_err(key_error)?;
+    Ok((
+        String::from_utf8(private_key).map_err(key_error)?,
+        String::from_utf8(public_key).map_err(key_error)?,
+    ))
+}
+
+/// Describe a PEM encoded P-256 public key as a JWK.
+fn to_jwk(kid: &str, public_key: &str) -> Result<Jwk, AppError> {
+    let key = PKey::public_key_from_pem(public_key.as_bytes())
+        .and_then(|key| key.ec_key())
+        .map_err(key_error)?;
+    let mut x = BigNum::new().map_err(key_error)?;
+    let mut y = BigNum::new().map_err(key_error)?;
+    let mut ctx = BigNumContext::new().map_err(key_error)?;
+    key.public_key()
+        .affine_coordinates(key.group(), &mut x, &mut y, &mut ctx)
+        .map_err(key_error)?;
+    let encode = |n: &BigNum| {
+        n.to_vec_padded(COORDINATE_BYTES)
+            .map(|bytes| base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+            .map_err(key_error)
+    };
+    Ok(Jwk {
+        kty: "EC".to_string(),
+        crv: "P-256".to_string(),
+        x: encode(&x)?,
+        y: encode(&y)?,
+        kid: kid.to_string(),
+        usage: "sig".to_string(),
+        alg: "ES256".to_string(),
+    })
+}
+
+/// Read a PEM file.
+fn read_pem(path: &str) -> Result<String, AppError> {
+    let mut buf = String::new();
+    let file = File::open(path).map_err(|_| {
+        AppError::CustomError(
+            format!("failed to open {}", path),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
+    BufReader::new(file).read_to_string(&mut buf).map_err(|_| {
+        AppError::CustomError(
+            format!("failed to read {}", path),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
+    Ok(buf)
+}
+
+fn key_error(e: impl std::fmt::Display) -> AppError {
+    AppError::CustomError(
+        format!("jwt key error: {}", e),
+        StatusCode::INTERNAL_SERVER_ERROR,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(status: KeyStatus, retires_at: Option<DateTime<Utc>>) -> JwtKey {
+        let (private_key, public_key) = generate_key().unwrap();
+        JwtKey {
+            kid: Uuid::new_v4().to_string(),
+            private_key: Some(private_key),
+            public_key,
+            status,
+            created_at: Utc::now(),
+            retires_at,
+        }
+    }
+
+    #[test]
+    fn verifying_keys_are_accepted_until_they_retire() {
+        let now = Utc::now();
+        let active = key(KeyStatus::Active, None);
+        let verifying = key(KeyStatus::Verifying, Some(now + Duration::minutes(1)));
+        let retired = key(KeyStatus::Retired, None);
+        let ring = KeyRing::new(&[active.clone(), verifying.clone(), retired.clone()]).unwrap();
+
+        assert_eq!(active.kid, ring.signing_key().unwrap().0);
+        assert!(ring.decoding_key(Some(&active.kid), now).is_some());
+        assert!(ring.decoding_key(None, now).is_some());
+        assert!(ring.decoding_key(Some(&verifying.kid), now).is_some());
+        assert!(ring.decoding_key(Some(&retired.kid), now).is_none());
+        let kids: Vec<_> = ring.jwks(now).keys.into_iter().map(|k| k.kid).collect();
+        assert_eq!(vec![active.kid.clone(), verifying.kid.clone()], kids);
+
+        let later = now + Duration::minutes(2);
+        assert!(ring.decoding_key(Some(&verifying.kid), later).is_none());
+        assert_eq!(1, ring.jwks(later).keys.len());
+    }
+}
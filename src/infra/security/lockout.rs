@@ -0,0 +1,204 @@
+//! Protection against guessing passwords.
+//!
+//! Failed sign-ins are counted for the username and for the address they come from. Each
+//! attempt is counted before the password is verified, and taken back if it succeeds. After a
+//! number of free attempts, each failure makes the next attempt wait twice as long as the one
+//! before, and a username that fails too often is locked until the lock expires or an admin
+//! unlocks it. Usernames that do not exist are counted and locked like any other, so that the
+//! responses do not tell which usernames exist.
+
+use crate::{
+    infra::{
+        configuration::LoginSettings,
+        error::{AppError, DbError},
+    },
+    model::login_model::LoginScope,
+    repository::{login_repository, user_repository},
+    DbPool, Tx,
+};
+use actix_http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use std::net::IpAddr;
+use tracing::Instrument;
+
+/// Verify the password of a user, unless too many sign-ins have failed for the username or the
+/// address, and return the id of the user.
+#[tracing::instrument(skip(db, settings, password))]
+pub async fn authenticate(
+    db: &DbPool,
+    settings: &LoginSettings,
+    username: &str,
+    password: &str,
+    address: Option<IpAddr>,
+) -> Result<i32, AppError> {
+    let now = Utc::now();
+    let address = address.map(|address| address.to_string());
+    let failures = reserve_attempt(db, settings, username, address.as_deref(), now).await?;
+
+    match user_repository::authenticate(db, username, password).await? {
+        Some(user_id) => {
+            let mut tx = db.begin().await.map_err(DbError::from)?;
+            login_repository::clear_failures(&mut tx, LoginScope::Username, username).await?;
+            if let Some(address) = &address {
+                login_repository::release_failure(&mut tx, LoginScope::Address, address).await?;
+            }
+            tx.commit().await.map_err(DbError::from)?;
+            Ok(user_id)
+        }
+        None => {
+            // Locked only now, so that the right password at the threshold does not lock
+            if failures >= settings.lockout_threshold {
+                let until = now + Duration::minutes(settings.lockout_minutes);
+                let mut tx = db.begin().await.map_err(DbError::from)?;
+                lock(&mut tx, username, until).await?;
+                tx.commit().await.map_err(DbError::from)?;
+            }
+            Err(AppError::AuthenticationError)
+        }
+    }
+}
+
+/// Count a sign-in as failed before the password is verified, and make further attempts wait
+/// if there have been too many. Counting first, with the rows locked while the block is
+/// checked, means that concurrent attempts cannot all get through before any of them is
+/// counted. Returns the number of failures for the username, this attempt included.
+async fn reserve_attempt(
+    db: &DbPool,
+    settings: &LoginSettings,
+    username: &str,
+    address: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<i32, AppError> {
+    let window_start = now - Duration::minutes(settings.failure_window_minutes);
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+
+    // Rows are always locked username first, so that attempts cannot deadlock
+    let by_username = login_repository::record_failure(
+        &mut tx,
+        LoginScope::Username,
+        username,
+        window_start,
+        now,
+    )
+    .await?;
+    let by_address = match address {
+        Some(address) => Some(
+            login_repository::record_failure(
+                &mut tx,
+                LoginScope::Address,
+                address,
+                window_start,
+                now,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+    let blocked_until = by_address
+        .iter()
+        .map(|failures| failures.blocked_until)
+        .fold(by_username.blocked_until, DateTime::max);
+    if blocked_until > now {
+        // Blocked attempts are not counted, so the transaction is rolled back
+        return Err(too_many_attempts(blocked_until - now));
+    }
+
+    // Past the threshold, the backoff holds off other attempts until this one is verified
+    if let Some(wait) = backoff(
+        settings,
+        by_username.failures,
+        settings.username_free_attempts,
+    ) {
+        login_repository::block(&mut tx, LoginScope::Username, username, now + wait).await?;
+    }
+    if let (Some(address), Some(by_address)) = (address, by_address) {
+        if let Some(wait) = backoff(
+            settings,
+            by_address.failures,
+            settings.address_free_attempts,
+        ) {
+            login_repository::block(&mut tx, LoginScope::Address, address, now + wait).await?;
+        }
+    }
+
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(by_username.failures)
+}
+
+/// Lock a username. Locking the username of an existing user goes in the audit log, on behalf
+/// of that user.
+async fn lock(tx: &mut Tx, username: &str, until: DateTime<Utc>) -> Result<(), AppError> {
+    match user_repository::fetch_user_by_username(&mut *tx, username).await {
+        Ok(user) => {
+            tracing::warn!("Locking `{}` until {}", username, until);
+            let span = tracing::info_span!("principal", principal = user.id);
+            login_repository::lock_user(tx, user.id, username, until)
+                .instrument(span)
+                .await?;
+        }
+        Err(DbError::NotFound) => {
+            login_repository::block(tx, LoginScope::Username, username, until).await?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+/// How long to wait after `failures` failures, if the free attempts are used up.
+fn backoff(settings: &LoginSettings, failures: i32, free_attempts: i32) -> Option<Duration> {
+    let exponent = failures.checked_sub(free_attempts + 1)?;
+    let exponent = u32::try_from(exponent).ok()?.min(32);
+    let seconds = settings
+        .backoff_base_seconds
+        .saturating_mul(1 << exponent)
+        .min(settings.backoff_max_seconds);
+    Some(Duration::seconds(seconds))
+}
+
+fn too_many_attempts(wait: Duration) -> AppError {
+    let seconds = (wait.num_milliseconds() + 999) / 1000;
+    AppError::CustomError(
+        format!("Too many failed sign-ins, try again in {} seconds", seconds),
+        StatusCode::TOO_MANY_REQUESTS,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let settings = LoginSettings {
+            failure_window_minutes: 60,
+            username_free_attempts: 3,
+            address_free_attempts: 20,
+            backoff_base_seconds: 2,
+            backoff_max_seconds: 60,
+            lockout_threshold: 10,
+            lockout_minutes: 15,
+        };
+        let waits: Vec<_> = (1..=10)
+            .map(|failures| backoff(&settings, failures, 3).map(|wait| wait.num_seconds()))
+            .collect();
+        assert_eq!(
+            vec![
+                None,
+                None,
+                None,
+                Some(2),
+                Some(4),
+                Some(8),
+                Some(16),
+                Some(32),
+                Some(60),
+                Some(60)
+            ],
+            waits
+        );
+        assert_eq!(
+            Some(60),
+            backoff(&settings, i32::MAX, 3).map(|w| w.num_seconds())
+        );
+    }
+}
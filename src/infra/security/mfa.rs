@@ -0,0 +1,237 @@
+//! Second factors for signing in: time-based one-time passwords and recovery codes.
+//!
+//! Codes follow RFC 6238 with the defaults authenticator apps expect: HMAC-SHA1, six digits
+//! and a 30 second time step. A code is accepted one step early or late to allow for clock
+//! drift, but never for a step at or before the last one accepted, so that a code cannot be
+//! replayed.
+
+use crate::infra::error::AppError;
+use actix_http::StatusCode;
+use chrono::{DateTime, Utc};
+use openssl::{error::ErrorStack, hash::MessageDigest, pkey::PKey, sign::Signer};
+
+/// The seconds each code is valid for.
+const STEP_SECONDS: i64 = 30;
+/// The number of digits in a code.
+const DIGITS: u32 = 6;
+/// The number of steps a code may be early or late.
+const ALLOWED_DRIFT: i64 = 1;
+/// The number of random bytes in a secret, which is the size of a SHA-1 hash.
+const SECRET_BYTES: usize = 20;
+/// The number of recovery codes a user gets.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// The number of random bytes in a recovery code, of which the first ten base32 characters
+/// are used.
+const RECOVERY_CODE_BYTES: usize = 7;
+/// The alphabet of RFC 4648 base32.
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generate a new secret, base32 encoded.
+pub fn generate_secret() -> Result<String, AppError> {
+    Ok(base32_encode(&random_bytes::<SECRET_BYTES>()?))
+}
+
+/// The `otpauth` URI that authenticator apps read, usually from a QR code.
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        issuer = percent_encode(issuer),
+        account = percent_encode(account),
+    )
+}
+
+/// The code for a base32 encoded secret at `time`.
+pub fn generate_code(secret: &str, time: DateTime<Utc>) -> Result<String, AppError> {
+    let secret = base32_decode(secret).ok_or_else(|| mfa_error("invalid secret"))?;
+    let code = hotp(&secret, step(time) as u64).map_err(mfa_error)?;
+    Ok(format!("{:0width$}", code, width = DIGITS as usize))
+}
+
+/// Check a code at `time`, and return the step it was for. Codes for steps at or before
+/// `last_used_step` are rejected.
+pub fn verify_code(
+    secret: &str,
+    code: &str,
+    time: DateTime<Utc>,
+    last_used_step: Option<i64>,
+) -> Result<Option<i64>, AppError> {
+    let secret = base32_decode(secret).ok_or_else(|| mfa_error("invalid secret"))?;
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+    let now = step(time);
+    for step in now - ALLOWED_DRIFT..=now + ALLOWED_DRIFT {
+        if last_used_step.is_some_and(|last| step <= last) {
+            continue;
+        }
+        let expected = hotp(&secret, step as u64).map_err(mfa_error)?;
+        if format!("{:0width$}", expected, width = DIGITS as usize) == code {
+            return Ok(Some(step));
+        }
+    }
+    Ok(None)
+}
+
+/// Generate recovery codes, formatted like `abcde-fghij`.
+pub fn generate_recovery_codes() -> Result<Vec<String>, AppError> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code = base32_encode(&random_bytes::<RECOVERY_CODE_BYTES>()?).to_lowercase();
+            Ok(format!("{}-{}", &code[..5], &code[5..10]))
+        })
+        .collect()
+}
+
+/// Hash a recovery code for storage, ignoring case and dashes.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    base64::encode(openssl::sha::sha256(normalized.as_bytes()))
+}
+
+/// The time step `time` falls in.
+fn step(time: DateTime<Utc>) -> i64 {
+    time.timestamp().div_euclid(STEP_SECONDS)
+}
+
+/// The HOTP value of RFC 4226 for a counter.
+fn hotp(secret: &[u8], counter: u64) -> Result<u32, ErrorStack> {
+    let key = PKey::hmac(secret)?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key)?;
+    signer.update(&counter.to_be_bytes())?;
+    let hmac = signer.sign_to_vec()?;
+    let offset = (hmac[hmac.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([
+        hmac[offset] & 0x7f,
+        hmac[offset + 1],
+        hmac[offset + 2],
+        hmac[offset + 3],
+    ]);
+    Ok(value % 10u32.pow(DIGITS))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], AppError> {
+    let mut bytes = [0; N];
+    openssl::rand::rand_bytes(&mut bytes).map_err(mfa_error)?;
+    Ok(bytes)
+}
+
+/// Encode bytes as base32 without padding.
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+/// Decode base32, ignoring case, spaces and padding.
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.bytes().filter(|c| !matches!(c, b' ' | b'=')) {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Encode everything but unreserved characters, for use in a URI.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn mfa_error(e: impl std::fmt::Display) -> AppError {
+    AppError::CustomError(
+        format!("multi-factor authentication error: {}", e),
+        StatusCode::INTERNAL_SERVER_ERROR,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn codes_match_rfc_6238() {
+        let secret = base32_encode(b"12345678901234567890");
+        assert_eq!("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", secret);
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            let time = Utc.timestamp_opt(time, 0).unwrap();
+            assert_eq!(code, generate_code(&secret, time).unwrap());
+        }
+    }
+
+    #[test]
+    fn codes_are_accepted_with_drift_but_not_replayed() {
+        let secret = generate_secret().unwrap();
+        assert_eq!(SECRET_BYTES, base32_decode(&secret).unwrap().len());
+        let now = Utc::now();
+        let code = generate_code(&secret, now).unwrap();
+        let step = verify_code(&secret, &code, now, None).unwrap().unwrap();
+        let later = now + chrono::Duration::seconds(STEP_SECONDS);
+        assert_eq!(
+            Some(step),
+            verify_code(&secret, &code, later, None).unwrap()
+        );
+        assert_eq!(None, verify_code(&secret, &code, now, Some(step)).unwrap());
+        let much_later = now + chrono::Duration::seconds(3 * STEP_SECONDS);
+        assert_eq!(None, verify_code(&secret, &code, much_later, None).unwrap());
+        assert_eq!(None, verify_code(&secret, "12345", now, None).unwrap());
+    }
+
+    #[test]
+    fn recovery_codes_are_hashed_loosely() {
+        let codes = generate_recovery_codes().unwrap();
+        assert_eq!(RECOVERY_CODE_COUNT, codes.len());
+        assert_eq!(11, codes[0].len());
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&codes[0].replace('-', "").to_uppercase())
+        );
+    }
+
+    #[test]
+    fn uris_are_encoded() {
+        assert_eq!(
+            "otpauth://totp/My%20Bank:alice%40example.com?secret=ABC&issuer=My%20Bank&algorithm=SHA1&digits=6&period=30",
+            otpauth_uri("My Bank", "alice@example.com", "ABC")
+        );
+    }
+}
@@ -1,5 +1,13 @@
 //! Security related types and functions.
 
+pub mod api_key;
 pub mod headers;
 pub mod jwt;
+pub mod keys;
+pub mod lockout;
+pub mod mfa;
+pub mod oauth;
+pub mod password;
+pub mod revocation;
+pub mod session;
 pub mod signature;
@@ -0,0 +1,609 @@
+//! An OAuth2 authorization server (RFC 6749) for registered clients.
+//!
+//! Confidential clients get tokens for the user they are registered with through the client
+//! credentials grant. Browser apps send users to the authorization endpoint, where they sign in
+//! and consent, and exchange the code they get back with the authorization code grant. Codes are
+//! always bound to an S256 PKCE challenge (RFC 7636), even for confidential clients.
+//!
+//! Tokens are jwts signed like those of `/token`, with no roles and only those permissions of
+//! the user that are among the scopes granted to the client.
+
+use crate::{
+    infra::{
+        configuration::{LoginSettings, MfaSettings, OAuthSettings},
+        error::{AppError, DbError, ServiceError},
+        security::{
+            jwt::{self, JwtContext, AMR_PASSWORD},
+            lockout, revocation, session,
+        },
+    },
+    model::{
+        mfa_model::MfaVerification,
+        oauth_model::{
+            Approval, AuthorizationCode, AuthorizationRequest, Consent, CreatedOAuthClient,
+            NewOAuthClient, OAuthClient, OAuthTokenRequest, OAuthTokenResponse, ProviderMetadata,
+        },
+        user_model::User,
+    },
+    repository::{mfa_repository, oauth_repository, role_repository, user_repository},
+    DbPool, Tx,
+};
+use actix_http::{body::BoxBody, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::net::IpAddr;
+use thiserror::Error;
+
+/// The grant for clients getting tokens for themselves.
+pub const GRANT_CLIENT_CREDENTIALS: &str = "client_credentials";
+/// The grant for exchanging an authorization code.
+pub const GRANT_AUTHORIZATION_CODE: &str = "authorization_code";
+/// The only supported PKCE method.
+pub const PKCE_METHOD: &str = "S256";
+
+/// The number of random bytes in a `client_id`.
+const CLIENT_ID_BYTES: usize = 16;
+/// The number of random bytes in a client secret or an authorization code.
+const SECRET_BYTES: usize = 32;
+
+/// An error as defined in section 5.2 of RFC 6749, sent to clients as json from the token
+/// endpoint and in the query of the redirect URI from the authorization endpoint.
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    /// A parameter is missing or malformed.
+    #[error("{0}")]
+    InvalidRequest(String),
+    /// The client is unknown or its secret is wrong.
+    #[error("client authentication failed")]
+    InvalidClient,
+    /// The code is unknown, used, expired or does not match the request.
+    #[error("the authorization grant is invalid")]
+    InvalidGrant,
+    /// The client is not allowed to use the grant.
+    #[error("the client is not allowed to use this grant")]
+    UnauthorizedClient,
+    /// The grant type is not supported.
+    #[error("the grant type is not supported")]
+    UnsupportedGrantType,
+    /// The response type is not supported.
+    #[error("the response type is not supported")]
+    UnsupportedResponseType,
+    /// The scope asks for more than the client is allowed.
+    #[error("the scope is not allowed for the client")]
+    InvalidScope,
+    /// The user did not approve the client.
+    #[error("the user denied access")]
+    AccessDenied,
+    /// Something went wrong on the server.
+    #[error("{0}")]
+    ServerError(#[from] AppError),
+}
+
+impl OAuthError {
+    /// Returns the `error` code sent to clients.
+    pub fn code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant => "invalid_grant",
+            OAuthError::UnauthorizedClient => "unauthorized_client",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::UnsupportedResponseType => "unsupported_response_type",
+            OAuthError::InvalidScope => "invalid_scope",
+            OAuthError::AccessDenied => "access_denied",
+            OAuthError::ServerError(_) => "server_error",
+        }
+    }
+}
+
+impl From<DbError> for OAuthError {
+    fn from(e: DbError) -> Self {
+        OAuthError::ServerError(e.into())
+    }
+}
+
+impl ResponseError for OAuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthError::ServerError(e) => e.status_code(),
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        let mut response = HttpResponse::build(self.status_code());
+        if let OAuthError::InvalidClient = self {
+            response.insert_header(("WWW-Authenticate", "Basic"));
+        }
+        response.json(json!({
+            "error": self.code(),
+            "error_description": self.to_string(),
+        }))
+    }
+}
+
+/// An authorization request that names a known client, one of its redirect URIs and a PKCE
+/// challenge, and asks for scopes the client is allowed.
+#[derive(Clone, Debug)]
+pub struct Authorization {
+    /// The client asking for authorization.
+    pub client: OAuthClient,
+    /// Where to send the code.
+    pub redirect_uri: String,
+    /// Whether the request named the redirect URI, rather than leaving the only registered one.
+    pub redirect_uri_given: bool,
+    /// The scopes to grant.
+    pub scopes: Vec<String>,
+    /// The PKCE code challenge.
+    pub code_challenge: String,
+}
+
+/// Register a new client. Confidential clients get a secret, which is only returned here.
+#[tracing::instrument(skip(db))]
+pub async fn register_client(
+    db: &DbPool,
+    new_client: &NewOAuthClient,
+) -> Result<CreatedOAuthClient, AppError> {
+    if new_client.user_id.is_some() && !new_client.confidential {
+        return Err(ServiceError::ValidationError(
+            "Only confidential clients can act for a user".to_string(),
+        )
+        .into());
+    }
+    if new_client.user_id.is_none() && new_client.redirect_uris.is_empty() {
+        return Err(ServiceError::ValidationError(
+            "Give redirect URIs, a user for the client to act for, or both".to_string(),
+        )
+        .into());
+    }
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    if let Some(user_id) = new_client.user_id {
+        match user_repository::fetch_user_by_id(&mut tx, &user_id).await {
+            Err(DbError::NotFound) => {
+                return Err(ServiceError::ValidationError("Unknown user".to_string()).into())
+            }
+            user => user?,
+        };
+    }
+    let client_id = random_token(CLIENT_ID_BYTES)?;
+    let client_secret = if new_client.confidential {
+        Some(random_token(SECRET_BYTES)?)
+    } else {
+        None
+    };
+    let secret_hash = client_secret.as_deref().map(hash_secret);
+    let client =
+        oauth_repository::insert_client(&mut tx, new_client, &client_id, secret_hash.as_deref())
+            .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(CreatedOAuthClient {
+        client,
+        client_secret,
+    })
+}
+
+/// Find the client of an authorization request and the redirect URI to use. Errors here must
+/// be shown to the user rather than sent to the redirect URI, which cannot be trusted.
+#[tracing::instrument(skip(db))]
+pub async fn check_client(
+    db: &DbPool,
+    request: &AuthorizationRequest,
+) -> Result<(OAuthClient, String), OAuthError> {
+    let client = match oauth_repository::fetch_client(db, &request.client_id).await {
+        Err(DbError::NotFound) => return Err(OAuthError::InvalidClient),
+        client => client?.0,
+    };
+    let redirect_uri = match (&request.redirect_uri, client.redirect_uris.as_slice()) {
+        (Some(uri), uris) if uris.contains(uri) => uri.clone(),
+        (None, [uri]) => uri.clone(),
+        _ => {
+            return Err(OAuthError::InvalidRequest(
+                "The redirect URI is not registered for the client".to_string(),
+            ))
+        }
+    };
+    Ok((client, redirect_uri))
+}
+
+impl Authorization {
+    /// Check the rest of an authorization request for a known client and redirect URI.
+    pub fn new(
+        client: OAuthClient,
+        redirect_uri: String,
+        request: &AuthorizationRequest,
+    ) -> Result<Self, OAuthError> {
+        if request.response_type.as_deref() != Some("code") {
+            return Err(OAuthError::UnsupportedResponseType);
+        }
+        let code_challenge = match (&request.code_challenge, &request.code_challenge_method) {
+            (Some(challenge), Some(method)) if method == PKCE_METHOD => challenge.clone(),
+            _ => {
+                return Err(OAuthError::InvalidRequest(
+                    "A code challenge with the S256 method is required".to_string(),
+                ))
+            }
+        };
+        let scopes = parse_scope(request.scope.as_deref(), &client.scopes)?;
+        Ok(Self {
+            client,
+            redirect_uri,
+            redirect_uri_given: request.redirect_uri.is_some(),
+            scopes,
+            code_challenge,
+        })
+    }
+}
+
+/// Authenticate a user on the consent page and issue a code for the client. Users with a
+/// second factor first get a challenge, and give the code from their authenticator app on a
+/// second step.
+#[tracing::instrument(skip(db, settings, mfa, login, authorization))]
+pub async fn approve(
+    db: &DbPool,
+    settings: &OAuthSettings,
+    mfa: &MfaSettings,
+    login: &LoginSettings,
+    authorization: &Authorization,
+    consent: &Consent,
+    address: Option<IpAddr>,
+) -> Result<Approval, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, amr) = if let Some(mfa_token) = consent.mfa_token {
+        let verification = MfaVerification {
+            mfa_token,
+            code: consent.code.clone(),
+            recovery_code: None,
+        };
+        let Some(verified) = session::verify_challenge(&mut tx, mfa, &verification).await? else {
+            // Keep the attempt, so that codes cannot be guessed
+            tx.commit().await.map_err(DbError::from)?;
+            return Err(AppError::AuthenticationError);
+        };
+        verified
+    } else {
+        let (Some(username), Some(password)) = (&consent.username, &consent.password) else {
+            return Err(ServiceError::ValidationError(
+                "Give a username and a password".to_string(),
+            )
+            .into());
+        };
+        let user_id = lockout::authenticate(db, login, username, password, address).await?;
+        if mfa_repository::is_enrolled(&mut tx, user_id).await? {
+            let challenge = session::start_challenge(&mut tx, mfa, user_id).await?;
+            tx.commit().await.map_err(DbError::from)?;
+            return Ok(Approval::MfaRequired(challenge));
+        }
+        (user_id, vec![AMR_PASSWORD.to_string()])
+    };
+
+    let code = random_token(SECRET_BYTES)?;
+    let authorization_code = AuthorizationCode {
+        client_id: authorization.client.id,
+        user_id,
+        redirect_uri: authorization.redirect_uri.clone(),
+        redirect_uri_given: authorization.redirect_uri_given,
+        scopes: authorization.scopes.clone(),
+        code_challenge: authorization.code_challenge.clone(),
+        amr,
+        expires_at: Utc::now() + Duration::minutes(settings.code_minutes_to_live),
+        used_at: None,
+    };
+    oauth_repository::insert_authorization_code(&mut tx, &hash_secret(&code), &authorization_code)
+        .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(Approval::Code(code))
+}
+
+/// Build the URI to send the user back to the client with, keeping the query of the redirect
+/// URI.
+pub fn redirect_uri(redirect_uri: &str, params: &[(&str, &str)]) -> Result<String, AppError> {
+    let mut url = url::Url::parse(redirect_uri).map_err(|e| {
+        AppError::CustomError(
+            format!("invalid redirect URI: {}", e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
+    url.query_pairs_mut().extend_pairs(params);
+    Ok(url.into())
+}
+
+/// Handle a request to the token endpoint, from a client authenticated with the `client_id`
+/// and secret of Basic authentication if given, or else with those in the body.
+#[tracing::instrument(skip(db, jwt, basic_auth))]
+pub async fn request_token(
+    db: &DbPool,
+    jwt: &JwtContext,
+    request: &OAuthTokenRequest,
+    basic_auth: Option<(&str, Option<&str>)>,
+) -> Result<OAuthTokenResponse, OAuthError> {
+    let (client_id, client_secret) = match basic_auth {
+        Some(credentials) => credentials,
+        None => (
+            request
+                .client_id
+                .as_deref()
+                .ok_or(OAuthError::InvalidClient)?,
+            request.client_secret.as_deref(),
+        ),
+    };
+    let client = authenticate_client(db, client_id, client_secret).await?;
+
+    match request.grant_type.as_str() {
+        GRANT_CLIENT_CREDENTIALS => client_credentials(db, jwt, &client, request).await,
+        GRANT_AUTHORIZATION_CODE => authorization_code(db, jwt, &client, request).await,
+        _ => Err(OAuthError::UnsupportedGrantType),
+    }
+}
+
+/// Describe the server for clients, as served from `/.well-known/openid-configuration`.
+#[tracing::instrument(skip_all)]
+pub async fn provider_metadata(
+    db: &DbPool,
+    settings: &OAuthSettings,
+) -> Result<ProviderMetadata, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let permissions = role_repository::fetch_permissions(&mut tx).await?;
+    tx.commit().await.map_err(DbError::from)?;
+
+    let issuer = settings.issuer.trim_end_matches('/');
+    let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+    Ok(ProviderMetadata {
+        issuer: issuer.to_string(),
+        authorization_endpoint: format!("{}/oauth/authorize", issuer),
+        token_endpoint: format!("{}/oauth/token", issuer),
+        jwks_uri: format!("{}/.well-known/jwks.json", issuer),
+        scopes_supported: permissions.into_iter().map(|p| p.name).collect(),
+        response_types_supported: strings(&["code"]),
+        grant_types_supported: strings(&[GRANT_AUTHORIZATION_CODE, GRANT_CLIENT_CREDENTIALS]),
+        token_endpoint_auth_methods_supported: strings(&[
+            "client_secret_basic",
+            "client_secret_post",
+            "none",
+        ]),
+        code_challenge_methods_supported: strings(&[PKCE_METHOD]),
+        subject_types_supported: strings(&["public"]),
+        id_token_signing_alg_values_supported: strings(&["ES256"]),
+    })
+}
+
+/// Check the secret of a confidential client. Public clients must not send one.
+async fn authenticate_client(
+    db: &DbPool,
+    client_id: &str,
+    client_secret: Option<&str>,
+) -> Result<OAuthClient, OAuthError> {
+    let (client, secret_hash) = match oauth_repository::fetch_client(db, client_id).await {
+        Err(DbError::NotFound) => return Err(OAuthError::InvalidClient),
+        client => client?,
+    };
+    match (secret_hash, client_secret) {
+        (Some(hash), Some(secret))
+            if openssl::memcmp::eq(hash.as_bytes(), hash_secret(secret).as_bytes()) =>
+        {
+            Ok(client)
+        }
+        (None, None) => Ok(client),
+        _ => Err(OAuthError::InvalidClient),
+    }
+}
+
+/// Issue a token for the user a confidential client is registered with.
+async fn client_credentials(
+    db: &DbPool,
+    jwt: &JwtContext,
+    client: &OAuthClient,
+    request: &OAuthTokenRequest,
+) -> Result<OAuthTokenResponse, OAuthError> {
+    let user_id = match client.user_id {
+        Some(user_id) if client.allows_client_credentials() => user_id,
+        _ => return Err(OAuthError::UnauthorizedClient),
+    };
+    let scopes = parse_scope(request.scope.as_deref(), &client.scopes)?;
+    let user = match user_repository::fetch_user_by_id(db, &user_id).await {
+        Err(DbError::NotFound) => return Err(OAuthError::UnauthorizedClient),
+        user => user?,
+    };
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let grant = Grant {
+        user: &user,
+        scopes: &scopes,
+        amr: vec![],
+        code_hash: None,
+    };
+    let response = issue_token(db, &mut tx, jwt, client, grant).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(response)
+}
+
+/// Exchange an authorization code, which can only be done once.
+async fn authorization_code(
+    db: &DbPool,
+    jwt: &JwtContext,
+    client: &OAuthClient,
+    request: &OAuthTokenRequest,
+) -> Result<OAuthTokenResponse, OAuthError> {
+    if !client.allows_authorization_code() {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+    let (Some(code), Some(code_verifier)) = (&request.code, &request.code_verifier) else {
+        return Err(OAuthError::InvalidRequest(
+            "A code and a code verifier are required".to_string(),
+        ));
+    };
+
+    let code_hash = hash_secret(code);
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let authorization_code =
+        match oauth_repository::lock_authorization_code(&mut tx, &code_hash).await {
+            Err(DbError::NotFound) => return Err(OAuthError::InvalidGrant),
+            code => code?,
+        };
+    // A redirect URI named in the authorization request must be named again (RFC 6749 4.1.3)
+    let redirect_uri_matches = match &request.redirect_uri {
+        Some(uri) => *uri == authorization_code.redirect_uri,
+        None => !authorization_code.redirect_uri_given,
+    };
+    if authorization_code.used_at.is_some() {
+        // The code may have been stolen, so the token it was exchanged for is revoked too
+        // (RFC 6749 4.1.2)
+        let revoked = oauth_repository::revoke_code_tokens(&mut tx, &code_hash).await?;
+        tx.commit().await.map_err(DbError::from)?;
+        revocation::revoke(&revoked);
+        return Err(OAuthError::InvalidGrant);
+    }
+    if authorization_code.expires_at <= Utc::now()
+        || authorization_code.client_id != client.id
+        || !redirect_uri_matches
+        || !verify_pkce(code_verifier, &authorization_code.code_challenge)
+    {
+        return Err(OAuthError::InvalidGrant);
+    }
+    oauth_repository::use_authorization_code(&mut tx, &code_hash).await?;
+    let user = match user_repository::fetch_user_by_id(&mut tx, &authorization_code.user_id).await {
+        Err(DbError::NotFound) => return Err(OAuthError::InvalidGrant),
+        user => user?,
+    };
+
+    let AuthorizationCode { scopes, amr, .. } = authorization_code;
+    let grant = Grant {
+        user: &user,
+        scopes: &scopes,
+        amr,
+        code_hash: Some(&code_hash),
+    };
+    let response = issue_token(db, &mut tx, jwt, client, grant).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(response)
+}
+
+/// What a token is issued for.
+struct Grant<'a> {
+    /// The user the client acts for.
+    user: &'a User,
+    /// The permissions of the user that the token has.
+    scopes: &'a [String],
+    /// How the user authenticated, if they did.
+    amr: Vec<String>,
+    /// The hash of the authorization code the token is exchanged for, if any.
+    code_hash: Option<&'a str>,
+}
+
+/// Sign a token for a user, limited to the scopes of the grant, and remember it so that it can
+/// be revoked.
+async fn issue_token(
+    db: &DbPool,
+    tx: &mut Tx,
+    jwt: &JwtContext,
+    client: &OAuthClient,
+    grant: Grant<'_>,
+) -> Result<OAuthTokenResponse, AppError> {
+    let client_id = Some(client.client_id.clone());
+    let Grant {
+        user,
+        scopes,
+        amr,
+        code_hash,
+    } = grant;
+    let claims =
+        jwt::load_scoped_claims(db, jwt, user.id, &user.name, scopes, amr, client_id).await?;
+    oauth_repository::insert_access_token(
+        tx,
+        claims.jti(),
+        user.id,
+        client.id,
+        code_hash,
+        claims.expires_at(),
+    )
+    .await?;
+    let access_token = jwt::encode_jwt(jwt, &claims)?;
+    Ok(OAuthTokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: (claims.expires_at() - Utc::now()).num_seconds(),
+        scope: claims.permissions().join(" "),
+    })
+}
+
+/// Parse the scopes asked for, separated by spaces, which must all be allowed. Asking for none
+/// means all those allowed.
+fn parse_scope(scope: Option<&str>, allowed: &[String]) -> Result<Vec<String>, OAuthError> {
+    let mut scopes: Vec<String> = scope
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(String::from)
+        .collect();
+    if scopes.is_empty() {
+        return Ok(allowed.to_vec());
+    }
+    if !scopes.iter().all(|scope| allowed.contains(scope)) {
+        return Err(OAuthError::InvalidScope);
+    }
+    scopes.sort();
+    scopes.dedup();
+    Ok(scopes)
+}
+
+/// Check a PKCE code verifier against an S256 code challenge.
+fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || "-._~".contains(c);
+    (43..=128).contains(&code_verifier.len())
+        && code_verifier.chars().all(valid_char)
+        && pkce_challenge(code_verifier) == code_challenge
+}
+
+/// Derive the S256 code challenge of a code verifier.
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    base64::encode_config(
+        openssl::sha::sha256(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+/// Create a random, url-safe token.
+fn random_token(bytes: usize) -> Result<String, AppError> {
+    let mut buf = vec![0; bytes];
+    openssl::rand::rand_bytes(&mut buf).map_err(|e| {
+        AppError::CustomError(
+            format!("failed to generate token: {}", e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
+    Ok(base64::encode_config(buf, base64::URL_SAFE_NO_PAD))
+}
+
+/// Hash a client secret or an authorization code for storage.
+fn hash_secret(secret: &str) -> String {
+    base64::encode(openssl::sha::sha256(secret.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pkce_matches_rfc_7636_example() {
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+        assert_eq!(challenge, pkce_challenge(verifier));
+        assert!(verify_pkce(verifier, challenge));
+        assert!(!verify_pkce("short", &pkce_challenge("short")));
+        assert!(!verify_pkce(&verifier.replace('d', "e"), challenge));
+    }
+
+    #[test]
+    fn scopes_must_be_allowed() {
+        let allowed = vec!["accounts:read".to_string(), "transfers:read".to_string()];
+        assert_eq!(allowed, parse_scope(None, &allowed).unwrap());
+        assert_eq!(
+            vec!["accounts:read".to_string()],
+            parse_scope(Some("accounts:read accounts:read"), &allowed).unwrap()
+        );
+        assert!(matches!(
+            parse_scope(Some("accounts:read roles:manage"), &allowed),
+            Err(OAuthError::InvalidScope)
+        ));
+    }
+}
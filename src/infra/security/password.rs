@@ -0,0 +1,278 @@
+//! Hashing of passwords, and the policy that new passwords must follow.
+//!
+//! New passwords are hashed with Argon2id and stored in the PHC string format. Hashes made with
+//! bcrypt before are still accepted, and are replaced with Argon2id hashes when their user next
+//! signs in. New passwords must have an acceptable length, must not be on a list of breached
+//! passwords, and must not be one of the last passwords of the user.
+
+use crate::{
+    infra::{
+        configuration::PasswordSettings,
+        error::{AppError, ServiceError},
+    },
+    model::user_model::{HashedPassword, User},
+    repository::user_repository,
+    Tx,
+};
+use actix_http::StatusCode;
+use argon2::{
+    password_hash::{self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use std::{collections::HashSet, fmt};
+
+/// The memory cost in KiB, as recommended by OWASP.
+const MEMORY_COST: u32 = 19 * 1024;
+/// The number of passes over the memory.
+const ITERATIONS: u32 = 2;
+/// The number of lanes.
+const LANES: u32 = 1;
+/// The number of bytes of the hash.
+const HASH_BYTES: usize = 32;
+/// The number of bytes of salt.
+const SALT_BYTES: usize = 16;
+
+/// The parameters that new hashes are made with.
+fn params() -> Params {
+    Params::new(MEMORY_COST, ITERATIONS, LANES, Some(HASH_BYTES))
+        .expect("the Argon2 parameters are valid")
+}
+
+/// Hash a password with Argon2id and a random salt, in the PHC string format.
+pub(crate) fn hash(password: &str) -> Result<String, AppError> {
+    let failed = |e: &dyn fmt::Display| {
+        AppError::CustomError(
+            format!("failed to hash password: {}", e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    };
+    let mut salt = [0; SALT_BYTES];
+    openssl::rand::rand_bytes(&mut salt).map_err(|e| failed(&e))?;
+    let salt = SaltString::encode_b64(&salt).map_err(|e| failed(&e))?;
+    let hashed = Argon2::new(Algorithm::Argon2id, Version::V0x13, params())
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| failed(&e))?;
+    Ok(hashed.to_string())
+}
+
+/// Check a password against an Argon2 or bcrypt hash. Hashes that cannot be read never match.
+pub(crate) fn verify(password: &str, hash: &str) -> bool {
+    if hash.starts_with("$2") {
+        return bcrypt::verify(password, hash).unwrap_or_else(|e| {
+            tracing::error!("Failed to verify bcrypt password hash: {}", e);
+            false
+        });
+    }
+    let parsed = match PasswordHash::new(hash) {
+        Ok(parsed) => parsed,
+        // Deleted users have an empty password
+        Err(_) if hash.is_empty() => return false,
+        Err(e) => {
+            tracing::error!("Failed to read password hash: {}", e);
+            return false;
+        }
+    };
+    // The algorithm and parameters are taken from the hash
+    match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+        Ok(()) => true,
+        Err(password_hash::Error::Password) => false,
+        Err(e) => {
+            tracing::error!("Failed to verify password hash: {}", e);
+            false
+        }
+    }
+}
+
+/// Whether a hash should be replaced, because it was not made with Argon2id and the current
+/// parameters.
+pub(crate) fn needs_rehash(hash: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return true;
+    };
+    parsed.algorithm != Algorithm::Argon2id.ident()
+        || parsed.version != Some(Version::V0x13.into())
+        || Params::try_from(&parsed).ok() != Some(params())
+}
+
+/// The rules that new passwords must follow.
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    blocklist: HashSet<String>,
+    history_size: usize,
+}
+
+impl PasswordPolicy {
+    /// Create the policy from its settings, reading the list of breached passwords if there is
+    /// one.
+    pub fn new(settings: &PasswordSettings) -> Result<Self, AppError> {
+        let blocklist = match &settings.blocklist_file {
+            Some(file) => std::fs::read_to_string(file)
+                .map_err(|e| {
+                    AppError::CustomError(
+                        format!("failed to read password blocklist {}: {}", file, e),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    )
+                })?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect(),
+            None => HashSet::new(),
+        };
+        Ok(Self {
+            min_length: settings.min_length,
+            max_length: settings.max_length,
+            blocklist,
+            history_size: settings.history_size,
+        })
+    }
+
+    /// Check that a new password has an acceptable length and is not a breached password.
+    pub fn check(&self, password: &str) -> Result<(), ServiceError> {
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(ServiceError::ValidationError(format!(
+                "The password must have at least {} characters",
+                self.min_length
+            )));
+        }
+        if length > self.max_length {
+            return Err(ServiceError::ValidationError(format!(
+                "The password must have at most {} characters",
+                self.max_length
+            )));
+        }
+        if self.blocklist.contains(&password.to_lowercase()) {
+            return Err(ServiceError::ValidationError(
+                "The password is too common".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PasswordPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordPolicy")
+            .field("min_length", &self.min_length)
+            .field("max_length", &self.max_length)
+            .field("blocklist", &self.blocklist.len())
+            .field("history_size", &self.history_size)
+            .finish()
+    }
+}
+
+/// Give a user a new password that follows the policy and is not one of their last passwords.
+/// The password it replaces is kept in their history.
+#[tracing::instrument(skip_all, fields(user_id = user.id))]
+pub(crate) async fn change_password(
+    tx: &mut Tx,
+    policy: &PasswordPolicy,
+    user: &User,
+    new_password: &str,
+) -> Result<(), AppError> {
+    policy.check(new_password)?;
+    if policy.history_size > 0 {
+        let previous = user_repository::fetch_password_history(
+            &mut *tx,
+            user.id,
+            policy.history_size as i64 - 1,
+        )
+        .await?;
+        let mut recent = std::iter::once(&user.password).chain(&previous);
+        if recent.any(|hash| hash.verify(new_password)) {
+            return Err(ServiceError::ValidationError(format!(
+                "The password must not be one of the last {} passwords",
+                policy.history_size
+            ))
+            .into());
+        }
+        user_repository::insert_password_history(
+            &mut *tx,
+            user.id,
+            &user.password,
+            policy.history_size as i64 - 1,
+        )
+        .await?;
+    }
+    let password = HashedPassword::new(new_password)?;
+    user_repository::update_password(&mut *tx, user.id, &password).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 16,
+            blocklist: HashSet::from(["password1".to_string()]),
+            history_size: 3,
+        }
+    }
+
+    #[test]
+    fn passwords_are_hashed_with_argon2id() {
+        let hashed = hash("correct horse").unwrap();
+        assert!(hashed.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+        assert_ne!(hashed, hash("correct horse").unwrap());
+        assert!(verify("correct horse", &hashed));
+        assert!(!verify("wrong horse", &hashed));
+        assert!(!needs_rehash(&hashed));
+    }
+
+    #[test]
+    fn bcrypt_hashes_are_accepted_and_replaced() {
+        let hashed = bcrypt::hash("correct horse", 4).unwrap();
+        assert!(verify("correct horse", &hashed));
+        assert!(!verify("wrong horse", &hashed));
+        assert!(needs_rehash(&hashed));
+    }
+
+    #[test]
+    fn hashes_with_other_parameters_are_replaced() {
+        let salt = SaltString::encode_b64(b"0123456789abcdef").unwrap();
+        let weaker = Params::new(MEMORY_COST, 1, LANES, Some(HASH_BYTES)).unwrap();
+        for argon2 in [
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, weaker),
+            Argon2::new(Algorithm::Argon2i, Version::V0x13, params()),
+        ] {
+            let hashed = argon2
+                .hash_password(b"correct horse", &salt)
+                .unwrap()
+                .to_string();
+            assert!(verify("correct horse", &hashed));
+            assert!(needs_rehash(&hashed));
+        }
+    }
+
+    #[test]
+    fn malformed_hashes_never_match() {
+        for hashed in [
+            "",
+            "plain",
+            "$2a$10$short",
+            "$argon2id$v=19$m=19456,t=2,p=1$c2FsdA",
+            "$argon2id$v=19$m=x,t=2,p=1$c2FsdHNhbHQ$aGFzaA",
+            "$scrypt$ln=16,r=8,p=1$c2FsdHNhbHQ$aGFzaA",
+        ] {
+            assert!(!verify("plain", hashed), "{}", hashed);
+            assert!(needs_rehash(hashed), "{}", hashed);
+        }
+    }
+
+    #[test]
+    fn policy_checks_length_and_blocklist() {
+        let policy = policy();
+        assert!(policy.check("correct horse").is_ok());
+        assert!(policy.check("short").is_err());
+        assert!(policy.check("a much too long password").is_err());
+        assert!(policy.check("PassWord1").is_err());
+        // Length is counted in characters, not bytes
+        assert!(policy.check("ääääääää").is_ok());
+    }
+}
@@ -0,0 +1,76 @@
+//! The list of access tokens that were revoked before they expired.
+//!
+//! [`decode_jwt`](super::jwt::decode_jwt) runs in synchronous code such as the gRPC interceptor,
+//! so it cannot ask the database. Instead it checks this in-memory list, which is updated as soon
+//! as this instance revokes a token, and synchronized from the database by
+//! [`run_token_revocation`](crate::worker::token_revocation::run_token_revocation) to pick up
+//! tokens revoked by other instances.
+
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+};
+use uuid::Uuid;
+
+/// The ids of revoked tokens, with when the tokens expire.
+static REVOKED: LazyLock<RwLock<HashMap<Uuid, DateTime<Utc>>>> = LazyLock::new(Default::default);
+
+/// An access token that was revoked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RevokedToken {
+    /// The `jti` claim of the token.
+    pub jti: Uuid,
+    /// When the token expires, after which it no longer needs to be remembered.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Remember revoked tokens.
+pub fn revoke(tokens: &[RevokedToken]) {
+    let mut revoked = REVOKED.write().unwrap_or_else(|e| e.into_inner());
+    for token in tokens {
+        revoked.insert(token.jti, token.expires_at);
+    }
+}
+
+/// Check whether a token has been revoked.
+pub fn is_revoked(jti: &Uuid) -> bool {
+    REVOKED
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains_key(jti)
+}
+
+/// Forget the tokens that expired before `now`, which are rejected anyway.
+pub fn prune(now: DateTime<Utc>) {
+    REVOKED
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|_, expires_at| *expires_at > now);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn revoked_tokens_are_forgotten_after_they_expire() {
+        let now = Utc::now();
+        let expired = RevokedToken {
+            jti: Uuid::new_v4(),
+            expires_at: now - Duration::minutes(1),
+        };
+        let live = RevokedToken {
+            jti: Uuid::new_v4(),
+            expires_at: now + Duration::minutes(1),
+        };
+        revoke(&[expired, live]);
+        assert!(is_revoked(&expired.jti));
+        assert!(is_revoked(&live.jti));
+
+        prune(now);
+        assert!(!is_revoked(&expired.jti));
+        assert!(is_revoked(&live.jti));
+    }
+}
@@ -0,0 +1,288 @@
+//! Sessions that keep users signed in with short-lived access tokens.
+//!
+//! Signing in starts a session and returns an access token together with an opaque refresh
+//! token. A refresh token can be exchanged once for a new pair of tokens, and only its hash is
+//! stored. Presenting a refresh token a second time means that it has leaked, so the whole
+//! session is revoked, including the access tokens issued in it.
+//!
+//! Users who have confirmed a TOTP secret get an [`MfaChallenge`] instead of tokens, and only
+//! get tokens once they complete it with a code or a recovery code. The methods they used are
+//! kept with the session and end up in the `amr` claim.
+
+use crate::{
+    infra::{
+        configuration::{LoginSettings, MfaSettings},
+        error::{AppError, DbError, ServiceError},
+        security::{
+            jwt::{self, Claims, JwtContext, AMR_MFA, AMR_OTP, AMR_PASSWORD},
+            lockout, mfa, revocation,
+        },
+    },
+    model::{
+        mfa_model::{MfaChallenge, MfaVerification},
+        session_model::{SignIn, TokenResponse},
+    },
+    repository::{mfa_repository, session_repository, user_repository},
+    DbPool, Tx,
+};
+use actix_http::StatusCode;
+use chrono::{Duration, Utc};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// The number of random bytes in a refresh token.
+const REFRESH_TOKEN_BYTES: usize = 32;
+
+/// Authenticate a user signing in from `address` and start a new session, or a challenge for
+/// the second factor if the user has one.
+#[tracing::instrument(skip(db, context, settings, login, password))]
+pub async fn start_session(
+    db: &DbPool,
+    context: &JwtContext,
+    settings: &MfaSettings,
+    login: &LoginSettings,
+    username: &str,
+    password: &str,
+    address: Option<IpAddr>,
+) -> Result<SignIn, AppError> {
+    let user_id = lockout::authenticate(db, login, username, password, address).await?;
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let sign_in = if mfa_repository::is_enrolled(&mut tx, user_id).await? {
+        SignIn::MfaRequired(start_challenge(&mut tx, settings, user_id).await?)
+    } else {
+        let amr = vec![AMR_PASSWORD.to_string()];
+        SignIn::Tokens(open_session(&mut tx, db, context, user_id, username, amr).await?)
+    };
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(sign_in)
+}
+
+/// Complete a sign-in with the second factor, and start a new session.
+#[tracing::instrument(skip(db, context, settings))]
+pub async fn complete_mfa(
+    db: &DbPool,
+    context: &JwtContext,
+    settings: &MfaSettings,
+    verification: &MfaVerification,
+) -> Result<TokenResponse, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let Some((user_id, amr)) = verify_challenge(&mut tx, settings, verification).await? else {
+        // Keep the attempt, so that codes cannot be guessed
+        tx.commit().await.map_err(DbError::from)?;
+        return Err(AppError::AuthenticationError);
+    };
+
+    let user = match user_repository::fetch_user_by_id(&mut tx, &user_id).await {
+        Err(DbError::NotFound) => return Err(AppError::AuthenticationError),
+        user => user?,
+    };
+    let tokens = open_session(&mut tx, db, context, user.id, &user.name, amr).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(tokens)
+}
+
+/// Start a challenge for the second factor of a user who gave their password.
+pub(crate) async fn start_challenge(
+    tx: &mut Tx,
+    settings: &MfaSettings,
+    user_id: i32,
+) -> Result<MfaChallenge, AppError> {
+    let expires_in = Duration::minutes(settings.challenge_minutes_to_live);
+    let mfa_token = mfa_repository::insert_challenge(tx, user_id, Utc::now() + expires_in).await?;
+    Ok(MfaChallenge {
+        mfa_token,
+        expires_in: expires_in.num_seconds(),
+    })
+}
+
+/// Check the second factor given for a challenge, and return the user and how they
+/// authenticated. A wrong code returns `None` but still counts as an attempt, so the
+/// transaction should be committed either way.
+pub(crate) async fn verify_challenge(
+    tx: &mut Tx,
+    settings: &MfaSettings,
+    verification: &MfaVerification,
+) -> Result<Option<(i32, Vec<String>)>, AppError> {
+    let now = Utc::now();
+    let challenge = match mfa_repository::lock_challenge(tx, verification.mfa_token).await {
+        Err(DbError::NotFound) => return Err(AppError::AuthenticationError),
+        challenge => challenge?,
+    };
+    if challenge.completed_at.is_some()
+        || challenge.expires_at <= now
+        || challenge.attempts >= settings.max_attempts
+    {
+        return Err(AppError::AuthenticationError);
+    }
+    mfa_repository::count_attempt(tx, challenge.id).await?;
+
+    let amr = match (&verification.code, &verification.recovery_code) {
+        (Some(code), None) => {
+            let secret = mfa_repository::lock_totp_secret(tx, challenge.user_id).await?;
+            match mfa::verify_code(&secret.secret, code, now, secret.last_used_step)? {
+                Some(step) => {
+                    mfa_repository::use_totp_step(tx, challenge.user_id, step).await?;
+                    Some(vec![AMR_PASSWORD, AMR_OTP, AMR_MFA])
+                }
+                None => None,
+            }
+        }
+        (None, Some(recovery_code)) => {
+            let code_hash = mfa::hash_recovery_code(recovery_code);
+            mfa_repository::use_recovery_code(tx, challenge.user_id, &code_hash)
+                .await?
+                .then(|| vec![AMR_PASSWORD, AMR_MFA])
+        }
+        _ => {
+            return Err(ServiceError::ValidationError(
+                "Give either a code or a recovery code".to_string(),
+            )
+            .into())
+        }
+    };
+    let Some(amr) = amr else {
+        return Ok(None);
+    };
+
+    mfa_repository::complete_challenge(tx, challenge.id).await?;
+    let amr = amr.into_iter().map(String::from).collect();
+    Ok(Some((challenge.user_id, amr)))
+}
+
+/// Exchange a refresh token for new tokens with the current roles and permissions of the user.
+#[tracing::instrument(skip_all)]
+pub async fn refresh_session(
+    db: &DbPool,
+    context: &JwtContext,
+    refresh_token: &str,
+) -> Result<TokenResponse, AppError> {
+    let token_hash = hash_token(refresh_token);
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let token = match session_repository::lock_refresh_token(&mut tx, &token_hash).await {
+        Err(DbError::NotFound) => return Err(AppError::AuthenticationError),
+        token => token?,
+    };
+
+    if token.used_at.is_some() {
+        tracing::warn!(
+            "Refresh token of session {} was reused, revoking the session",
+            token.session_id
+        );
+        let revoked = session_repository::revoke_session(&mut tx, token.session_id).await?;
+        tx.commit().await.map_err(DbError::from)?;
+        revocation::revoke(&revoked);
+        return Err(AppError::AuthenticationError);
+    }
+    if token.revoked_at.is_some() || token.expires_at <= Utc::now() {
+        return Err(AppError::AuthenticationError);
+    }
+    session_repository::use_refresh_token(&mut tx, &token_hash).await?;
+
+    // Users that have been deleted since signing in get no new tokens
+    let user = match user_repository::fetch_user_by_id(&mut tx, &token.user_id).await {
+        Err(DbError::NotFound) => return Err(AppError::AuthenticationError),
+        user => user?,
+    };
+    let claims = jwt::load_claims(
+        db,
+        context,
+        user.id,
+        &user.name,
+        Some(token.session_id),
+        token.amr,
+    )
+    .await?;
+    let tokens = issue_tokens(&mut tx, context, &claims, token.session_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(tokens)
+}
+
+/// Revoke a session, so that neither its refresh token nor its access tokens work any more.
+#[tracing::instrument(skip(db))]
+pub async fn end_session(db: &DbPool, session_id: Uuid) -> Result<(), AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let revoked = session_repository::revoke_session(&mut tx, session_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    revocation::revoke(&revoked);
+    Ok(())
+}
+
+/// Start a session for a user who authenticated with the methods in `amr`.
+async fn open_session(
+    tx: &mut Tx,
+    db: &DbPool,
+    context: &JwtContext,
+    user_id: i32,
+    username: &str,
+    amr: Vec<String>,
+) -> Result<TokenResponse, AppError> {
+    let session_id = session_repository::insert_session(tx, user_id, &amr).await?;
+    let claims = jwt::load_claims(db, context, user_id, username, Some(session_id), amr).await?;
+    issue_tokens(tx, context, &claims, session_id).await
+}
+
+/// Sign the claims and create a refresh token, storing both in the session.
+async fn issue_tokens(
+    tx: &mut Tx,
+    context: &JwtContext,
+    claims: &Claims,
+    session_id: Uuid,
+) -> Result<TokenResponse, AppError> {
+    let refresh_token = generate_token()?;
+    let refresh_expires_at =
+        Utc::now() + Duration::days(context.settings().refresh_token_days_to_live);
+    session_repository::insert_refresh_token(
+        tx,
+        session_id,
+        &hash_token(&refresh_token),
+        refresh_expires_at,
+    )
+    .await?;
+    session_repository::insert_access_token(
+        tx,
+        claims.jti(),
+        claims.id(),
+        session_id,
+        claims.expires_at(),
+    )
+    .await?;
+
+    Ok(TokenResponse {
+        access_token: jwt::encode_jwt(context, claims)?,
+        token_type: "Bearer".to_string(),
+        expires_in: (claims.expires_at() - Utc::now()).num_seconds(),
+        refresh_token,
+    })
+}
+
+/// Create a random, url-safe token.
+fn generate_token() -> Result<String, AppError> {
+    let mut bytes = [0; REFRESH_TOKEN_BYTES];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| {
+        AppError::CustomError(
+            format!("failed to generate token: {}", e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
+    Ok(base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+}
+
+/// Hash a token for storage.
+fn hash_token(token: &str) -> String {
+    base64::encode(openssl::sha::sha256(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_random_and_stored_hashed() {
+        let token = generate_token().unwrap();
+        assert_eq!(43, token.len());
+        assert_ne!(token, generate_token().unwrap());
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(token, hash_token(&token));
+    }
+}
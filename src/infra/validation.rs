@@ -126,7 +126,7 @@ mod tests {
         let email = Email {
             email: "not_an_email".to_string(),
         };
-        assert!(matches!(Validated::new(email), Err(_)));
+        assert!(Validated::new(email).is_err());
     }
 
     #[test]
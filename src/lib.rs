@@ -10,8 +10,10 @@
 use crate::graphql::schema::create_schema;
 use crate::grpc::account::AccountServiceImpl;
 use crate::grpc::string::MyStringService;
+use crate::infra::configuration::{LoginSettings, MfaSettings};
 use crate::infra::middleware::{DigestFilter, SignatureFilter};
-use crate::infra::security::jwt::{jwt_interceptor, Role};
+use crate::infra::security::jwt::{jwt_interceptor, JwtContext, Role};
+use crate::infra::security::password::PasswordPolicy;
 use crate::infra::{configuration, middleware, security};
 use actix_cors::Cors;
 use actix_web::web::{Json, Payload};
@@ -26,7 +28,7 @@ use paperclip::v2::models::{DefaultApiRaw, Info, SecurityScheme};
 use rest::{
     client_context::client_context,
     health_check::health,
-    token::{request_token, verify_token},
+    token::{complete_mfa, logout, refresh_token, request_token, verify_token},
 };
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Postgres, Transaction};
@@ -41,6 +43,7 @@ pub mod infra;
 pub mod model;
 pub mod repository;
 pub mod rest;
+pub mod worker;
 
 /// A common response type for services.
 pub type AppResult<T> = Result<T, AppError>;
@@ -52,31 +55,67 @@ pub type DbPool = PgPool;
 pub type Tx = Transaction<'static, Postgres>;
 
 /// Starts the gRPC server.
-pub async fn run_grpc(addr: SocketAddr, db: DbPool) -> anyhow::Result<()> {
+#[allow(clippy::result_large_err)]
+pub async fn run_grpc(addr: SocketAddr, db: DbPool, jwt: JwtContext) -> anyhow::Result<()> {
     tracing::info!("Starting tonic on address {}", addr);
 
     tonic::transport::Server::builder()
-        .layer(interceptor(jwt_interceptor))
-        .add_service(StringServiceServer::new(MyStringService::default()))
+        .layer(interceptor(move |request| jwt_interceptor(&jwt, request)))
+        .add_service(StringServiceServer::new(MyStringService))
         .add_service(AccountServiceServer::new(AccountServiceImpl::new(db)))
         .serve(addr)
         .await
         .map_err(Into::into)
 }
 
+/// Starts the background workers.
+pub async fn run_workers(db: DbPool, jwt: JwtContext) -> anyhow::Result<()> {
+    let settings = configuration::load_configuration()?;
+    tokio::try_join!(
+        worker::scheduled_transfer::run_scheduled_transfers(db.clone(), settings.scheduler),
+        worker::payment_import::run_payment_imports(db.clone(), settings.scheduler),
+        worker::account_product::run_account_products(db.clone(), settings.scheduler),
+        worker::hold_expiry::run_hold_expiry(db.clone(), settings.scheduler),
+        worker::idempotency_expiry::run_idempotency_expiry(db.clone(), settings.scheduler),
+        worker::token_revocation::run_token_revocation(db.clone(), settings.scheduler),
+        worker::jwt_keys::run_jwt_keys(db, jwt, settings.scheduler),
+    )?;
+    Ok(())
+}
+
 /// Starts a [`Server`].
-pub fn run_actix(http_listener: TcpListener, db_pool: DbPool) -> anyhow::Result<Server> {
+pub fn run_actix(
+    http_listener: TcpListener,
+    db_pool: DbPool,
+    jwt: JwtContext,
+) -> anyhow::Result<Server> {
     tracing::info!("Starting actix on address {}", http_listener.local_addr()?,);
+    let settings = configuration::load_configuration()?;
     let pool = web::Data::new(db_pool.clone());
+    let idempotency = web::Data::new(settings.idempotency);
+    let users = web::Data::new(settings.users);
+    let mfa = web::Data::new(settings.mfa);
+    let login = web::Data::new(settings.login);
+    let oauth = web::Data::new(settings.oauth);
+    let passwords = web::Data::new(PasswordPolicy::new(&settings.passwords)?);
+    // Hash it now rather than on the first sign-in, so that a failure stops the server
+    repository::user_repository::unknown_user_password();
+    let jwt = web::Data::new(jwt);
     let schema = Arc::new(create_schema(db_pool));
     let server = HttpServer::new(move || {
         App::new()
             // Database pool
             .app_data(pool.clone())
+            .app_data(idempotency.clone())
+            .app_data(users.clone())
+            .app_data(mfa.clone())
+            .app_data(login.clone())
+            .app_data(oauth.clone())
+            .app_data(passwords.clone())
+            .app_data(jwt.clone())
             // Set default content type
             .wrap(middleware::HeaderSetter::new())
             // Middleware to apply to all requests
-            .wrap(middleware::Authenticator::new())
             .wrap(TracingLogger::default())
             // GraphQL
             .app_data(web::Data::from(schema.clone()))
@@ -86,7 +125,17 @@ pub fn run_actix(http_listener: TcpListener, db_pool: DbPool) -> anyhow::Result<
             // Health check
             .service(health)
             .service(request_token)
+            .service(refresh_token)
+            .service(complete_mfa)
+            .service(logout)
             .service(verify_token)
+            .service(rest::key_api::jwks)
+            // OAuth2
+            .service(rest::oauth_api::openid_configuration)
+            .service(rest::oauth_api::authorize)
+            .service(rest::oauth_api::consent)
+            .service(rest::oauth_api::token)
+            .service(rest::user_api::register)
             // Other
             .service(client_context)
             // Api
@@ -94,9 +143,23 @@ pub fn run_actix(http_listener: TcpListener, db_pool: DbPool) -> anyhow::Result<
                 web::scope("/api")
                     .wrap(middleware::AuthenticationFilter::new())
                     .wrap(middleware::RequestLogger::new())
+                    // Only here, so that signing in does not also authenticate the credentials
+                    .wrap(middleware::Authenticator::new())
                     .configure(rest::account_api::account_config)
                     .configure(rest::user_api::user_config)
                     .configure(rest::transfer_api::transfer_config)
+                    .configure(rest::ledger_api::ledger_config)
+                    .configure(rest::currency_api::currency_config)
+                    .configure(rest::limit_api::limit_config)
+                    .configure(rest::payment_import_api::payment_import_config)
+                    .configure(rest::product_api::product_config)
+                    .configure(rest::hold_api::hold_config)
+                    .configure(rest::member_api::member_config)
+                    .configure(rest::role_api::role_config)
+                    .configure(rest::key_api::key_config)
+                    .configure(rest::mfa_api::mfa_config)
+                    .configure(rest::api_key_api::api_key_config)
+                    .configure(rest::oauth_api::oauth_client_config)
                     // Secure endpoints
                     .route("/user", web::get().to(user))
                     .route("/admin", web::get().to(admin)),
@@ -123,14 +186,23 @@ pub fn run_actix(http_listener: TcpListener, db_pool: DbPool) -> anyhow::Result<
 }
 
 /// Starts the axum server.
-pub async fn run_axum(addr: SocketAddr, db_pool: DbPool) -> Result<(), hyper::Error> {
+pub async fn run_axum(
+    addr: SocketAddr,
+    db_pool: DbPool,
+    jwt: JwtContext,
+    mfa: MfaSettings,
+    login: LoginSettings,
+) -> Result<(), hyper::Error> {
     let svc = axum::Router::new()
         .route("/health", axum::routing::get(rest::health_check::health2))
         .route("/token", axum::routing::post(rest::token::request_token2))
         .route("/verify", axum::routing::get(rest::token::verify_token2))
-        .layer(axum::Extension(db_pool));
+        .layer(axum::Extension(db_pool))
+        .layer(axum::Extension(jwt))
+        .layer(axum::Extension(mfa))
+        .layer(axum::Extension(login));
     axum::Server::bind(&addr)
-        .serve(svc.into_make_service())
+        .serve(svc.into_make_service_with_connect_info::<SocketAddr>())
         .await
 }
 
@@ -1,4 +1,7 @@
-use actix_web_demo::infra::configuration::load_configuration;
+use actix_web_demo::infra::{
+    configuration::load_configuration,
+    security::{jwt::JwtContext, keys},
+};
 use sqlx::{
     pool::PoolOptions,
     postgres::{PgConnectOptions, PgSslMode},
@@ -32,19 +35,28 @@ async fn main() -> anyhow::Result<()> {
         tokio::time::sleep(Duration::from_secs(30)).await;
     }
 
+    // Load the jwt keys once, storing the configured ones and loading the ones in use
+    let jwt = JwtContext::new(configuration.security.clone())?;
+    keys::initialize_keys(&db_pool, &jwt).await?;
+
     let grpc_addr = format!(
         "{}:{}",
         configuration.server.grpc_address, configuration.server.grpc_port
     )
     .parse()?;
-    let grpc = actix_web_demo::run_grpc(grpc_addr, db_pool.clone());
+    let grpc = actix_web_demo::run_grpc(grpc_addr, db_pool.clone(), jwt.clone());
     tokio::spawn(grpc);
 
     tokio::spawn(actix_web_demo::run_axum(
         "0.0.0.0:8081".parse()?,
         db_pool.clone(),
+        jwt.clone(),
+        configuration.mfa.clone(),
+        configuration.login,
     ));
 
+    tokio::spawn(actix_web_demo::run_workers(db_pool.clone(), jwt.clone()));
+
     // Create http listener
     let http_addr = format!(
         "{}:{}",
@@ -53,7 +65,7 @@ async fn main() -> anyhow::Result<()> {
     let http_listener = TcpListener::bind(http_addr)?;
 
     // Start application
-    actix_web_demo::run_actix(http_listener, db_pool.clone())?.await?;
+    actix_web_demo::run_actix(http_listener, db_pool.clone(), jwt)?.await?;
 
     Ok(())
 }
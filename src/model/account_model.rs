@@ -1,19 +1,36 @@
 //! Account related types.
 
+use super::{
+    money::{Amount, CurrencyCode},
+    pagination::{default_page_size, SortOrder, MAX_PAGE_SIZE},
+};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use validator::Validate;
 
 /// A new account.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NewAccount {
     name: String,
+    #[serde(default)]
+    currency: CurrencyCode,
 }
 
 impl NewAccount {
-    /// Creates a new account.
+    /// Creates a new account in the default currency.
     #[must_use]
     pub fn new(name: String) -> Self {
-        Self { name }
+        Self {
+            name,
+            currency: CurrencyCode::default(),
+        }
+    }
+
+    /// Use a different currency for the new account.
+    #[must_use]
+    pub fn with_currency(mut self, currency: CurrencyCode) -> Self {
+        self.currency = currency;
+        self
     }
 
     /// Get a reference to the new account's name.
@@ -21,6 +38,43 @@ impl NewAccount {
     pub fn name(&self) -> &str {
         self.name.as_ref()
     }
+
+    /// Get a reference to the new account's currency.
+    #[must_use]
+    pub fn currency(&self) -> &CurrencyCode {
+        &self.currency
+    }
+}
+
+/// Changes to an existing account.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountUpdate {
+    name: String,
+}
+
+impl AccountUpdate {
+    /// Creates a new account update.
+    #[must_use]
+    pub fn new(name: String) -> Self {
+        Self { name }
+    }
+
+    /// Get a reference to the account's new name.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+}
+
+/// Whether an account can still be used.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "account_status")]
+pub enum AccountStatus {
+    /// The account is in use.
+    #[default]
+    Open,
+    /// The account has been closed and can no longer move money.
+    Closed,
 }
 
 /// An existing account.
@@ -30,21 +84,42 @@ pub struct Account {
     pub id: i32,
     /// The name of the account.
     pub name: String,
-    /// The current balance of the account.
+    /// The current balance of the account, in the minor unit of its currency.
     pub balance: i64,
+    /// The currency of the account.
+    pub currency: CurrencyCode,
     /// The owner of the account.
     pub owner_id: i32,
+    /// The status of the account.
+    pub status: AccountStatus,
+    /// How far below zero the balance may go.
+    pub overdraft_limit: i64,
+    /// How much may be withdrawn or transferred out of the account per day, if limited.
+    pub daily_limit: Option<i64>,
+    /// How much may be withdrawn or transferred out of the account per month, if limited.
+    pub monthly_limit: Option<i64>,
+    /// The product of the account, which decides its interest and fees, if any.
+    pub product_id: Option<i32>,
+    /// The amount reserved by active holds, which cannot be spent.
+    pub held: i64,
 }
 
 impl Account {
-    /// Creates a new account.
+    /// Creates a new open account in the default currency.
     #[must_use]
     pub fn new(id: i32, name: String, balance: i64, owner_id: i32) -> Self {
         Self {
             id,
             name,
             balance,
+            currency: CurrencyCode::default(),
             owner_id,
+            status: AccountStatus::Open,
+            overdraft_limit: 0,
+            daily_limit: None,
+            monthly_limit: None,
+            product_id: None,
+            held: 0,
         }
     }
 
@@ -66,29 +141,115 @@ impl Account {
         self.balance
     }
 
+    /// Get a reference to the account's currency.
+    #[must_use]
+    pub fn currency(&self) -> &CurrencyCode {
+        &self.currency
+    }
+
     /// Get the account's owner id.
     #[must_use]
     pub fn owner_id(&self) -> i32 {
         self.owner_id
     }
+
+    /// Get the account's status.
+    #[must_use]
+    pub fn status(&self) -> AccountStatus {
+        self.status
+    }
+
+    /// Get the account's overdraft limit.
+    #[must_use]
+    pub fn overdraft_limit(&self) -> i64 {
+        self.overdraft_limit
+    }
+
+    /// Get the amount reserved by active holds.
+    #[must_use]
+    pub fn held(&self) -> i64 {
+        self.held
+    }
+
+    /// Get the amount that can be taken out of the account before reaching the overdraft limit,
+    /// not counting money reserved by holds.
+    #[must_use]
+    pub fn available_balance(&self) -> i64 {
+        self.balance + self.overdraft_limit - self.held
+    }
+
+    /// Get the account's product id, if any.
+    #[must_use]
+    pub fn product_id(&self) -> Option<i32> {
+        self.product_id
+    }
+
+    /// Check if the account is open.
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        self.status == AccountStatus::Open
+    }
+}
+
+/// Fields that accounts can be sorted by.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountSortField {
+    /// Sort by account id.
+    #[default]
+    Id,
+    /// Sort by account name.
+    Name,
+    /// Sort by balance.
+    Balance,
+}
+
+impl AccountSortField {
+    /// The name of the field as used in queries.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountSortField::Id => "id",
+            AccountSortField::Name => "name",
+            AccountSortField::Balance => "balance",
+        }
+    }
+}
+
+/// Parameters for listing accounts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct AccountQuery {
+    /// The zero-indexed page to fetch.
+    #[serde(default)]
+    pub page: u32,
+    /// The number of accounts on each page.
+    #[serde(default = "default_page_size")]
+    #[validate(range(min = 1, max = "MAX_PAGE_SIZE"))]
+    pub size: u32,
+    /// The field to sort by.
+    #[serde(default)]
+    pub sort: AccountSortField,
+    /// The direction to sort in.
+    #[serde(default)]
+    pub order: SortOrder,
 }
 
 /// A deposit.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Deposit {
-    amount: u32,
+    amount: Amount,
 }
 
 impl Deposit {
     /// Creates a new deposit.
     #[must_use]
-    pub fn new(amount: u32) -> Self {
+    pub fn new(amount: Amount) -> Self {
         Self { amount }
     }
 
-    /// Get the deposit's amount.
+    /// Get the deposit's amount, in the minor unit of the account's currency.
     #[must_use]
-    pub fn amount(&self) -> u32 {
+    pub fn amount(&self) -> Amount {
         self.amount
     }
 }
@@ -96,19 +257,19 @@ impl Deposit {
 /// A withdrawal.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Withdrawal {
-    amount: u32,
+    amount: Amount,
 }
 
 impl Withdrawal {
     /// Creates a new withdrawal.
     #[must_use]
-    pub fn new(amount: u32) -> Self {
+    pub fn new(amount: Amount) -> Self {
         Self { amount }
     }
 
-    /// Get the withdrawal's amount.
+    /// Get the withdrawal's amount, in the minor unit of the account's currency.
     #[must_use]
-    pub fn amount(&self) -> u32 {
+    pub fn amount(&self) -> Amount {
         self.amount
     }
 }
@@ -0,0 +1,71 @@
+//! Models for personal API keys, which scripts use instead of a user's password.
+
+use super::permission_model::validate_permission_name;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+/// Check that a key has at least one scope, and that each is a permission name.
+pub(crate) fn validate_scopes(scopes: &[String]) -> Result<(), ValidationError> {
+    if scopes.is_empty() {
+        return Err(ValidationError::new("scopes"));
+    }
+    scopes
+        .iter()
+        .try_for_each(|scope| validate_permission_name(scope))
+}
+
+/// A new API key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct NewApiKey {
+    /// A name for telling keys apart, such as the script that uses it.
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    /// The permissions the key is limited to, which the user must have.
+    #[validate(custom = "validate_scopes")]
+    pub scopes: Vec<String>,
+    /// When the key stops working, if ever.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A stored API key, without the key itself.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// The id of the key.
+    pub id: i32,
+    /// The user the key acts for.
+    pub user_id: i32,
+    /// The name of the key.
+    pub name: String,
+    /// The start of the key, for recognizing it.
+    pub prefix: String,
+    /// The permissions the key is limited to.
+    pub scopes: Vec<String>,
+    /// When the key stops working, if ever.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// When the key was last used.
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// When the key was revoked.
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// When the key was created.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A key that was just created. This is the only time the key itself is shown.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedApiKey {
+    /// The stored key.
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    /// The key to send in an `Authorization: ApiKey <key>` header.
+    pub key: String,
+}
+
+impl std::fmt::Debug for CreatedApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CreatedApiKey")
+            .field("api_key", &self.api_key)
+            .finish_non_exhaustive()
+    }
+}
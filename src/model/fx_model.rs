@@ -0,0 +1,28 @@
+//! Models representing exchange rates between currencies.
+
+use super::money::CurrencyCode;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// The rate for converting from one currency to another.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FxRate {
+    /// The currency converted from.
+    pub base_currency: CurrencyCode,
+    /// The currency converted to.
+    pub quote_currency: CurrencyCode,
+    /// The value of one unit of the base currency in the quote currency.
+    pub rate: Decimal,
+    /// When the rate was last changed.
+    pub updated_at: DateTime<Utc>,
+    /// The user that last changed the rate.
+    pub updated_by: Option<i32>,
+}
+
+/// A new or updated exchange rate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewFxRate {
+    /// The value of one unit of the base currency in the quote currency.
+    pub rate: Decimal,
+}
@@ -0,0 +1,125 @@
+//! Models representing holds, which reserve money on an account before it is captured.
+
+use super::money::Amount;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The longest time a hold can reserve money for.
+pub const MAX_HOLD_DAYS: i64 = 30;
+
+/// A new hold.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewHold {
+    /// The amount to reserve, in the minor unit of the account's currency.
+    pub amount: Amount,
+    /// When the hold is released if it has not been captured.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl NewHold {
+    /// Check if the expiry is in the future, and at most [`MAX_HOLD_DAYS`] after `now`.
+    #[must_use]
+    pub fn expires_in_range(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at > now && self.expires_at <= now + Duration::days(MAX_HOLD_DAYS)
+    }
+}
+
+/// The state of a hold.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "hold_status")]
+pub enum HoldStatus {
+    /// The money is reserved.
+    Active,
+    /// The money, or part of it, has been taken out of the account.
+    Captured,
+    /// The hold was released before it expired.
+    Released,
+    /// The hold was released because it expired.
+    Expired,
+}
+
+/// A stored hold.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hold {
+    /// The id of the hold.
+    pub id: i32,
+    /// The account the money is reserved on.
+    pub account_id: i32,
+    /// The amount reserved, in the minor unit of the account's currency.
+    pub amount: i64,
+    /// The amount taken out of the account, if the hold was captured.
+    pub captured_amount: Option<i64>,
+    /// The state of the hold.
+    pub status: HoldStatus,
+    /// When the hold is released if it has not been captured.
+    pub expires_at: DateTime<Utc>,
+    /// When the hold was created.
+    pub created_at: DateTime<Utc>,
+    /// When the hold was captured or released.
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+impl Hold {
+    /// Check if the hold still reserves money and can be captured at `now`.
+    #[must_use]
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.status == HoldStatus::Active && self.expires_at > now
+    }
+}
+
+/// A capture of a hold.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HoldCapture {
+    /// The amount to take out of the account. The whole hold is captured if not set,
+    /// and the rest of the hold is released if it is less.
+    pub amount: Option<Amount>,
+}
+
+/// Parameters for listing holds.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HoldQuery {
+    /// Only include holds in this state.
+    pub status: Option<HoldStatus>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hold, HoldStatus, NewHold};
+    use crate::model::money::Amount;
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn expiry_must_be_in_range() {
+        let now = Utc.with_ymd_and_hms(2022, 10, 12, 12, 0, 0).unwrap();
+        let hold = |expires_at| NewHold {
+            amount: Amount::new(100).unwrap(),
+            expires_at,
+        };
+        assert!(hold(now + Duration::hours(1)).expires_in_range(now));
+        assert!(hold(now + Duration::days(30)).expires_in_range(now));
+        assert!(!hold(now + Duration::days(31)).expires_in_range(now));
+        assert!(!hold(now).expires_in_range(now));
+    }
+
+    #[test]
+    fn expired_hold_is_not_active() {
+        let now = Utc.with_ymd_and_hms(2022, 10, 12, 12, 0, 0).unwrap();
+        let hold = Hold {
+            id: 1,
+            account_id: 1,
+            amount: 100,
+            captured_amount: None,
+            status: HoldStatus::Active,
+            expires_at: now + Duration::minutes(5),
+            created_at: now,
+            closed_at: None,
+        };
+        assert!(hold.is_active(now));
+        assert!(!hold.is_active(now + Duration::minutes(5)));
+        let released = Hold {
+            status: HoldStatus::Released,
+            ..hold
+        };
+        assert!(!released.is_active(now));
+    }
+}
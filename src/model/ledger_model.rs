@@ -0,0 +1,73 @@
+//! Models representing the double-entry ledger behind account balances.
+
+use super::{
+    money::CurrencyCode,
+    pagination::{default_page_size, MAX_PAGE_SIZE},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// What caused money to move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "ledger_entry_kind")]
+pub enum LedgerEntryKind {
+    /// The balance an account had when the ledger was introduced.
+    Opening,
+    /// Money deposited into an account.
+    Deposit,
+    /// Money withdrawn from an account.
+    Withdrawal,
+    /// Money transferred between accounts.
+    Transfer,
+    /// Interest paid to an account.
+    Interest,
+    /// A fee charged to an account.
+    Fee,
+}
+
+/// A single posting to an account.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// The id of the entry.
+    pub id: i32,
+    /// The journal the entry belongs to. All entries in a journal sum to zero.
+    pub journal_id: Uuid,
+    /// The account the entry was posted to.
+    pub account_id: i32,
+    /// The amount credited to the account, negative if it was debited.
+    pub amount: i64,
+    /// The currency of the amount.
+    pub currency: CurrencyCode,
+    /// The balance of the account after this entry.
+    pub balance: i64,
+    /// What caused the entry.
+    pub kind: LedgerEntryKind,
+    /// The transfer that caused the entry, if any.
+    pub transfer_id: Option<i32>,
+    /// A timestamp for the entry.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Parameters for listing ledger entries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct LedgerEntryQuery {
+    /// Only include entries older than the entry with this id.
+    pub cursor: Option<i32>,
+    /// The maximum number of entries to return.
+    #[serde(default = "default_page_size")]
+    #[validate(range(min = 1, max = "MAX_PAGE_SIZE"))]
+    pub limit: u32,
+}
+
+/// An account whose cached balance disagrees with its ledger.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceMismatch {
+    /// The account id.
+    pub account_id: i32,
+    /// The balance stored on the account.
+    pub balance: i64,
+    /// The sum of the account's ledger entries.
+    pub ledger_balance: i64,
+}
@@ -0,0 +1,97 @@
+//! Models representing overdraft and spending limits.
+
+use super::money::CurrencyCode;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use validator::Validate;
+
+/// The limits of a single account.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct AccountLimits {
+    /// How far below zero the balance may go.
+    #[serde(default)]
+    #[validate(range(min = 0))]
+    pub overdraft_limit: i64,
+    /// How much may be taken out of the account per day, if limited.
+    #[validate(range(min = 0))]
+    pub daily_limit: Option<i64>,
+    /// How much may be taken out of the account per month, if limited.
+    #[validate(range(min = 0))]
+    pub monthly_limit: Option<i64>,
+}
+
+/// New or updated spending limits for a user.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct NewUserSpendingLimit {
+    /// How much may be taken out of the user's accounts per day, if limited.
+    #[validate(range(min = 0))]
+    pub daily_limit: Option<i64>,
+    /// How much may be taken out of the user's accounts per month, if limited.
+    #[validate(range(min = 0))]
+    pub monthly_limit: Option<i64>,
+}
+
+/// How much a user may take out of all their accounts in one currency.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserSpendingLimit {
+    /// The user the limits apply to.
+    pub user_id: i32,
+    /// The currency of the accounts the limits apply to.
+    pub currency: CurrencyCode,
+    /// How much may be taken out per day, if limited.
+    pub daily_limit: Option<i64>,
+    /// How much may be taken out per month, if limited.
+    pub monthly_limit: Option<i64>,
+}
+
+/// The period a spending limit applies to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LimitPeriod {
+    /// The current UTC day.
+    Daily,
+    /// The current UTC month.
+    Monthly,
+}
+
+impl LimitPeriod {
+    /// The start of the period that contains `now`.
+    #[must_use]
+    pub fn start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let day = match self {
+            LimitPeriod::Daily => now.day(),
+            LimitPeriod::Monthly => 1,
+        };
+        Utc.with_ymd_and_hms(now.year(), now.month(), day, 0, 0, 0)
+            .single()
+            .unwrap_or(now)
+    }
+}
+
+impl Display for LimitPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitPeriod::Daily => f.write_str("daily"),
+            LimitPeriod::Monthly => f.write_str("monthly"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LimitPeriod;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn period_starts_at_midnight_utc() {
+        let now = Utc.with_ymd_and_hms(2022, 3, 15, 17, 30, 5).unwrap();
+        assert_eq!(
+            Utc.with_ymd_and_hms(2022, 3, 15, 0, 0, 0).unwrap(),
+            LimitPeriod::Daily.start(now)
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2022, 3, 1, 0, 0, 0).unwrap(),
+            LimitPeriod::Monthly.start(now)
+        );
+    }
+}
@@ -0,0 +1,23 @@
+//! Models for keeping track of failed sign-ins.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What failed sign-ins are counted for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "login_scope")]
+pub enum LoginScope {
+    /// The username that was tried, whether or not a user has it.
+    Username,
+    /// The address the sign-in came from.
+    Address,
+}
+
+/// The failed sign-ins counted for a username or an address.
+#[derive(Copy, Clone, Debug)]
+pub struct LoginFailures {
+    /// The number of failures in the current window.
+    pub failures: i32,
+    /// No sign-ins are tried before this time.
+    pub blocked_until: DateTime<Utc>,
+}
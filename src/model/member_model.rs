@@ -0,0 +1,82 @@
+//! Models representing the users with access to an account.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What a member may do with an account.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "account_role")]
+pub enum AccountRole {
+    /// The holder of the account, who decides who else has access to it.
+    Owner,
+    /// A joint holder, who can move money but not manage access.
+    CoOwner,
+    /// A user who can see the account, its ledger and its transfers, but not change it.
+    Viewer,
+}
+
+/// The kinds of access to an account.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccountAccess {
+    /// Read the account and its history.
+    View,
+    /// Move money in or out of the account, or change its details.
+    Transact,
+    /// Close the account, or invite and revoke members.
+    Manage,
+}
+
+impl AccountRole {
+    /// Check if the role grants `access` to the account.
+    #[must_use]
+    pub fn allows(self, access: AccountAccess) -> bool {
+        match access {
+            AccountAccess::View => true,
+            AccountAccess::Transact => matches!(self, Self::Owner | Self::CoOwner),
+            AccountAccess::Manage => self == Self::Owner,
+        }
+    }
+}
+
+/// A user to give access to an account.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewAccountMember {
+    /// The user to invite.
+    pub user_id: i32,
+    /// What the user may do. An account has exactly one owner, so this cannot be
+    /// [`AccountRole::Owner`].
+    pub role: AccountRole,
+}
+
+/// A user with access to an account.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountMember {
+    /// The account.
+    pub account_id: i32,
+    /// The user with access to the account.
+    pub user_id: i32,
+    /// What the user may do.
+    pub role: AccountRole,
+    /// When the user was given access.
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountAccess, AccountRole};
+
+    #[test]
+    fn roles_grant_increasing_access() {
+        let allowed = |role: AccountRole| {
+            [
+                AccountAccess::View,
+                AccountAccess::Transact,
+                AccountAccess::Manage,
+            ]
+            .map(|access| role.allows(access))
+        };
+        assert_eq!([true, true, true], allowed(AccountRole::Owner));
+        assert_eq!([true, true, false], allowed(AccountRole::CoOwner));
+        assert_eq!([true, false, false], allowed(AccountRole::Viewer));
+    }
+}
@@ -0,0 +1,108 @@
+//! Models for multi-factor authentication.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A new TOTP secret, to be added to an authenticator app and confirmed with a code.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TotpEnrollment {
+    /// The base32 encoded secret, for typing in by hand.
+    pub secret: String,
+    /// An `otpauth` URI with the secret, for showing as a QR code.
+    pub otpauth_uri: String,
+}
+
+impl std::fmt::Debug for TotpEnrollment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TotpEnrollment").finish_non_exhaustive()
+    }
+}
+
+/// A code from an authenticator app.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TotpCode {
+    /// The six digit code.
+    pub code: String,
+}
+
+/// Single-use codes for signing in without the authenticator app. They are only shown once.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecoveryCodes {
+    /// The codes.
+    pub recovery_codes: Vec<String>,
+}
+
+impl std::fmt::Debug for RecoveryCodes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecoveryCodes").finish_non_exhaustive()
+    }
+}
+
+/// A stored TOTP secret.
+#[derive(Clone)]
+pub struct TotpSecret {
+    /// The user the secret belongs to.
+    pub user_id: i32,
+    /// The base32 encoded secret.
+    pub secret: String,
+    /// When the user confirmed the secret with a code, after which it is required to sign in.
+    pub confirmed_at: Option<DateTime<Utc>>,
+    /// The last time step a code was accepted for.
+    pub last_used_step: Option<i64>,
+}
+
+impl std::fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TotpSecret")
+            .field("user_id", &self.user_id)
+            .field("confirmed_at", &self.confirmed_at)
+            .field("last_used_step", &self.last_used_step)
+            .finish()
+    }
+}
+
+/// Returned instead of tokens when a user must also give a second factor.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MfaChallenge {
+    /// The token to send with the second factor.
+    pub mfa_token: Uuid,
+    /// The number of seconds until the challenge expires.
+    pub expires_in: i64,
+}
+
+/// The second step of signing in, with either a TOTP code or a recovery code.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MfaVerification {
+    /// The token from the [`MfaChallenge`].
+    pub mfa_token: Uuid,
+    /// A code from the authenticator app.
+    #[serde(default)]
+    pub code: Option<String>,
+    /// One of the recovery codes.
+    #[serde(default)]
+    pub recovery_code: Option<String>,
+}
+
+impl std::fmt::Debug for MfaVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MfaVerification")
+            .field("mfa_token", &self.mfa_token)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A sign-in waiting for the second factor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StoredMfaChallenge {
+    /// The id of the challenge, sent as the `mfa_token`.
+    pub id: Uuid,
+    /// The user signing in.
+    pub user_id: i32,
+    /// When the challenge expires.
+    pub expires_at: DateTime<Utc>,
+    /// How many second factors have been tried.
+    pub attempts: i32,
+    /// When the sign-in was completed.
+    pub completed_at: Option<DateTime<Utc>>,
+}
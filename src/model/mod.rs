@@ -1,5 +1,23 @@
 //! Models used in all services.
 
 pub mod account_model;
+pub mod api_key_model;
+pub mod fx_model;
+pub mod hold_model;
+pub mod ledger_model;
+pub mod limit_model;
+pub mod login_model;
+pub mod member_model;
+pub mod mfa_model;
+pub mod money;
+pub mod oauth_model;
+pub mod pagination;
+pub mod payment_import_model;
+pub mod permission_model;
+pub mod product_model;
+pub mod scheduled_transfer_model;
+pub mod session_model;
+pub mod statement_model;
+pub mod transfer_batch_model;
 pub mod transfer_model;
 pub mod user_model;
@@ -0,0 +1,257 @@
+//! Types for representing amounts of money in different currencies.
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use thiserror::Error;
+
+/// The currency used when none is specified.
+pub const DEFAULT_CURRENCY: &str = "NOK";
+
+/// An error when constructing or converting an amount of money.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum MoneyError {
+    /// The amount was zero or negative.
+    #[error("amount must be positive")]
+    NotPositive,
+    /// The amount was larger than [`Amount::MAX`].
+    #[error("amount must be at most {}", Amount::MAX)]
+    TooLarge,
+    /// The amount has more decimals than the minor unit of its currency.
+    #[error("amount must have at most {0} decimals")]
+    TooPrecise(i16),
+    /// The currency code is not a valid ISO 4217 code.
+    #[error("invalid currency code `{0}`")]
+    InvalidCurrency(String),
+}
+
+/// A positive amount of money in the minor unit of a currency, such as cents.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "i64", into = "i64")]
+pub struct Amount(i64);
+
+impl Amount {
+    /// The largest amount that can be moved at once.
+    pub const MAX: i64 = 1_000_000_000_000_000;
+
+    /// Creates a new amount, rejecting amounts that are not positive or too large.
+    pub fn new(minor_units: i64) -> Result<Self, MoneyError> {
+        if minor_units <= 0 {
+            Err(MoneyError::NotPositive)
+        } else if minor_units > Self::MAX {
+            Err(MoneyError::TooLarge)
+        } else {
+            Ok(Self(minor_units))
+        }
+    }
+
+    /// Get the amount in minor units.
+    #[must_use]
+    pub fn minor_units(&self) -> i64 {
+        self.0
+    }
+}
+
+impl TryFrom<i64> for Amount {
+    type Error = MoneyError;
+
+    fn try_from(minor_units: i64) -> Result<Self, Self::Error> {
+        Amount::new(minor_units)
+    }
+}
+
+impl From<Amount> for i64 {
+    fn from(amount: Amount) -> Self {
+        amount.0
+    }
+}
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// An ISO 4217 currency code, such as `NOK` or `USD`.
+#[derive(
+    Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(try_from = "String", into = "String")]
+#[sqlx(transparent)]
+pub struct CurrencyCode(String);
+
+impl CurrencyCode {
+    /// Creates a new currency code, which must be three uppercase letters.
+    pub fn new(code: impl Into<String>) -> Result<Self, MoneyError> {
+        let code = code.into();
+        if code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase()) {
+            Ok(Self(code))
+        } else {
+            Err(MoneyError::InvalidCurrency(code))
+        }
+    }
+
+    /// Get the currency code as a string.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for CurrencyCode {
+    fn default() -> Self {
+        Self(DEFAULT_CURRENCY.to_string())
+    }
+}
+
+impl TryFrom<String> for CurrencyCode {
+    type Error = MoneyError;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        CurrencyCode::new(code)
+    }
+}
+
+impl From<CurrencyCode> for String {
+    fn from(code: CurrencyCode) -> Self {
+        code.0
+    }
+}
+
+impl Display for CurrencyCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A currency supported by the application.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Currency {
+    /// The ISO 4217 currency code.
+    pub code: CurrencyCode,
+    /// The number of decimals in the minor unit, e.g. 2 for cents.
+    pub minor_units: i16,
+}
+
+impl Currency {
+    /// Converts an amount of minor units to a decimal amount in the major unit.
+    #[must_use]
+    pub fn to_major(&self, minor_units: i64) -> Decimal {
+        Decimal::new(minor_units, self.minor_units as u32)
+    }
+
+    /// Converts a decimal amount in the major unit to minor units,
+    /// rejecting amounts that cannot be represented exactly.
+    pub fn to_minor(&self, major: Decimal) -> Result<Amount, MoneyError> {
+        let mut minor = major.normalize();
+        if minor.scale() > self.minor_units as u32 {
+            return Err(MoneyError::TooPrecise(self.minor_units));
+        }
+        minor.rescale(self.minor_units as u32);
+        let minor_units = i64::try_from(minor.mantissa()).map_err(|_| MoneyError::TooLarge)?;
+        Amount::new(minor_units)
+    }
+
+    /// Converts an amount in this currency to another currency, where one major unit
+    /// of this currency is worth `rate` major units of the other. Rounds half to even.
+    pub fn convert(
+        &self,
+        amount: Amount,
+        to: &Currency,
+        rate: Decimal,
+    ) -> Result<Amount, MoneyError> {
+        let converted = self
+            .to_major(amount.minor_units())
+            .checked_mul(rate)
+            .ok_or(MoneyError::TooLarge)?;
+        let mut converted = converted
+            .round_dp_with_strategy(to.minor_units as u32, RoundingStrategy::MidpointNearestEven);
+        converted.rescale(to.minor_units as u32);
+        let minor_units = i64::try_from(converted.mantissa()).map_err(|_| MoneyError::TooLarge)?;
+        Amount::new(minor_units)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Amount, Currency, CurrencyCode, MoneyError};
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn currency(code: &str, minor_units: i16) -> Currency {
+        Currency {
+            code: CurrencyCode::new(code).unwrap(),
+            minor_units,
+        }
+    }
+
+    #[test]
+    fn amount_must_be_positive_and_bounded() {
+        assert_eq!(Err(MoneyError::NotPositive), Amount::new(0));
+        assert_eq!(Err(MoneyError::NotPositive), Amount::new(-5));
+        assert_eq!(Err(MoneyError::TooLarge), Amount::new(Amount::MAX + 1));
+        assert_eq!(5, Amount::new(5).unwrap().minor_units());
+    }
+
+    #[test]
+    fn parsing_invalid_amount_fails() {
+        assert!(serde_json::from_str::<Amount>("0").is_err());
+        assert!(serde_json::from_str::<Amount>("9223372036854775807").is_err());
+        assert_eq!(
+            Amount::new(10).unwrap(),
+            serde_json::from_str("10").unwrap()
+        );
+    }
+
+    #[test]
+    fn currency_code_must_be_three_uppercase_letters() {
+        assert!(CurrencyCode::new("NOK").is_ok());
+        assert!(CurrencyCode::new("nok").is_err());
+        assert!(CurrencyCode::new("NOKK").is_err());
+        assert!(serde_json::from_str::<CurrencyCode>(r#""US""#).is_err());
+    }
+
+    #[test]
+    fn convert_handles_minor_units() {
+        let usd = currency("USD", 2);
+        let jpy = currency("JPY", 0);
+        let kwd = currency("KWD", 3);
+
+        // 10.00 USD at 150.255 JPY per USD is 1502.55 JPY, rounded to 1503 JPY
+        let rate = Decimal::from_str("150.255").unwrap();
+        let converted = usd.convert(Amount::new(1000).unwrap(), &jpy, rate).unwrap();
+        assert_eq!(1503, converted.minor_units());
+
+        // 1 JPY at 0.0021 KWD per JPY is 0.0021 KWD, rounded to 0.002 KWD
+        let rate = Decimal::from_str("0.0021").unwrap();
+        let converted = jpy.convert(Amount::new(1).unwrap(), &kwd, rate).unwrap();
+        assert_eq!(2, converted.minor_units());
+
+        // Midpoints round to even
+        let rate = Decimal::from_str("0.5").unwrap();
+        let converted = usd.convert(Amount::new(5).unwrap(), &usd, rate).unwrap();
+        assert_eq!(2, converted.minor_units());
+    }
+
+    #[test]
+    fn to_minor_rejects_inexact_amounts() {
+        let usd = currency("USD", 2);
+        let jpy = currency("JPY", 0);
+        let minor = |c: &Currency, s| c.to_minor(Decimal::from_str(s).unwrap());
+        assert_eq!(Ok(Amount::new(10050).unwrap()), minor(&usd, "100.50"));
+        assert_eq!(Ok(Amount::new(100).unwrap()), minor(&jpy, "100.000"));
+        assert_eq!(Err(MoneyError::TooPrecise(2)), minor(&usd, "1.005"));
+        assert_eq!(Err(MoneyError::NotPositive), minor(&usd, "-1"));
+    }
+
+    #[test]
+    fn convert_rejects_amounts_that_round_to_zero() {
+        let usd = currency("USD", 2);
+        let jpy = currency("JPY", 0);
+        let rate = Decimal::from_str("0.0049").unwrap();
+        assert_eq!(
+            Err(MoneyError::NotPositive),
+            jpy.convert(Amount::new(1).unwrap(), &usd, rate)
+        );
+    }
+}
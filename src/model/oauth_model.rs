@@ -0,0 +1,253 @@
+//! Models for the OAuth2 authorization server and the clients registered with it.
+
+use super::{api_key_model::validate_scopes, mfa_model::MfaChallenge};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+/// Check that redirect URIs are absolute and have no fragment, as RFC 6749 requires.
+fn validate_redirect_uris(redirect_uris: &[String]) -> Result<(), ValidationError> {
+    redirect_uris
+        .iter()
+        .try_for_each(|uri| match url::Url::parse(uri) {
+            Ok(url) if url.fragment().is_none() => Ok(()),
+            _ => Err(ValidationError::new("redirect_uri")),
+        })
+}
+
+/// A new OAuth client.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct NewOAuthClient {
+    /// The name shown to users when they are asked for consent.
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    /// Where authorization codes may be sent. Clients without any cannot use the authorization
+    /// code grant.
+    #[serde(default)]
+    #[validate(custom = "validate_redirect_uris")]
+    pub redirect_uris: Vec<String>,
+    /// The permissions that tokens of the client are limited to.
+    #[validate(custom = "validate_scopes")]
+    pub scopes: Vec<String>,
+    /// Whether the client gets a secret, unlike browser apps that cannot keep one.
+    #[serde(default)]
+    pub confidential: bool,
+    /// The user that the client credentials grant acts for. Only confidential clients can have
+    /// one.
+    #[serde(default)]
+    pub user_id: Option<i32>,
+}
+
+/// A registered OAuth client, without its secret.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthClient {
+    /// The id of the client in the database.
+    pub id: i32,
+    /// The `client_id` the client identifies itself with.
+    pub client_id: String,
+    /// The name of the client.
+    pub name: String,
+    /// Whether the client has a secret.
+    pub confidential: bool,
+    /// Where authorization codes may be sent.
+    pub redirect_uris: Vec<String>,
+    /// The permissions that tokens of the client are limited to.
+    pub scopes: Vec<String>,
+    /// The user that the client credentials grant acts for, if any.
+    pub user_id: Option<i32>,
+    /// When the client was revoked.
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// When the client was registered.
+    pub created_at: DateTime<Utc>,
+}
+
+impl OAuthClient {
+    /// Check whether the client can get tokens for users with the authorization code grant.
+    pub fn allows_authorization_code(&self) -> bool {
+        !self.redirect_uris.is_empty()
+    }
+
+    /// Check whether the client can get tokens for itself with the client credentials grant.
+    pub fn allows_client_credentials(&self) -> bool {
+        self.confidential && self.user_id.is_some()
+    }
+}
+
+/// A client that was just registered. This is the only time the secret is shown.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedOAuthClient {
+    /// The stored client.
+    #[serde(flatten)]
+    pub client: OAuthClient,
+    /// The secret of a confidential client.
+    pub client_secret: Option<String>,
+}
+
+impl std::fmt::Debug for CreatedOAuthClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CreatedOAuthClient")
+            .field("client", &self.client)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A request to the authorization endpoint, from the query or the consent form.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    /// Must be `code`.
+    pub response_type: Option<String>,
+    /// The client asking for authorization.
+    pub client_id: String,
+    /// Where to send the code. Can be left out if the client has only one.
+    pub redirect_uri: Option<String>,
+    /// The permissions asked for, separated by spaces. All those of the client if left out.
+    pub scope: Option<String>,
+    /// An opaque value returned with the code.
+    pub state: Option<String>,
+    /// The PKCE code challenge.
+    pub code_challenge: Option<String>,
+    /// Must be `S256`.
+    pub code_challenge_method: Option<String>,
+}
+
+/// What the user decided on the consent page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsentDecision {
+    /// Let the client act for the user.
+    Approve,
+    /// Send the user back to the client without a code.
+    Deny,
+}
+
+/// The consent form, with the authorization request it was shown for.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Consent {
+    /// The authorization request.
+    #[serde(flatten)]
+    pub request: AuthorizationRequest,
+    /// What the user decided.
+    pub decision: ConsentDecision,
+    /// The username, on the first step.
+    pub username: Option<String>,
+    /// The password, on the first step.
+    pub password: Option<String>,
+    /// The challenge of users with a second factor, on the second step.
+    pub mfa_token: Option<Uuid>,
+    /// The code from the authenticator app, on the second step.
+    pub code: Option<String>,
+}
+
+impl std::fmt::Debug for Consent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Consent")
+            .field("request", &self.request)
+            .field("decision", &self.decision)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The result of a user approving a client on the consent page.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Approval {
+    /// The code to send back to the client.
+    Code(String),
+    /// The user must also give a second factor.
+    MfaRequired(MfaChallenge),
+}
+
+/// A request to the token endpoint.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct OAuthTokenRequest {
+    /// `client_credentials` or `authorization_code`.
+    pub grant_type: String,
+    /// The authorization code.
+    pub code: Option<String>,
+    /// The redirect URI the code was sent to.
+    pub redirect_uri: Option<String>,
+    /// The PKCE code verifier.
+    pub code_verifier: Option<String>,
+    /// The client, unless it authenticates with Basic authentication.
+    pub client_id: Option<String>,
+    /// The secret of the client, unless it authenticates with Basic authentication.
+    pub client_secret: Option<String>,
+    /// The permissions asked for with client credentials, separated by spaces.
+    pub scope: Option<String>,
+}
+
+impl std::fmt::Debug for OAuthTokenRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OAuthTokenRequest")
+            .field("grant_type", &self.grant_type)
+            .field("redirect_uri", &self.redirect_uri)
+            .field("client_id", &self.client_id)
+            .field("scope", &self.scope)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A successful response from the token endpoint.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthTokenResponse {
+    /// A jwt to send as a bearer token.
+    pub access_token: String,
+    /// Always `Bearer`.
+    pub token_type: String,
+    /// The number of seconds until the access token expires.
+    pub expires_in: i64,
+    /// The permissions granted, separated by spaces.
+    pub scope: String,
+}
+
+/// A stored authorization code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorizationCode {
+    /// The id of the client in the database.
+    pub client_id: i32,
+    /// The user who consented.
+    pub user_id: i32,
+    /// The redirect URI the code was sent to.
+    pub redirect_uri: String,
+    /// Whether the authorization request named the redirect URI, so that the token request must
+    /// name it too.
+    pub redirect_uri_given: bool,
+    /// The permissions the user consented to.
+    pub scopes: Vec<String>,
+    /// The S256 PKCE challenge.
+    pub code_challenge: String,
+    /// How the user authenticated when they consented.
+    pub amr: Vec<String>,
+    /// When the code stops working.
+    pub expires_at: DateTime<Utc>,
+    /// When the code was exchanged, if it was.
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// The discovery document of the server, as in OpenID Connect Discovery and RFC 8414.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    /// The public URL of the server.
+    pub issuer: String,
+    /// Where users consent to clients.
+    pub authorization_endpoint: String,
+    /// Where clients get tokens.
+    pub token_endpoint: String,
+    /// Where the keys that tokens are signed with are published.
+    pub jwks_uri: String,
+    /// The permissions clients can ask for.
+    pub scopes_supported: Vec<String>,
+    /// Always `code`.
+    pub response_types_supported: Vec<String>,
+    /// The grants of the token endpoint.
+    pub grant_types_supported: Vec<String>,
+    /// How confidential clients authenticate at the token endpoint.
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    /// Always `S256`.
+    pub code_challenge_methods_supported: Vec<String>,
+    /// Always `public`.
+    pub subject_types_supported: Vec<String>,
+    /// The algorithms tokens are signed with.
+    pub id_token_signing_alg_values_supported: Vec<String>,
+}
@@ -0,0 +1,84 @@
+//! Types for paginating and sorting collections.
+
+use serde::{Deserialize, Serialize};
+
+/// The default number of items on a page.
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+
+/// The largest number of items a client may request on a page.
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+pub(crate) fn default_page_size() -> u32 {
+    DEFAULT_PAGE_SIZE
+}
+
+/// The direction to sort a collection in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Smallest first.
+    #[default]
+    Asc,
+    /// Largest first.
+    Desc,
+}
+
+impl SortOrder {
+    /// The name of the sort order as used in queries.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+/// A page of items from a larger collection.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page<T> {
+    /// The items on this page.
+    pub items: Vec<T>,
+    /// The zero-indexed page number.
+    pub page: u32,
+    /// The maximum number of items on a page.
+    pub size: u32,
+    /// The total number of items in the collection.
+    pub total: i64,
+}
+
+impl<T> Page<T> {
+    /// Creates a new page.
+    #[must_use]
+    pub fn new(items: Vec<T>, page: u32, size: u32, total: i64) -> Self {
+        Self {
+            items,
+            page,
+            size,
+            total,
+        }
+    }
+}
+
+/// A slice of a collection that continues after a cursor.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorPage<T> {
+    /// The items in this slice.
+    pub items: Vec<T>,
+    /// The cursor to pass to fetch the next slice, if there might be more items.
+    pub next_cursor: Option<i32>,
+}
+
+impl<T> CursorPage<T> {
+    /// Creates a new slice, using `cursor` to find the cursor of the last item.
+    /// A full slice means there might be more items after it.
+    #[must_use]
+    pub fn new(items: Vec<T>, limit: u32, cursor: impl Fn(&T) -> i32) -> Self {
+        let next_cursor = if items.len() == limit as usize {
+            items.last().map(cursor)
+        } else {
+            None
+        };
+        Self { items, next_cursor }
+    }
+}
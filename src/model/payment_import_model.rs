@@ -0,0 +1,492 @@
+//! Models representing imported ISO 20022 `pain.001` payment files and their status reports.
+
+use crate::infra::xml::escape_xml;
+use chrono::{DateTime, Utc};
+use roxmltree::{Document, Node};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write, str::FromStr};
+use thiserror::Error;
+
+/// The maximum number of payments in a file.
+pub const MAX_IMPORT_SIZE: usize = 10_000;
+
+/// An error when parsing a `pain.001` document.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum Pain001Error {
+    /// The document is not well-formed XML.
+    #[error("invalid XML: {0}")]
+    InvalidXml(String),
+    /// The document is not a customer credit transfer initiation.
+    #[error("document is not a pain.001 customer credit transfer initiation")]
+    NotPain001,
+    /// A required element is missing.
+    #[error("missing element {0}")]
+    MissingElement(&'static str),
+    /// An element has a value that cannot be parsed.
+    #[error("invalid value `{1}` in element {0}")]
+    InvalidValue(&'static str, String),
+    /// The number of transactions does not match `GrpHdr/NbOfTxs`.
+    #[error("expected {0} transactions but found {1}")]
+    TransactionCount(usize, usize),
+    /// The sum of the amounts does not match `GrpHdr/CtrlSum`.
+    #[error("expected a control sum of {0} but found {1}")]
+    ControlSum(Decimal, Decimal),
+    /// The document has no payments, or too many.
+    #[error("a file must have between 1 and {} payments", MAX_IMPORT_SIZE)]
+    Size,
+}
+
+/// A parsed `pain.001` document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentFile {
+    /// The id the sender gave the message, `GrpHdr/MsgId`.
+    pub message_id: String,
+    /// The payments, in document order.
+    pub payments: Vec<PaymentInstruction>,
+}
+
+/// A single credit transfer from a `pain.001` document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentInstruction {
+    /// The payment information block the transfer belongs to, `PmtInf/PmtInfId`.
+    pub payment_info_id: String,
+    /// The id of the transfer assigned by the sender, `PmtId/EndToEndId`.
+    pub end_to_end_id: String,
+    /// The account to take money from, `DbtrAcct/Id`.
+    pub debtor_account: String,
+    /// The account to send money to, `CdtrAcct/Id`.
+    pub creditor_account: String,
+    /// The amount in the major unit of the currency, `Amt/InstdAmt`.
+    pub amount: Decimal,
+    /// The currency of the amount, `Amt/InstdAmt/@Ccy`.
+    pub currency: String,
+}
+
+impl PaymentFile {
+    /// Parses a `pain.001` customer credit transfer initiation of any version.
+    /// Elements are matched by name, regardless of namespace.
+    pub fn parse(xml: &str) -> Result<Self, Pain001Error> {
+        let document = Document::parse(xml).map_err(|e| Pain001Error::InvalidXml(e.to_string()))?;
+        let initiation =
+            child(document.root_element(), "CstmrCdtTrfInitn").ok_or(Pain001Error::NotPain001)?;
+        let header = required(initiation, "GrpHdr")?;
+        let message_id = text(header, &["MsgId"], "GrpHdr/MsgId")?;
+        let expected_count = text(header, &["NbOfTxs"], "GrpHdr/NbOfTxs")?;
+        let expected_count = usize::from_str(&expected_count)
+            .map_err(|_| Pain001Error::InvalidValue("GrpHdr/NbOfTxs", expected_count))?;
+
+        let mut payments = vec![];
+        for payment_info in children(initiation, "PmtInf") {
+            let payment_info_id = text(payment_info, &["PmtInfId"], "PmtInf/PmtInfId")?;
+            let debtor_account = account(payment_info, "DbtrAcct")?;
+            for transaction in children(payment_info, "CdtTrfTxInf") {
+                let end_to_end_id =
+                    text(transaction, &["PmtId", "EndToEndId"], "PmtId/EndToEndId")?;
+                let instructed = path(transaction, &["Amt", "InstdAmt"])
+                    .ok_or(Pain001Error::MissingElement("Amt/InstdAmt"))?;
+                let amount = instructed.text().unwrap_or_default().trim();
+                let amount = Decimal::from_str(amount)
+                    .map_err(|_| Pain001Error::InvalidValue("Amt/InstdAmt", amount.to_string()))?;
+                let currency = instructed
+                    .attribute("Ccy")
+                    .ok_or(Pain001Error::MissingElement("Amt/InstdAmt/@Ccy"))?;
+                payments.push(PaymentInstruction {
+                    payment_info_id: payment_info_id.clone(),
+                    end_to_end_id,
+                    debtor_account: debtor_account.clone(),
+                    creditor_account: account(transaction, "CdtrAcct")?,
+                    amount,
+                    currency: currency.to_string(),
+                });
+            }
+        }
+
+        if payments.is_empty() || payments.len() > MAX_IMPORT_SIZE {
+            return Err(Pain001Error::Size);
+        }
+        if payments.len() != expected_count {
+            return Err(Pain001Error::TransactionCount(
+                expected_count,
+                payments.len(),
+            ));
+        }
+        if let Some(control_sum) = path(header, &["CtrlSum"]).and_then(|n| n.text()) {
+            let control_sum = Decimal::from_str(control_sum.trim()).map_err(|_| {
+                Pain001Error::InvalidValue("GrpHdr/CtrlSum", control_sum.to_string())
+            })?;
+            let sum: Decimal = payments.iter().map(|p| p.amount).sum();
+            if sum != control_sum {
+                return Err(Pain001Error::ControlSum(control_sum, sum));
+            }
+        }
+        Ok(Self {
+            message_id,
+            payments,
+        })
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|n| n.is_element() && n.tag_name().name() == name)
+}
+
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |n| n.is_element() && n.tag_name().name() == name)
+}
+
+fn path<'a, 'input>(node: Node<'a, 'input>, names: &[&str]) -> Option<Node<'a, 'input>> {
+    names.iter().try_fold(node, |node, name| child(node, name))
+}
+
+fn required<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> Result<Node<'a, 'input>, Pain001Error> {
+    child(node, name).ok_or(Pain001Error::MissingElement(name))
+}
+
+fn text(node: Node<'_, '_>, names: &[&str], element: &'static str) -> Result<String, Pain001Error> {
+    path(node, names)
+        .and_then(|n| n.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .ok_or(Pain001Error::MissingElement(element))
+}
+
+/// The identification of an account, either an IBAN or another id such as an account number.
+fn account(node: Node<'_, '_>, name: &'static str) -> Result<String, Pain001Error> {
+    let id = path(node, &[name, "Id"]).ok_or(Pain001Error::MissingElement(name))?;
+    text(id, &["IBAN"], name).or_else(|_| text(id, &["Othr", "Id"], name))
+}
+
+/// The state of an imported payment file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "payment_import_status")]
+pub enum PaymentImportStatus {
+    /// The file has been validated and its payments are waiting to be executed.
+    Received,
+    /// All payments were executed.
+    Accepted,
+    /// Some payments were executed.
+    PartiallyAccepted,
+    /// No payments were executed.
+    Rejected,
+}
+
+impl PaymentImportStatus {
+    /// The ISO 20022 group status code.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            PaymentImportStatus::Received => "RCVD",
+            PaymentImportStatus::Accepted => "ACCP",
+            PaymentImportStatus::PartiallyAccepted => "PART",
+            PaymentImportStatus::Rejected => "RJCT",
+        }
+    }
+}
+
+/// The state of a single imported payment.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "imported_payment_status")]
+pub enum ImportedPaymentStatus {
+    /// The payment is waiting to be executed.
+    Pending,
+    /// The payment was executed.
+    Accepted,
+    /// The payment was invalid or could not be executed.
+    Rejected,
+}
+
+impl ImportedPaymentStatus {
+    /// The ISO 20022 transaction status code.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            ImportedPaymentStatus::Pending => "PDNG",
+            ImportedPaymentStatus::Accepted => "ACSC",
+            ImportedPaymentStatus::Rejected => "RJCT",
+        }
+    }
+}
+
+/// ISO 20022 codes for why a payment was rejected.
+pub mod reason {
+    /// The account does not exist or cannot be used by the sender.
+    pub const INVALID_ACCOUNT: &str = "AC01";
+    /// The amount is not valid for the currency.
+    pub const INVALID_AMOUNT: &str = "AM12";
+    /// The currency is not the currency of the debtor account.
+    pub const INVALID_CURRENCY: &str = "AM03";
+    /// The payment could not be executed; the reason is described in text.
+    pub const NARRATIVE: &str = "NARR";
+}
+
+/// A stored payment file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentImport {
+    /// The id of the import.
+    pub id: i32,
+    /// The user that uploaded the file.
+    pub owner_id: i32,
+    /// The id the sender gave the file.
+    pub message_id: String,
+    /// The state of the import.
+    pub status: PaymentImportStatus,
+    /// When the file was uploaded.
+    pub created_at: DateTime<Utc>,
+    /// When the payments were executed.
+    pub processed_at: Option<DateTime<Utc>>,
+}
+
+/// A payment from a stored payment file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportedPayment {
+    /// The position of the payment in the file, starting at 0.
+    pub position: i32,
+    /// The payment information block the payment belongs to.
+    pub payment_info_id: String,
+    /// The id of the payment assigned by the sender.
+    pub end_to_end_id: String,
+    /// The account to take money from, as given in the file.
+    pub debtor_account: String,
+    /// The account to send money to, as given in the file.
+    pub creditor_account: String,
+    /// The amount in the major unit of the currency, as given in the file.
+    pub instructed_amount: Decimal,
+    /// The currency of the amount, as given in the file.
+    pub currency: String,
+    /// The account to take money from, once validated.
+    pub from_account: Option<i32>,
+    /// The account to send money to, once validated.
+    pub to_account: Option<i32>,
+    /// The amount in minor units, once validated.
+    pub amount: Option<i64>,
+    /// The state of the payment.
+    pub status: ImportedPaymentStatus,
+    /// The ISO 20022 code for why the payment was rejected, if it was.
+    pub reason_code: Option<String>,
+    /// Why the payment was rejected, if it was.
+    pub reason: Option<String>,
+    /// The resulting transfer, if the payment was executed.
+    pub transfer_id: Option<i32>,
+}
+
+impl ImportedPayment {
+    /// A payment that is waiting to be executed.
+    #[must_use]
+    pub fn pending(
+        position: i32,
+        instruction: PaymentInstruction,
+        from_account: i32,
+        to_account: i32,
+        amount: i64,
+    ) -> Self {
+        Self {
+            from_account: Some(from_account),
+            to_account: Some(to_account),
+            amount: Some(amount),
+            status: ImportedPaymentStatus::Pending,
+            reason_code: None,
+            reason: None,
+            ..Self::rejected(position, instruction, "", String::new())
+        }
+    }
+
+    /// A payment that was rejected when the file was validated.
+    #[must_use]
+    pub fn rejected(
+        position: i32,
+        instruction: PaymentInstruction,
+        reason_code: &str,
+        reason: String,
+    ) -> Self {
+        Self {
+            position,
+            payment_info_id: instruction.payment_info_id,
+            end_to_end_id: instruction.end_to_end_id,
+            debtor_account: instruction.debtor_account,
+            creditor_account: instruction.creditor_account,
+            instructed_amount: instruction.amount,
+            currency: instruction.currency,
+            from_account: None,
+            to_account: None,
+            amount: None,
+            status: ImportedPaymentStatus::Rejected,
+            reason_code: Some(reason_code.to_string()),
+            reason: Some(reason),
+            transfer_id: None,
+        }
+    }
+}
+
+/// A payment file together with the state of each of its payments.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentImportReport {
+    /// The import.
+    #[serde(flatten)]
+    pub import: PaymentImport,
+    /// The state of each payment, in order.
+    pub payments: Vec<ImportedPayment>,
+}
+
+impl PaymentImportReport {
+    /// Renders the report as an ISO 20022 `pain.002.001.03` payment status report.
+    #[must_use]
+    pub fn to_pain002(&self, created_at: DateTime<Utc>) -> String {
+        let mut xml = String::new();
+        let _ = write!(
+            xml,
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                "\n",
+                r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.002.001.03">"#,
+                "<CstmrPmtStsRpt>",
+                "<GrpHdr><MsgId>STS-{id}</MsgId><CreDtTm>{created_at}</CreDtTm></GrpHdr>",
+                "<OrgnlGrpInfAndSts><OrgnlMsgId>{message_id}</OrgnlMsgId>",
+                "<OrgnlMsgNmId>pain.001</OrgnlMsgNmId><OrgnlNbOfTxs>{count}</OrgnlNbOfTxs>",
+                "<GrpSts>{status}</GrpSts></OrgnlGrpInfAndSts>"
+            ),
+            id = self.import.id,
+            created_at = created_at.format("%Y-%m-%dT%H:%M:%SZ"),
+            message_id = escape_xml(&self.import.message_id),
+            count = self.payments.len(),
+            status = self.import.status.code(),
+        );
+        // Group payments by their payment information block, keeping document order
+        let mut blocks: Vec<(&str, Vec<&ImportedPayment>)> = vec![];
+        for payment in &self.payments {
+            match blocks.last_mut() {
+                Some((id, payments)) if *id == payment.payment_info_id => payments.push(payment),
+                _ => blocks.push((&payment.payment_info_id, vec![payment])),
+            }
+        }
+        for (payment_info_id, payments) in blocks {
+            let _ = write!(
+                xml,
+                "<OrgnlPmtInfAndSts><OrgnlPmtInfId>{}</OrgnlPmtInfId>",
+                escape_xml(payment_info_id)
+            );
+            for payment in payments {
+                let _ = write!(
+                    xml,
+                    concat!(
+                        "<TxInfAndSts><StsId>{id}-{position}</StsId>",
+                        "<OrgnlEndToEndId>{end_to_end_id}</OrgnlEndToEndId>",
+                        "<TxSts>{status}</TxSts>"
+                    ),
+                    id = self.import.id,
+                    position = payment.position,
+                    end_to_end_id = escape_xml(&payment.end_to_end_id),
+                    status = payment.status.code(),
+                );
+                if let Some(code) = &payment.reason_code {
+                    let _ = write!(
+                        xml,
+                        "<StsRsnInf><Rsn><Cd>{}</Cd></Rsn><AddtlInf>{}</AddtlInf></StsRsnInf>",
+                        escape_xml(code),
+                        escape_xml(payment.reason.as_deref().unwrap_or_default())
+                    );
+                }
+                let _ = write!(
+                    xml,
+                    concat!(
+                        "<OrgnlTxRef><Amt><InstdAmt Ccy=\"{currency}\">{amount}</InstdAmt></Amt></OrgnlTxRef>",
+                        "</TxInfAndSts>"
+                    ),
+                    currency = escape_xml(&payment.currency),
+                    amount = payment.instructed_amount,
+                );
+            }
+            xml.push_str("</OrgnlPmtInfAndSts>");
+        }
+        xml.push_str("</CstmrPmtStsRpt></Document>\n");
+        xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pain001Error, PaymentFile};
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    const PAIN001: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.03">
+  <CstmrCdtTrfInitn>
+    <GrpHdr>
+      <MsgId>MSG-1</MsgId>
+      <CreDtTm>2022-10-10T12:00:00</CreDtTm>
+      <NbOfTxs>2</NbOfTxs>
+      <CtrlSum>1.75</CtrlSum>
+    </GrpHdr>
+    <PmtInf>
+      <PmtInfId>PMT-1</PmtInfId>
+      <PmtMtd>TRF</PmtMtd>
+      <DbtrAcct><Id><Othr><Id>1</Id></Othr></Id></DbtrAcct>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>E2E-1</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="NOK">1.50</InstdAmt></Amt>
+        <CdtrAcct><Id><Othr><Id>2</Id></Othr></Id></CdtrAcct>
+      </CdtTrfTxInf>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>E2E-2</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="NOK">0.25</InstdAmt></Amt>
+        <CdtrAcct><Id><IBAN>NO9386011117947</IBAN></Id></CdtrAcct>
+      </CdtTrfTxInf>
+    </PmtInf>
+  </CstmrCdtTrfInitn>
+</Document>"#;
+
+    #[test]
+    fn parses_payments_in_order() {
+        let file = PaymentFile::parse(PAIN001).unwrap();
+        assert_eq!("MSG-1", file.message_id);
+        assert_eq!(2, file.payments.len());
+        let first = &file.payments[0];
+        assert_eq!("PMT-1", first.payment_info_id);
+        assert_eq!("E2E-1", first.end_to_end_id);
+        assert_eq!("1", first.debtor_account);
+        assert_eq!("2", first.creditor_account);
+        assert_eq!(Decimal::from_str("1.50").unwrap(), first.amount);
+        assert_eq!("NOK", first.currency);
+        assert_eq!("NO9386011117947", file.payments[1].creditor_account);
+    }
+
+    #[test]
+    fn rejects_inconsistent_headers() {
+        let wrong_count = PAIN001.replace("<NbOfTxs>2<", "<NbOfTxs>3<");
+        assert_eq!(
+            Err(Pain001Error::TransactionCount(3, 2)),
+            PaymentFile::parse(&wrong_count)
+        );
+        let wrong_sum = PAIN001.replace("<CtrlSum>1.75<", "<CtrlSum>2<");
+        assert!(matches!(
+            PaymentFile::parse(&wrong_sum),
+            Err(Pain001Error::ControlSum(_, _))
+        ));
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(matches!(
+            PaymentFile::parse("<Document"),
+            Err(Pain001Error::InvalidXml(_))
+        ));
+        assert_eq!(
+            Err(Pain001Error::NotPain001),
+            PaymentFile::parse("<Document><FIToFICstmrCdtTrf/></Document>")
+        );
+        let missing = PAIN001.replace("<MsgId>MSG-1</MsgId>", "");
+        assert_eq!(
+            Err(Pain001Error::MissingElement("GrpHdr/MsgId")),
+            PaymentFile::parse(&missing)
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Models for roles and the fine-grained permissions attached to them.
+
+use crate::infra::security::jwt::Role;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+/// Check that a permission name is made of lowercase words separated by colons,
+/// such as `transfers:create`.
+pub(crate) fn validate_permission_name(name: &str) -> Result<(), ValidationError> {
+    let mut parts = name.split(':');
+    let valid_part =
+        |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+    if parts.clone().count() >= 2 && parts.all(valid_part) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("permission_name"))
+    }
+}
+
+/// A new permission.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct NewPermission {
+    /// The name handlers check for, such as `transfers:create`.
+    #[validate(length(max = 100), custom = "validate_permission_name")]
+    pub name: String,
+    /// What the permission allows.
+    #[serde(default)]
+    #[validate(length(max = 500))]
+    pub description: String,
+}
+
+/// A stored permission.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permission {
+    /// The id of the permission.
+    pub id: i32,
+    /// The name handlers check for.
+    pub name: String,
+    /// What the permission allows.
+    pub description: String,
+    /// When the permission was defined.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A role with what it grants.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoleDetails {
+    /// The role.
+    pub name: Role,
+    /// The roles that users with this role also have, directly or through other roles.
+    pub implies: Vec<Role>,
+    /// The permissions granted directly to the role, not including those of implied roles.
+    pub permissions: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_permission_name;
+
+    #[test]
+    fn permission_names_are_scoped() {
+        assert!(validate_permission_name("transfers:create").is_ok());
+        assert!(validate_permission_name("accounts:members:manage").is_ok());
+        assert!(validate_permission_name("transfers").is_err());
+        assert!(validate_permission_name("transfers:").is_err());
+        assert!(validate_permission_name("Transfers:create").is_err());
+    }
+}
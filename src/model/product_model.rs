@@ -0,0 +1,251 @@
+//! Models representing account products, and the interest and fees they bring.
+//!
+//! Interest accrues daily on the balance at the end of each UTC day, using the
+//! actual/365 day count convention. Daily accruals are kept in fractions of the minor unit,
+//! rounded half to even to [`ACCRUAL_DECIMALS`] decimals, and the sum for a month is rounded
+//! half to even to whole minor units when the month is posted. Rounding once per month keeps
+//! the rounding error at half a minor unit, no matter how many days there are.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// The number of days in a year for the interest day count.
+pub const DAYS_PER_YEAR: i64 = 365;
+
+/// The number of decimals of the minor unit kept for daily accruals.
+pub const ACCRUAL_DECIMALS: u32 = 8;
+
+/// The number of decimals allowed in an interest rate.
+pub const RATE_DECIMALS: u32 = 6;
+
+/// A new or updated account product.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct NewAccountProduct {
+    /// The name of the product, such as "Savings".
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    /// The yearly interest rate, e.g. 0.025 for 2.5 %.
+    #[serde(default)]
+    pub interest_rate: Decimal,
+    /// The fee charged each month, in the minor unit of the account's currency.
+    #[serde(default)]
+    #[validate(range(min = 0))]
+    pub monthly_fee: i64,
+}
+
+/// An account product, which decides the interest and fees of the accounts using it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountProduct {
+    /// The product id.
+    pub id: i32,
+    /// The name of the product.
+    pub name: String,
+    /// The yearly interest rate.
+    pub interest_rate: Decimal,
+    /// The fee charged each month, in the minor unit of the account's currency.
+    pub monthly_fee: i64,
+    /// When the product was created.
+    pub created_at: DateTime<Utc>,
+}
+
+/// The product to use for an account.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProductAssignment {
+    /// The product id, or `None` for an account without interest and fees.
+    pub product_id: Option<i32>,
+}
+
+/// What interest is accrued from for an account on a given day.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AccrualBasis {
+    /// The account id.
+    pub account_id: i32,
+    /// The product of the account.
+    pub product_id: i32,
+    /// The balance of the account at the end of the day.
+    pub balance: i64,
+    /// The yearly interest rate of the product.
+    pub interest_rate: Decimal,
+}
+
+impl AccrualBasis {
+    /// Accrues interest for a single day.
+    #[must_use]
+    pub fn accrue(&self, accrual_date: NaiveDate) -> InterestAccrual {
+        InterestAccrual {
+            account_id: self.account_id,
+            accrual_date,
+            product_id: self.product_id,
+            balance: self.balance,
+            interest_rate: self.interest_rate,
+            amount: daily_interest(self.balance, self.interest_rate),
+        }
+    }
+}
+
+/// Interest earned by an account on a single day.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterestAccrual {
+    /// The account id.
+    pub account_id: i32,
+    /// The day the interest was earned.
+    pub accrual_date: NaiveDate,
+    /// The product of the account on that day.
+    pub product_id: i32,
+    /// The balance of the account at the end of the day.
+    pub balance: i64,
+    /// The yearly interest rate used.
+    pub interest_rate: Decimal,
+    /// The interest earned, in fractions of the minor unit.
+    pub amount: Decimal,
+}
+
+/// What is owed to and by an account for a month that has not been posted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PostingBasis {
+    /// The account id.
+    pub account_id: i32,
+    /// The first day of the month.
+    pub month: NaiveDate,
+    /// The sum of the interest accrued in the month, in fractions of the minor unit.
+    pub accrued: Decimal,
+    /// The monthly fee of the product the account had at the end of the month.
+    pub monthly_fee: i64,
+}
+
+impl PostingBasis {
+    /// The interest to post, in whole minor units.
+    #[must_use]
+    pub fn interest(&self) -> i64 {
+        monthly_interest(self.accrued)
+    }
+
+    /// The fee to charge an account that has `available` left after the interest is posted.
+    /// The fee is reduced rather than taking the account beyond its overdraft limit.
+    #[must_use]
+    pub fn fee(&self, available: i64) -> i64 {
+        self.monthly_fee.min(available.max(0))
+    }
+}
+
+/// The interest and fee booked for an account at the end of a month.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonthlyPosting {
+    /// The account id.
+    pub account_id: i32,
+    /// The first day of the month.
+    pub month: NaiveDate,
+    /// The interest paid, in the minor unit of the account's currency.
+    pub interest: i64,
+    /// The fee charged, in the minor unit of the account's currency.
+    pub fee: i64,
+    /// When the month was posted.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Parameters for listing the accrual history of an account.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccrualQuery {
+    /// The first day to include, if any.
+    pub from: Option<NaiveDate>,
+    /// The last day to include, if any.
+    pub to: Option<NaiveDate>,
+}
+
+/// The interest accrued by an account, and what has been posted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccrualHistory {
+    /// Daily accruals, oldest first.
+    pub accruals: Vec<InterestAccrual>,
+    /// Monthly postings, oldest first.
+    pub postings: Vec<MonthlyPosting>,
+}
+
+/// The interest earned in a day on a balance, in fractions of the minor unit.
+/// Negative balances earn nothing.
+#[must_use]
+pub fn daily_interest(balance: i64, interest_rate: Decimal) -> Decimal {
+    if balance <= 0 {
+        return Decimal::ZERO;
+    }
+    let yearly = Decimal::from(balance) * interest_rate;
+    let mut daily = (yearly / Decimal::from(DAYS_PER_YEAR))
+        .round_dp_with_strategy(ACCRUAL_DECIMALS, RoundingStrategy::MidpointNearestEven);
+    daily.rescale(ACCRUAL_DECIMALS);
+    daily
+}
+
+/// The interest to post for a month, in whole minor units.
+#[must_use]
+pub fn monthly_interest(accrued: Decimal) -> i64 {
+    let rounded = accrued.round_dp_with_strategy(0, RoundingStrategy::MidpointNearestEven);
+    i64::try_from(rounded).unwrap_or(0)
+}
+
+/// The first day of the month that contains `date`.
+#[must_use]
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{daily_interest, month_start, monthly_interest, PostingBasis};
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn decimal(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn daily_interest_is_rounded_half_to_even() {
+        // 100 000 * 0.0365 / 365 = 10 exactly
+        assert_eq!(
+            decimal("10.00000000"),
+            daily_interest(100_000, decimal("0.0365"))
+        );
+        // 1 000 * 0.025 / 365 = 0.068493150684...
+        assert_eq!(
+            decimal("0.06849315"),
+            daily_interest(1_000, decimal("0.025"))
+        );
+        // 1 * 0.000009125 / 365 = 0.000000025, halfway between two accrual decimals
+        assert_eq!(
+            decimal("0.00000002"),
+            daily_interest(1, decimal("0.000009125"))
+        );
+        assert_eq!(Decimal::ZERO, daily_interest(-5_000, decimal("0.05")));
+        assert_eq!(Decimal::ZERO, daily_interest(0, decimal("0.05")));
+    }
+
+    #[test]
+    fn monthly_interest_is_rounded_once() {
+        // 30 days of 0.06849315 would be 0 if every day was rounded to minor units
+        let accrued = daily_interest(1_000, decimal("0.025")) * Decimal::from(30);
+        assert_eq!(2, monthly_interest(accrued));
+        assert_eq!(2, monthly_interest(decimal("2.5")));
+        assert_eq!(4, monthly_interest(decimal("3.5")));
+        assert_eq!(0, monthly_interest(Decimal::ZERO));
+    }
+
+    #[test]
+    fn fee_stays_within_the_available_balance() {
+        let basis = PostingBasis {
+            account_id: 1,
+            month: NaiveDate::from_ymd_opt(2022, 10, 1).unwrap(),
+            accrued: Decimal::ZERO,
+            monthly_fee: 50,
+        };
+        assert_eq!(50, basis.fee(100));
+        assert_eq!(20, basis.fee(20));
+        assert_eq!(0, basis.fee(-10));
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2022, 2, 1).unwrap(),
+            month_start(NaiveDate::from_ymd_opt(2022, 2, 28).unwrap())
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Models representing transfers that run in the future or on a recurrence.
+
+use super::{
+    money::Amount,
+    pagination::{default_page_size, MAX_PAGE_SIZE},
+};
+use chrono::{DateTime, Duration, Months, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// How often a scheduled transfer repeats.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "transfer_recurrence")]
+pub enum TransferRecurrence {
+    /// Every day.
+    Daily,
+    /// Every week.
+    Weekly,
+    /// Every month, on the same day as the first occurrence if possible.
+    Monthly,
+}
+
+impl TransferRecurrence {
+    /// The time of occurrence `n` of a schedule starting at `start`,
+    /// where occurrence 0 is `start` itself.
+    #[must_use]
+    pub fn nth(&self, start: DateTime<Utc>, n: u32) -> Option<DateTime<Utc>> {
+        match self {
+            TransferRecurrence::Daily => start.checked_add_signed(Duration::days(n.into())),
+            TransferRecurrence::Weekly => start.checked_add_signed(Duration::weeks(n.into())),
+            TransferRecurrence::Monthly => start.checked_add_months(Months::new(n)),
+        }
+    }
+}
+
+/// The state of a scheduled transfer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "scheduled_transfer_status")]
+pub enum ScheduledTransferStatus {
+    /// The transfer will run at `next_run_at`.
+    Active,
+    /// All occurrences have been run.
+    Completed,
+    /// A one-off transfer that could not be executed.
+    Failed,
+    /// The transfer was cancelled by the user.
+    Cancelled,
+}
+
+/// The outcome of executing a scheduled transfer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "scheduled_transfer_run_status")]
+pub enum ScheduledTransferRunStatus {
+    /// The transfer was executed.
+    Succeeded,
+    /// The transfer could not be executed, e.g. because the balance was too low.
+    Failed,
+}
+
+/// A new scheduled transfer.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct NewScheduledTransfer {
+    /// The account to take money from.
+    pub from_account: i32,
+    /// The account to send money to.
+    pub to_account: i32,
+    /// The amount of money, in the currency of the account it is taken from.
+    pub amount: Amount,
+    /// When to run the transfer the first time.
+    pub start_at: DateTime<Utc>,
+    /// How often to repeat the transfer. It runs only once if not set.
+    pub recurrence: Option<TransferRecurrence>,
+    /// Do not run the transfer after this time.
+    pub end_at: Option<DateTime<Utc>>,
+}
+
+/// Changes to a scheduled transfer.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ScheduledTransferUpdate {
+    /// The new amount of money.
+    pub amount: Option<Amount>,
+    /// The new end time.
+    pub end_at: Option<DateTime<Utc>>,
+}
+
+/// A stored scheduled transfer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledTransfer {
+    /// The id of the scheduled transfer.
+    pub id: i32,
+    /// The user that scheduled the transfer.
+    pub owner_id: i32,
+    /// The account to take money from.
+    pub from_account: i32,
+    /// The account to send money to.
+    pub to_account: i32,
+    /// The amount of money, in the currency of the account it is taken from.
+    pub amount: i64,
+    /// How often the transfer repeats.
+    pub recurrence: Option<TransferRecurrence>,
+    /// When the transfer runs the first time.
+    pub start_at: DateTime<Utc>,
+    /// The transfer does not run after this time.
+    pub end_at: Option<DateTime<Utc>>,
+    /// When the transfer runs next.
+    pub next_run_at: DateTime<Utc>,
+    /// The number of occurrences that have been run or skipped.
+    pub occurrences: i32,
+    /// The state of the scheduled transfer.
+    pub status: ScheduledTransferStatus,
+    /// When the transfer was scheduled.
+    pub created_at: DateTime<Utc>,
+}
+
+impl ScheduledTransfer {
+    /// The first occurrence after the current one that is later than `now`,
+    /// together with its number. Missed occurrences are skipped.
+    /// Returns `None` if the schedule has no more occurrences.
+    #[must_use]
+    pub fn next_occurrence(&self, now: DateTime<Utc>) -> Option<(i32, DateTime<Utc>)> {
+        let recurrence = self.recurrence?;
+        let mut n = self.occurrences.checked_add(1)?;
+        loop {
+            let at = recurrence.nth(self.start_at, u32::try_from(n).ok()?)?;
+            if self.end_at.is_some_and(|end| at > end) {
+                return None;
+            }
+            if at > now {
+                return Some((n, at));
+            }
+            n = n.checked_add(1)?;
+        }
+    }
+}
+
+/// A record of executing a scheduled transfer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledTransferRun {
+    /// The id of the run.
+    pub id: i32,
+    /// The scheduled transfer that was executed.
+    pub scheduled_transfer_id: i32,
+    /// When the transfer was scheduled to run.
+    pub scheduled_for: DateTime<Utc>,
+    /// Whether the transfer was executed.
+    pub status: ScheduledTransferRunStatus,
+    /// The resulting transfer, if it succeeded.
+    pub transfer_id: Option<i32>,
+    /// Why the transfer failed, if it did.
+    pub error: Option<String>,
+    /// How many attempts were needed.
+    pub attempts: i32,
+    /// When the transfer was executed.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Parameters for listing scheduled transfers or their runs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct ScheduledTransferQuery {
+    /// Only include items older than the item with this id.
+    pub cursor: Option<i32>,
+    /// The maximum number of items to return.
+    #[serde(default = "default_page_size")]
+    #[validate(range(min = 1, max = "MAX_PAGE_SIZE"))]
+    pub limit: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScheduledTransfer, ScheduledTransferStatus, TransferRecurrence};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+
+    fn schedule(
+        start_at: DateTime<Utc>,
+        recurrence: Option<TransferRecurrence>,
+        end_at: Option<DateTime<Utc>>,
+    ) -> ScheduledTransfer {
+        ScheduledTransfer {
+            id: 1,
+            owner_id: 1,
+            from_account: 1,
+            to_account: 2,
+            amount: 10,
+            recurrence,
+            start_at,
+            end_at,
+            next_run_at: start_at,
+            occurrences: 0,
+            status: ScheduledTransferStatus::Active,
+            created_at: start_at,
+        }
+    }
+
+    #[test]
+    fn monthly_recurrence_keeps_day_of_month() {
+        let start = Utc.with_ymd_and_hms(2022, 1, 31, 12, 0, 0).unwrap();
+        let monthly = TransferRecurrence::Monthly;
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2022, 2, 28, 12, 0, 0).unwrap()),
+            monthly.nth(start, 1)
+        );
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2022, 3, 31, 12, 0, 0).unwrap()),
+            monthly.nth(start, 2)
+        );
+    }
+
+    #[test]
+    fn one_off_schedule_has_no_next_occurrence() {
+        let start = Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(None, schedule(start, None, None).next_occurrence(start));
+    }
+
+    #[test]
+    fn next_occurrence_skips_missed_runs_and_respects_end() {
+        let start = Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap();
+        let daily = schedule(start, Some(TransferRecurrence::Daily), None);
+        let now = start + Duration::days(3) + Duration::hours(1);
+        assert_eq!(
+            Some((4, start + Duration::days(4))),
+            daily.next_occurrence(now)
+        );
+
+        let ending = schedule(
+            start,
+            Some(TransferRecurrence::Weekly),
+            Some(start + Duration::days(10)),
+        );
+        assert_eq!(
+            Some((1, start + Duration::weeks(1))),
+            ending.next_occurrence(start)
+        );
+        assert_eq!(None, ending.next_occurrence(start + Duration::weeks(1)));
+    }
+}
@@ -0,0 +1,60 @@
+//! Models for sign-in sessions and the tokens issued in them.
+
+use crate::model::mfa_model::MfaChallenge;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The tokens returned when signing in or refreshing a session.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenResponse {
+    /// A jwt to send as a bearer token.
+    pub access_token: String,
+    /// Always `Bearer`.
+    pub token_type: String,
+    /// The number of seconds until the access token expires.
+    pub expires_in: i64,
+    /// An opaque token that can be exchanged for new tokens once.
+    pub refresh_token: String,
+}
+
+/// The result of signing in with a password.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignIn {
+    /// The user is signed in.
+    Tokens(TokenResponse),
+    /// The user must also give a second factor.
+    MfaRequired(MfaChallenge),
+}
+
+/// A request for new tokens.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RefreshRequest {
+    /// The refresh token returned last.
+    pub refresh_token: String,
+}
+
+impl std::fmt::Debug for RefreshRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RefreshRequest")
+            .field("refresh_token", &"***")
+            .finish()
+    }
+}
+
+/// A stored refresh token, with the session it belongs to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefreshToken {
+    /// The session the token belongs to.
+    pub session_id: Uuid,
+    /// The user who signed in.
+    pub user_id: i32,
+    /// When the token stops working.
+    pub expires_at: DateTime<Utc>,
+    /// When the token was exchanged, if it was.
+    pub used_at: Option<DateTime<Utc>>,
+    /// When the session was revoked, if it was.
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// How the user authenticated when the session started.
+    pub amr: Vec<String>,
+}
@@ -0,0 +1,436 @@
+//! Models representing account statements and their export formats.
+
+use super::{account_model::Account, ledger_model::LedgerEntryKind, money::Currency};
+use crate::infra::xml::escape_xml;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// A format a statement can be exported in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementFormat {
+    /// The statement as JSON.
+    #[default]
+    Json,
+    /// One row per movement, with the opening and closing balances as the first and last rows.
+    Csv,
+    /// An Open Financial Exchange 2.2 bank statement response.
+    Ofx,
+    /// An ISO 20022 `camt.053.001.02` bank-to-customer statement.
+    #[serde(rename = "camt053")]
+    Camt053,
+}
+
+impl StatementFormat {
+    /// The format matching a media type, if any.
+    #[must_use]
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/json" => Some(StatementFormat::Json),
+            "text/csv" => Some(StatementFormat::Csv),
+            "application/x-ofx" => Some(StatementFormat::Ofx),
+            "application/xml" | "text/xml" => Some(StatementFormat::Camt053),
+            _ => None,
+        }
+    }
+
+    /// The media type of statements in this format.
+    #[must_use]
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            StatementFormat::Json => "application/json",
+            StatementFormat::Csv => "text/csv; charset=utf-8",
+            StatementFormat::Ofx => "application/x-ofx",
+            StatementFormat::Camt053 => "application/xml",
+        }
+    }
+
+    /// The file extension of statements in this format.
+    #[must_use]
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StatementFormat::Json => "json",
+            StatementFormat::Csv => "csv",
+            StatementFormat::Ofx => "ofx",
+            StatementFormat::Camt053 => "xml",
+        }
+    }
+}
+
+/// Parameters for fetching a statement.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatementQuery {
+    /// The first day of the statement, in UTC.
+    pub from: NaiveDate,
+    /// The last day of the statement, in UTC.
+    pub to: NaiveDate,
+    /// The format of the statement. If not set, the `Accept` header decides.
+    pub format: Option<StatementFormat>,
+}
+
+impl StatementQuery {
+    /// The start of the first day of the statement.
+    #[must_use]
+    pub fn start(&self) -> DateTime<Utc> {
+        midnight(self.from)
+    }
+
+    /// The start of the day after the last day of the statement.
+    #[must_use]
+    pub fn end(&self) -> DateTime<Utc> {
+        midnight(self.to) + Duration::days(1)
+    }
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_time(NaiveTime::default()))
+}
+
+/// A single movement on a statement.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatementLine {
+    /// The ledger entry behind the movement.
+    pub entry_id: i32,
+    /// When the movement was booked.
+    pub booked_at: DateTime<Utc>,
+    /// What caused the movement.
+    pub kind: LedgerEntryKind,
+    /// The amount credited to the account, negative if it was debited.
+    pub amount: i64,
+    /// The balance of the account after the movement.
+    pub balance: i64,
+    /// The transfer that caused the movement, if any.
+    pub transfer_id: Option<i32>,
+    /// The other account of the transfer, if any.
+    pub counterparty_account: Option<i32>,
+}
+
+impl StatementLine {
+    fn description(&self) -> String {
+        match (self.kind, self.counterparty_account) {
+            (LedgerEntryKind::Transfer, Some(other)) if self.amount < 0 => {
+                format!("Transfer to account {}", other)
+            }
+            (LedgerEntryKind::Transfer, Some(other)) => format!("Transfer from account {}", other),
+            (kind, _) => format!("{:?}", kind),
+        }
+    }
+}
+
+/// The movements on an account over a period, with the balances before and after.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Statement {
+    /// The account the statement is for.
+    pub account: Account,
+    /// The currency of the account.
+    pub currency: Currency,
+    /// The first day of the statement.
+    pub from: NaiveDate,
+    /// The last day of the statement.
+    pub to: NaiveDate,
+    /// The balance at the start of the first day.
+    pub opening_balance: i64,
+    /// The balance at the end of the last day.
+    pub closing_balance: i64,
+    /// The movements, oldest first.
+    pub lines: Vec<StatementLine>,
+    /// When the statement was created.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A row in a CSV statement.
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    booked_at: String,
+    kind: &'a str,
+    description: String,
+    transfer_id: Option<i32>,
+    counterparty_account: Option<i32>,
+    amount: String,
+    balance: String,
+    currency: &'a str,
+}
+
+impl Statement {
+    /// Renders the statement as CSV.
+    pub fn to_csv(&self) -> Result<String, csv::Error> {
+        let currency = self.currency.code.as_str();
+        let balance_row = |kind, at: NaiveDate, balance| CsvRow {
+            booked_at: at.to_string(),
+            kind,
+            description: String::new(),
+            transfer_id: None,
+            counterparty_account: None,
+            amount: String::new(),
+            balance: self.major(balance),
+            currency,
+        };
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.serialize(balance_row(
+            "OpeningBalance",
+            self.from,
+            self.opening_balance,
+        ))?;
+        for line in &self.lines {
+            writer.serialize(CsvRow {
+                booked_at: line.booked_at.to_rfc3339(),
+                kind: kind_name(line.kind),
+                description: line.description(),
+                transfer_id: line.transfer_id,
+                counterparty_account: line.counterparty_account,
+                amount: self.major(line.amount),
+                balance: self.major(line.balance),
+                currency,
+            })?;
+        }
+        writer.serialize(balance_row("ClosingBalance", self.to, self.closing_balance))?;
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| std::io::Error::new(e.error().kind(), e.to_string()))?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Renders the statement as an OFX 2.2 bank statement response.
+    /// OFX has no opening balance, so only the closing balance is included.
+    #[must_use]
+    pub fn to_ofx(&self) -> String {
+        let mut ofx = String::new();
+        let currency = self.currency.code.as_str();
+        let _ = write!(
+            ofx,
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>"#,
+                "\n",
+                r#"<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>"#,
+                "\n<OFX>",
+                "<SIGNONMSGSRSV1><SONRS>",
+                "<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>",
+                "<DTSERVER>{created_at}</DTSERVER><LANGUAGE>ENG</LANGUAGE>",
+                "</SONRS></SIGNONMSGSRSV1>",
+                "<BANKMSGSRSV1><STMTTRNRS><TRNUID>0</TRNUID>",
+                "<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>",
+                "<STMTRS><CURDEF>{currency}</CURDEF>",
+                "<BANKACCTFROM><BANKID>0</BANKID><ACCTID>{account_id}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>",
+                "<BANKTRANLIST><DTSTART>{start}</DTSTART><DTEND>{end}</DTEND>"
+            ),
+            created_at = ofx_time(self.created_at),
+            currency = currency,
+            account_id = self.account.id(),
+            start = ofx_date(self.from),
+            end = ofx_date(self.to),
+        );
+        for line in &self.lines {
+            let kind = match line.kind {
+                LedgerEntryKind::Transfer => "XFER",
+                LedgerEntryKind::Interest => "INT",
+                LedgerEntryKind::Fee => "FEE",
+                _ if line.amount < 0 => "DEBIT",
+                _ => "CREDIT",
+            };
+            let _ = write!(
+                ofx,
+                concat!(
+                    "<STMTTRN><TRNTYPE>{kind}</TRNTYPE><DTPOSTED>{posted}</DTPOSTED>",
+                    "<TRNAMT>{amount}</TRNAMT><FITID>{id}</FITID><NAME>{name}</NAME></STMTTRN>"
+                ),
+                kind = kind,
+                posted = ofx_time(line.booked_at),
+                amount = self.major(line.amount),
+                id = line.entry_id,
+                name = escape_xml(&line.description()),
+            );
+        }
+        let _ = write!(
+            ofx,
+            concat!(
+                "</BANKTRANLIST>",
+                "<LEDGERBAL><BALAMT>{balance}</BALAMT><DTASOF>{end}</DTASOF></LEDGERBAL>",
+                "</STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>\n"
+            ),
+            balance = self.major(self.closing_balance),
+            end = ofx_date(self.to),
+        );
+        ofx
+    }
+
+    /// Renders the statement as an ISO 20022 `camt.053.001.02` document.
+    #[must_use]
+    pub fn to_camt053(&self) -> String {
+        let mut xml = String::new();
+        let currency = self.currency.code.as_str();
+        let id = format!(
+            "STMT-{}-{}-{}",
+            self.account.id(),
+            self.from.format("%Y%m%d"),
+            self.to.format("%Y%m%d")
+        );
+        let created_at = self.created_at.format("%Y-%m-%dT%H:%M:%SZ");
+        let _ = write!(
+            xml,
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                "\n",
+                r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">"#,
+                "<BkToCstmrStmt>",
+                "<GrpHdr><MsgId>{id}</MsgId><CreDtTm>{created_at}</CreDtTm></GrpHdr>",
+                "<Stmt><Id>{id}</Id><CreDtTm>{created_at}</CreDtTm>",
+                "<FrToDt><FrDtTm>{from}T00:00:00Z</FrDtTm><ToDtTm>{to}T23:59:59Z</ToDtTm></FrToDt>",
+                "<Acct><Id><Othr><Id>{account_id}</Id></Othr></Id><Ccy>{currency}</Ccy><Nm>{name}</Nm></Acct>"
+            ),
+            id = id,
+            created_at = created_at,
+            from = self.from,
+            to = self.to,
+            account_id = self.account.id(),
+            currency = currency,
+            name = escape_xml(self.account.name()),
+        );
+        for (code, balance, date) in [
+            ("OPBD", self.opening_balance, self.from),
+            ("CLBD", self.closing_balance, self.to),
+        ] {
+            let _ = write!(
+                xml,
+                concat!(
+                    "<Bal><Tp><CdOrPrtry><Cd>{code}</Cd></CdOrPrtry></Tp>",
+                    r#"<Amt Ccy="{currency}">{amount}</Amt><CdtDbtInd>{indicator}</CdtDbtInd>"#,
+                    "<Dt><Dt>{date}</Dt></Dt></Bal>"
+                ),
+                code = code,
+                currency = currency,
+                amount = self.major(balance.abs()),
+                indicator = credit_debit(balance),
+                date = date,
+            );
+        }
+        for line in &self.lines {
+            let _ = write!(
+                xml,
+                concat!(
+                    "<Ntry><NtryRef>{id}</NtryRef>",
+                    r#"<Amt Ccy="{currency}">{amount}</Amt><CdtDbtInd>{indicator}</CdtDbtInd>"#,
+                    "<Sts>BOOK</Sts><BookgDt><DtTm>{booked_at}</DtTm></BookgDt>",
+                    "<BkTxCd><Prtry><Cd>{kind}</Cd></Prtry></BkTxCd>",
+                    "<NtryDtls><TxDtls><Refs><EndToEndId>{reference}</EndToEndId></Refs></TxDtls></NtryDtls>",
+                    "<AddtlNtryInf>{description}</AddtlNtryInf></Ntry>"
+                ),
+                id = line.entry_id,
+                currency = currency,
+                amount = self.major(line.amount.abs()),
+                indicator = credit_debit(line.amount),
+                booked_at = line.booked_at.format("%Y-%m-%dT%H:%M:%SZ"),
+                kind = kind_name(line.kind),
+                reference = line
+                    .transfer_id
+                    .map_or_else(|| "NOTPROVIDED".to_string(), |id| id.to_string()),
+                description = escape_xml(&line.description()),
+            );
+        }
+        xml.push_str("</Stmt></BkToCstmrStmt></Document>\n");
+        xml
+    }
+
+    /// Formats an amount of minor units in the major unit of the account's currency.
+    fn major(&self, minor_units: i64) -> String {
+        self.currency.to_major(minor_units).to_string()
+    }
+}
+
+fn kind_name(kind: LedgerEntryKind) -> &'static str {
+    match kind {
+        LedgerEntryKind::Opening => "Opening",
+        LedgerEntryKind::Deposit => "Deposit",
+        LedgerEntryKind::Withdrawal => "Withdrawal",
+        LedgerEntryKind::Transfer => "Transfer",
+        LedgerEntryKind::Interest => "Interest",
+        LedgerEntryKind::Fee => "Fee",
+    }
+}
+
+fn credit_debit(amount: i64) -> &'static str {
+    if amount < 0 {
+        "DBIT"
+    } else {
+        "CRDT"
+    }
+}
+
+fn ofx_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+fn ofx_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%d%H%M%S").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Statement, StatementLine};
+    use crate::model::{
+        account_model::Account,
+        ledger_model::LedgerEntryKind,
+        money::{Currency, CurrencyCode},
+    };
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    fn statement() -> Statement {
+        let booked_at = Utc.with_ymd_and_hms(2022, 10, 2, 9, 30, 0).unwrap();
+        Statement {
+            account: Account::new(1, "Bills & <rent>".to_string(), 2500, 1),
+            currency: Currency {
+                code: CurrencyCode::default(),
+                minor_units: 2,
+            },
+            from: NaiveDate::from_ymd_opt(2022, 10, 1).unwrap(),
+            to: NaiveDate::from_ymd_opt(2022, 10, 31).unwrap(),
+            opening_balance: 10000,
+            closing_balance: 2500,
+            lines: vec![StatementLine {
+                entry_id: 7,
+                booked_at,
+                kind: LedgerEntryKind::Transfer,
+                amount: -7500,
+                balance: 2500,
+                transfer_id: Some(3),
+                counterparty_account: Some(2),
+            }],
+            created_at: booked_at,
+        }
+    }
+
+    #[test]
+    fn csv_has_opening_and_closing_rows() {
+        let csv = statement().to_csv().unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            vec![
+                "booked_at,kind,description,transfer_id,counterparty_account,amount,balance,currency",
+                "2022-10-01,OpeningBalance,,,,,100.00,NOK",
+                "2022-10-02T09:30:00+00:00,Transfer,Transfer to account 2,3,2,-75.00,25.00,NOK",
+                "2022-10-31,ClosingBalance,,,,,25.00,NOK",
+            ],
+            rows
+        );
+    }
+
+    #[test]
+    fn camt053_uses_absolute_amounts_and_escapes_text() {
+        let xml = statement().to_camt053();
+        assert!(xml.contains("<Nm>Bills &amp; &lt;rent&gt;</Nm>"));
+        assert!(xml.contains(
+            r#"<Cd>OPBD</Cd></CdOrPrtry></Tp><Amt Ccy="NOK">100.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>"#
+        ));
+        assert!(xml.contains(r#"<Amt Ccy="NOK">75.00</Amt><CdtDbtInd>DBIT</CdtDbtInd>"#));
+        assert!(xml.contains("<EndToEndId>3</EndToEndId>"));
+    }
+
+    #[test]
+    fn ofx_lists_transactions_and_closing_balance() {
+        let ofx = statement().to_ofx();
+        assert!(ofx.contains("<TRNTYPE>XFER</TRNTYPE><DTPOSTED>20221002093000</DTPOSTED>"));
+        assert!(ofx.contains("<TRNAMT>-75.00</TRNAMT><FITID>7</FITID>"));
+        assert!(ofx.contains("<LEDGERBAL><BALAMT>25.00</BALAMT><DTASOF>20221031</DTASOF>"));
+    }
+}
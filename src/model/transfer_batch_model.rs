@@ -0,0 +1,99 @@
+//! Models representing batches of transfers that are submitted together.
+
+use super::transfer_model::NewTransfer;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// The maximum number of transfers in a batch.
+pub const MAX_BATCH_SIZE: usize = 1000;
+
+/// How failures in a batch are handled.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "transfer_batch_mode")]
+pub enum TransferBatchMode {
+    /// Either all transfers are executed, or none of them are.
+    #[default]
+    Atomic,
+    /// Every transfer that can be executed is executed.
+    BestEffort,
+}
+
+/// The overall outcome of a batch.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "transfer_batch_status")]
+pub enum TransferBatchStatus {
+    /// All transfers were executed.
+    Succeeded,
+    /// Some transfers were executed.
+    PartiallySucceeded,
+    /// No transfers were executed.
+    Failed,
+}
+
+/// The outcome of a single transfer in a batch.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "transfer_batch_item_status")]
+pub enum TransferBatchItemStatus {
+    /// The transfer was executed.
+    Succeeded,
+    /// The transfer could not be executed.
+    Failed,
+    /// The transfer was not executed, or was rolled back, because another transfer failed.
+    NotExecuted,
+}
+
+/// A new batch of transfers.
+#[derive(Clone, Debug, Serialize, Deserialize, Validate)]
+pub struct NewTransferBatch {
+    /// How failures are handled.
+    #[serde(default)]
+    pub mode: TransferBatchMode,
+    /// The transfers to execute, in order.
+    #[validate(length(min = 1, max = "MAX_BATCH_SIZE"))]
+    pub transfers: Vec<NewTransfer>,
+}
+
+/// A stored batch of transfers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferBatch {
+    /// The id of the batch.
+    pub id: i32,
+    /// The user that submitted the batch.
+    pub owner_id: i32,
+    /// How failures were handled.
+    pub mode: TransferBatchMode,
+    /// The overall outcome.
+    pub status: TransferBatchStatus,
+    /// When the batch was submitted.
+    pub created_at: DateTime<Utc>,
+}
+
+/// The outcome of a transfer in a batch.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferBatchItem {
+    /// The position of the transfer in the batch, starting at 0.
+    pub position: i32,
+    /// The account to take money from.
+    pub from_account: i32,
+    /// The account to send money to.
+    pub to_account: i32,
+    /// The amount of money.
+    pub amount: i64,
+    /// The outcome of the transfer.
+    pub status: TransferBatchItemStatus,
+    /// The resulting transfer, if it was executed.
+    pub transfer_id: Option<i32>,
+    /// Why the transfer failed, if it did.
+    pub error: Option<String>,
+}
+
+/// A batch together with the outcome of each of its transfers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferBatchReport {
+    /// The batch.
+    #[serde(flatten)]
+    pub batch: TransferBatch,
+    /// The outcome of each transfer, in order.
+    pub items: Vec<TransferBatchItem>,
+}
@@ -1,8 +1,14 @@
 //! Models representing transfers.
 
+use super::{
+    money::Amount,
+    pagination::{default_page_size, MAX_PAGE_SIZE},
+};
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use validator::Validate;
 
 /// A new transfer between accounts.
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
@@ -11,8 +17,8 @@ pub struct NewTransfer {
     pub from_account: i32,
     /// The account to send money to.
     pub to_account: i32,
-    /// The amount of money.
-    pub amount: u32,
+    /// The amount of money, in the currency of the account it is taken from.
+    pub amount: Amount,
 }
 
 /// A stored transfer between accounts.
@@ -24,8 +30,61 @@ pub struct Transfer {
     pub from_account: i32,
     /// The account to send money to.
     pub to_account: i32,
-    /// The amount of money.
+    /// The amount of money, in the currency of the account it is taken from.
     pub amount: i64,
+    /// The amount received, in the currency of the account it is sent to.
+    pub to_amount: i64,
+    /// The exchange rate used if the accounts have different currencies.
+    /// A reversal uses the rate of the transfer it reverses.
+    pub exchange_rate: Option<Decimal>,
+    /// The transfer that this transfer reverses, if it is a reversal.
+    pub reversal_of: Option<i32>,
+    /// The transfer that reversed this transfer, if it has been reversed.
+    pub reversed_by: Option<i32>,
     /// A timestamp for the transaction.
     pub created_at: DateTime<Utc>,
 }
+
+/// The direction money moves relative to an account or user.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    /// Money received.
+    Incoming,
+    /// Money sent.
+    Outgoing,
+}
+
+impl TransferDirection {
+    /// The name of the direction as used in queries.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferDirection::Incoming => "incoming",
+            TransferDirection::Outgoing => "outgoing",
+        }
+    }
+}
+
+/// Parameters for searching for transfers.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct TransferQuery {
+    /// Only include transfers to or from this account.
+    pub account: Option<i32>,
+    /// Only include transfers in this direction.
+    pub direction: Option<TransferDirection>,
+    /// Only include transfers of at least this amount.
+    pub min_amount: Option<i64>,
+    /// Only include transfers of at most this amount.
+    pub max_amount: Option<i64>,
+    /// Only include transfers created at or after this time.
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only include transfers created before this time.
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only include transfers older than the transfer with this id.
+    pub cursor: Option<i32>,
+    /// The maximum number of transfers to return.
+    #[serde(default = "default_page_size")]
+    #[validate(range(min = 1, max = "MAX_PAGE_SIZE"))]
+    pub limit: u32,
+}
@@ -1,30 +1,46 @@
 //! Models for representing subscriptions.
 
-use std::ops::Deref;
+use std::{fmt, ops::Deref};
 
+use crate::infra::{error::AppError, security::password};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 
 /// A wrapper type that guarantees that a password is hashed.
 #[derive(
-    Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::FromRow, sqlx::Type,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    sqlx::FromRow,
+    sqlx::Type,
 )]
 #[serde(transparent)]
 #[sqlx(transparent)]
 pub struct HashedPassword(String);
 
 impl HashedPassword {
-    /// Creates a hashed password from an unhashed [`String`].
-    pub fn new(password: String) -> Self {
-        Self(bcrypt::hash(password, 10).unwrap())
+    /// Hashes a password with Argon2id.
+    pub fn new(password: &str) -> Result<Self, AppError> {
+        Ok(Self(password::hash(password)?))
     }
     /// Returns the hashed password.
     pub fn hashed_password(&self) -> &str {
         &self.0
     }
-    /// Compares the provided string to the stored password.
+    /// Compares the provided string to the stored password, which may also be a bcrypt hash.
     pub fn verify(&self, password: &str) -> bool {
-        bcrypt::verify(password, &self.0).unwrap()
+        password::verify(password, &self.0)
+    }
+    /// Whether the password should be hashed again with the current algorithm and parameters.
+    pub fn needs_rehash(&self) -> bool {
+        password::needs_rehash(&self.0)
     }
 }
 
@@ -36,9 +52,10 @@ impl Deref for HashedPassword {
 }
 
 /// A new user.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Validate)]
 pub struct NewUser {
     /// The name of the new user.
+    #[validate(length(min = 1, max = 100))]
     pub name: String,
     /// The password of the new user.
     pub password: String,
@@ -51,8 +68,43 @@ pub struct User {
     pub id: i32,
     /// The name of the new user.
     pub name: String,
-    /// The password of the new user.
+    /// The password of the new user. Never sent to clients.
+    #[serde(skip)]
     pub password: HashedPassword,
+    /// The user's full name.
+    pub full_name: Option<String>,
+    /// The user's email address.
+    pub email: Option<String>,
     /// Creation date.
     pub created_at: DateTime<Utc>,
 }
+
+/// Changes to a user's profile. Fields that are not set are left unchanged.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Validate)]
+pub struct UserUpdate {
+    /// The new name, which the user signs in with.
+    #[validate(length(min = 1, max = 100))]
+    pub name: Option<String>,
+    /// The new full name.
+    #[validate(length(min = 1, max = 200))]
+    pub full_name: Option<String>,
+    /// The new email address.
+    #[validate(email)]
+    pub email: Option<String>,
+}
+
+/// A change of a user's own password.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PasswordChange {
+    /// The password the user signs in with now.
+    pub current_password: String,
+    /// The password to sign in with from now on.
+    pub new_password: String,
+}
+
+// Keep passwords out of logs and audit records
+impl fmt::Debug for PasswordChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordChange").finish_non_exhaustive()
+    }
+}
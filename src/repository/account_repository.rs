@@ -2,7 +2,10 @@
 
 use crate::{
     infra::error::DbError,
-    model::account_model::{Account, NewAccount},
+    model::{
+        account_model::{Account, AccountQuery, AccountStatus, NewAccount},
+        money::{Amount, CurrencyCode},
+    },
     Tx,
 };
 
@@ -16,12 +19,13 @@ pub async fn insert_account(
     sqlx::query_as!(
         Account,
         r#"
-            INSERT INTO accounts (name, balance, owner_id)
-            VALUES ($1, $2, $3)
-            RETURNING *
+            INSERT INTO accounts (name, balance, currency, owner_id)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
         "#,
         new_account.name(),
         0i64,
+        new_account.currency().as_str(),
         user_id,
     )
     .fetch_one(tx)
@@ -29,12 +33,17 @@ pub async fn insert_account(
     .map_err(DbError::from)
 }
 
-/// Fetch an account from the account table.
+/// Fetch an account that a user is a member of, in any role.
 #[tracing::instrument(skip(tx), fields(audit, entity_id = account_id), ret)]
 pub async fn fetch_account(tx: &mut Tx, user_id: i32, account_id: i32) -> Result<Account, DbError> {
     sqlx::query_as!(
         Account,
-        r#"SELECT * FROM accounts WHERE owner_id = $1 AND id = $2"#,
+        r#"
+            SELECT id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
+            FROM accounts
+            WHERE id = $2
+              AND EXISTS (SELECT 1 FROM account_members m WHERE m.account_id = accounts.id AND m.user_id = $1)
+        "#,
         user_id,
         account_id
     )
@@ -43,35 +52,215 @@ pub async fn fetch_account(tx: &mut Tx, user_id: i32, account_id: i32) -> Result
     .map_err(DbError::from)
 }
 
+/// Fetch the currency of an account, whoever it belongs to.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_currency(tx: &mut Tx, account_id: i32) -> Result<CurrencyCode, DbError> {
+    let currency = sqlx::query_scalar!(
+        r#"SELECT currency as "currency: CurrencyCode" FROM accounts WHERE id = $1"#,
+        account_id
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(currency)
+}
+
+/// Fetch and lock accounts for the rest of the transaction.
+/// Rows are locked in order of id, so concurrent callers cannot deadlock.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn lock_accounts(tx: &mut Tx, account_ids: &[i32]) -> Result<Vec<Account>, DbError> {
+    sqlx::query_as!(
+        Account,
+        r#"
+            SELECT id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
+            FROM accounts
+            WHERE id = ANY($1)
+            ORDER BY id
+            FOR UPDATE
+        "#,
+        account_ids,
+    )
+    .fetch_all(tx)
+    .await
+    .map_err(DbError::from)
+}
+
+/// Fetch a page of the accounts a user is a member of.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_accounts(
+    tx: &mut Tx,
+    user_id: i32,
+    query: &AccountQuery,
+) -> Result<Vec<Account>, DbError> {
+    let limit = i64::from(query.size);
+    let offset = i64::from(query.page) * limit;
+    sqlx::query_as!(
+        Account,
+        r#"
+            SELECT id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
+            FROM accounts
+            WHERE id IN (SELECT account_id FROM account_members WHERE user_id = $1)
+            ORDER BY
+                CASE WHEN $2 = 'id' AND $3 = 'asc' THEN id END ASC,
+                CASE WHEN $2 = 'id' AND $3 = 'desc' THEN id END DESC,
+                CASE WHEN $2 = 'name' AND $3 = 'asc' THEN name END ASC,
+                CASE WHEN $2 = 'name' AND $3 = 'desc' THEN name END DESC,
+                CASE WHEN $2 = 'balance' AND $3 = 'asc' THEN balance END ASC,
+                CASE WHEN $2 = 'balance' AND $3 = 'desc' THEN balance END DESC,
+                id ASC
+            LIMIT $4
+            OFFSET $5
+        "#,
+        user_id,
+        query.sort.as_str(),
+        query.order.as_str(),
+        limit,
+        offset,
+    )
+    .fetch_all(tx)
+    .await
+    .map_err(DbError::from)
+}
+
+/// Count the accounts a user is a member of.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn count_accounts(tx: &mut Tx, user_id: i32) -> Result<i64, DbError> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM account_members WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(count)
+}
+
+/// Count the open accounts a user is the owner of, by their membership. Every account has a
+/// single owner, so these accounts would be left without one if the user was deleted.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn count_open_accounts(tx: &mut Tx, user_id: i32) -> Result<i64, DbError> {
+    let count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM accounts a
+        JOIN account_members m ON m.account_id = a.id
+        WHERE m.user_id = $1 AND m.role = 'Owner' AND a.status = 'Open'
+        "#,
+        user_id
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(count)
+}
+
+/// Change the name of an account.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = account_id), ret)]
+pub async fn rename_account(tx: &mut Tx, account_id: i32, name: &str) -> Result<Account, DbError> {
+    sqlx::query_as!(
+        Account,
+        r#"
+            UPDATE accounts
+            SET name = $1
+            WHERE id = $2
+            RETURNING id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
+        "#,
+        name,
+        account_id,
+    )
+    .fetch_one(tx)
+    .await
+    .map_err(DbError::from)
+}
+
+/// Close an account. Only accounts with a zero balance and nothing held can be closed.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = account_id), ret)]
+pub async fn close_account(tx: &mut Tx, account_id: i32) -> Result<Account, DbError> {
+    sqlx::query_as!(
+        Account,
+        r#"
+            UPDATE accounts
+            SET status = 'Closed'
+            WHERE id = $1 AND balance = 0 AND held = 0
+            RETURNING id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
+        "#,
+        account_id,
+    )
+    .fetch_one(tx)
+    .await
+    .map_err(DbError::from)
+}
+
 /// Increase balance on an account.
 #[tracing::instrument(skip(tx), ret)]
-pub async fn deposit(tx: &mut Tx, account_id: i32, amount: u32) -> Result<(), DbError> {
-    sqlx::query!(
+pub async fn deposit(tx: &mut Tx, account_id: i32, amount: Amount) -> Result<Account, DbError> {
+    let account = sqlx::query_as!(
+        Account,
         r#"
-        UPDATE accounts
-        SET balance = balance + $1
-        WHERE id = $2
+            UPDATE accounts
+            SET balance = balance + $1
+            WHERE id = $2
+            RETURNING id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
         "#,
-        amount as i64,
+        amount.minor_units(),
         account_id,
     )
-    .execute(tx)
+    .fetch_one(tx)
     .await?;
-    Ok(())
+    Ok(account)
 }
 
 /// Decrease balance on an account.
 #[tracing::instrument(skip(tx), ret)]
-pub async fn withdraw(tx: &mut Tx, account_id: i32, withdrawal: u32) -> Result<Account, DbError> {
+pub async fn withdraw(
+    tx: &mut Tx,
+    account_id: i32,
+    withdrawal: Amount,
+) -> Result<Account, DbError> {
     let account = sqlx::query_as!(
         Account,
         r#"
             UPDATE accounts
             SET balance = balance - $1
             WHERE id = $2
-            RETURNING *
+            RETURNING id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
+        "#,
+        withdrawal.minor_units(),
+        account_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(account)
+}
+
+/// Reserve money on an account for a hold.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn reserve(tx: &mut Tx, account_id: i32, amount: Amount) -> Result<Account, DbError> {
+    let account = sqlx::query_as!(
+        Account,
+        r#"
+            UPDATE accounts
+            SET held = held + $1
+            WHERE id = $2
+            RETURNING id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
+        "#,
+        amount.minor_units(),
+        account_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(account)
+}
+
+/// Free money reserved on an account by a hold.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn unreserve(tx: &mut Tx, account_id: i32, amount: Amount) -> Result<Account, DbError> {
+    let account = sqlx::query_as!(
+        Account,
+        r#"
+            UPDATE accounts
+            SET held = held - $1
+            WHERE id = $2
+            RETURNING id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
         "#,
-        withdrawal as i64,
+        amount.minor_units(),
         account_id,
     )
     .fetch_one(tx)
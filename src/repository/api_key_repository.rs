@@ -0,0 +1,103 @@
+//! Functions for storing API keys and looking them up by their hash.
+
+use crate::{
+    infra::error::DbError,
+    model::api_key_model::{ApiKey, NewApiKey},
+};
+use sqlx::PgExecutor;
+
+/// Store a new API key for a user, given the hash and the start of the key.
+#[tracing::instrument(skip(conn, key_hash), fields(audit, entity_id = user_id), ret)]
+pub async fn insert_api_key(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    new_key: &NewApiKey,
+    key_hash: &str,
+    prefix: &str,
+) -> Result<ApiKey, DbError> {
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"
+        INSERT INTO api_keys (user_id, name, key_hash, prefix, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, user_id, name, prefix, scopes, expires_at, last_used_at, revoked_at,
+            created_at
+        "#,
+        user_id,
+        new_key.name,
+        key_hash,
+        prefix,
+        &new_key.scopes,
+        new_key.expires_at,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(api_key)
+}
+
+/// Fetch the API keys of a user, including revoked and expired ones.
+#[tracing::instrument(skip(conn))]
+pub async fn fetch_api_keys(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+) -> Result<Vec<ApiKey>, DbError> {
+    let api_keys = sqlx::query_as!(
+        ApiKey,
+        r#"
+        SELECT id, user_id, name, prefix, scopes, expires_at, last_used_at, revoked_at,
+            created_at
+        FROM api_keys
+        WHERE user_id = $1
+        ORDER BY id
+        "#,
+        user_id,
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(api_keys)
+}
+
+/// Fetch an API key by its hash, unless it is revoked or expired, and record that it was used.
+#[tracing::instrument(skip_all)]
+pub async fn use_api_key(conn: impl PgExecutor<'_>, key_hash: &str) -> Result<ApiKey, DbError> {
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"
+        UPDATE api_keys
+        SET last_used_at = CURRENT_TIMESTAMP
+        WHERE key_hash = $1
+            AND revoked_at IS NULL
+            AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+        RETURNING id, user_id, name, prefix, scopes, expires_at, last_used_at, revoked_at,
+            created_at
+        "#,
+        key_hash,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(api_key)
+}
+
+/// Revoke an API key of a user, so that it can no longer be used.
+#[tracing::instrument(skip(conn), fields(audit, entity_id = id), ret)]
+pub async fn revoke_api_key(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    id: i32,
+) -> Result<ApiKey, DbError> {
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"
+        UPDATE api_keys
+        SET revoked_at = COALESCE(revoked_at, CURRENT_TIMESTAMP)
+        WHERE user_id = $1 AND id = $2
+        RETURNING id, user_id, name, prefix, scopes, expires_at, last_used_at, revoked_at,
+            created_at
+        "#,
+        user_id,
+        id,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(api_key)
+}
@@ -0,0 +1,140 @@
+//! Functions for interacting with currencies and exchange rates.
+
+use crate::{
+    infra::error::DbError,
+    model::{
+        fx_model::FxRate,
+        money::{Currency, CurrencyCode},
+    },
+    Tx,
+};
+use rust_decimal::Decimal;
+
+/// Fetch all supported currencies.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_currencies(tx: &mut Tx) -> Result<Vec<Currency>, DbError> {
+    let currencies = sqlx::query_as!(
+        Currency,
+        r#"SELECT code as "code: CurrencyCode", minor_units FROM currencies ORDER BY code"#
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(currencies)
+}
+
+/// Fetch a supported currency.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_currency(tx: &mut Tx, code: &CurrencyCode) -> Result<Currency, DbError> {
+    let currency = sqlx::query_as!(
+        Currency,
+        r#"SELECT code as "code: CurrencyCode", minor_units FROM currencies WHERE code = $1"#,
+        code.as_str(),
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(currency)
+}
+
+/// Fetch all exchange rates.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_fx_rates(tx: &mut Tx) -> Result<Vec<FxRate>, DbError> {
+    let rates = sqlx::query_as!(
+        FxRate,
+        r#"
+        SELECT
+            base_currency as "base_currency: CurrencyCode",
+            quote_currency as "quote_currency: CurrencyCode",
+            rate,
+            updated_at,
+            updated_by
+        FROM fx_rates
+        ORDER BY base_currency, quote_currency
+        "#
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(rates)
+}
+
+/// Fetch the rate for converting from `base` to `quote`.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_fx_rate(
+    tx: &mut Tx,
+    base: &CurrencyCode,
+    quote: &CurrencyCode,
+) -> Result<FxRate, DbError> {
+    let rate = sqlx::query_as!(
+        FxRate,
+        r#"
+        SELECT
+            base_currency as "base_currency: CurrencyCode",
+            quote_currency as "quote_currency: CurrencyCode",
+            rate,
+            updated_at,
+            updated_by
+        FROM fx_rates
+        WHERE base_currency = $1 AND quote_currency = $2
+        "#,
+        base.as_str(),
+        quote.as_str(),
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(rate)
+}
+
+/// Create or replace the rate for converting from `base` to `quote`.
+#[tracing::instrument(skip(tx), fields(audit), ret)]
+pub async fn upsert_fx_rate(
+    tx: &mut Tx,
+    base: &CurrencyCode,
+    quote: &CurrencyCode,
+    rate: Decimal,
+    updated_by: i32,
+) -> Result<FxRate, DbError> {
+    let rate = sqlx::query_as!(
+        FxRate,
+        r#"
+        INSERT INTO fx_rates (base_currency, quote_currency, rate, updated_by)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (base_currency, quote_currency) DO UPDATE
+        SET rate = EXCLUDED.rate,
+            updated_at = CURRENT_TIMESTAMP,
+            updated_by = EXCLUDED.updated_by
+        RETURNING
+            base_currency as "base_currency: CurrencyCode",
+            quote_currency as "quote_currency: CurrencyCode",
+            rate,
+            updated_at,
+            updated_by
+        "#,
+        base.as_str(),
+        quote.as_str(),
+        rate,
+        updated_by,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(rate)
+}
+
+/// Remove the rate for converting from `base` to `quote`.
+#[tracing::instrument(skip(tx), fields(audit), ret)]
+pub async fn delete_fx_rate(
+    tx: &mut Tx,
+    base: &CurrencyCode,
+    quote: &CurrencyCode,
+) -> Result<(), DbError> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM fx_rates WHERE base_currency = $1 AND quote_currency = $2"#,
+        base.as_str(),
+        quote.as_str(),
+    )
+    .execute(tx)
+    .await?
+    .rows_affected();
+    if deleted == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! Functions for storing holds on accounts.
+
+use crate::{
+    infra::error::DbError,
+    model::hold_model::{Hold, HoldQuery, HoldStatus, NewHold},
+    Tx,
+};
+use chrono::{DateTime, Utc};
+
+/// Store a new hold. The money must be reserved on the account separately.
+#[tracing::instrument(skip(tx), fields(audit), ret)]
+pub async fn insert_hold(
+    tx: &mut Tx,
+    account_id: i32,
+    new_hold: &NewHold,
+) -> Result<Hold, DbError> {
+    let hold = sqlx::query_as!(
+        Hold,
+        r#"
+        INSERT INTO holds (account_id, amount, expires_at)
+        VALUES ($1, $2, $3)
+        RETURNING
+            id, account_id, amount, captured_amount,
+            status as "status: HoldStatus",
+            expires_at, created_at, closed_at
+        "#,
+        account_id,
+        new_hold.amount.minor_units(),
+        new_hold.expires_at,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(hold)
+}
+
+/// Fetch a hold on an account.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_hold(tx: &mut Tx, account_id: i32, hold_id: i32) -> Result<Hold, DbError> {
+    let hold = sqlx::query_as!(
+        Hold,
+        r#"
+        SELECT
+            id, account_id, amount, captured_amount,
+            status as "status: HoldStatus",
+            expires_at, created_at, closed_at
+        FROM holds
+        WHERE account_id = $1 AND id = $2
+        "#,
+        account_id,
+        hold_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(hold)
+}
+
+/// Fetch the holds on an account, newest first.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_holds(
+    tx: &mut Tx,
+    account_id: i32,
+    query: &HoldQuery,
+) -> Result<Vec<Hold>, DbError> {
+    let holds = sqlx::query_as!(
+        Hold,
+        r#"
+        SELECT
+            id, account_id, amount, captured_amount,
+            status as "status: HoldStatus",
+            expires_at, created_at, closed_at
+        FROM holds
+        WHERE account_id = $1 AND ($2::HOLD_STATUS IS NULL OR status = $2)
+        ORDER BY id DESC
+        "#,
+        account_id,
+        query.status as Option<HoldStatus>,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(holds)
+}
+
+/// Fetch and lock a hold on an account for the rest of the transaction.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn lock_hold(tx: &mut Tx, account_id: i32, hold_id: i32) -> Result<Hold, DbError> {
+    let hold = sqlx::query_as!(
+        Hold,
+        r#"
+        SELECT
+            id, account_id, amount, captured_amount,
+            status as "status: HoldStatus",
+            expires_at, created_at, closed_at
+        FROM holds
+        WHERE account_id = $1 AND id = $2
+        FOR UPDATE
+        "#,
+        account_id,
+        hold_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(hold)
+}
+
+/// Claim up to `limit` active holds that expired before `now`, together with their accounts.
+/// Holds that are locked by other transactions, or whose account is, are skipped, so this never
+/// waits for the locks that requests take on an account and then on its holds.
+#[tracing::instrument(skip(tx))]
+pub async fn lock_expired_holds(
+    tx: &mut Tx,
+    now: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Hold>, DbError> {
+    let holds = sqlx::query_as!(
+        Hold,
+        r#"
+        SELECT
+            h.id, h.account_id, h.amount, h.captured_amount,
+            h.status as "status: HoldStatus",
+            h.expires_at, h.created_at, h.closed_at
+        FROM holds h
+        JOIN accounts a ON a.id = h.account_id
+        WHERE h.status = 'Active' AND h.expires_at <= $1
+        ORDER BY h.expires_at
+        LIMIT $2
+        FOR UPDATE OF h, a SKIP LOCKED
+        "#,
+        now,
+        limit,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(holds)
+}
+
+/// Capture or release a hold. The money must be freed on the account separately.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = hold_id), ret)]
+pub async fn close_hold(
+    tx: &mut Tx,
+    hold_id: i32,
+    status: HoldStatus,
+    captured_amount: Option<i64>,
+) -> Result<Hold, DbError> {
+    let hold = sqlx::query_as!(
+        Hold,
+        r#"
+        UPDATE holds
+        SET status = $2, captured_amount = $3, closed_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        RETURNING
+            id, account_id, amount, captured_amount,
+            status as "status: HoldStatus",
+            expires_at, created_at, closed_at
+        "#,
+        hold_id,
+        status as HoldStatus,
+        captured_amount,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(hold)
+}
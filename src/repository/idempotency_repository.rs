@@ -0,0 +1,122 @@
+//! Functions for storing and replaying responses to idempotent requests.
+
+use crate::infra::error::DbError;
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+
+/// A stored idempotency key and the response it produced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct IdempotencyRecord {
+    pub(crate) request_hash: String,
+    pub(crate) response_code: Option<i32>,
+    pub(crate) response_content_type: Option<String>,
+    pub(crate) response_body: Option<Vec<u8>>,
+}
+
+/// Claim an idempotency key for a request.
+/// Returns `false` if the user has already used the key and it has not expired.
+pub(crate) async fn try_insert_key(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    key: &str,
+    request_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<bool, DbError> {
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO idempotency_keys (user_id, idempotency_key, request_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id, idempotency_key) DO UPDATE
+        SET request_hash = EXCLUDED.request_hash,
+            response_code = NULL,
+            response_content_type = NULL,
+            response_body = NULL,
+            created_at = CURRENT_TIMESTAMP,
+            expires_at = EXCLUDED.expires_at
+        WHERE idempotency_keys.expires_at < CURRENT_TIMESTAMP
+        "#,
+        user_id,
+        key,
+        request_hash,
+        expires_at,
+    )
+    .execute(conn)
+    .await?
+    .rows_affected();
+    Ok(inserted == 1)
+}
+
+/// Fetch a stored idempotency key.
+pub(crate) async fn fetch_key(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    key: &str,
+) -> Result<IdempotencyRecord, DbError> {
+    let record = sqlx::query_as!(
+        IdempotencyRecord,
+        r#"
+        SELECT request_hash, response_code, response_content_type, response_body
+        FROM idempotency_keys
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        key,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(record)
+}
+
+/// Store the response to a request so it can be replayed.
+pub(crate) async fn store_response(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    key: &str,
+    response_code: i32,
+    response_content_type: Option<&str>,
+    response_body: &[u8],
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE idempotency_keys
+        SET response_code = $3, response_content_type = $4, response_body = $5
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        key,
+        response_code,
+        response_content_type,
+        response_body,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Release an idempotency key so that the request can be retried.
+pub(crate) async fn delete_key(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    key: &str,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"DELETE FROM idempotency_keys WHERE user_id = $1 AND idempotency_key = $2"#,
+        user_id,
+        key,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Delete the keys that expired before `now`, and return how many were deleted.
+pub(crate) async fn delete_expired_keys(
+    conn: impl PgExecutor<'_>,
+    now: DateTime<Utc>,
+) -> Result<u64, DbError> {
+    let deleted = sqlx::query!(r#"DELETE FROM idempotency_keys WHERE expires_at < $1"#, now,)
+        .execute(conn)
+        .await?
+        .rows_affected();
+    Ok(deleted)
+}
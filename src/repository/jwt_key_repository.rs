@@ -0,0 +1,111 @@
+//! Functions for storing the keys that jwts are signed and verified with.
+
+use crate::{
+    infra::{
+        error::DbError,
+        security::keys::{JwtKey, KeyStatus},
+    },
+    Tx,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+
+/// Store a key from the configuration, unless a key with the same id, or another active key,
+/// is already stored.
+#[tracing::instrument(skip(tx))]
+pub async fn import_key(tx: &mut Tx, key: &JwtKey) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO jwt_keys (kid, private_key, public_key, status)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        "#,
+        key.kid,
+        key.private_key,
+        key.public_key,
+        key.status as KeyStatus,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Store a new key.
+#[tracing::instrument(skip(tx), fields(audit), ret)]
+pub async fn insert_key(tx: &mut Tx, key: &JwtKey) -> Result<JwtKey, DbError> {
+    let key = sqlx::query_as!(
+        JwtKey,
+        r#"
+        INSERT INTO jwt_keys (kid, private_key, public_key, status, retires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING
+            kid, private_key, public_key, status as "status: KeyStatus", created_at, retires_at
+        "#,
+        key.kid,
+        key.private_key,
+        key.public_key,
+        key.status as KeyStatus,
+        key.retires_at,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(key)
+}
+
+/// Make the active key verify tokens only, until `retires_at`.
+#[tracing::instrument(skip(tx))]
+pub async fn demote_active_key(
+    tx: &mut Tx,
+    retires_at: DateTime<Utc>,
+) -> Result<Option<JwtKey>, DbError> {
+    let key = sqlx::query_as!(
+        JwtKey,
+        r#"
+        UPDATE jwt_keys
+        SET status = 'Verifying', retires_at = $1
+        WHERE status = 'Active'
+        RETURNING
+            kid, private_key, public_key, status as "status: KeyStatus", created_at, retires_at
+        "#,
+        retires_at,
+    )
+    .fetch_optional(tx)
+    .await?;
+    Ok(key)
+}
+
+/// Retire the verifying keys whose overlap ended before `now`, and return how many there were.
+#[tracing::instrument(skip(conn))]
+pub async fn retire_expired_keys(
+    conn: impl PgExecutor<'_>,
+    now: DateTime<Utc>,
+) -> Result<u64, DbError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE jwt_keys
+        SET status = 'Retired'
+        WHERE status = 'Verifying' AND retires_at <= $1
+        "#,
+        now,
+    )
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Fetch all keys, newest first.
+#[tracing::instrument(skip(conn))]
+pub async fn fetch_keys(conn: impl PgExecutor<'_>) -> Result<Vec<JwtKey>, DbError> {
+    let keys = sqlx::query_as!(
+        JwtKey,
+        r#"
+        SELECT
+            kid, private_key, public_key, status as "status: KeyStatus", created_at, retires_at
+        FROM jwt_keys
+        ORDER BY created_at DESC, kid
+        "#,
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(keys)
+}
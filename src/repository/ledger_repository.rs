@@ -0,0 +1,243 @@
+//! Functions for interacting with the ledger.
+
+use crate::{
+    infra::error::DbError,
+    model::{
+        ledger_model::{BalanceMismatch, LedgerEntry, LedgerEntryKind, LedgerEntryQuery},
+        money::CurrencyCode,
+        statement_model::StatementLine,
+        transfer_model::Transfer,
+    },
+    Tx,
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Post a journal of entries. The amounts must sum to zero in each currency,
+/// which is verified by the database when the transaction commits.
+/// An entry without an account is money entering or leaving the system.
+async fn insert_journal(
+    tx: &mut Tx,
+    kind: LedgerEntryKind,
+    transfer_id: Option<i32>,
+    postings: &[(Option<i32>, i64, &CurrencyCode)],
+) -> Result<Uuid, DbError> {
+    let journal_id = Uuid::new_v4();
+    for (account_id, amount, currency) in postings {
+        sqlx::query!(
+            r#"
+            INSERT INTO ledger_entries (journal_id, account_id, amount, currency, kind, transfer_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            journal_id,
+            *account_id,
+            *amount,
+            currency.as_str(),
+            kind as LedgerEntryKind,
+            transfer_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(journal_id)
+}
+
+/// Record money entering an account.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn record_deposit(
+    tx: &mut Tx,
+    account_id: i32,
+    currency: &CurrencyCode,
+    amount: i64,
+) -> Result<Uuid, DbError> {
+    let postings = [
+        (Some(account_id), amount, currency),
+        (None, -amount, currency),
+    ];
+    insert_journal(tx, LedgerEntryKind::Deposit, None, &postings).await
+}
+
+/// Record money leaving an account.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn record_withdrawal(
+    tx: &mut Tx,
+    account_id: i32,
+    currency: &CurrencyCode,
+    amount: i64,
+) -> Result<Uuid, DbError> {
+    let postings = [
+        (Some(account_id), -amount, currency),
+        (None, amount, currency),
+    ];
+    insert_journal(tx, LedgerEntryKind::Withdrawal, None, &postings).await
+}
+
+/// Record interest paid to an account.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn record_interest(
+    tx: &mut Tx,
+    account_id: i32,
+    currency: &CurrencyCode,
+    amount: i64,
+) -> Result<Uuid, DbError> {
+    let postings = [
+        (Some(account_id), amount, currency),
+        (None, -amount, currency),
+    ];
+    insert_journal(tx, LedgerEntryKind::Interest, None, &postings).await
+}
+
+/// Record a fee charged to an account.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn record_fee(
+    tx: &mut Tx,
+    account_id: i32,
+    currency: &CurrencyCode,
+    amount: i64,
+) -> Result<Uuid, DbError> {
+    let postings = [
+        (Some(account_id), -amount, currency),
+        (None, amount, currency),
+    ];
+    insert_journal(tx, LedgerEntryKind::Fee, None, &postings).await
+}
+
+/// Record money moving between two accounts.
+/// Money changing currency passes through external entries for the exchange.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn record_transfer(
+    tx: &mut Tx,
+    transfer: &Transfer,
+    from_currency: &CurrencyCode,
+    to_currency: &CurrencyCode,
+) -> Result<Uuid, DbError> {
+    let mut postings = vec![
+        (Some(transfer.from_account), -transfer.amount, from_currency),
+        (Some(transfer.to_account), transfer.to_amount, to_currency),
+    ];
+    if from_currency != to_currency {
+        postings.push((None, transfer.amount, from_currency));
+        postings.push((None, -transfer.to_amount, to_currency));
+    }
+    insert_journal(tx, LedgerEntryKind::Transfer, Some(transfer.id), &postings).await
+}
+
+/// Fetch the entries of an account, newest first, with the balance after each entry.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_entries(
+    tx: &mut Tx,
+    account_id: i32,
+    query: &LedgerEntryQuery,
+) -> Result<Vec<LedgerEntry>, DbError> {
+    let entries = sqlx::query_as!(
+        LedgerEntry,
+        r#"
+        SELECT
+            id as "id!",
+            journal_id as "journal_id!",
+            account_id as "account_id!",
+            amount as "amount!",
+            currency as "currency!: CurrencyCode",
+            balance as "balance!",
+            kind as "kind!: LedgerEntryKind",
+            transfer_id,
+            created_at as "created_at!"
+        FROM (
+            SELECT e.*, SUM(e.amount) OVER (ORDER BY e.id)::BIGINT AS balance
+            FROM ledger_entries e
+            WHERE e.account_id = $1
+        ) entries
+        WHERE ($2::INT IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#,
+        account_id,
+        query.cursor,
+        i64::from(query.limit),
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(entries)
+}
+
+/// Fetch the entries of an account booked from `start` until `end`, oldest first,
+/// with the balance after each entry and the other account of each transfer.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_statement_lines(
+    tx: &mut Tx,
+    account_id: i32,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<StatementLine>, DbError> {
+    let lines = sqlx::query_as!(
+        StatementLine,
+        r#"
+        SELECT
+            entries.id as "entry_id!",
+            entries.created_at as "booked_at!",
+            entries.kind as "kind!: LedgerEntryKind",
+            entries.amount as "amount!",
+            entries.balance as "balance!",
+            entries.transfer_id,
+            CASE WHEN t.from_account = $1 THEN t.to_account ELSE t.from_account END
+                as counterparty_account
+        FROM (
+            SELECT e.*, SUM(e.amount) OVER (ORDER BY e.id)::BIGINT AS balance
+            FROM ledger_entries e
+            WHERE e.account_id = $1 AND e.created_at < $3
+        ) entries
+        LEFT JOIN transfers t ON t.id = entries.transfer_id
+        WHERE entries.created_at >= $2
+        ORDER BY entries.id
+        "#,
+        account_id,
+        start,
+        end,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(lines)
+}
+
+/// Fetch the balance of an account from the entries booked before `at`.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_balance_before(
+    tx: &mut Tx,
+    account_id: i32,
+    at: DateTime<Utc>,
+) -> Result<i64, DbError> {
+    let balance = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(SUM(amount), 0)::BIGINT as "balance!"
+        FROM ledger_entries
+        WHERE account_id = $1 AND created_at < $2
+        "#,
+        account_id,
+        at,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(balance)
+}
+
+/// Find all accounts whose balance does not match the sum of their ledger entries.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_balance_mismatches(tx: &mut Tx) -> Result<Vec<BalanceMismatch>, DbError> {
+    let mismatches = sqlx::query_as!(
+        BalanceMismatch,
+        r#"
+        SELECT
+            a.id as account_id,
+            a.balance,
+            COALESCE(SUM(e.amount), 0)::BIGINT as "ledger_balance!"
+        FROM accounts a
+        LEFT JOIN ledger_entries e ON e.account_id = a.id
+        GROUP BY a.id
+        HAVING a.balance <> COALESCE(SUM(e.amount), 0)
+        ORDER BY a.id
+        "#,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(mismatches)
+}
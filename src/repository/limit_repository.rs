@@ -0,0 +1,208 @@
+//! Functions for interacting with overdraft and spending limits.
+
+use crate::{
+    infra::error::DbError,
+    model::{
+        account_model::{Account, AccountStatus},
+        limit_model::{AccountLimits, NewUserSpendingLimit, UserSpendingLimit},
+        money::CurrencyCode,
+    },
+    Tx,
+};
+use chrono::{DateTime, Utc};
+
+/// Replace the limits of an account.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = account_id), ret)]
+pub async fn update_account_limits(
+    tx: &mut Tx,
+    account_id: i32,
+    limits: &AccountLimits,
+) -> Result<Account, DbError> {
+    let account = sqlx::query_as!(
+        Account,
+        r#"
+            UPDATE accounts
+            SET overdraft_limit = $1, daily_limit = $2, monthly_limit = $3
+            WHERE id = $4
+            RETURNING id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
+        "#,
+        limits.overdraft_limit,
+        limits.daily_limit,
+        limits.monthly_limit,
+        account_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(account)
+}
+
+/// Fetch the spending limits of a user in all currencies.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_user_limits(
+    tx: &mut Tx,
+    user_id: i32,
+) -> Result<Vec<UserSpendingLimit>, DbError> {
+    let limits = sqlx::query_as!(
+        UserSpendingLimit,
+        r#"
+            SELECT user_id, currency as "currency: CurrencyCode", daily_limit, monthly_limit
+            FROM user_spending_limits
+            WHERE user_id = $1
+            ORDER BY currency
+        "#,
+        user_id,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(limits)
+}
+
+/// Fetch and lock the spending limits of a user in a currency, if there are any.
+/// Locking serializes concurrent spending from different accounts of the user.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn lock_user_limit(
+    tx: &mut Tx,
+    user_id: i32,
+    currency: &CurrencyCode,
+) -> Result<Option<UserSpendingLimit>, DbError> {
+    let limit = sqlx::query_as!(
+        UserSpendingLimit,
+        r#"
+            SELECT user_id, currency as "currency: CurrencyCode", daily_limit, monthly_limit
+            FROM user_spending_limits
+            WHERE user_id = $1 AND currency = $2
+            FOR UPDATE
+        "#,
+        user_id,
+        currency.as_str(),
+    )
+    .fetch_optional(tx)
+    .await?;
+    Ok(limit)
+}
+
+/// Create or replace the spending limits of a user in a currency.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = user_id), ret)]
+pub async fn upsert_user_limit(
+    tx: &mut Tx,
+    user_id: i32,
+    currency: &CurrencyCode,
+    limit: &NewUserSpendingLimit,
+) -> Result<UserSpendingLimit, DbError> {
+    let limit = sqlx::query_as!(
+        UserSpendingLimit,
+        r#"
+            INSERT INTO user_spending_limits (user_id, currency, daily_limit, monthly_limit)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, currency) DO UPDATE
+            SET daily_limit = EXCLUDED.daily_limit,
+                monthly_limit = EXCLUDED.monthly_limit
+            RETURNING user_id, currency as "currency: CurrencyCode", daily_limit, monthly_limit
+        "#,
+        user_id,
+        currency.as_str(),
+        limit.daily_limit,
+        limit.monthly_limit,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(limit)
+}
+
+/// Remove the spending limits of a user in a currency.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = user_id), ret)]
+pub async fn delete_user_limit(
+    tx: &mut Tx,
+    user_id: i32,
+    currency: &CurrencyCode,
+) -> Result<(), DbError> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM user_spending_limits WHERE user_id = $1 AND currency = $2"#,
+        user_id,
+        currency.as_str(),
+    )
+    .execute(tx)
+    .await?
+    .rows_affected();
+    if deleted == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+/// Sum the money withdrawn or transferred out of an account since `since`, and the money
+/// reserved by holds created since then. Reversals are not counted, since they are not
+/// initiated by the owner.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_account_spending(
+    tx: &mut Tx,
+    account_id: i32,
+    since: DateTime<Utc>,
+) -> Result<i64, DbError> {
+    let spent = sqlx::query_scalar!(
+        r#"
+            SELECT COALESCE(SUM(amount), 0)::BIGINT as "spent!"
+            FROM (
+                SELECT -e.amount AS amount
+                FROM ledger_entries e
+                LEFT JOIN transfers t ON t.id = e.transfer_id
+                WHERE e.account_id = $1
+                  AND e.amount < 0
+                  AND e.kind IN ('Withdrawal', 'Transfer')
+                  AND t.reversal_of IS NULL
+                  AND e.created_at >= $2
+                UNION ALL
+                SELECT h.amount
+                FROM holds h
+                WHERE h.account_id = $1 AND h.status = 'Active' AND h.created_at >= $2
+            ) spending
+        "#,
+        account_id,
+        since,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(spent)
+}
+
+/// Sum the money withdrawn or transferred out of all accounts of a user in a currency
+/// since `since`, and the money reserved by holds created since then. Reversals are not counted.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_user_spending(
+    tx: &mut Tx,
+    user_id: i32,
+    currency: &CurrencyCode,
+    since: DateTime<Utc>,
+) -> Result<i64, DbError> {
+    let spent = sqlx::query_scalar!(
+        r#"
+            SELECT COALESCE(SUM(amount), 0)::BIGINT as "spent!"
+            FROM (
+                SELECT -e.amount AS amount
+                FROM ledger_entries e
+                JOIN accounts a ON a.id = e.account_id
+                LEFT JOIN transfers t ON t.id = e.transfer_id
+                WHERE a.owner_id = $1
+                  AND a.currency = $2
+                  AND e.amount < 0
+                  AND e.kind IN ('Withdrawal', 'Transfer')
+                  AND t.reversal_of IS NULL
+                  AND e.created_at >= $3
+                UNION ALL
+                SELECT h.amount
+                FROM holds h
+                JOIN accounts a ON a.id = h.account_id
+                WHERE a.owner_id = $1
+                  AND a.currency = $2
+                  AND h.status = 'Active'
+                  AND h.created_at >= $3
+            ) spending
+        "#,
+        user_id,
+        currency.as_str(),
+        since,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(spent)
+}
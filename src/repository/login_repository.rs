@@ -0,0 +1,121 @@
+//! Functions for counting failed sign-ins and blocking further attempts.
+
+use crate::{
+    infra::error::DbError,
+    model::login_model::{LoginFailures, LoginScope},
+    Tx,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+
+/// Count a sign-in attempt as failed before it is tried, and return the failures so far along
+/// with the time until which sign-ins were already blocked. The row stays locked until `tx`
+/// ends, so that concurrent attempts are counted one after the other. Failures from before
+/// `window_start` are forgotten.
+#[tracing::instrument(skip(tx))]
+pub async fn record_failure(
+    tx: &mut Tx,
+    scope: LoginScope,
+    key: &str,
+    window_start: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<LoginFailures, DbError> {
+    let failures = sqlx::query_as!(
+        LoginFailures,
+        r#"
+        INSERT INTO login_failures (scope, key, failures, last_failure_at, blocked_until)
+        VALUES ($1, $2, 1, $4, $4)
+        ON CONFLICT (scope, key) DO UPDATE
+        SET failures = CASE
+                WHEN login_failures.last_failure_at < $3 THEN 1
+                ELSE login_failures.failures + 1
+            END,
+            last_failure_at = $4
+        RETURNING failures, blocked_until
+        "#,
+        scope as LoginScope,
+        key,
+        window_start,
+        now,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(failures)
+}
+
+/// Take back an attempt counted by [`record_failure`] that turned out to succeed.
+#[tracing::instrument(skip(conn))]
+pub async fn release_failure(
+    conn: impl PgExecutor<'_>,
+    scope: LoginScope,
+    key: &str,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE login_failures SET failures = GREATEST(failures - 1, 0)
+        WHERE scope = $1 AND key = $2
+        "#,
+        scope as LoginScope,
+        key,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Block sign-ins for a username or an address until `until`.
+#[tracing::instrument(skip(tx))]
+pub async fn block(
+    tx: &mut Tx,
+    scope: LoginScope,
+    key: &str,
+    until: DateTime<Utc>,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"UPDATE login_failures SET blocked_until = $3 WHERE scope = $1 AND key = $2"#,
+        scope as LoginScope,
+        key,
+        until,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Lock the account of a user until `until`, after too many failed sign-ins.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = user_id), ret)]
+pub async fn lock_user(
+    tx: &mut Tx,
+    user_id: i32,
+    username: &str,
+    until: DateTime<Utc>,
+) -> Result<(), DbError> {
+    block(tx, LoginScope::Username, username, until).await
+}
+
+/// Unlock the account of a user, forgetting the failed sign-ins for their username.
+#[tracing::instrument(skip(conn), fields(audit, entity_id = user_id), ret)]
+pub async fn unlock_user(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    username: &str,
+) -> Result<(), DbError> {
+    clear_failures(conn, LoginScope::Username, username).await
+}
+
+/// Forget the failed sign-ins for a username or an address.
+#[tracing::instrument(skip(conn))]
+pub async fn clear_failures(
+    conn: impl PgExecutor<'_>,
+    scope: LoginScope,
+    key: &str,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"DELETE FROM login_failures WHERE scope = $1 AND key = $2"#,
+        scope as LoginScope,
+        key,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Functions for storing who has access to an account.
+
+use crate::{
+    infra::error::DbError,
+    model::member_model::{AccountMember, AccountRole},
+    Tx,
+};
+
+/// Give a user access to an account. Fails with a conflict if the user is already a member.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = account_id), ret)]
+pub async fn insert_member(
+    tx: &mut Tx,
+    account_id: i32,
+    user_id: i32,
+    role: AccountRole,
+) -> Result<AccountMember, DbError> {
+    let member = sqlx::query_as!(
+        AccountMember,
+        r#"
+        INSERT INTO account_members (account_id, user_id, role)
+        VALUES ($1, $2, $3)
+        RETURNING account_id, user_id, role as "role: AccountRole", created_at
+        "#,
+        account_id,
+        user_id,
+        role as AccountRole,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(member)
+}
+
+/// Fetch the role of a user on an account, or [`DbError::NotFound`] if the user has no access.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_role(
+    tx: &mut Tx,
+    account_id: i32,
+    user_id: i32,
+) -> Result<AccountRole, DbError> {
+    let role = sqlx::query_scalar!(
+        r#"
+        SELECT role as "role: AccountRole"
+        FROM account_members
+        WHERE account_id = $1 AND user_id = $2
+        "#,
+        account_id,
+        user_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(role)
+}
+
+/// Fetch the members of an account, owner first.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_members(tx: &mut Tx, account_id: i32) -> Result<Vec<AccountMember>, DbError> {
+    let members = sqlx::query_as!(
+        AccountMember,
+        r#"
+        SELECT account_id, user_id, role as "role: AccountRole", created_at
+        FROM account_members
+        WHERE account_id = $1
+        ORDER BY role, user_id
+        "#,
+        account_id,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(members)
+}
+
+/// Take away a user's access to an account. The owner cannot be removed.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = account_id), ret)]
+pub async fn delete_member(
+    tx: &mut Tx,
+    account_id: i32,
+    user_id: i32,
+) -> Result<AccountMember, DbError> {
+    let member = sqlx::query_as!(
+        AccountMember,
+        r#"
+        DELETE FROM account_members
+        WHERE account_id = $1 AND user_id = $2 AND role <> 'Owner'
+        RETURNING account_id, user_id, role as "role: AccountRole", created_at
+        "#,
+        account_id,
+        user_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(member)
+}
@@ -0,0 +1,217 @@
+//! Functions for storing second factors and the sign-ins waiting for them.
+
+use crate::{
+    infra::error::DbError,
+    model::mfa_model::{StoredMfaChallenge, TotpSecret},
+    Tx,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+/// Store a new TOTP secret for a user, replacing one that was never confirmed. Fails with a
+/// conflict if the user already has a confirmed secret.
+#[tracing::instrument(skip(tx, secret), fields(audit, entity_id = user_id))]
+pub async fn upsert_totp_secret(tx: &mut Tx, user_id: i32, secret: &str) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO totp_secrets (user_id, secret)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE
+        SET secret = EXCLUDED.secret, created_at = CURRENT_TIMESTAMP
+        WHERE totp_secrets.confirmed_at IS NULL
+        "#,
+        user_id,
+        secret,
+    )
+    .execute(tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::Conflict);
+    }
+    Ok(())
+}
+
+/// Fetch and lock the TOTP secret of a user.
+#[tracing::instrument(skip(tx))]
+pub async fn lock_totp_secret(tx: &mut Tx, user_id: i32) -> Result<TotpSecret, DbError> {
+    let secret = sqlx::query_as!(
+        TotpSecret,
+        r#"
+        SELECT user_id, secret, confirmed_at, last_used_step
+        FROM totp_secrets
+        WHERE user_id = $1
+        FOR UPDATE
+        "#,
+        user_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(secret)
+}
+
+/// Check whether a user has confirmed a TOTP secret, and so must give a second factor.
+#[tracing::instrument(skip(conn))]
+pub async fn is_enrolled(conn: impl PgExecutor<'_>, user_id: i32) -> Result<bool, DbError> {
+    let enrolled = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM totp_secrets WHERE user_id = $1 AND confirmed_at IS NOT NULL
+        ) as "enrolled!"
+        "#,
+        user_id,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(enrolled)
+}
+
+/// Confirm the TOTP secret of a user with a code for `step`.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = user_id))]
+pub async fn confirm_totp_secret(tx: &mut Tx, user_id: i32, step: i64) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE totp_secrets
+        SET confirmed_at = CURRENT_TIMESTAMP, last_used_step = $2
+        WHERE user_id = $1
+        "#,
+        user_id,
+        step,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Remember that a code for `step` was used.
+#[tracing::instrument(skip(tx))]
+pub async fn use_totp_step(tx: &mut Tx, user_id: i32, step: i64) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"UPDATE totp_secrets SET last_used_step = $2 WHERE user_id = $1"#,
+        user_id,
+        step,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Remove the TOTP secret and the recovery codes of a user.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = user_id))]
+pub async fn delete_mfa(tx: &mut Tx, user_id: i32) -> Result<(), DbError> {
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut *tx)
+        .await?;
+    let result = sqlx::query!(r#"DELETE FROM totp_secrets WHERE user_id = $1"#, user_id)
+        .execute(tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+/// Replace the recovery codes of a user with new ones, given as hashes.
+#[tracing::instrument(skip(tx, code_hashes), fields(audit, entity_id = user_id))]
+pub async fn replace_recovery_codes(
+    tx: &mut Tx,
+    user_id: i32,
+    code_hashes: &[String],
+) -> Result<(), DbError> {
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO recovery_codes (code_hash, user_id)
+        SELECT code_hash, $1 FROM UNNEST($2::TEXT[]) as code_hash
+        "#,
+        user_id,
+        code_hashes,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Use up a recovery code of a user, and return whether it was valid.
+#[tracing::instrument(skip(tx, code_hash))]
+pub async fn use_recovery_code(
+    tx: &mut Tx,
+    user_id: i32,
+    code_hash: &str,
+) -> Result<bool, DbError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE recovery_codes
+        SET used_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        code_hash,
+    )
+    .execute(tx)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Start a sign-in that waits for the second factor.
+#[tracing::instrument(skip(tx))]
+pub async fn insert_challenge(
+    tx: &mut Tx,
+    user_id: i32,
+    expires_at: DateTime<Utc>,
+) -> Result<Uuid, DbError> {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO mfa_challenges (id, user_id, expires_at) VALUES ($1, $2, $3)"#,
+        id,
+        user_id,
+        expires_at,
+    )
+    .execute(tx)
+    .await?;
+    Ok(id)
+}
+
+/// Fetch and lock a sign-in waiting for the second factor.
+#[tracing::instrument(skip(tx))]
+pub async fn lock_challenge(tx: &mut Tx, id: Uuid) -> Result<StoredMfaChallenge, DbError> {
+    let challenge = sqlx::query_as!(
+        StoredMfaChallenge,
+        r#"
+        SELECT id, user_id, expires_at, attempts, completed_at
+        FROM mfa_challenges
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(challenge)
+}
+
+/// Count an attempt at giving the second factor.
+#[tracing::instrument(skip(tx))]
+pub async fn count_attempt(tx: &mut Tx, id: Uuid) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"UPDATE mfa_challenges SET attempts = attempts + 1 WHERE id = $1"#,
+        id
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Mark a sign-in as completed, so that the challenge cannot be used again.
+#[tracing::instrument(skip(tx))]
+pub async fn complete_challenge(tx: &mut Tx, id: Uuid) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"UPDATE mfa_challenges SET completed_at = CURRENT_TIMESTAMP WHERE id = $1"#,
+        id
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
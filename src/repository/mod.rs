@@ -1,7 +1,24 @@
 //! Database interaction.
 
 pub mod account_repository;
+pub mod api_key_repository;
 pub mod audit_log_repository;
+pub mod currency_repository;
+pub mod hold_repository;
+pub mod idempotency_repository;
+pub mod jwt_key_repository;
+pub mod ledger_repository;
+pub mod limit_repository;
+pub mod login_repository;
+pub mod member_repository;
+pub mod mfa_repository;
+pub mod oauth_repository;
+pub mod payment_import_repository;
+pub mod product_repository;
 pub mod request_repository;
+pub mod role_repository;
+pub mod scheduled_transfer_repository;
+pub mod session_repository;
+pub mod transfer_batch_repository;
 pub mod transfer_repository;
 pub mod user_repository;
@@ -0,0 +1,220 @@
+//! Functions for storing OAuth clients and the authorization codes and access tokens issued to
+//! them.
+
+use crate::{
+    infra::{error::DbError, security::revocation::RevokedToken},
+    model::oauth_model::{AuthorizationCode, NewOAuthClient, OAuthClient},
+    Tx,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+/// Register a new client, given its `client_id` and the hash of its secret if it has one.
+#[tracing::instrument(skip(conn, secret_hash), fields(audit), ret)]
+pub async fn insert_client(
+    conn: impl PgExecutor<'_>,
+    new_client: &NewOAuthClient,
+    client_id: &str,
+    secret_hash: Option<&str>,
+) -> Result<OAuthClient, DbError> {
+    let client = sqlx::query_as!(
+        OAuthClient,
+        r#"
+        INSERT INTO oauth_clients (client_id, name, secret_hash, redirect_uris, scopes, user_id)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, client_id, name, secret_hash IS NOT NULL AS "confidential!",
+            redirect_uris, scopes, user_id, revoked_at, created_at
+        "#,
+        client_id,
+        new_client.name,
+        secret_hash,
+        &new_client.redirect_uris,
+        &new_client.scopes,
+        new_client.user_id,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(client)
+}
+
+/// Fetch all clients, including revoked ones.
+#[tracing::instrument(skip(conn))]
+pub async fn fetch_clients(conn: impl PgExecutor<'_>) -> Result<Vec<OAuthClient>, DbError> {
+    let clients = sqlx::query_as!(
+        OAuthClient,
+        r#"
+        SELECT id, client_id, name, secret_hash IS NOT NULL AS "confidential!",
+            redirect_uris, scopes, user_id, revoked_at, created_at
+        FROM oauth_clients
+        ORDER BY id
+        "#,
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(clients)
+}
+
+/// Fetch a client that is not revoked by its `client_id`, with the hash of its secret.
+#[tracing::instrument(skip(conn))]
+pub async fn fetch_client(
+    conn: impl PgExecutor<'_>,
+    client_id: &str,
+) -> Result<(OAuthClient, Option<String>), DbError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT id, client_id, name, secret_hash, redirect_uris, scopes, user_id, revoked_at,
+            created_at
+        FROM oauth_clients
+        WHERE client_id = $1 AND revoked_at IS NULL
+        "#,
+        client_id,
+    )
+    .fetch_one(conn)
+    .await?;
+    let client = OAuthClient {
+        id: row.id,
+        client_id: row.client_id,
+        name: row.name,
+        confidential: row.secret_hash.is_some(),
+        redirect_uris: row.redirect_uris,
+        scopes: row.scopes,
+        user_id: row.user_id,
+        revoked_at: row.revoked_at,
+        created_at: row.created_at,
+    };
+    Ok((client, row.secret_hash))
+}
+
+/// Revoke a client, so that it can no longer get tokens.
+#[tracing::instrument(skip(conn), fields(audit, entity_id = id), ret)]
+pub async fn revoke_client(conn: impl PgExecutor<'_>, id: i32) -> Result<OAuthClient, DbError> {
+    let client = sqlx::query_as!(
+        OAuthClient,
+        r#"
+        UPDATE oauth_clients
+        SET revoked_at = COALESCE(revoked_at, CURRENT_TIMESTAMP)
+        WHERE id = $1
+        RETURNING id, client_id, name, secret_hash IS NOT NULL AS "confidential!",
+            redirect_uris, scopes, user_id, revoked_at, created_at
+        "#,
+        id,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(client)
+}
+
+/// Store an authorization code, given its hash.
+#[tracing::instrument(skip(conn, code_hash, code))]
+pub async fn insert_authorization_code(
+    conn: impl PgExecutor<'_>,
+    code_hash: &str,
+    code: &AuthorizationCode,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO oauth_authorization_codes
+            (code_hash, client_id, user_id, redirect_uri, redirect_uri_given, scopes,
+            code_challenge, amr, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+        code_hash,
+        code.client_id,
+        code.user_id,
+        code.redirect_uri,
+        code.redirect_uri_given,
+        &code.scopes,
+        code.code_challenge,
+        &code.amr,
+        code.expires_at,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Fetch and lock an authorization code by its hash.
+#[tracing::instrument(skip_all)]
+pub async fn lock_authorization_code(
+    tx: &mut Tx,
+    code_hash: &str,
+) -> Result<AuthorizationCode, DbError> {
+    let code = sqlx::query_as!(
+        AuthorizationCode,
+        r#"
+        SELECT client_id, user_id, redirect_uri, redirect_uri_given, scopes, code_challenge,
+            amr, expires_at, used_at
+        FROM oauth_authorization_codes
+        WHERE code_hash = $1
+        FOR UPDATE
+        "#,
+        code_hash,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(code)
+}
+
+/// Mark an authorization code as exchanged, so that it cannot be used again.
+#[tracing::instrument(skip_all)]
+pub async fn use_authorization_code(tx: &mut Tx, code_hash: &str) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE oauth_authorization_codes SET used_at = CURRENT_TIMESTAMP
+        WHERE code_hash = $1
+        "#,
+        code_hash,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Remember an access token issued to a client, so that it can be revoked. Tokens exchanged for
+/// an authorization code are linked to it by `code_hash`.
+#[tracing::instrument(skip(tx, code_hash))]
+pub async fn insert_access_token(
+    tx: &mut Tx,
+    jti: Uuid,
+    user_id: i32,
+    client_id: i32,
+    code_hash: Option<&str>,
+    expires_at: DateTime<Utc>,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO access_tokens (jti, user_id, client_id, code_hash, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        jti,
+        user_id,
+        client_id,
+        code_hash,
+        expires_at,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Revoke the access tokens exchanged for an authorization code that have not expired yet.
+#[tracing::instrument(skip_all)]
+pub async fn revoke_code_tokens(
+    tx: &mut Tx,
+    code_hash: &str,
+) -> Result<Vec<RevokedToken>, DbError> {
+    let tokens = sqlx::query_as!(
+        RevokedToken,
+        r#"
+        UPDATE access_tokens
+        SET revoked_at = CURRENT_TIMESTAMP
+        WHERE code_hash = $1 AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        RETURNING jti, expires_at
+        "#,
+        code_hash,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(tokens)
+}
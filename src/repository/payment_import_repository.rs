@@ -0,0 +1,206 @@
+//! Functions for storing imported payment files and the outcome of their payments.
+
+use crate::{
+    infra::error::DbError,
+    model::payment_import_model::{
+        ImportedPayment, ImportedPaymentStatus, PaymentImport, PaymentImportStatus,
+    },
+    Tx,
+};
+
+/// Store an uploaded payment file and its payments.
+#[tracing::instrument(skip(tx, payments), fields(audit), ret)]
+pub async fn insert_import(
+    tx: &mut Tx,
+    owner_id: i32,
+    message_id: &str,
+    payments: &[ImportedPayment],
+) -> Result<PaymentImport, DbError> {
+    let import = sqlx::query_as!(
+        PaymentImport,
+        r#"
+        INSERT INTO payment_imports (owner_id, message_id)
+        VALUES ($1, $2)
+        RETURNING
+            id, owner_id, message_id,
+            status as "status: PaymentImportStatus",
+            created_at, processed_at
+        "#,
+        owner_id,
+        message_id,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    for payment in payments {
+        sqlx::query!(
+            r#"
+            INSERT INTO imported_payments (
+                import_id, position, payment_info_id, end_to_end_id,
+                debtor_account, creditor_account, instructed_amount, currency,
+                from_account, to_account, amount, status, reason_code, reason
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            "#,
+            import.id,
+            payment.position,
+            payment.payment_info_id,
+            payment.end_to_end_id,
+            payment.debtor_account,
+            payment.creditor_account,
+            payment.instructed_amount,
+            payment.currency,
+            payment.from_account,
+            payment.to_account,
+            payment.amount,
+            payment.status as ImportedPaymentStatus,
+            payment.reason_code,
+            payment.reason,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(import)
+}
+
+/// Check if a user has already uploaded a file with the given message id.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn import_exists(tx: &mut Tx, owner_id: i32, message_id: &str) -> Result<bool, DbError> {
+    let exists = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM payment_imports WHERE owner_id = $1 AND message_id = $2
+        ) as "exists!"
+        "#,
+        owner_id,
+        message_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(exists)
+}
+
+/// Fetch a payment file uploaded by a user.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_import(
+    tx: &mut Tx,
+    owner_id: i32,
+    import_id: i32,
+) -> Result<PaymentImport, DbError> {
+    let import = sqlx::query_as!(
+        PaymentImport,
+        r#"
+        SELECT
+            id, owner_id, message_id,
+            status as "status: PaymentImportStatus",
+            created_at, processed_at
+        FROM payment_imports
+        WHERE owner_id = $1 AND id = $2
+        "#,
+        owner_id,
+        import_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(import)
+}
+
+/// Fetch the payments of a file, in order.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_payments(tx: &mut Tx, import_id: i32) -> Result<Vec<ImportedPayment>, DbError> {
+    let payments = sqlx::query_as!(
+        ImportedPayment,
+        r#"
+        SELECT
+            position, payment_info_id, end_to_end_id,
+            debtor_account, creditor_account, instructed_amount, currency,
+            from_account, to_account, amount,
+            status as "status: ImportedPaymentStatus",
+            reason_code, reason, transfer_id
+        FROM imported_payments
+        WHERE import_id = $1
+        ORDER BY position
+        "#,
+        import_id,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(payments)
+}
+
+/// Claim the oldest file whose payments have not been executed, if any.
+/// Files claimed by other transactions are skipped.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn lock_next_received(tx: &mut Tx) -> Result<Option<PaymentImport>, DbError> {
+    let import = sqlx::query_as!(
+        PaymentImport,
+        r#"
+        SELECT
+            id, owner_id, message_id,
+            status as "status: PaymentImportStatus",
+            created_at, processed_at
+        FROM payment_imports
+        WHERE status = 'Received'
+        ORDER BY id
+        LIMIT 1
+        FOR UPDATE SKIP LOCKED
+        "#,
+    )
+    .fetch_optional(tx)
+    .await?;
+    Ok(import)
+}
+
+/// Record the outcome of executing a payment.
+#[tracing::instrument(skip(tx))]
+pub async fn update_payment(
+    tx: &mut Tx,
+    import_id: i32,
+    position: i32,
+    status: ImportedPaymentStatus,
+    transfer_id: Option<i32>,
+    reason_code: Option<&str>,
+    reason: Option<&str>,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE imported_payments
+        SET status = $3, transfer_id = $4, reason_code = $5, reason = $6
+        WHERE import_id = $1 AND position = $2
+        "#,
+        import_id,
+        position,
+        status as ImportedPaymentStatus,
+        transfer_id,
+        reason_code,
+        reason,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Mark a file as processed.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = import_id), ret)]
+pub async fn complete_import(
+    tx: &mut Tx,
+    import_id: i32,
+    status: PaymentImportStatus,
+) -> Result<PaymentImport, DbError> {
+    let import = sqlx::query_as!(
+        PaymentImport,
+        r#"
+        UPDATE payment_imports
+        SET status = $2, processed_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        RETURNING
+            id, owner_id, message_id,
+            status as "status: PaymentImportStatus",
+            created_at, processed_at
+        "#,
+        import_id,
+        status as PaymentImportStatus,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(import)
+}
@@ -0,0 +1,315 @@
+//! Functions for account products, interest accruals and monthly postings.
+
+use crate::{
+    infra::error::DbError,
+    model::{
+        account_model::{Account, AccountStatus},
+        money::CurrencyCode,
+        product_model::{
+            AccountProduct, AccrualBasis, AccrualQuery, InterestAccrual, MonthlyPosting,
+            NewAccountProduct, PostingBasis,
+        },
+    },
+    Tx,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Store a new account product.
+#[tracing::instrument(skip(tx), fields(audit), ret)]
+pub async fn insert_product(
+    tx: &mut Tx,
+    product: &NewAccountProduct,
+) -> Result<AccountProduct, DbError> {
+    let product = sqlx::query_as!(
+        AccountProduct,
+        r#"
+        INSERT INTO account_products (name, interest_rate, monthly_fee)
+        VALUES ($1, $2, $3)
+        RETURNING id, name, interest_rate, monthly_fee, created_at
+        "#,
+        product.name,
+        product.interest_rate,
+        product.monthly_fee,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(product)
+}
+
+/// Fetch all account products.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_products(tx: &mut Tx) -> Result<Vec<AccountProduct>, DbError> {
+    let products = sqlx::query_as!(
+        AccountProduct,
+        r#"
+        SELECT id, name, interest_rate, monthly_fee, created_at
+        FROM account_products
+        ORDER BY id
+        "#,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(products)
+}
+
+/// Fetch an account product.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_product(tx: &mut Tx, product_id: i32) -> Result<AccountProduct, DbError> {
+    let product = sqlx::query_as!(
+        AccountProduct,
+        r#"
+        SELECT id, name, interest_rate, monthly_fee, created_at
+        FROM account_products
+        WHERE id = $1
+        "#,
+        product_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(product)
+}
+
+/// Change the name, interest rate or fee of a product.
+/// New rates apply from the next accrual, and new fees from the next posting.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = product_id), ret)]
+pub async fn update_product(
+    tx: &mut Tx,
+    product_id: i32,
+    product: &NewAccountProduct,
+) -> Result<AccountProduct, DbError> {
+    let product = sqlx::query_as!(
+        AccountProduct,
+        r#"
+        UPDATE account_products
+        SET name = $2, interest_rate = $3, monthly_fee = $4
+        WHERE id = $1
+        RETURNING id, name, interest_rate, monthly_fee, created_at
+        "#,
+        product_id,
+        product.name,
+        product.interest_rate,
+        product.monthly_fee,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(product)
+}
+
+/// Change the product of an account.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = account_id), ret)]
+pub async fn assign_product(
+    tx: &mut Tx,
+    account_id: i32,
+    product_id: Option<i32>,
+) -> Result<Account, DbError> {
+    let account = sqlx::query_as!(
+        Account,
+        r#"
+            UPDATE accounts
+            SET product_id = $2
+            WHERE id = $1
+            RETURNING id, name, balance, currency as "currency: CurrencyCode", owner_id, status as "status: AccountStatus", overdraft_limit, daily_limit, monthly_limit, product_id, held
+        "#,
+        account_id,
+        product_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(account)
+}
+
+/// Claim a day for accruing interest. Returns false if the day has already been accrued.
+/// Concurrent callers wait for each other, so a day is only accrued once.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn insert_accrual_run(
+    tx: &mut Tx,
+    accrual_date: NaiveDate,
+    accounts: i32,
+) -> Result<bool, DbError> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO interest_accrual_runs (accrual_date, accounts)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        accrual_date,
+        accounts,
+    )
+    .execute(tx)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Fetch the last day interest was accrued for, if any.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_last_accrual_run(tx: &mut Tx) -> Result<Option<NaiveDate>, DbError> {
+    let date = sqlx::query_scalar!(r#"SELECT MAX(accrual_date) FROM interest_accrual_runs"#)
+        .fetch_one(tx)
+        .await?;
+    Ok(date)
+}
+
+/// Fetch what open accounts with a product should accrue interest from,
+/// using the balance from the ledger entries booked before `end`.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_accrual_bases(
+    tx: &mut Tx,
+    end: DateTime<Utc>,
+) -> Result<Vec<AccrualBasis>, DbError> {
+    let bases = sqlx::query_as!(
+        AccrualBasis,
+        r#"
+        SELECT
+            a.id as account_id,
+            p.id as product_id,
+            COALESCE((
+                SELECT SUM(e.amount) FROM ledger_entries e
+                WHERE e.account_id = a.id AND e.created_at < $1
+            ), 0)::BIGINT as "balance!",
+            p.interest_rate
+        FROM accounts a
+        JOIN account_products p ON p.id = a.product_id
+        WHERE a.status = 'Open'
+        ORDER BY a.id
+        "#,
+        end,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(bases)
+}
+
+/// Store the interest accrued by an account on a day.
+#[tracing::instrument(skip(tx))]
+pub async fn insert_accrual(tx: &mut Tx, accrual: &InterestAccrual) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO interest_accruals (
+            account_id, accrual_date, product_id, balance, interest_rate, amount
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        accrual.account_id,
+        accrual.accrual_date,
+        accrual.product_id,
+        accrual.balance,
+        accrual.interest_rate,
+        accrual.amount,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Fetch the accruals of every account and month before `before` that has not been posted.
+/// The fee is taken from the product the account had on the last accrued day of the month.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_unposted_months(
+    tx: &mut Tx,
+    before: NaiveDate,
+) -> Result<Vec<PostingBasis>, DbError> {
+    let bases = sqlx::query_as!(
+        PostingBasis,
+        r#"
+        SELECT
+            i.account_id,
+            date_trunc('month', i.accrual_date)::DATE as "month!",
+            SUM(i.amount) as "accrued!",
+            (ARRAY_AGG(p.monthly_fee ORDER BY i.accrual_date DESC))[1] as "monthly_fee!"
+        FROM interest_accruals i
+        JOIN account_products p ON p.id = i.product_id
+        WHERE i.accrual_date < $1
+            AND NOT EXISTS (
+                SELECT 1 FROM monthly_postings m
+                WHERE m.account_id = i.account_id
+                    AND m.month = date_trunc('month', i.accrual_date)::DATE
+            )
+        GROUP BY i.account_id, date_trunc('month', i.accrual_date)
+        ORDER BY 2, 1
+        "#,
+        before,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(bases)
+}
+
+/// Record the interest and fee posted for an account and month.
+/// Returns `None` if the month has already been posted.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = account_id), ret)]
+pub async fn insert_posting(
+    tx: &mut Tx,
+    account_id: i32,
+    month: NaiveDate,
+    interest: i64,
+    fee: i64,
+) -> Result<Option<MonthlyPosting>, DbError> {
+    let posting = sqlx::query_as!(
+        MonthlyPosting,
+        r#"
+        INSERT INTO monthly_postings (account_id, month, interest, fee)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        RETURNING account_id, month, interest, fee, created_at
+        "#,
+        account_id,
+        month,
+        interest,
+        fee,
+    )
+    .fetch_optional(tx)
+    .await?;
+    Ok(posting)
+}
+
+/// Fetch the accruals of an account, oldest first.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_accruals(
+    tx: &mut Tx,
+    account_id: i32,
+    query: &AccrualQuery,
+) -> Result<Vec<InterestAccrual>, DbError> {
+    let accruals = sqlx::query_as!(
+        InterestAccrual,
+        r#"
+        SELECT account_id, accrual_date, product_id, balance, interest_rate, amount
+        FROM interest_accruals
+        WHERE account_id = $1
+            AND ($2::DATE IS NULL OR accrual_date >= $2)
+            AND ($3::DATE IS NULL OR accrual_date <= $3)
+        ORDER BY accrual_date
+        "#,
+        account_id,
+        query.from,
+        query.to,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(accruals)
+}
+
+/// Fetch the monthly postings of an account, oldest first.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_postings(
+    tx: &mut Tx,
+    account_id: i32,
+    query: &AccrualQuery,
+) -> Result<Vec<MonthlyPosting>, DbError> {
+    let postings = sqlx::query_as!(
+        MonthlyPosting,
+        r#"
+        SELECT account_id, month, interest, fee, created_at
+        FROM monthly_postings
+        WHERE account_id = $1
+            AND ($2::DATE IS NULL OR month >= date_trunc('month', $2::DATE))
+            AND ($3::DATE IS NULL OR month <= $3)
+        ORDER BY month
+        "#,
+        account_id,
+        query.from,
+        query.to,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(postings)
+}
@@ -0,0 +1,136 @@
+//! Functions for storing roles, their hierarchy and their permissions.
+
+use crate::{
+    infra::{error::DbError, security::jwt::Role},
+    model::permission_model::{NewPermission, Permission, RoleDetails},
+    Tx,
+};
+
+/// Fetch all roles, with the roles they imply and the permissions granted to them.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_roles(tx: &mut Tx) -> Result<Vec<RoleDetails>, DbError> {
+    let roles = sqlx::query_as!(
+        RoleDetails,
+        r#"
+        SELECT
+            r.name as "name: Role",
+            ARRAY(
+                WITH RECURSIVE implied(role_id) AS (
+                    SELECT implied_role_id FROM role_hierarchy WHERE role_id = r.id
+                    UNION
+                    SELECT h.implied_role_id FROM role_hierarchy h JOIN implied i ON i.role_id = h.role_id
+                )
+                SELECT i.name FROM role i WHERE i.id IN (SELECT role_id FROM implied) ORDER BY i.id
+            ) as "implies!: Vec<Role>",
+            ARRAY(
+                SELECT p.name
+                FROM permissions p
+                JOIN role_permissions rp ON rp.permission_id = p.id
+                WHERE rp.role_id = r.id
+                ORDER BY p.name
+            ) as "permissions!"
+        FROM role r
+        ORDER BY r.id
+        "#,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(roles)
+}
+
+/// Define a new permission. Fails with a conflict if the name is taken.
+#[tracing::instrument(skip(tx), fields(audit), ret)]
+pub async fn insert_permission(
+    tx: &mut Tx,
+    new_permission: &NewPermission,
+) -> Result<Permission, DbError> {
+    let permission = sqlx::query_as!(
+        Permission,
+        r#"
+        INSERT INTO permissions (name, description)
+        VALUES ($1, $2)
+        RETURNING id, name, description, created_at
+        "#,
+        new_permission.name,
+        new_permission.description,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(permission)
+}
+
+/// Fetch all permissions by name.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_permissions(tx: &mut Tx) -> Result<Vec<Permission>, DbError> {
+    let permissions = sqlx::query_as!(
+        Permission,
+        r#"
+        SELECT id, name, description, created_at
+        FROM permissions
+        ORDER BY name
+        "#,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(permissions)
+}
+
+/// Remove a permission from all roles and delete it.
+#[tracing::instrument(skip(tx), fields(audit), ret)]
+pub async fn delete_permission(tx: &mut Tx, name: &str) -> Result<Permission, DbError> {
+    let permission = sqlx::query_as!(
+        Permission,
+        r#"
+        DELETE FROM permissions
+        WHERE name = $1
+        RETURNING id, name, description, created_at
+        "#,
+        name,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(permission)
+}
+
+/// Grant a permission to a role. Granting it again has no effect.
+#[tracing::instrument(skip(tx), fields(audit))]
+pub async fn grant_permission(tx: &mut Tx, role: Role, permission: &str) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO role_permissions (role_id, permission_id)
+        SELECT r.id, p.id FROM role r, permissions p WHERE r.name = $1 AND p.name = $2
+        ON CONFLICT DO NOTHING
+        "#,
+        role as Role,
+        permission,
+    )
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        // Either it was already granted, or the permission does not exist
+        sqlx::query!(r#"SELECT id FROM permissions WHERE name = $1"#, permission)
+            .fetch_one(&mut *tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Take a permission away from a role.
+#[tracing::instrument(skip(tx), fields(audit))]
+pub async fn revoke_permission(tx: &mut Tx, role: Role, permission: &str) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM role_permissions rp
+        USING role r, permissions p
+        WHERE rp.role_id = r.id AND rp.permission_id = p.id AND r.name = $1 AND p.name = $2
+        "#,
+        role as Role,
+        permission,
+    )
+    .execute(tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
@@ -0,0 +1,270 @@
+//! Functions for interacting with scheduled transfers and their runs.
+
+use crate::{
+    infra::error::DbError,
+    model::scheduled_transfer_model::{
+        NewScheduledTransfer, ScheduledTransfer, ScheduledTransferQuery, ScheduledTransferRun,
+        ScheduledTransferRunStatus, ScheduledTransferStatus, ScheduledTransferUpdate,
+        TransferRecurrence,
+    },
+    Tx,
+};
+use chrono::{DateTime, Utc};
+
+/// Store a new scheduled transfer.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn insert_scheduled_transfer(
+    tx: &mut Tx,
+    owner_id: i32,
+    new_transfer: &NewScheduledTransfer,
+) -> Result<ScheduledTransfer, DbError> {
+    let scheduled_transfer = sqlx::query_as!(
+        ScheduledTransfer,
+        r#"
+        INSERT INTO scheduled_transfers
+            (owner_id, from_account, to_account, amount, recurrence, start_at, end_at, next_run_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $6)
+        RETURNING
+            id, owner_id, from_account, to_account, amount,
+            recurrence as "recurrence: TransferRecurrence",
+            start_at, end_at, next_run_at, occurrences,
+            status as "status: ScheduledTransferStatus",
+            created_at
+        "#,
+        owner_id,
+        new_transfer.from_account,
+        new_transfer.to_account,
+        new_transfer.amount.minor_units(),
+        new_transfer.recurrence as Option<TransferRecurrence>,
+        new_transfer.start_at,
+        new_transfer.end_at,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(scheduled_transfer)
+}
+
+/// Fetch a scheduled transfer owned by a user.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_scheduled_transfer(
+    tx: &mut Tx,
+    owner_id: i32,
+    id: i32,
+) -> Result<ScheduledTransfer, DbError> {
+    let scheduled_transfer = sqlx::query_as!(
+        ScheduledTransfer,
+        r#"
+        SELECT
+            id, owner_id, from_account, to_account, amount,
+            recurrence as "recurrence: TransferRecurrence",
+            start_at, end_at, next_run_at, occurrences,
+            status as "status: ScheduledTransferStatus",
+            created_at
+        FROM scheduled_transfers
+        WHERE owner_id = $1 AND id = $2
+        "#,
+        owner_id,
+        id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(scheduled_transfer)
+}
+
+/// Fetch the scheduled transfers owned by a user, newest first.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_scheduled_transfers(
+    tx: &mut Tx,
+    owner_id: i32,
+    query: &ScheduledTransferQuery,
+) -> Result<Vec<ScheduledTransfer>, DbError> {
+    let scheduled_transfers = sqlx::query_as!(
+        ScheduledTransfer,
+        r#"
+        SELECT
+            id, owner_id, from_account, to_account, amount,
+            recurrence as "recurrence: TransferRecurrence",
+            start_at, end_at, next_run_at, occurrences,
+            status as "status: ScheduledTransferStatus",
+            created_at
+        FROM scheduled_transfers
+        WHERE owner_id = $1 AND ($2::INT IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#,
+        owner_id,
+        query.cursor,
+        i64::from(query.limit),
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(scheduled_transfers)
+}
+
+/// Change the amount or end time of a scheduled transfer.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = id), ret)]
+pub async fn update_scheduled_transfer(
+    tx: &mut Tx,
+    id: i32,
+    update: &ScheduledTransferUpdate,
+) -> Result<ScheduledTransfer, DbError> {
+    let scheduled_transfer = sqlx::query_as!(
+        ScheduledTransfer,
+        r#"
+        UPDATE scheduled_transfers
+        SET amount = COALESCE($2, amount), end_at = COALESCE($3, end_at)
+        WHERE id = $1
+        RETURNING
+            id, owner_id, from_account, to_account, amount,
+            recurrence as "recurrence: TransferRecurrence",
+            start_at, end_at, next_run_at, occurrences,
+            status as "status: ScheduledTransferStatus",
+            created_at
+        "#,
+        id,
+        update.amount.map(|a| a.minor_units()),
+        update.end_at,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(scheduled_transfer)
+}
+
+/// Stop a scheduled transfer from running again.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = id), ret)]
+pub async fn cancel_scheduled_transfer(tx: &mut Tx, id: i32) -> Result<ScheduledTransfer, DbError> {
+    let scheduled_transfer = sqlx::query_as!(
+        ScheduledTransfer,
+        r#"
+        UPDATE scheduled_transfers
+        SET status = 'Cancelled'
+        WHERE id = $1
+        RETURNING
+            id, owner_id, from_account, to_account, amount,
+            recurrence as "recurrence: TransferRecurrence",
+            start_at, end_at, next_run_at, occurrences,
+            status as "status: ScheduledTransferStatus",
+            created_at
+        "#,
+        id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(scheduled_transfer)
+}
+
+/// Lock the active scheduled transfer that has been due the longest.
+/// Transfers locked by other workers are skipped, so several workers can run at once.
+#[tracing::instrument(skip(tx))]
+pub async fn lock_next_due(
+    tx: &mut Tx,
+    now: DateTime<Utc>,
+) -> Result<Option<ScheduledTransfer>, DbError> {
+    let scheduled_transfer = sqlx::query_as!(
+        ScheduledTransfer,
+        r#"
+        SELECT
+            id, owner_id, from_account, to_account, amount,
+            recurrence as "recurrence: TransferRecurrence",
+            start_at, end_at, next_run_at, occurrences,
+            status as "status: ScheduledTransferStatus",
+            created_at
+        FROM scheduled_transfers
+        WHERE status = 'Active' AND next_run_at <= $1
+        ORDER BY next_run_at
+        LIMIT 1
+        FOR UPDATE SKIP LOCKED
+        "#,
+        now,
+    )
+    .fetch_optional(tx)
+    .await?;
+    Ok(scheduled_transfer)
+}
+
+/// Move a scheduled transfer on to its next occurrence, or finish it.
+#[tracing::instrument(skip(tx))]
+pub async fn advance_scheduled_transfer(
+    tx: &mut Tx,
+    id: i32,
+    occurrences: i32,
+    next_run_at: DateTime<Utc>,
+    status: ScheduledTransferStatus,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE scheduled_transfers
+        SET occurrences = $2, next_run_at = $3, status = $4
+        WHERE id = $1
+        "#,
+        id,
+        occurrences,
+        next_run_at,
+        status as ScheduledTransferStatus,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Record the outcome of executing a scheduled transfer.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn insert_run(
+    tx: &mut Tx,
+    scheduled_transfer_id: i32,
+    scheduled_for: DateTime<Utc>,
+    status: ScheduledTransferRunStatus,
+    transfer_id: Option<i32>,
+    error: Option<&str>,
+    attempts: i32,
+) -> Result<ScheduledTransferRun, DbError> {
+    let run = sqlx::query_as!(
+        ScheduledTransferRun,
+        r#"
+        INSERT INTO scheduled_transfer_runs
+            (scheduled_transfer_id, scheduled_for, status, transfer_id, error, attempts)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING
+            id, scheduled_transfer_id, scheduled_for,
+            status as "status: ScheduledTransferRunStatus",
+            transfer_id, error, attempts, created_at
+        "#,
+        scheduled_transfer_id,
+        scheduled_for,
+        status as ScheduledTransferRunStatus,
+        transfer_id,
+        error,
+        attempts,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(run)
+}
+
+/// Fetch the runs of a scheduled transfer, newest first.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_runs(
+    tx: &mut Tx,
+    scheduled_transfer_id: i32,
+    query: &ScheduledTransferQuery,
+) -> Result<Vec<ScheduledTransferRun>, DbError> {
+    let runs = sqlx::query_as!(
+        ScheduledTransferRun,
+        r#"
+        SELECT
+            id, scheduled_transfer_id, scheduled_for,
+            status as "status: ScheduledTransferRunStatus",
+            transfer_id, error, attempts, created_at
+        FROM scheduled_transfer_runs
+        WHERE scheduled_transfer_id = $1 AND ($2::INT IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#,
+        scheduled_transfer_id,
+        query.cursor,
+        i64::from(query.limit),
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(runs)
+}
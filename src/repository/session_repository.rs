@@ -0,0 +1,190 @@
+//! Functions for storing sessions, their refresh tokens and the access tokens issued in them.
+
+use crate::{
+    infra::{error::DbError, security::revocation::RevokedToken},
+    model::session_model::RefreshToken,
+    Tx,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+/// Start a new session for a user, who authenticated with the methods in `amr`.
+#[tracing::instrument(skip(tx))]
+pub async fn insert_session(tx: &mut Tx, user_id: i32, amr: &[String]) -> Result<Uuid, DbError> {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO sessions (id, user_id, amr) VALUES ($1, $2, $3)"#,
+        id,
+        user_id,
+        amr,
+    )
+    .execute(tx)
+    .await?;
+    Ok(id)
+}
+
+/// Store the hash of a refresh token.
+#[tracing::instrument(skip(tx, token_hash))]
+pub async fn insert_refresh_token(
+    tx: &mut Tx,
+    session_id: Uuid,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (token_hash, session_id, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+        token_hash,
+        session_id,
+        expires_at,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Remember an access token issued in a session, so that it can be revoked.
+#[tracing::instrument(skip(tx))]
+pub async fn insert_access_token(
+    tx: &mut Tx,
+    jti: Uuid,
+    user_id: i32,
+    session_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO access_tokens (jti, user_id, session_id, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        jti,
+        user_id,
+        session_id,
+        expires_at,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Fetch and lock a refresh token by its hash, so that it can only be exchanged once.
+#[tracing::instrument(skip_all)]
+pub async fn lock_refresh_token(tx: &mut Tx, token_hash: &str) -> Result<RefreshToken, DbError> {
+    let token = sqlx::query_as!(
+        RefreshToken,
+        r#"
+        SELECT t.session_id, s.user_id, t.expires_at, t.used_at, s.revoked_at, s.amr
+        FROM refresh_tokens t
+        JOIN sessions s ON s.id = t.session_id
+        WHERE t.token_hash = $1
+        FOR UPDATE OF t
+        "#,
+        token_hash,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(token)
+}
+
+/// Mark a refresh token as exchanged.
+#[tracing::instrument(skip_all)]
+pub async fn use_refresh_token(tx: &mut Tx, token_hash: &str) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE refresh_tokens
+        SET used_at = CURRENT_TIMESTAMP
+        WHERE token_hash = $1
+        "#,
+        token_hash,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Revoke a session and the access tokens issued in it that have not expired yet.
+#[tracing::instrument(skip(tx))]
+pub async fn revoke_session(tx: &mut Tx, session_id: Uuid) -> Result<Vec<RevokedToken>, DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET revoked_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND revoked_at IS NULL
+        "#,
+        session_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+    let tokens = sqlx::query_as!(
+        RevokedToken,
+        r#"
+        UPDATE access_tokens
+        SET revoked_at = CURRENT_TIMESTAMP
+        WHERE session_id = $1 AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        RETURNING jti, expires_at
+        "#,
+        session_id,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(tokens)
+}
+
+/// Revoke all sessions of a user but `except`, and the access tokens issued to them, whether in
+/// a session or to an OAuth client.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = user_id))]
+pub async fn revoke_user_sessions(
+    tx: &mut Tx,
+    user_id: i32,
+    except: Option<Uuid>,
+) -> Result<Vec<RevokedToken>, DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET revoked_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND revoked_at IS NULL AND id IS DISTINCT FROM $2
+        "#,
+        user_id,
+        except,
+    )
+    .execute(&mut *tx)
+    .await?;
+    let tokens = sqlx::query_as!(
+        RevokedToken,
+        r#"
+        UPDATE access_tokens
+        SET revoked_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND ($2::UUID IS NULL OR session_id IS DISTINCT FROM $2)
+            AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        RETURNING jti, expires_at
+        "#,
+        user_id,
+        except,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(tokens)
+}
+
+/// Fetch the revoked access tokens that expire after `now`.
+#[tracing::instrument(skip(conn))]
+pub async fn fetch_revoked_tokens(
+    conn: impl PgExecutor<'_>,
+    now: DateTime<Utc>,
+) -> Result<Vec<RevokedToken>, DbError> {
+    let tokens = sqlx::query_as!(
+        RevokedToken,
+        r#"
+        SELECT jti, expires_at
+        FROM access_tokens
+        WHERE revoked_at IS NOT NULL AND expires_at > $1
+        "#,
+        now,
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(tokens)
+}
@@ -0,0 +1,105 @@
+//! Functions for storing batches of transfers and their outcomes.
+
+use crate::{
+    infra::error::DbError,
+    model::transfer_batch_model::{
+        TransferBatch, TransferBatchItem, TransferBatchItemStatus, TransferBatchMode,
+        TransferBatchStatus,
+    },
+    Tx,
+};
+
+/// Store a batch and the outcome of each of its transfers.
+#[tracing::instrument(skip(tx, items), fields(audit), ret)]
+pub async fn insert_batch(
+    tx: &mut Tx,
+    owner_id: i32,
+    mode: TransferBatchMode,
+    status: TransferBatchStatus,
+    items: &[TransferBatchItem],
+) -> Result<TransferBatch, DbError> {
+    let batch = sqlx::query_as!(
+        TransferBatch,
+        r#"
+        INSERT INTO transfer_batches (owner_id, mode, status)
+        VALUES ($1, $2, $3)
+        RETURNING
+            id, owner_id,
+            mode as "mode: TransferBatchMode",
+            status as "status: TransferBatchStatus",
+            created_at
+        "#,
+        owner_id,
+        mode as TransferBatchMode,
+        status as TransferBatchStatus,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    for item in items {
+        sqlx::query!(
+            r#"
+            INSERT INTO transfer_batch_items
+                (batch_id, position, from_account, to_account, amount, status, transfer_id, error)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            batch.id,
+            item.position,
+            item.from_account,
+            item.to_account,
+            item.amount,
+            item.status as TransferBatchItemStatus,
+            item.transfer_id,
+            item.error,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(batch)
+}
+
+/// Fetch a batch submitted by a user.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_batch(
+    tx: &mut Tx,
+    owner_id: i32,
+    batch_id: i32,
+) -> Result<TransferBatch, DbError> {
+    let batch = sqlx::query_as!(
+        TransferBatch,
+        r#"
+        SELECT
+            id, owner_id,
+            mode as "mode: TransferBatchMode",
+            status as "status: TransferBatchStatus",
+            created_at
+        FROM transfer_batches
+        WHERE owner_id = $1 AND id = $2
+        "#,
+        owner_id,
+        batch_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(batch)
+}
+
+/// Fetch the outcome of each transfer in a batch, in order.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_items(tx: &mut Tx, batch_id: i32) -> Result<Vec<TransferBatchItem>, DbError> {
+    let items = sqlx::query_as!(
+        TransferBatchItem,
+        r#"
+        SELECT
+            position, from_account, to_account, amount,
+            status as "status: TransferBatchItemStatus",
+            transfer_id, error
+        FROM transfer_batch_items
+        WHERE batch_id = $1
+        ORDER BY position
+        "#,
+        batch_id,
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(items)
+}
@@ -1,29 +1,160 @@
-//! Utilities for interacting with the account table.
+//! Utilities for interacting with the transfer table.
 
 use crate::{
     infra::error::DbError,
-    model::transfer_model::{NewTransfer, Transfer},
+    model::{
+        money::Amount,
+        transfer_model::{NewTransfer, Transfer, TransferQuery},
+    },
+    Tx,
 };
+use rust_decimal::Decimal;
 use sqlx::{Postgres, Transaction};
 
-/// Performs a transfer between two accounts.
+/// Stores a transfer between two accounts.
+/// The amount received is `to_amount`, converted using `exchange_rate` if the currencies differ.
 pub async fn insert_transfer(
     tx: &mut Transaction<'_, Postgres>,
     new_transfer: NewTransfer,
+    to_amount: Amount,
+    exchange_rate: Option<Decimal>,
 ) -> Result<Transfer, DbError> {
     // Store transfer
     let transfer = sqlx::query_as!(
         Transfer,
         r#"
-        INSERT INTO transfers (from_account, to_account, amount)
-        VALUES ($1, $2, $3)
-        RETURNING *
+        INSERT INTO transfers (from_account, to_account, amount, to_amount, exchange_rate)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, from_account, to_account, amount, to_amount, exchange_rate, reversal_of, reversed_by, created_at
         "#,
         new_transfer.from_account,
         new_transfer.to_account,
-        new_transfer.amount as i64,
+        new_transfer.amount.minor_units(),
+        to_amount.minor_units(),
+        exchange_rate,
     )
     .fetch_one(tx)
     .await?;
     Ok(transfer)
 }
+
+/// Fetch and lock a transfer for the rest of the transaction.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn lock_transfer(tx: &mut Tx, transfer_id: i32) -> Result<Transfer, DbError> {
+    let transfer = sqlx::query_as!(
+        Transfer,
+        r#"
+        SELECT
+            id, from_account, to_account, amount, to_amount, exchange_rate,
+            reversal_of, reversed_by, created_at
+        FROM transfers
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        transfer_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(transfer)
+}
+
+/// Stores a transfer that moves the money of `original` back, and links the two.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = original.id), ret)]
+pub async fn insert_reversal(tx: &mut Tx, original: &Transfer) -> Result<Transfer, DbError> {
+    let reversal = sqlx::query_as!(
+        Transfer,
+        r#"
+        INSERT INTO transfers (from_account, to_account, amount, to_amount, exchange_rate, reversal_of)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, from_account, to_account, amount, to_amount, exchange_rate, reversal_of, reversed_by, created_at
+        "#,
+        original.to_account,
+        original.from_account,
+        original.to_amount,
+        original.amount,
+        original.exchange_rate,
+        original.id,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE transfers SET reversed_by = $1 WHERE id = $2"#,
+        reversal.id,
+        original.id,
+    )
+    .execute(&mut *tx)
+    .await?;
+    Ok(reversal)
+}
+
+/// Fetch a transfer. If `user_id` is set, the transfer must involve an account they are a member of.
+#[tracing::instrument(skip(tx), ret)]
+pub async fn fetch_transfer(
+    tx: &mut Tx,
+    user_id: Option<i32>,
+    transfer_id: i32,
+) -> Result<Transfer, DbError> {
+    let transfer = sqlx::query_as!(
+        Transfer,
+        r#"
+        SELECT
+            t.id, t.from_account, t.to_account, t.amount, t.to_amount, t.exchange_rate,
+            t.reversal_of, t.reversed_by, t.created_at
+        FROM transfers t
+        LEFT JOIN account_members f ON f.account_id = t.from_account AND f.user_id = $1
+        LEFT JOIN account_members r ON r.account_id = t.to_account AND r.user_id = $1
+        WHERE t.id = $2
+          AND ($1::INT IS NULL OR f.user_id IS NOT NULL OR r.user_id IS NOT NULL)
+        "#,
+        user_id,
+        transfer_id,
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(transfer)
+}
+
+/// Search for transfers, newest first. If `user_id` is set, the transfers must be to or from the
+/// accounts they are a member of.
+#[tracing::instrument(skip(tx))]
+pub async fn fetch_transfers(
+    tx: &mut Tx,
+    user_id: Option<i32>,
+    query: &TransferQuery,
+) -> Result<Vec<Transfer>, DbError> {
+    let transfers = sqlx::query_as!(
+        Transfer,
+        r#"
+        SELECT
+            t.id, t.from_account, t.to_account, t.amount, t.to_amount, t.exchange_rate,
+            t.reversal_of, t.reversed_by, t.created_at
+        FROM transfers t
+        LEFT JOIN account_members f ON f.account_id = t.from_account AND f.user_id = $1
+        LEFT JOIN account_members r ON r.account_id = t.to_account AND r.user_id = $1
+        WHERE ($1::INT IS NULL OR f.user_id IS NOT NULL OR r.user_id IS NOT NULL)
+          AND ($2::INT IS NULL OR t.from_account = $2 OR t.to_account = $2)
+          AND ($3::TEXT IS NULL
+               OR ($3 = 'outgoing' AND ($1::INT IS NULL OR f.user_id IS NOT NULL) AND ($2::INT IS NULL OR t.from_account = $2))
+               OR ($3 = 'incoming' AND ($1::INT IS NULL OR r.user_id IS NOT NULL) AND ($2::INT IS NULL OR t.to_account = $2)))
+          AND ($4::BIGINT IS NULL OR t.amount >= $4)
+          AND ($5::BIGINT IS NULL OR t.amount <= $5)
+          AND ($6::TIMESTAMPTZ IS NULL OR t.created_at >= $6)
+          AND ($7::TIMESTAMPTZ IS NULL OR t.created_at < $7)
+          AND ($8::INT IS NULL OR t.id < $8)
+        ORDER BY t.id DESC
+        LIMIT $9
+        "#,
+        user_id,
+        query.account,
+        query.direction.map(|d| d.as_str()),
+        query.min_amount,
+        query.max_amount,
+        query.created_after,
+        query.created_before,
+        query.cursor,
+        i64::from(query.limit),
+    )
+    .fetch_all(tx)
+    .await?;
+    Ok(transfers)
+}
@@ -2,34 +2,44 @@
 
 use crate::{
     infra::{error::DbError, security::jwt::Role},
-    model::user_model::{HashedPassword, NewUser, User},
+    model::user_model::{HashedPassword, User, UserUpdate},
+    DbPool, Tx,
 };
 use sqlx::PgExecutor;
+use std::sync::OnceLock;
+use uuid::Uuid;
 
 /// Store a new user in the database.
-pub async fn store_user(conn: impl PgExecutor<'_>, new_user: &NewUser) -> Result<User, DbError> {
-    tracing::info!("Storing user with name {}", &new_user.name);
-    let hashed_password = HashedPassword::new(new_user.password.clone());
+pub async fn store_user(
+    conn: impl PgExecutor<'_>,
+    name: &str,
+    password: &HashedPassword,
+) -> Result<User, DbError> {
+    tracing::info!("Storing user with name {}", name);
     let user = sqlx::query_as!(
         User,
         r#"
         INSERT INTO users (name, password)
         VALUES ($1, $2)
-        RETURNING id, name, password as "password: HashedPassword", created_at
+        RETURNING id, name, password as "password: HashedPassword", full_name, email, created_at
         "#,
-        &new_user.name,
-        &hashed_password.hashed_password()
+        name,
+        password.hashed_password()
     )
     .fetch_one(conn)
     .await?;
     Ok(user)
 }
 
-/// Fetch a user from the database by id.
+/// Fetch a user from the database by id, unless they have been deleted.
 pub async fn fetch_user_by_id(conn: impl PgExecutor<'_>, id: &i32) -> Result<User, DbError> {
     let user = sqlx::query_as!(
         User,
-        r#"SELECT id, name, password as "password: HashedPassword", created_at FROM users WHERE id = $1"#,
+        r#"
+        SELECT id, name, password as "password: HashedPassword", full_name, email, created_at
+        FROM users
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
         id
     )
     .fetch_one(conn)
@@ -38,14 +48,18 @@ pub async fn fetch_user_by_id(conn: impl PgExecutor<'_>, id: &i32) -> Result<Use
     Ok(user)
 }
 
-/// Fetch a user from the database by name.
+/// Fetch a user from the database by name, unless they have been deleted.
 pub async fn fetch_user_by_username(
     conn: impl PgExecutor<'_>,
     username: &str,
 ) -> Result<User, DbError> {
     let user = sqlx::query_as!(
         User,
-        r#"SELECT id, name, password as "password: HashedPassword", created_at FROM users WHERE name = $1"#,
+        r#"
+        SELECT id, name, password as "password: HashedPassword", full_name, email, created_at
+        FROM users
+        WHERE name = $1 AND deleted_at IS NULL
+        "#,
         username
     )
     .fetch_one(conn)
@@ -54,11 +68,15 @@ pub async fn fetch_user_by_username(
     Ok(user)
 }
 
-/// List all users.
+/// List all users that have not been deleted.
 pub async fn fetch_all_users(conn: impl PgExecutor<'_>) -> Result<Vec<User>, DbError> {
     let users = sqlx::query_as!(
         User,
-        r#"SELECT id, name, password as "password: HashedPassword", created_at FROM users"#,
+        r#"
+        SELECT id, name, password as "password: HashedPassword", full_name, email, created_at
+        FROM users
+        WHERE deleted_at IS NULL
+        "#,
     )
     .fetch_all(conn)
     .await?;
@@ -66,16 +84,238 @@ pub async fn fetch_all_users(conn: impl PgExecutor<'_>) -> Result<Vec<User>, DbE
     Ok(users)
 }
 
-/// Verify a password.
+/// Give a user a role. Granting it again has no effect.
+#[tracing::instrument(skip(conn), fields(audit, entity_id = user_id))]
+pub async fn grant_role(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    role: Role,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_role (user_id, role_id)
+        SELECT $1, id FROM role WHERE name = $2
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        role as Role,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Change a user's profile. Fields that are not set in the update are left unchanged.
+#[tracing::instrument(skip(conn), fields(audit, entity_id = id))]
+pub async fn update_user(
+    conn: impl PgExecutor<'_>,
+    id: i32,
+    update: &UserUpdate,
+) -> Result<User, DbError> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        UPDATE users
+        SET name = COALESCE($2, name),
+            full_name = COALESCE($3, full_name),
+            email = COALESCE($4, email)
+        WHERE id = $1 AND deleted_at IS NULL
+        RETURNING id, name, password as "password: HashedPassword", full_name, email, created_at
+        "#,
+        id,
+        update.name,
+        update.full_name,
+        update.email,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(user)
+}
+
+/// Replace a user's password.
+#[tracing::instrument(skip(conn, password), fields(audit, entity_id = id))]
+pub async fn update_password(
+    conn: impl PgExecutor<'_>,
+    id: i32,
+    password: &HashedPassword,
+) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        r#"UPDATE users SET password = $2 WHERE id = $1 AND deleted_at IS NULL"#,
+        id,
+        password.hashed_password(),
+    )
+    .execute(conn)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
+
+/// Replace a password hash with one made with the current algorithm, unless the password has
+/// been changed since the old hash was read.
+#[tracing::instrument(skip_all, fields(user_id = id))]
+pub async fn rehash_password(
+    conn: impl PgExecutor<'_>,
+    id: i32,
+    old: &HashedPassword,
+    new: &HashedPassword,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"UPDATE users SET password = $3 WHERE id = $1 AND password = $2"#,
+        id,
+        old.hashed_password(),
+        new.hashed_password(),
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Fetch the hashes of the last `limit` passwords a user had before their current one.
+#[tracing::instrument(skip(conn))]
+pub async fn fetch_password_history(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    limit: i64,
+) -> Result<Vec<HashedPassword>, DbError> {
+    let passwords = sqlx::query_scalar!(
+        r#"
+        SELECT password as "password: HashedPassword"
+        FROM password_history
+        WHERE user_id = $1
+        ORDER BY id DESC
+        LIMIT $2
+        "#,
+        user_id,
+        limit,
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(passwords)
+}
+
+/// Add a password that a user no longer has to their history, keeping only the last `keep`.
+#[tracing::instrument(skip(tx, password))]
+pub async fn insert_password_history(
+    tx: &mut Tx,
+    user_id: i32,
+    password: &HashedPassword,
+    keep: i64,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"INSERT INTO password_history (user_id, password) VALUES ($1, $2)"#,
+        user_id,
+        password.hashed_password(),
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM password_history
+        WHERE user_id = $1 AND id NOT IN (
+            SELECT id FROM password_history WHERE user_id = $1 ORDER BY id DESC LIMIT $2
+        )
+        "#,
+        user_id,
+        keep,
+    )
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+/// Soft delete a user. Their name, password and profile are wiped, and they lose their roles,
+/// their API keys and their access to other users' accounts, but the row is kept for the accounts they owned.
+#[tracing::instrument(skip(tx), fields(audit, entity_id = id))]
+pub async fn delete_user(tx: &mut Tx, id: i32) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE users
+        SET name = 'deleted-' || id, password = '', full_name = NULL, email = NULL,
+            deleted_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+        id,
+    )
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    sqlx::query!(r#"DELETE FROM user_role WHERE user_id = $1"#, id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(r#"DELETE FROM password_history WHERE user_id = $1"#, id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(r#"DELETE FROM totp_secrets WHERE user_id = $1"#, id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        r#"
+        UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND revoked_at IS NULL
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM account_members WHERE user_id = $1 AND role <> 'Owner'"#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+/// A hash to check passwords against for usernames that do not exist, so that they take as long
+/// to reject as wrong passwords.
+static UNKNOWN_USER_PASSWORD: OnceLock<HashedPassword> = OnceLock::new();
+
+/// The hash that passwords of unknown usernames are checked against. It is an Argon2id hash
+/// like any other, and the server does not start if it cannot be made.
+pub fn unknown_user_password() -> &'static HashedPassword {
+    UNKNOWN_USER_PASSWORD.get_or_init(|| {
+        HashedPassword::new(&Uuid::new_v4().to_string())
+            .expect("failed to hash the password for unknown usernames")
+    })
+}
+
+/// Verify a password. Usernames that do not exist are rejected like wrong passwords. Passwords
+/// that were hashed with an older algorithm or weaker parameters are hashed again.
 #[tracing::instrument(skip_all, fields(username = username))]
 pub async fn authenticate(
-    conn: impl PgExecutor<'_>,
+    db: &DbPool,
     username: &str,
     password: &str,
 ) -> Result<Option<i32>, DbError> {
-    let user = fetch_user_by_username(conn, username).await?;
+    let user = match fetch_user_by_username(db, username).await {
+        Ok(user) => user,
+        Err(DbError::NotFound) => {
+            unknown_user_password().verify(password);
+            tracing::debug!("Failed to authenticate `{}`", username);
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
     if user.password.verify(password) {
         tracing::debug!("Authenticated `{}`", user.name);
+        if user.password.needs_rehash() {
+            // Signing in must not fail because the new hash could not be stored
+            match HashedPassword::new(password) {
+                Ok(rehashed) => {
+                    if let Err(e) = rehash_password(db, user.id, &user.password, &rehashed).await {
+                        tracing::warn!("Failed to rehash the password of `{}`: {}", user.name, e);
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to rehash the password of `{}`: {}", user.name, e),
+            }
+        }
         Ok(Some(user.id))
     } else {
         tracing::debug!("Failed to authenticate `{}`", user.name);
@@ -83,34 +323,104 @@ pub async fn authenticate(
     }
 }
 
-/// Extract grants from the database.
+/// Extract the roles of a user from the database, including the roles they imply.
 #[tracing::instrument(skip_all, fields(user_id = username))]
 pub async fn fetch_roles(conn: impl PgExecutor<'_>, username: &str) -> Result<Vec<Role>, DbError> {
-    // Get roles from db
-    let mut roles: Vec<Role> = sqlx::query!(
+    let roles = sqlx::query_scalar!(
         r#"
-        select r.name as "name: Role"
-        from role r, user_role u_r, users u
-        where r.id = u_r.role_id
-          and u_r.user_id = u.id
-          and u.name = $1;
+        WITH RECURSIVE granted(role_id) AS (
+            SELECT u_r.role_id
+            FROM user_role u_r
+            JOIN users u ON u.id = u_r.user_id
+            WHERE u.name = $1
+            UNION
+            SELECT h.implied_role_id
+            FROM role_hierarchy h
+            JOIN granted g ON g.role_id = h.role_id
+        )
+        SELECT r.name as "name!: Role"
+        FROM role r
+        WHERE r.id IN (SELECT role_id FROM granted)
+        ORDER BY r.id
         "#,
         username
     )
     .fetch_all(conn)
-    .await?
-    .into_iter()
-    .map(|r| r.name)
-    .collect();
-
-    // Add more roles based on role hierarchy
-    let mut extra_roles = Vec::new();
-    for r in &roles {
-        if r == &Role::Admin {
-            extra_roles.push(Role::User)
-        }
-    }
-    roles.append(&mut extra_roles);
+    .await?;
+    Ok(roles)
+}
 
+/// Extract the permissions of a user from the database, through all of their roles.
+#[tracing::instrument(skip_all, fields(user_id = username))]
+pub async fn fetch_permissions(
+    conn: impl PgExecutor<'_>,
+    username: &str,
+) -> Result<Vec<String>, DbError> {
+    let permissions = sqlx::query_scalar!(
+        r#"
+        WITH RECURSIVE granted(role_id) AS (
+            SELECT u_r.role_id
+            FROM user_role u_r
+            JOIN users u ON u.id = u_r.user_id
+            WHERE u.name = $1
+            UNION
+            SELECT h.implied_role_id
+            FROM role_hierarchy h
+            JOIN granted g ON g.role_id = h.role_id
+        )
+        SELECT DISTINCT p.name
+        FROM permissions p
+        JOIN role_permissions rp ON rp.permission_id = p.id
+        WHERE rp.role_id IN (SELECT role_id FROM granted)
+        ORDER BY p.name
+        "#,
+        username
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(permissions)
+}
+
+/// Fetch the roles granted directly to a user, without those they imply.
+#[tracing::instrument(skip(conn))]
+pub async fn fetch_user_roles(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+) -> Result<Vec<Role>, DbError> {
+    let roles = sqlx::query_scalar!(
+        r#"
+        SELECT r.name as "name: Role"
+        FROM role r
+        JOIN user_role u_r ON u_r.role_id = r.id
+        WHERE u_r.user_id = $1
+        ORDER BY r.id
+        "#,
+        user_id
+    )
+    .fetch_all(conn)
+    .await?;
     Ok(roles)
 }
+
+/// Take a role away from a user.
+#[tracing::instrument(skip(conn), fields(audit, entity_id = user_id))]
+pub async fn revoke_role(
+    conn: impl PgExecutor<'_>,
+    user_id: i32,
+    role: Role,
+) -> Result<(), DbError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM user_role
+        WHERE user_id = $1 AND role_id = (SELECT id FROM role WHERE name = $2)
+        "#,
+        user_id,
+        role as Role,
+    )
+    .execute(conn)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(DbError::NotFound);
+    }
+    Ok(())
+}
@@ -1,27 +1,153 @@
 //! An API for creating and modifying accounts.
 
+use crate::infra::middleware::Idempotency;
+use crate::infra::validation::Validated;
+use crate::model::account_model::{Account, AccountQuery, AccountUpdate};
+use crate::model::limit_model::LimitPeriod;
+use crate::model::member_model::{AccountAccess, AccountRole};
+use crate::model::money::Amount;
+use crate::model::pagination::Page;
 use crate::security::jwt::{Claims, Role};
 use crate::{
     infra::error::{AppError, DbError, ServiceError},
     model::account_model::{Deposit, NewAccount, Withdrawal},
-    DbPool,
+    DbPool, Tx,
+};
+use crate::{
+    repository::{
+        account_repository, currency_repository, ledger_repository, limit_repository,
+        member_repository,
+    },
+    AppResult,
 };
-use crate::{repository::account_repository, AppResult};
 use actix_web::{web, HttpResponse};
-use actix_web_grants::proc_macro::has_roles;
+use actix_web_grants::proc_macro::has_permissions;
+use chrono::Utc;
 
 /// Configures the account service.
 pub fn account_config(cfg: &mut web::ServiceConfig) {
     cfg.service(post_account)
+        .service(list_accounts)
         .service(get_account)
+        .service(patch_account)
+        .service(close_account)
         .service(deposit)
         .service(withdraw);
 }
 
+/// Fails unless `user_id` is a member of the account in a role that allows `access`.
+/// Users who are not members get [`DbError::NotFound`], so they cannot tell if the account exists.
+pub(crate) async fn ensure_access(
+    tx: &mut Tx,
+    account_id: i32,
+    user_id: i32,
+    access: AccountAccess,
+) -> AppResult<AccountRole> {
+    let role = member_repository::fetch_role(tx, account_id, user_id).await?;
+    if !role.allows(access) {
+        return Err(AppError::AuthorizationError);
+    }
+    Ok(role)
+}
+
+/// Fails if money can no longer be moved in or out of the account.
+pub(crate) fn ensure_open(account: &Account) -> Result<(), ServiceError> {
+    if !account.is_open() {
+        return Err(ServiceError::ValidationError(format!(
+            "Account {} is closed",
+            account.id()
+        )));
+    }
+    Ok(())
+}
+
+/// Fails if taking `amount` out of the account would exceed its overdraft limit, or the
+/// daily or monthly spending limits of the account or its owner. The account must be locked.
+pub(crate) async fn ensure_within_limits(
+    tx: &mut Tx,
+    account: &Account,
+    amount: Amount,
+) -> AppResult<()> {
+    let amount = amount.minor_units();
+    if amount > account.available_balance() {
+        return Err(ServiceError::ValidationError(format!(
+            "Balance is too low, required {} but account {} has {} available with an overdraft limit of {}",
+            amount,
+            account.id(),
+            account.available_balance().max(0),
+            account.overdraft_limit()
+        ))
+        .into());
+    }
+
+    let now = Utc::now();
+    let account_limits = [
+        (LimitPeriod::Daily, account.daily_limit),
+        (LimitPeriod::Monthly, account.monthly_limit),
+    ];
+    for (period, limit) in account_limits {
+        if let Some(limit) = limit {
+            let spent =
+                limit_repository::fetch_account_spending(tx, account.id(), period.start(now))
+                    .await?;
+            ensure_headroom(amount, limit, spent, || {
+                format!("{} limit of account {}", period, account.id())
+            })?;
+        }
+    }
+
+    let user_limit =
+        limit_repository::lock_user_limit(tx, account.owner_id(), account.currency()).await?;
+    if let Some(user_limit) = user_limit {
+        let user_limits = [
+            (LimitPeriod::Daily, user_limit.daily_limit),
+            (LimitPeriod::Monthly, user_limit.monthly_limit),
+        ];
+        for (period, limit) in user_limits {
+            if let Some(limit) = limit {
+                let spent = limit_repository::fetch_user_spending(
+                    tx,
+                    account.owner_id(),
+                    account.currency(),
+                    period.start(now),
+                )
+                .await?;
+                ensure_headroom(amount, limit, spent, || {
+                    format!(
+                        "{} {} limit of user {}",
+                        period,
+                        account.currency(),
+                        account.owner_id()
+                    )
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn ensure_headroom(
+    amount: i64,
+    limit: i64,
+    spent: i64,
+    name: impl FnOnce() -> String,
+) -> Result<(), ServiceError> {
+    let headroom = limit.saturating_sub(spent).max(0);
+    if amount > headroom {
+        return Err(ServiceError::ValidationError(format!(
+            "Amount {} exceeds the {} of {}, {} remaining",
+            amount,
+            name(),
+            limit,
+            headroom
+        )));
+    }
+    Ok(())
+}
+
 #[actix_web::post("/users/{user_id}/accounts")]
-#[has_roles(
-    "Role::User",
-    type = "Role",
+#[has_permissions(
+    "accounts:write",
     secure = "*user_id == claims.id() || claims.has_role(&Role::Admin)"
 )]
 #[tracing::instrument(skip_all)]
@@ -32,16 +158,44 @@ pub async fn post_account(
     new_account: web::Json<NewAccount>,
 ) -> AppResult<HttpResponse> {
     let mut tx = db.begin().await.map_err(DbError::from)?;
+    let currency = new_account.currency();
+    match currency_repository::fetch_currency(&mut tx, currency).await {
+        Err(DbError::NotFound) => {
+            return Err(
+                ServiceError::ValidationError(format!("Unsupported currency {}", currency)).into(),
+            )
+        }
+        result => result?,
+    };
     let account =
         account_repository::insert_account(&mut tx, *user_id, new_account.into_inner()).await?;
+    member_repository::insert_member(&mut tx, account.id(), *user_id, AccountRole::Owner).await?;
     tx.commit().await.map_err(DbError::from)?;
     Ok(HttpResponse::Created().json(account))
 }
 
+#[actix_web::get("/users/{user_id}/accounts")]
+#[has_permissions(
+    "accounts:read",
+    secure = "*user_id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn list_accounts(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    user_id: web::Path<i32>,
+    query: web::Query<Validated<AccountQuery>>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let accounts = account_repository::fetch_accounts(&mut tx, *user_id, &query).await?;
+    let total = account_repository::count_accounts(&mut tx, *user_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(Page::new(accounts, query.page, query.size, total)))
+}
+
 #[actix_web::get("/users/{user_id}/accounts/{account_id}")]
-#[has_roles(
-    "Role::User",
-    type = "Role",
+#[has_permissions(
+    "accounts:read",
     secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
 )]
 #[tracing::instrument(skip_all)]
@@ -53,17 +207,79 @@ pub async fn get_account(
     let mut tx = db.begin().await.map_err(DbError::from)?;
     let (user_id, account_id) = *path_params;
     let account = account_repository::fetch_account(&mut tx, user_id, account_id).await?;
-    if account.owner_id != claims.id() && !claims.has_role(&Role::Admin) {
-        return Err(AppError::AuthorizationError);
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(account))
+}
+
+#[actix_web::patch("/users/{user_id}/accounts/{account_id}")]
+#[has_permissions(
+    "accounts:write",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn patch_account(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+    update: web::Json<AccountUpdate>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, account_id) = *path_params;
+
+    ensure_access(&mut tx, account_id, user_id, AccountAccess::Transact).await?;
+
+    let account = account_repository::rename_account(&mut tx, account_id, update.name()).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(account))
+}
+
+#[actix_web::post("/users/{user_id}/accounts/{account_id}/close")]
+#[has_permissions(
+    "accounts:write",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn close_account(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, account_id) = *path_params;
+
+    ensure_access(&mut tx, account_id, user_id, AccountAccess::Manage).await?;
+    // Lock the account, so that no money moves in or out between the checks and closing it
+    let accounts = account_repository::lock_accounts(&mut tx, &[account_id]).await?;
+    let account = accounts.first().ok_or(DbError::NotFound)?;
+    ensure_open(account)?;
+    if account.balance() != 0 {
+        return Err(ServiceError::ValidationError(format!(
+            "Cannot close account {} with a balance of {}",
+            account_id,
+            account.balance()
+        ))
+        .into());
     }
+    if account.held() != 0 {
+        return Err(ServiceError::ValidationError(format!(
+            "Cannot close account {} with {} reserved by holds",
+            account_id,
+            account.held()
+        ))
+        .into());
+    }
+
+    let account = account_repository::close_account(&mut tx, account_id).await?;
     tx.commit().await.map_err(DbError::from)?;
     Ok(HttpResponse::Ok().json(account))
 }
 
-#[actix_web::post("/users/{user_id}/accounts/{account_id}/deposits")]
-#[has_roles(
-    "Role::User",
-    type = "Role",
+#[actix_web::post(
+    "/users/{user_id}/accounts/{account_id}/deposits",
+    wrap = "Idempotency"
+)]
+#[has_permissions(
+    "deposits:create",
     secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
 )]
 #[tracing::instrument(skip_all)]
@@ -74,16 +290,31 @@ pub async fn deposit(
     deposit: web::Json<Deposit>,
 ) -> AppResult<HttpResponse> {
     let mut tx = db.begin().await.map_err(DbError::from)?;
-    let account_id = path_params.1;
+    let (user_id, account_id) = *path_params;
+    ensure_access(&mut tx, account_id, user_id, AccountAccess::Transact).await?;
+
+    // Lock the account, so that it cannot be closed before the money lands
+    let accounts = account_repository::lock_accounts(&mut tx, &[account_id]).await?;
+    let account = accounts.first().ok_or(DbError::NotFound)?;
+    ensure_open(account)?;
     account_repository::deposit(&mut tx, account_id, deposit.amount()).await?;
+    ledger_repository::record_deposit(
+        &mut tx,
+        account_id,
+        account.currency(),
+        deposit.amount().minor_units(),
+    )
+    .await?;
     tx.commit().await.map_err(DbError::from)?;
     Ok(HttpResponse::Ok().finish())
 }
 
-#[actix_web::post("/users/{user_id}/accounts/{account_id}/withdrawals")]
-#[has_roles(
-    "Role::User",
-    type = "Role",
+#[actix_web::post(
+    "/users/{user_id}/accounts/{account_id}/withdrawals",
+    wrap = "Idempotency"
+)]
+#[has_permissions(
+    "withdrawals:create",
     secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
 )]
 #[tracing::instrument(skip_all)]
@@ -95,10 +326,16 @@ pub async fn withdraw(
 ) -> AppResult<HttpResponse> {
     let mut tx = db.begin().await.map_err(DbError::from)?;
 
-    let account_id = path_params.1;
+    let (user_id, account_id) = *path_params;
     let withdrawal = withdrawal.into_inner();
+    ensure_access(&mut tx, account_id, user_id, AccountAccess::Transact).await?;
+
+    // Lock the account so concurrent withdrawals see each other's spending
+    let accounts = account_repository::lock_accounts(&mut tx, &[account_id]).await?;
+    let account = accounts.first().ok_or(DbError::NotFound)?;
+    ensure_open(account)?;
+    ensure_within_limits(&mut tx, account, withdrawal.amount()).await?;
 
-    // Try to make withdrawal
     tracing::debug!(
         "Withdrawing {} from account {}",
         withdrawal.amount(),
@@ -106,15 +343,13 @@ pub async fn withdraw(
     );
     let account = account_repository::withdraw(&mut tx, account_id, withdrawal.amount()).await?;
 
-    // Check if balance became negative
-    if account.balance() < 0 {
-        return Err(ServiceError::ValidationError(format!(
-            "Too low balance, required {} but had {}",
-            withdrawal.amount(),
-            account.balance() + withdrawal.amount() as i64
-        ))
-        .into());
-    }
+    ledger_repository::record_withdrawal(
+        &mut tx,
+        account_id,
+        account.currency(),
+        withdrawal.amount().minor_units(),
+    )
+    .await?;
 
     tx.commit().await.map_err(DbError::from)?;
     Ok(HttpResponse::Ok().finish())
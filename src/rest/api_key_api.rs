@@ -0,0 +1,92 @@
+//! Routes for managing personal API keys.
+
+use crate::infra::error::{AppError, DbError, ServiceError};
+use crate::infra::security::api_key;
+use crate::infra::validation::Validated;
+use crate::model::api_key_model::{CreatedApiKey, NewApiKey};
+use crate::repository::{api_key_repository, user_repository};
+use crate::security::jwt::{Claims, Role};
+use crate::DbPool;
+use actix_web::{
+    web::{self, Data, Json, Path, ReqData},
+    HttpResponse,
+};
+use actix_web_grants::proc_macro::has_roles;
+use chrono::Utc;
+
+/// Configure the api key service.
+pub fn api_key_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_api_key)
+        .service(list_api_keys)
+        .service(revoke_api_key);
+}
+
+/// Creates an API key for a user, limited to permissions the user has. The response is the
+/// only time the key is shown.
+#[actix_web::post("/users/{id}/api-keys")]
+#[has_roles("Role::User", type = "Role", secure = "*id == claims.id()")]
+#[tracing::instrument(skip_all)]
+pub async fn create_api_key(
+    db: Data<DbPool>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+    new_key: Json<Validated<NewApiKey>>,
+) -> Result<HttpResponse, AppError> {
+    if matches!(new_key.expires_at, Some(expires_at) if expires_at <= Utc::now()) {
+        return Err(ServiceError::ValidationError("Expiry is in the past".to_string()).into());
+    }
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let user = user_repository::fetch_user_by_id(&mut tx, &id).await?;
+    let permissions = user_repository::fetch_permissions(&mut tx, &user.name).await?;
+    if let Some(scope) = new_key.scopes.iter().find(|s| !permissions.contains(s)) {
+        return Err(
+            ServiceError::ValidationError(format!("Missing permission `{}`", scope)).into(),
+        );
+    }
+    let (key, prefix) = api_key::generate_key()?;
+    let api_key = api_key_repository::insert_api_key(
+        &mut tx,
+        user.id,
+        &new_key,
+        &api_key::hash_key(&key),
+        &prefix,
+    )
+    .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Created().json(CreatedApiKey { api_key, key }))
+}
+
+/// Lists the API keys of a user, including revoked and expired ones.
+#[actix_web::get("/users/{id}/api-keys")]
+#[has_roles(
+    "Role::User",
+    type = "Role",
+    secure = "*id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn list_api_keys(
+    db: Data<DbPool>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let api_keys = api_key_repository::fetch_api_keys(db.get_ref(), *id).await?;
+    Ok(HttpResponse::Ok().json(api_keys))
+}
+
+/// Revokes an API key of a user.
+#[actix_web::delete("/users/{id}/api-keys/{key_id}")]
+#[has_roles(
+    "Role::User",
+    type = "Role",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn revoke_api_key(
+    db: Data<DbPool>,
+    claims: ReqData<Claims>,
+    path_params: Path<(i32, i32)>,
+) -> Result<HttpResponse, AppError> {
+    let (user_id, key_id) = *path_params;
+    api_key_repository::revoke_api_key(db.get_ref(), user_id, key_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -43,6 +43,7 @@ impl<'a> From<CannotParseHeaderError<'a>> for InternalError<String> {
 }
 
 /// Attempts to retrieve and parse the value of a header.
+#[allow(clippy::result_large_err)]
 fn try_parse_header<T>(hm: &HeaderMap, header_name: &str) -> Result<T, InternalError<String>>
 where
     T: FromStr,
@@ -74,6 +75,7 @@ impl ClientContext {
             token: token.into(),
         }
     }
+    #[allow(clippy::result_large_err)]
     fn from_request(req: &actix_web::HttpRequest) -> Result<ClientContext, InternalError<String>> {
         let hm = req.headers();
         let user_id: usize = try_parse_header(hm, "user_id")?;
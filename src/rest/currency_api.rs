@@ -0,0 +1,94 @@
+//! An API for currencies and the exchange rates between them.
+
+use crate::{
+    infra::error::{DbError, ServiceError},
+    model::{fx_model::NewFxRate, money::CurrencyCode},
+    repository::currency_repository,
+    security::jwt::{Claims, Role},
+    AppResult, DbPool,
+};
+use actix_web::{web, HttpResponse};
+use actix_web_grants::proc_macro::{has_permissions, has_roles};
+
+/// Configure the currency service.
+pub fn currency_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_currencies)
+        .service(list_fx_rates)
+        .service(put_fx_rate)
+        .service(delete_fx_rate);
+}
+
+#[actix_web::get("/currencies")]
+#[has_permissions("currencies:read")]
+#[tracing::instrument(skip_all)]
+pub async fn list_currencies(db: web::Data<DbPool>) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let currencies = currency_repository::fetch_currencies(&mut tx).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(currencies))
+}
+
+#[actix_web::get("/fx-rates")]
+#[has_permissions("currencies:read")]
+#[tracing::instrument(skip_all)]
+pub async fn list_fx_rates(db: web::Data<DbPool>) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let rates = currency_repository::fetch_fx_rates(&mut tx).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(rates))
+}
+
+#[actix_web::put("/fx-rates/{base}/{quote}")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn put_fx_rate(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(CurrencyCode, CurrencyCode)>,
+    new_rate: web::Json<NewFxRate>,
+) -> AppResult<HttpResponse> {
+    let (base, quote) = path_params.into_inner();
+    if base == quote {
+        return Err(ServiceError::ValidationError(format!(
+            "Cannot set a rate from {} to itself",
+            base
+        ))
+        .into());
+    }
+    if new_rate.rate.is_sign_negative() || new_rate.rate.is_zero() {
+        return Err(ServiceError::ValidationError("Rate must be positive".to_string()).into());
+    }
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    for currency in [&base, &quote] {
+        match currency_repository::fetch_currency(&mut tx, currency).await {
+            Err(DbError::NotFound) => {
+                return Err(ServiceError::ValidationError(format!(
+                    "Unsupported currency {}",
+                    currency
+                ))
+                .into())
+            }
+            result => result?,
+        };
+    }
+    let rate =
+        currency_repository::upsert_fx_rate(&mut tx, &base, &quote, new_rate.rate, claims.id())
+            .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(rate))
+}
+
+#[actix_web::delete("/fx-rates/{base}/{quote}")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn delete_fx_rate(
+    db: web::Data<DbPool>,
+    path_params: web::Path<(CurrencyCode, CurrencyCode)>,
+) -> AppResult<HttpResponse> {
+    let (base, quote) = path_params.into_inner();
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    currency_repository::delete_fx_rate(&mut tx, &base, &quote).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,205 @@
+//! An API for reserving money on an account, and capturing or releasing it later.
+//!
+//! A hold reduces the available balance of an account but not its ledger balance.
+//! Accounts are locked before their holds. [`crate::worker::hold_expiry`] skips holds whose
+//! account is locked, so it never waits for a request.
+
+use crate::{
+    infra::{
+        error::{AppError, DbError, ServiceError},
+        middleware::Idempotency,
+    },
+    model::{
+        hold_model::{Hold, HoldCapture, HoldQuery, HoldStatus, NewHold, MAX_HOLD_DAYS},
+        member_model::AccountAccess,
+        money::Amount,
+    },
+    repository::{account_repository, hold_repository, ledger_repository},
+    rest::account_api::{ensure_access, ensure_open, ensure_within_limits},
+    security::jwt::{Claims, Role},
+    AppResult, DbPool,
+};
+use actix_http::StatusCode;
+use actix_web::{web, HttpResponse};
+use actix_web_grants::proc_macro::has_permissions;
+use chrono::{DateTime, Utc};
+
+/// Configure the hold service.
+pub fn hold_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_hold)
+        .service(list_holds)
+        .service(get_hold)
+        .service(capture_hold)
+        .service(release_hold);
+}
+
+/// Fails if the hold has been closed or has expired.
+fn ensure_active(hold: &Hold, now: DateTime<Utc>) -> AppResult<()> {
+    if hold.status != HoldStatus::Active {
+        return Err(AppError::CustomError(
+            format!("Hold {} is {:?}", hold.id, hold.status),
+            StatusCode::CONFLICT,
+        ));
+    }
+    if !hold.is_active(now) {
+        return Err(AppError::CustomError(
+            format!("Hold {} expired at {}", hold.id, hold.expires_at),
+            StatusCode::CONFLICT,
+        ));
+    }
+    Ok(())
+}
+
+#[actix_web::post("/users/{user_id}/accounts/{account_id}/holds", wrap = "Idempotency")]
+#[has_permissions(
+    "holds:write",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn create_hold(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+    new_hold: web::Json<NewHold>,
+) -> AppResult<HttpResponse> {
+    let (user_id, account_id) = *path_params;
+    let new_hold = new_hold.into_inner();
+    if !new_hold.expires_in_range(Utc::now()) {
+        return Err(ServiceError::ValidationError(format!(
+            "Hold must expire in the future and within {} days",
+            MAX_HOLD_DAYS
+        ))
+        .into());
+    }
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    ensure_access(&mut tx, account_id, user_id, AccountAccess::Transact).await?;
+    // Lock the account so concurrent holds and withdrawals see each other
+    let accounts = account_repository::lock_accounts(&mut tx, &[account_id]).await?;
+    let account = accounts.first().ok_or(DbError::NotFound)?;
+    ensure_open(account)?;
+    ensure_within_limits(&mut tx, account, new_hold.amount).await?;
+
+    account_repository::reserve(&mut tx, account_id, new_hold.amount).await?;
+    let hold = hold_repository::insert_hold(&mut tx, account_id, &new_hold).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Created().json(hold))
+}
+
+#[actix_web::get("/users/{user_id}/accounts/{account_id}/holds")]
+#[has_permissions(
+    "holds:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn list_holds(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+    query: web::Query<HoldQuery>,
+) -> AppResult<HttpResponse> {
+    let (user_id, account_id) = *path_params;
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    account_repository::fetch_account(&mut tx, user_id, account_id).await?;
+    let holds = hold_repository::fetch_holds(&mut tx, account_id, &query).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(holds))
+}
+
+#[actix_web::get("/users/{user_id}/accounts/{account_id}/holds/{hold_id}")]
+#[has_permissions(
+    "holds:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_hold(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let (user_id, account_id, hold_id) = *path_params;
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    account_repository::fetch_account(&mut tx, user_id, account_id).await?;
+    let hold = hold_repository::fetch_hold(&mut tx, account_id, hold_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+#[actix_web::post(
+    "/users/{user_id}/accounts/{account_id}/holds/{hold_id}/capture",
+    wrap = "Idempotency"
+)]
+#[has_permissions(
+    "holds:write",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn capture_hold(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32, i32)>,
+    capture: web::Json<HoldCapture>,
+) -> AppResult<HttpResponse> {
+    let (user_id, account_id, hold_id) = *path_params;
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    ensure_access(&mut tx, account_id, user_id, AccountAccess::Transact).await?;
+    let accounts = account_repository::lock_accounts(&mut tx, &[account_id]).await?;
+    ensure_open(accounts.first().ok_or(DbError::NotFound)?)?;
+    let hold = hold_repository::lock_hold(&mut tx, account_id, hold_id).await?;
+    ensure_active(&hold, Utc::now())?;
+
+    let held = Amount::new(hold.amount).map_err(ServiceError::from)?;
+    let amount = capture.amount.unwrap_or(held);
+    if amount > held {
+        return Err(ServiceError::ValidationError(format!(
+            "Cannot capture {} from hold {} of {}",
+            amount, hold.id, hold.amount
+        ))
+        .into());
+    }
+
+    // The rest of the hold is released, and the captured amount leaves the account
+    account_repository::unreserve(&mut tx, account_id, held).await?;
+    let account = account_repository::withdraw(&mut tx, account_id, amount).await?;
+    ledger_repository::record_withdrawal(
+        &mut tx,
+        account_id,
+        account.currency(),
+        amount.minor_units(),
+    )
+    .await?;
+    let hold = hold_repository::close_hold(
+        &mut tx,
+        hold_id,
+        HoldStatus::Captured,
+        Some(amount.minor_units()),
+    )
+    .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+#[actix_web::post("/users/{user_id}/accounts/{account_id}/holds/{hold_id}/release")]
+#[has_permissions(
+    "holds:write",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn release_hold(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let (user_id, account_id, hold_id) = *path_params;
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    ensure_access(&mut tx, account_id, user_id, AccountAccess::Transact).await?;
+    account_repository::lock_accounts(&mut tx, &[account_id]).await?;
+    let hold = hold_repository::lock_hold(&mut tx, account_id, hold_id).await?;
+    ensure_active(&hold, Utc::now())?;
+
+    let held = Amount::new(hold.amount).map_err(ServiceError::from)?;
+    account_repository::unreserve(&mut tx, account_id, held).await?;
+    let hold = hold_repository::close_hold(&mut tx, hold_id, HoldStatus::Released, None).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(hold))
+}
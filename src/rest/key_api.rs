@@ -0,0 +1,45 @@
+//! Routes for publishing and rotating the keys that jwts are signed with.
+
+use crate::{
+    infra::security::{jwt::JwtContext, keys},
+    repository::jwt_key_repository,
+    security::jwt::Role,
+    AppResult, DbPool,
+};
+use actix_web::{web, HttpResponse};
+use actix_web_grants::proc_macro::has_roles;
+use chrono::Utc;
+
+/// Configure the key service.
+pub fn key_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_keys).service(rotate_keys);
+}
+
+/// Publishes the public keys that tokens are accepted from, so that other services can verify
+/// our tokens.
+#[actix_web::get("/.well-known/jwks.json")]
+#[tracing::instrument(skip_all)]
+pub async fn jwks(jwt: web::Data<JwtContext>) -> AppResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(jwt.keys().jwks(Utc::now())))
+}
+
+#[actix_web::get("/jwt-keys")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn list_keys(db: web::Data<DbPool>) -> AppResult<HttpResponse> {
+    let keys = jwt_key_repository::fetch_keys(db.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(keys))
+}
+
+/// Generates a new signing key. The previous one keeps verifying tokens for the configured
+/// overlap.
+#[actix_web::post("/jwt-keys/rotate")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn rotate_keys(
+    db: web::Data<DbPool>,
+    jwt: web::Data<JwtContext>,
+) -> AppResult<HttpResponse> {
+    let key = keys::rotate_keys(db.get_ref(), &jwt).await?;
+    Ok(HttpResponse::Created().json(key))
+}
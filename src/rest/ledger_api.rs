@@ -0,0 +1,150 @@
+//! An API for inspecting the ledger behind account balances.
+
+use crate::{
+    infra::{
+        error::{AppError, DbError, ServiceError},
+        validation::Validated,
+    },
+    model::{
+        ledger_model::{LedgerEntry, LedgerEntryQuery},
+        pagination::CursorPage,
+        statement_model::{Statement, StatementFormat, StatementQuery},
+    },
+    repository::{account_repository, currency_repository, ledger_repository},
+    security::jwt::{Claims, Role},
+    AppResult, DbPool,
+};
+use actix_http::StatusCode;
+use actix_web::{
+    http::header::{self, Accept},
+    web, HttpResponse,
+};
+use actix_web_grants::proc_macro::{has_permissions, has_roles};
+use chrono::Utc;
+
+/// Configure the ledger service.
+pub fn ledger_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_entries)
+        .service(get_statement)
+        .service(reconcile);
+}
+
+#[actix_web::get("/users/{user_id}/accounts/{account_id}/entries")]
+#[has_permissions(
+    "accounts:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn list_entries(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+    query: web::Query<Validated<LedgerEntryQuery>>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, account_id) = *path_params;
+    account_repository::fetch_account(&mut tx, user_id, account_id).await?;
+    let entries = ledger_repository::fetch_entries(&mut tx, account_id, &query).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    let page = CursorPage::new(entries, query.limit, |e: &LedgerEntry| e.id);
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// The format asked for in the query, or else the most preferred format in the `Accept` header.
+fn statement_format(query: &StatementQuery, accept: Option<&Accept>) -> StatementFormat {
+    query
+        .format
+        .or_else(|| {
+            accept?
+                .ranked()
+                .iter()
+                .find_map(|mime| StatementFormat::from_media_type(mime.essence_str()))
+        })
+        .unwrap_or_default()
+}
+
+#[actix_web::get("/users/{user_id}/accounts/{account_id}/statement")]
+#[has_permissions(
+    "accounts:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_statement(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+    query: web::Query<StatementQuery>,
+    accept: Option<web::Header<Accept>>,
+) -> AppResult<HttpResponse> {
+    if query.to < query.from {
+        return Err(ServiceError::ValidationError(
+            "Statement cannot end before it starts".to_string(),
+        )
+        .into());
+    }
+    let format = statement_format(&query, accept.as_deref());
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, account_id) = *path_params;
+    let account = account_repository::fetch_account(&mut tx, user_id, account_id).await?;
+    let currency = currency_repository::fetch_currency(&mut tx, account.currency()).await?;
+    let lines =
+        ledger_repository::fetch_statement_lines(&mut tx, account_id, query.start(), query.end())
+            .await?;
+    // Derive the balances from the lines when possible, so they always agree
+    let opening_balance = match lines.first() {
+        Some(line) => line.balance - line.amount,
+        None => ledger_repository::fetch_balance_before(&mut tx, account_id, query.start()).await?,
+    };
+    let closing_balance = lines.last().map_or(opening_balance, |line| line.balance);
+    tx.commit().await.map_err(DbError::from)?;
+
+    let statement = Statement {
+        account,
+        currency,
+        from: query.from,
+        to: query.to,
+        opening_balance,
+        closing_balance,
+        lines,
+        created_at: Utc::now(),
+    };
+    let body = match format {
+        StatementFormat::Json => return Ok(HttpResponse::Ok().json(statement)),
+        StatementFormat::Csv => statement.to_csv().map_err(|e| {
+            AppError::CustomError(
+                format!("Could not write statement: {}", e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        })?,
+        StatementFormat::Ofx => statement.to_ofx(),
+        StatementFormat::Camt053 => statement.to_camt053(),
+    };
+    let filename = format!(
+        "statement-{}-{}-{}.{}",
+        account_id,
+        query.from,
+        query.to,
+        format.extension()
+    );
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        ))
+        .body(body))
+}
+
+#[actix_web::get("/ledger/reconciliation")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn reconcile(db: web::Data<DbPool>) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let mismatches = ledger_repository::fetch_balance_mismatches(&mut tx).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    if !mismatches.is_empty() {
+        tracing::warn!("Found {} accounts out of balance", mismatches.len());
+    }
+    Ok(HttpResponse::Ok().json(mismatches))
+}
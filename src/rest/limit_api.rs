@@ -0,0 +1,94 @@
+//! An API for overdraft and spending limits.
+
+use crate::{
+    infra::{
+        error::{DbError, ServiceError},
+        validation::Validated,
+    },
+    model::{
+        limit_model::{AccountLimits, NewUserSpendingLimit},
+        money::CurrencyCode,
+    },
+    repository::{currency_repository, limit_repository, user_repository},
+    security::jwt::{Claims, Role},
+    AppResult, DbPool,
+};
+use actix_web::{web, HttpResponse};
+use actix_web_grants::proc_macro::{has_permissions, has_roles};
+
+/// Configure the limit service.
+pub fn limit_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(put_account_limits)
+        .service(list_user_limits)
+        .service(put_user_limit)
+        .service(delete_user_limit);
+}
+
+#[actix_web::put("/accounts/{account_id}/limits")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn put_account_limits(
+    db: web::Data<DbPool>,
+    account_id: web::Path<i32>,
+    limits: web::Json<Validated<AccountLimits>>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let account = limit_repository::update_account_limits(&mut tx, *account_id, &limits).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(account))
+}
+
+#[actix_web::get("/users/{user_id}/limits")]
+#[has_permissions(
+    "accounts:read",
+    secure = "*user_id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn list_user_limits(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    user_id: web::Path<i32>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let limits = limit_repository::fetch_user_limits(&mut tx, *user_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(limits))
+}
+
+#[actix_web::put("/users/{user_id}/limits/{currency}")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn put_user_limit(
+    db: web::Data<DbPool>,
+    path_params: web::Path<(i32, CurrencyCode)>,
+    limit: web::Json<Validated<NewUserSpendingLimit>>,
+) -> AppResult<HttpResponse> {
+    let (user_id, currency) = path_params.into_inner();
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    user_repository::fetch_user_by_id(&mut tx, &user_id).await?;
+    match currency_repository::fetch_currency(&mut tx, &currency).await {
+        Err(DbError::NotFound) => {
+            return Err(
+                ServiceError::ValidationError(format!("Unsupported currency {}", currency)).into(),
+            )
+        }
+        result => result?,
+    };
+    let limit = limit_repository::upsert_user_limit(&mut tx, user_id, &currency, &limit).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(limit))
+}
+
+#[actix_web::delete("/users/{user_id}/limits/{currency}")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn delete_user_limit(
+    db: web::Data<DbPool>,
+    path_params: web::Path<(i32, CurrencyCode)>,
+) -> AppResult<HttpResponse> {
+    let (user_id, currency) = path_params.into_inner();
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    limit_repository::delete_user_limit(&mut tx, user_id, &currency).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,115 @@
+//! An API for sharing an account with other users.
+//!
+//! The owner of an account can invite co-owners, who can move money, and viewers, who can
+//! only read it. Members can see who else has access, and can leave the account themselves.
+
+use crate::{
+    infra::error::{DbError, ServiceError},
+    model::member_model::{AccountAccess, AccountRole, NewAccountMember},
+    repository::{member_repository, user_repository},
+    rest::account_api::ensure_access,
+    security::jwt::{Claims, Role},
+    AppResult, DbPool,
+};
+use actix_web::{web, HttpResponse};
+use actix_web_grants::proc_macro::has_permissions;
+
+/// Configure the account member service.
+pub fn member_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_members)
+        .service(invite_member)
+        .service(revoke_member);
+}
+
+#[actix_web::get("/users/{user_id}/accounts/{account_id}/members")]
+#[has_permissions(
+    "accounts:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn list_members(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let (user_id, account_id) = *path_params;
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    ensure_access(&mut tx, account_id, user_id, AccountAccess::View).await?;
+    let members = member_repository::fetch_members(&mut tx, account_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(members))
+}
+
+#[actix_web::post("/users/{user_id}/accounts/{account_id}/members")]
+#[has_permissions(
+    "accounts:write",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn invite_member(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+    new_member: web::Json<NewAccountMember>,
+) -> AppResult<HttpResponse> {
+    let (user_id, account_id) = *path_params;
+    if new_member.role == AccountRole::Owner {
+        return Err(ServiceError::ValidationError(format!(
+            "Account {} already has an owner",
+            account_id
+        ))
+        .into());
+    }
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    ensure_access(&mut tx, account_id, user_id, AccountAccess::Manage).await?;
+    match user_repository::fetch_user_by_id(&mut tx, &new_member.user_id).await {
+        Err(DbError::NotFound) => {
+            return Err(ServiceError::ValidationError(format!(
+                "Unknown user {}",
+                new_member.user_id
+            ))
+            .into())
+        }
+        result => result?,
+    };
+    let member =
+        member_repository::insert_member(&mut tx, account_id, new_member.user_id, new_member.role)
+            .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Created().json(member))
+}
+
+#[actix_web::delete("/users/{user_id}/accounts/{account_id}/members/{member_id}")]
+#[has_permissions(
+    "accounts:write",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn revoke_member(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let (user_id, account_id, member_id) = *path_params;
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+
+    // Only the owner can revoke others, but anyone else can leave
+    let access = if member_id == user_id {
+        AccountAccess::View
+    } else {
+        AccountAccess::Manage
+    };
+    ensure_access(&mut tx, account_id, user_id, access).await?;
+    if member_repository::fetch_role(&mut tx, account_id, member_id).await? == AccountRole::Owner {
+        return Err(ServiceError::ValidationError(format!(
+            "The owner of account {} cannot be removed",
+            account_id
+        ))
+        .into());
+    }
+
+    member_repository::delete_member(&mut tx, account_id, member_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,127 @@
+//! Routes for setting up multi-factor authentication.
+
+use crate::infra::configuration::MfaSettings;
+use crate::infra::error::{AppError, DbError, ServiceError};
+use crate::infra::security::mfa;
+use crate::model::mfa_model::{RecoveryCodes, TotpCode, TotpEnrollment};
+use crate::repository::{mfa_repository, user_repository};
+use crate::security::jwt::{Claims, Role};
+use crate::DbPool;
+use actix_web::{
+    web::{self, Data, Json, Path, ReqData},
+    HttpResponse,
+};
+use actix_web_grants::proc_macro::has_roles;
+use chrono::Utc;
+
+/// Configure the mfa service.
+pub fn mfa_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(enroll_totp)
+        .service(confirm_totp)
+        .service(regenerate_recovery_codes)
+        .service(delete_mfa);
+}
+
+/// Generates a new TOTP secret for a user. It is only required to sign in once it is
+/// confirmed with a code.
+#[actix_web::post("/users/{id}/mfa/totp")]
+#[has_roles("Role::User", type = "Role", secure = "*id == claims.id()")]
+#[tracing::instrument(skip_all)]
+pub async fn enroll_totp(
+    db: Data<DbPool>,
+    settings: Data<MfaSettings>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let user = user_repository::fetch_user_by_id(&mut tx, &id).await?;
+    let secret = mfa::generate_secret()?;
+    mfa_repository::upsert_totp_secret(&mut tx, user.id, &secret).await?;
+    tx.commit().await.map_err(DbError::from)?;
+
+    let otpauth_uri = mfa::otpauth_uri(&settings.issuer, &user.name, &secret);
+    Ok(HttpResponse::Created().json(TotpEnrollment {
+        secret,
+        otpauth_uri,
+    }))
+}
+
+/// Confirms a TOTP secret with a code from the authenticator app, and returns the recovery
+/// codes.
+#[actix_web::post("/users/{id}/mfa/totp/confirm")]
+#[has_roles("Role::User", type = "Role", secure = "*id == claims.id()")]
+#[tracing::instrument(skip_all)]
+pub async fn confirm_totp(
+    db: Data<DbPool>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+    code: Json<TotpCode>,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let secret = mfa_repository::lock_totp_secret(&mut tx, *id).await?;
+    if secret.confirmed_at.is_some() {
+        return Err(DbError::Conflict.into());
+    }
+    let step = mfa::verify_code(&secret.secret, &code.code, Utc::now(), None)?
+        .ok_or_else(|| ServiceError::ValidationError("Invalid code".to_string()))?;
+    mfa_repository::confirm_totp_secret(&mut tx, *id, step).await?;
+    let recovery_codes = replace_recovery_codes(&mut tx, *id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(recovery_codes))
+}
+
+/// Replaces the recovery codes of a user. Requires a token from a sign-in with a second factor.
+#[actix_web::post("/users/{id}/mfa/recovery-codes")]
+#[has_roles(
+    "Role::User",
+    type = "Role",
+    secure = "*id == claims.id() && claims.has_mfa()"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn regenerate_recovery_codes(
+    db: Data<DbPool>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    if !mfa_repository::is_enrolled(&mut tx, *id).await? {
+        return Err(DbError::NotFound.into());
+    }
+    let recovery_codes = replace_recovery_codes(&mut tx, *id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(recovery_codes))
+}
+
+/// Removes the second factor of a user. Users must have signed in with it, so that a stolen
+/// password is not enough; admins can remove it for users who lost it.
+#[actix_web::delete("/users/{id}/mfa")]
+#[has_roles(
+    "Role::User",
+    type = "Role",
+    secure = "*id == claims.id() && claims.has_mfa() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn delete_mfa(
+    db: Data<DbPool>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    mfa_repository::delete_mfa(&mut tx, *id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Generate new recovery codes, storing only their hashes.
+async fn replace_recovery_codes(
+    tx: &mut crate::Tx,
+    user_id: i32,
+) -> Result<RecoveryCodes, AppError> {
+    let recovery_codes = mfa::generate_recovery_codes()?;
+    let code_hashes: Vec<_> = recovery_codes
+        .iter()
+        .map(|code| mfa::hash_recovery_code(code))
+        .collect();
+    mfa_repository::replace_recovery_codes(tx, user_id, &code_hashes).await?;
+    Ok(RecoveryCodes { recovery_codes })
+}
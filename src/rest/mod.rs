@@ -1,8 +1,20 @@
 //! Rest API implementation.
 
 pub mod account_api;
+pub mod api_key_api;
 pub mod client_context;
+pub mod currency_api;
 pub mod health_check;
+pub mod hold_api;
+pub mod key_api;
+pub mod ledger_api;
+pub mod limit_api;
+pub mod member_api;
+pub mod mfa_api;
+pub mod oauth_api;
+pub mod payment_import_api;
+pub mod product_api;
+pub mod role_api;
 pub mod token;
 pub mod transfer_api;
 pub mod user_api;
@@ -0,0 +1,321 @@
+//! Routes for the OAuth2 authorization server and for registering clients with it.
+
+use crate::infra::configuration::{LoginSettings, MfaSettings, OAuthSettings};
+use crate::infra::error::AppError;
+use crate::infra::security::jwt::{JwtContext, Role};
+use crate::infra::security::oauth::{self, Authorization, OAuthError};
+use crate::infra::validation::Validated;
+use crate::model::oauth_model::{
+    Approval, AuthorizationRequest, Consent, ConsentDecision, NewOAuthClient, OAuthTokenRequest,
+};
+use crate::repository::oauth_repository;
+use crate::DbPool;
+use actix_http::{header, StatusCode};
+use actix_web::{
+    web::{self, Data, Form, Json, Path, Query},
+    HttpRequest, HttpResponse, ResponseError,
+};
+use actix_web_grants::proc_macro::has_roles;
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use uuid::Uuid;
+
+/// Configure the service for registering clients.
+pub fn oauth_client_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(register_client)
+        .service(list_clients)
+        .service(revoke_client);
+}
+
+/// Registers a client. The response is the only time the secret of a confidential client is
+/// shown.
+#[actix_web::post("/oauth/clients")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn register_client(
+    db: Data<DbPool>,
+    new_client: Json<Validated<NewOAuthClient>>,
+) -> Result<HttpResponse, AppError> {
+    let client = oauth::register_client(db.get_ref(), &new_client).await?;
+    Ok(HttpResponse::Created().json(client))
+}
+
+/// Lists all clients, including revoked ones.
+#[actix_web::get("/oauth/clients")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn list_clients(db: Data<DbPool>) -> Result<HttpResponse, AppError> {
+    let clients = oauth_repository::fetch_clients(db.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(clients))
+}
+
+/// Revokes a client, so that it can no longer get tokens. Tokens it already got keep working
+/// until they expire.
+#[actix_web::delete("/oauth/clients/{id}")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn revoke_client(db: Data<DbPool>, id: Path<i32>) -> Result<HttpResponse, AppError> {
+    oauth_repository::revoke_client(db.get_ref(), *id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Publishes where the endpoints of the server are and what they support.
+#[actix_web::get("/.well-known/openid-configuration")]
+#[tracing::instrument(skip_all)]
+pub async fn openid_configuration(
+    db: Data<DbPool>,
+    settings: Data<OAuthSettings>,
+) -> Result<HttpResponse, AppError> {
+    let metadata = oauth::provider_metadata(db.get_ref(), &settings).await?;
+    Ok(HttpResponse::Ok().json(metadata))
+}
+
+/// Shows the consent page, where users sign in and decide whether to let a client act for
+/// them.
+#[actix_web::get("/oauth/authorize")]
+#[tracing::instrument(skip_all, fields(client_id = %request.client_id))]
+pub async fn authorize(
+    db: Data<DbPool>,
+    request: Query<AuthorizationRequest>,
+) -> Result<HttpResponse, AppError> {
+    let authorization = match check_request(db.get_ref(), &request).await? {
+        Ok(authorization) => authorization,
+        Err(response) => return Ok(response),
+    };
+    Ok(consent_page(
+        StatusCode::OK,
+        &authorization,
+        &request,
+        None,
+        None,
+    ))
+}
+
+/// Handles the consent page, sending the user back to the client with a code once they have
+/// signed in and approved it.
+#[actix_web::post("/oauth/authorize")]
+#[tracing::instrument(skip_all, fields(client_id = %consent.request.client_id))]
+pub async fn consent(
+    db: Data<DbPool>,
+    settings: Data<OAuthSettings>,
+    mfa: Data<MfaSettings>,
+    login: Data<LoginSettings>,
+    req: HttpRequest,
+    consent: Form<Consent>,
+) -> Result<HttpResponse, AppError> {
+    let request = &consent.request;
+    let authorization = match check_request(db.get_ref(), request).await? {
+        Ok(authorization) => authorization,
+        Err(response) => return Ok(response),
+    };
+    if consent.decision == ConsentDecision::Deny {
+        return error_redirect(
+            &authorization.redirect_uri,
+            request,
+            &OAuthError::AccessDenied,
+        );
+    }
+
+    let address = req.peer_addr().map(|addr| addr.ip());
+    match oauth::approve(
+        &db,
+        &settings,
+        &mfa,
+        &login,
+        &authorization,
+        &consent,
+        address,
+    )
+    .await
+    {
+        Ok(Approval::Code(code)) => {
+            let mut params = vec![("code", code.as_str())];
+            params.extend(request.state.as_deref().map(|state| ("state", state)));
+            redirect(&oauth::redirect_uri(&authorization.redirect_uri, &params)?)
+        }
+        Ok(Approval::MfaRequired(challenge)) => Ok(consent_page(
+            StatusCode::OK,
+            &authorization,
+            request,
+            Some(challenge.mfa_token),
+            None,
+        )),
+        Err(e) if e.status_code().is_server_error() => Err(e),
+        Err(e) => {
+            let message = match &e {
+                AppError::AuthenticationError if consent.mfa_token.is_some() => {
+                    "The code is wrong or the sign-in has expired".to_string()
+                }
+                AppError::AuthenticationError => "Wrong username or password".to_string(),
+                e => e.to_string(),
+            };
+            Ok(consent_page(
+                e.status_code(),
+                &authorization,
+                request,
+                consent.mfa_token,
+                Some(&message),
+            ))
+        }
+    }
+}
+
+/// Issues tokens to clients, with the client credentials or authorization code grant. Clients
+/// authenticate with Basic authentication or with `client_id` and `client_secret` in the body.
+#[actix_web::post("/oauth/token")]
+#[tracing::instrument(skip_all, fields(grant_type = %request.grant_type))]
+pub async fn token(
+    db: Data<DbPool>,
+    jwt: Data<JwtContext>,
+    credentials: Option<BasicAuth>,
+    request: Form<OAuthTokenRequest>,
+) -> Result<HttpResponse, OAuthError> {
+    let basic_auth = credentials
+        .as_ref()
+        .map(|credentials| (credentials.user_id(), credentials.password()));
+    let tokens = oauth::request_token(db.get_ref(), &jwt, &request, basic_auth).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(tokens))
+}
+
+/// Check an authorization request. Problems with the client or the redirect URI are shown to
+/// the user, and other problems are sent to the client.
+async fn check_request(
+    db: &DbPool,
+    request: &AuthorizationRequest,
+) -> Result<Result<Authorization, HttpResponse>, AppError> {
+    let (client, redirect_uri) = match oauth::check_client(db, request).await {
+        Ok(found) => found,
+        Err(OAuthError::ServerError(e)) => return Err(e),
+        Err(e) => return Ok(Err(error_page(&e))),
+    };
+    match Authorization::new(client, redirect_uri.clone(), request) {
+        Ok(authorization) => Ok(Ok(authorization)),
+        Err(e) => Ok(Err(error_redirect(&redirect_uri, request, &e)?)),
+    }
+}
+
+/// Send the user back to the client with an error.
+fn error_redirect(
+    redirect_uri: &str,
+    request: &AuthorizationRequest,
+    error: &OAuthError,
+) -> Result<HttpResponse, AppError> {
+    let description = error.to_string();
+    let mut params = vec![
+        ("error", error.code()),
+        ("error_description", description.as_str()),
+    ];
+    params.extend(request.state.as_deref().map(|state| ("state", state)));
+    redirect(&oauth::redirect_uri(redirect_uri, &params)?)
+}
+
+fn redirect(location: &str) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, location))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .finish())
+}
+
+/// Render a page for an authorization request that cannot be sent back to the client.
+fn error_page(error: &OAuthError) -> HttpResponse {
+    html(
+        StatusCode::BAD_REQUEST,
+        "Authorization failed",
+        &format!("<p>{}</p>", escape_html(&error.to_string())),
+    )
+}
+
+/// Render the consent page, asking for the password, or for the code from the authenticator
+/// app once `mfa_token` is known.
+fn consent_page(
+    status: StatusCode,
+    authorization: &Authorization,
+    request: &AuthorizationRequest,
+    mfa_token: Option<Uuid>,
+    error: Option<&str>,
+) -> HttpResponse {
+    let mut body = format!(
+        "<p>{} wants to act for you with these permissions:</p>\n<ul>\n",
+        escape_html(&authorization.client.name)
+    );
+    for scope in &authorization.scopes {
+        body += &format!("<li>{}</li>\n", escape_html(scope));
+    }
+    body += "</ul>\n";
+    if let Some(error) = error {
+        body += &format!("<p role=\"alert\">{}</p>\n", escape_html(error));
+    }
+
+    body += "<form method=\"post\" action=\"/oauth/authorize\">\n";
+    let hidden = [
+        ("response_type", request.response_type.as_deref()),
+        ("client_id", Some(request.client_id.as_str())),
+        ("redirect_uri", request.redirect_uri.as_deref()),
+        ("scope", request.scope.as_deref()),
+        ("state", request.state.as_deref()),
+        ("code_challenge", request.code_challenge.as_deref()),
+        (
+            "code_challenge_method",
+            request.code_challenge_method.as_deref(),
+        ),
+    ];
+    for (name, value) in hidden {
+        if let Some(value) = value {
+            body += &format!(
+                "<input type=\"hidden\" name=\"{}\" value=\"{}\">\n",
+                name,
+                escape_html(value)
+            );
+        }
+    }
+    match mfa_token {
+        Some(mfa_token) => {
+            body += &format!(
+                "<input type=\"hidden\" name=\"mfa_token\" value=\"{}\">\n\
+                 <label>Code from your authenticator app \
+                 <input name=\"code\" autocomplete=\"one-time-code\" required></label>\n",
+                mfa_token
+            );
+        }
+        None => {
+            body += "<label>Username <input name=\"username\" autocomplete=\"username\" \
+                     required></label>\n\
+                     <label>Password <input type=\"password\" name=\"password\" \
+                     autocomplete=\"current-password\" required></label>\n";
+        }
+    }
+    body += "<button type=\"submit\" name=\"decision\" value=\"approve\">Allow</button>\n\
+             <button type=\"submit\" name=\"decision\" value=\"deny\" formnovalidate>Deny\
+             </button>\n</form>";
+
+    let title = format!("Authorize {}", authorization.client.name);
+    html(status, &title, &body)
+}
+
+/// Wrap a page body, and keep the page out of frames and caches.
+fn html(status: StatusCode, title: &str, body: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("text/html; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .insert_header((header::X_FRAME_OPTIONS, "DENY"))
+        .insert_header((
+            header::CONTENT_SECURITY_POLICY,
+            "default-src 'none'; form-action 'self'; frame-ancestors 'none'",
+        ))
+        .body(format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             </head>\n<body>\n<h1>{}</h1>\n{}\n</body>\n</html>\n",
+            escape_html(title),
+            escape_html(title),
+            body
+        ))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
@@ -0,0 +1,222 @@
+//! An API for importing ISO 20022 `pain.001` payment files.
+//!
+//! Uploaded files are validated and stored right away, and their payments are executed
+//! by [`crate::worker::payment_import`]. The state of each payment can be polled, either
+//! as JSON or as a `pain.002` payment status report.
+
+use crate::{
+    infra::{
+        configuration::MfaSettings,
+        error::{AppError, DbError, ServiceError},
+        middleware::Idempotency,
+    },
+    model::{
+        account_model::Account,
+        member_model::AccountAccess,
+        money::Currency,
+        payment_import_model::{
+            reason, ImportedPayment, PaymentFile, PaymentImportReport, PaymentInstruction,
+        },
+    },
+    repository::{
+        account_repository, currency_repository, member_repository, payment_import_repository,
+    },
+    rest::transfer_api::ensure_mfa,
+    security::jwt::{Claims, Role},
+    AppResult, DbPool, Tx,
+};
+use actix_http::StatusCode;
+use actix_web::{web, HttpResponse};
+use actix_web_grants::proc_macro::has_permissions;
+use chrono::Utc;
+use std::collections::{hash_map::Entry, HashMap};
+
+/// Configure the payment import service.
+pub fn payment_import_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_payment_import)
+        .service(get_payment_import)
+        .service(get_payment_status_report);
+}
+
+/// The accounts the caller can take payments from, looked up so far, with their currency.
+type DebtorAccounts = HashMap<i32, Option<(Account, Currency)>>;
+
+/// Checks that a payment can be executed by the caller, or rejects it with a reason.
+async fn validate_payment(
+    tx: &mut Tx,
+    user_id: i32,
+    debtors: &mut DebtorAccounts,
+    position: i32,
+    instruction: PaymentInstruction,
+) -> AppResult<ImportedPayment> {
+    let debtor = match instruction.debtor_account.parse::<i32>() {
+        Ok(id) => {
+            if let Entry::Vacant(entry) = debtors.entry(id) {
+                let debtor = match member_repository::fetch_role(tx, id, user_id).await {
+                    Ok(role) if role.allows(AccountAccess::Transact) => {
+                        let account = account_repository::fetch_account(tx, user_id, id).await?;
+                        let currency =
+                            currency_repository::fetch_currency(tx, account.currency()).await?;
+                        Some((account, currency))
+                    }
+                    Ok(_) | Err(DbError::NotFound) => None,
+                    Err(e) => return Err(e.into()),
+                };
+                entry.insert(debtor);
+            }
+            debtors[&id].as_ref()
+        }
+        Err(_) => None,
+    };
+    let (account, currency) = match debtor {
+        Some(debtor) => debtor,
+        None => {
+            let message = format!(
+                "Debtor account {} is not an account you can pay from",
+                instruction.debtor_account
+            );
+            return Ok(ImportedPayment::rejected(
+                position,
+                instruction,
+                reason::INVALID_ACCOUNT,
+                message,
+            ));
+        }
+    };
+    let to_account = match instruction.creditor_account.parse::<i32>() {
+        Ok(id) => id,
+        Err(_) => {
+            let message = format!(
+                "Creditor account {} is not a known account",
+                instruction.creditor_account
+            );
+            return Ok(ImportedPayment::rejected(
+                position,
+                instruction,
+                reason::INVALID_ACCOUNT,
+                message,
+            ));
+        }
+    };
+    if instruction.currency != currency.code.as_str() {
+        let message = format!(
+            "Amount is in {} but account {} is in {}",
+            instruction.currency,
+            account.id(),
+            currency.code
+        );
+        return Ok(ImportedPayment::rejected(
+            position,
+            instruction,
+            reason::INVALID_CURRENCY,
+            message,
+        ));
+    }
+    match currency.to_minor(instruction.amount) {
+        Ok(amount) => Ok(ImportedPayment::pending(
+            position,
+            instruction,
+            account.id(),
+            to_account,
+            amount.minor_units(),
+        )),
+        Err(e) => Ok(ImportedPayment::rejected(
+            position,
+            instruction,
+            reason::INVALID_AMOUNT,
+            e.to_string(),
+        )),
+    }
+}
+
+#[actix_web::post("/users/{user_id}/payment-imports", wrap = "Idempotency")]
+#[has_permissions(
+    "transfers:create",
+    secure = "*user_id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn create_payment_import(
+    db: web::Data<DbPool>,
+    mfa: web::Data<MfaSettings>,
+    claims: web::ReqData<Claims>,
+    user_id: web::Path<i32>,
+    body: String,
+) -> AppResult<HttpResponse> {
+    let file = PaymentFile::parse(&body).map_err(ServiceError::from)?;
+    let user_id = *user_id;
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    if payment_import_repository::import_exists(&mut tx, user_id, &file.message_id).await? {
+        return Err(AppError::CustomError(
+            format!("File {} has already been imported", file.message_id),
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    let mut debtors = DebtorAccounts::new();
+    let mut payments = Vec::with_capacity(file.payments.len());
+    for (position, instruction) in file.payments.into_iter().enumerate() {
+        let payment =
+            validate_payment(&mut tx, user_id, &mut debtors, position as i32, instruction).await?;
+        payments.push(payment);
+    }
+
+    // The payments are executed later, so the second factor is checked for all of them now
+    let amounts: Vec<_> = payments
+        .iter()
+        .filter_map(|payment| {
+            let (_, currency) = debtors.get(&payment.from_account?)?.as_ref()?;
+            Some((currency.code.clone(), payment.amount?))
+        })
+        .collect();
+    ensure_mfa(&claims, &mfa, &amounts)?;
+
+    let import =
+        payment_import_repository::insert_import(&mut tx, user_id, &file.message_id, &payments)
+            .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Accepted().json(PaymentImportReport { import, payments }))
+}
+
+/// Fetch an import and its payments.
+async fn fetch_report(db: &DbPool, user_id: i32, import_id: i32) -> AppResult<PaymentImportReport> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let import = payment_import_repository::fetch_import(&mut tx, user_id, import_id).await?;
+    let payments = payment_import_repository::fetch_payments(&mut tx, import_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(PaymentImportReport { import, payments })
+}
+
+#[actix_web::get("/users/{user_id}/payment-imports/{import_id}")]
+#[has_permissions(
+    "transfers:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_payment_import(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let (user_id, import_id) = *path_params;
+    let report = fetch_report(&db, user_id, import_id).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[actix_web::get("/users/{user_id}/payment-imports/{import_id}/status-report")]
+#[has_permissions(
+    "transfers:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_payment_status_report(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let (user_id, import_id) = *path_params;
+    let report = fetch_report(&db, user_id, import_id).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml")
+        .body(report.to_pain002(Utc::now())))
+}
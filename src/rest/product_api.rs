@@ -0,0 +1,124 @@
+//! An API for managing account products and viewing the interest accrued by accounts.
+
+use crate::{
+    infra::{
+        error::{DbError, ServiceError},
+        validation::Validated,
+    },
+    model::product_model::{
+        AccrualHistory, AccrualQuery, NewAccountProduct, ProductAssignment, RATE_DECIMALS,
+    },
+    repository::product_repository,
+    security::jwt::Role,
+    AppResult, DbPool,
+};
+use actix_web::{web, HttpResponse};
+use actix_web_grants::proc_macro::has_roles;
+
+/// Configure the product service.
+pub fn product_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_products)
+        .service(create_product)
+        .service(update_product)
+        .service(put_account_product)
+        .service(get_accruals);
+}
+
+/// Checks that an interest rate is not negative and fits the precision of accruals.
+fn check_interest_rate(product: &NewAccountProduct) -> AppResult<()> {
+    if product.interest_rate.is_sign_negative() {
+        return Err(ServiceError::ValidationError(
+            "Interest rate must not be negative".to_string(),
+        )
+        .into());
+    }
+    if product.interest_rate.normalize().scale() > RATE_DECIMALS {
+        return Err(ServiceError::ValidationError(format!(
+            "Interest rate must have at most {} decimals",
+            RATE_DECIMALS
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+#[actix_web::get("/products")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn list_products(db: web::Data<DbPool>) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let products = product_repository::fetch_products(&mut tx).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(products))
+}
+
+#[actix_web::post("/products")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn create_product(
+    db: web::Data<DbPool>,
+    product: web::Json<Validated<NewAccountProduct>>,
+) -> AppResult<HttpResponse> {
+    check_interest_rate(&product)?;
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let product = product_repository::insert_product(&mut tx, &product).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Created().json(product))
+}
+
+#[actix_web::put("/products/{product_id}")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn update_product(
+    db: web::Data<DbPool>,
+    product_id: web::Path<i32>,
+    product: web::Json<Validated<NewAccountProduct>>,
+) -> AppResult<HttpResponse> {
+    check_interest_rate(&product)?;
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let product = product_repository::update_product(&mut tx, *product_id, &product).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(product))
+}
+
+#[actix_web::put("/accounts/{account_id}/product")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn put_account_product(
+    db: web::Data<DbPool>,
+    account_id: web::Path<i32>,
+    assignment: web::Json<ProductAssignment>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    if let Some(product_id) = assignment.product_id {
+        match product_repository::fetch_product(&mut tx, product_id).await {
+            Err(DbError::NotFound) => {
+                return Err(ServiceError::ValidationError(format!(
+                    "Unknown product {}",
+                    product_id
+                ))
+                .into())
+            }
+            result => result?,
+        };
+    }
+    let account =
+        product_repository::assign_product(&mut tx, *account_id, assignment.product_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(account))
+}
+
+#[actix_web::get("/accounts/{account_id}/accruals")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn get_accruals(
+    db: web::Data<DbPool>,
+    account_id: web::Path<i32>,
+    query: web::Query<AccrualQuery>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let accruals = product_repository::fetch_accruals(&mut tx, *account_id, &query).await?;
+    let postings = product_repository::fetch_postings(&mut tx, *account_id, &query).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(AccrualHistory { accruals, postings }))
+}
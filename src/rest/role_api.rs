@@ -0,0 +1,156 @@
+//! An API for administering roles, permissions and the roles of users.
+//!
+//! Roles and the hierarchy between them are fixed, but the permissions each role grants are
+//! data. The permissions of a user end up in their [`Claims`], where handlers can check them
+//! with [`has_permissions`].
+
+use crate::{
+    infra::{
+        error::{DbError, ServiceError},
+        validation::Validated,
+    },
+    model::permission_model::NewPermission,
+    repository::{role_repository, user_repository},
+    security::jwt::{Claims, Role},
+    AppResult, DbPool,
+};
+use actix_web::{web, HttpResponse};
+use actix_web_grants::proc_macro::has_permissions;
+
+/// Configure the role service.
+pub fn role_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_roles)
+        .service(list_permissions)
+        .service(post_permission)
+        .service(delete_permission)
+        .service(grant_permission)
+        .service(revoke_permission)
+        .service(list_user_roles)
+        .service(grant_user_role)
+        .service(revoke_user_role);
+}
+
+#[actix_web::get("/roles")]
+#[has_permissions("roles:manage")]
+#[tracing::instrument(skip_all)]
+pub async fn list_roles(db: web::Data<DbPool>) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let roles = role_repository::fetch_roles(&mut tx).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+#[actix_web::get("/permissions")]
+#[has_permissions("roles:manage")]
+#[tracing::instrument(skip_all)]
+pub async fn list_permissions(db: web::Data<DbPool>) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let permissions = role_repository::fetch_permissions(&mut tx).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(permissions))
+}
+
+#[actix_web::post("/permissions")]
+#[has_permissions("roles:manage")]
+#[tracing::instrument(skip_all)]
+pub async fn post_permission(
+    db: web::Data<DbPool>,
+    new_permission: web::Json<Validated<NewPermission>>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let permission = role_repository::insert_permission(&mut tx, &new_permission).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Created().json(permission))
+}
+
+#[actix_web::delete("/permissions/{name}")]
+#[has_permissions("roles:manage")]
+#[tracing::instrument(skip_all)]
+pub async fn delete_permission(
+    db: web::Data<DbPool>,
+    name: web::Path<String>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    role_repository::delete_permission(&mut tx, &name).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[actix_web::put("/roles/{role}/permissions/{name}")]
+#[has_permissions("roles:manage")]
+#[tracing::instrument(skip_all)]
+pub async fn grant_permission(
+    db: web::Data<DbPool>,
+    path_params: web::Path<(Role, String)>,
+) -> AppResult<HttpResponse> {
+    let (role, name) = path_params.into_inner();
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    role_repository::grant_permission(&mut tx, role, &name).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[actix_web::delete("/roles/{role}/permissions/{name}")]
+#[has_permissions("roles:manage")]
+#[tracing::instrument(skip_all)]
+pub async fn revoke_permission(
+    db: web::Data<DbPool>,
+    path_params: web::Path<(Role, String)>,
+) -> AppResult<HttpResponse> {
+    let (role, name) = path_params.into_inner();
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    role_repository::revoke_permission(&mut tx, role, &name).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[actix_web::get("/users/{user_id}/roles")]
+#[has_permissions("roles:manage")]
+#[tracing::instrument(skip_all)]
+pub async fn list_user_roles(
+    db: web::Data<DbPool>,
+    user_id: web::Path<i32>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    user_repository::fetch_user_by_id(&mut tx, &user_id).await?;
+    let roles = user_repository::fetch_user_roles(&mut tx, *user_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+#[actix_web::put("/users/{user_id}/roles/{role}")]
+#[has_permissions("roles:manage")]
+#[tracing::instrument(skip_all)]
+pub async fn grant_user_role(
+    db: web::Data<DbPool>,
+    path_params: web::Path<(i32, Role)>,
+) -> AppResult<HttpResponse> {
+    let (user_id, role) = path_params.into_inner();
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    user_repository::fetch_user_by_id(&mut tx, &user_id).await?;
+    user_repository::grant_role(&mut tx, user_id, role).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[actix_web::delete("/users/{user_id}/roles/{role}")]
+#[has_permissions("roles:manage")]
+#[tracing::instrument(skip_all)]
+pub async fn revoke_user_role(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, Role)>,
+) -> AppResult<HttpResponse> {
+    let (user_id, role) = path_params.into_inner();
+    // Admins could otherwise lock everyone out by revoking their own role
+    if user_id == claims.id() && role == Role::Admin {
+        return Err(ServiceError::ValidationError(
+            "Admins cannot revoke their own admin role".to_string(),
+        )
+        .into());
+    }
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    user_repository::revoke_role(&mut tx, user_id, role).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
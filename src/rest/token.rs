@@ -1,28 +1,102 @@
 //! A service that can receive user information and validate it.
 
 use crate::{
-    infra::{error::AppError, security::jwt::Claims},
-    security::jwt::{decode_jwt, encode_jwt},
+    infra::{
+        configuration::{LoginSettings, MfaSettings},
+        error::{AppError, ServiceError},
+        security::{
+            jwt::{Claims, JwtContext},
+            session,
+        },
+    },
+    model::{
+        mfa_model::MfaVerification,
+        session_model::{RefreshRequest, SignIn, TokenResponse},
+    },
+    security::jwt::decode_jwt,
     AppResult, DbPool,
 };
-use actix_web::{web::Data, HttpResponse};
+use actix_web::{
+    web::{Data, Json},
+    HttpRequest, HttpResponse,
+};
 use actix_web_httpauth::extractors::{basic::BasicAuth, bearer::BearerAuth};
 use http::StatusCode;
+use std::net::SocketAddr;
 
+/// Starts a session for a user authenticating themselves. Users with a second factor get a
+/// challenge to complete with `POST /token/mfa` instead of tokens.
 #[actix_web::post("/token")]
 #[tracing::instrument(skip_all, fields(username = credentials.user_id()))]
-pub async fn request_token(pool: Data<DbPool>, credentials: BasicAuth) -> AppResult<HttpResponse> {
+pub async fn request_token(
+    pool: Data<DbPool>,
+    jwt: Data<JwtContext>,
+    mfa: Data<MfaSettings>,
+    login: Data<LoginSettings>,
+    req: HttpRequest,
+    credentials: BasicAuth,
+) -> AppResult<HttpResponse> {
     // Load user information
     let username = credentials.user_id();
     tracing::debug!("Token requested by `{}`", credentials.user_id());
     let password = credentials
         .password()
         .ok_or(AppError::AuthenticationError)?;
+    let address = req.peer_addr().map(|addr| addr.ip());
 
-    let token = encode_jwt(pool.get_ref(), username, password).await?;
-    tracing::debug!("Sending token to `{}`", credentials.user_id());
+    let sign_in =
+        session::start_session(&pool, &jwt, &mfa, &login, username, password, address).await?;
+    match sign_in {
+        SignIn::Tokens(tokens) => {
+            tracing::debug!("Sending token to `{}`", credentials.user_id());
+            Ok(HttpResponse::Created().json(tokens))
+        }
+        SignIn::MfaRequired(challenge) => {
+            tracing::debug!("Sending mfa challenge to `{}`", credentials.user_id());
+            Ok(HttpResponse::Accepted().json(challenge))
+        }
+    }
+}
 
-    Ok(HttpResponse::Created().body(token))
+/// Completes a sign-in with a code from an authenticator app or a recovery code.
+#[actix_web::post("/token/mfa")]
+#[tracing::instrument(skip_all)]
+pub async fn complete_mfa(
+    pool: Data<DbPool>,
+    jwt: Data<JwtContext>,
+    mfa: Data<MfaSettings>,
+    verification: Json<MfaVerification>,
+) -> AppResult<HttpResponse> {
+    let tokens = session::complete_mfa(pool.get_ref(), &jwt, &mfa, &verification).await?;
+    Ok(HttpResponse::Created().json(tokens))
+}
+
+/// Exchanges a refresh token for new tokens. Each refresh token can only be used once.
+#[actix_web::post("/token/refresh")]
+#[tracing::instrument(skip_all)]
+pub async fn refresh_token(
+    pool: Data<DbPool>,
+    jwt: Data<JwtContext>,
+    request: Json<RefreshRequest>,
+) -> AppResult<HttpResponse> {
+    let tokens = session::refresh_session(pool.get_ref(), &jwt, &request.refresh_token).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+/// Signs out by revoking the session of the access token.
+#[actix_web::post("/logout")]
+#[tracing::instrument(skip_all)]
+pub async fn logout(
+    pool: Data<DbPool>,
+    jwt: Data<JwtContext>,
+    auth: BearerAuth,
+) -> AppResult<HttpResponse> {
+    let claims = decode_jwt(&jwt, auth.token())?;
+    let session_id = claims.session_id().ok_or_else(|| {
+        ServiceError::ValidationError("The token does not belong to a session".to_string())
+    })?;
+    session::end_session(pool.get_ref(), session_id).await?;
+    Ok(HttpResponse::NoContent().finish())
 }
 
 /// A basic authentication header value.
@@ -79,28 +153,51 @@ where
     }
 }
 
-/// Creates a JWT for a user authenticating themselves.
+/// Starts a session for a user authenticating themselves.
 #[tracing::instrument(skip_all, fields(username = credentials.user_id))]
 pub async fn request_token2(
     axum::Extension(pool): axum::Extension<DbPool>,
+    axum::Extension(jwt): axum::Extension<JwtContext>,
+    axum::Extension(mfa): axum::Extension<MfaSettings>,
+    axum::Extension(login): axum::Extension<LoginSettings>,
+    axum::extract::ConnectInfo(addr): axum::extract::ConnectInfo<SocketAddr>,
     credentials: BasicAuth2,
-) -> axum::response::Result<String> {
+) -> axum::response::Result<axum::Json<TokenResponse>> {
     // Load user information
     let username = credentials.user_id;
     tracing::debug!("Token requested by `{}`", username);
     let password = credentials.password;
 
-    let token = encode_jwt(&pool, &username, &password).await?;
+    let sign_in = session::start_session(
+        &pool,
+        &jwt,
+        &mfa,
+        &login,
+        &username,
+        &password,
+        Some(addr.ip()),
+    )
+    .await?;
+    let tokens = match sign_in {
+        SignIn::Tokens(tokens) => tokens,
+        SignIn::MfaRequired(_) => {
+            return Err(AppError::CustomError(
+                "Multi-factor authentication required, use POST /token".to_string(),
+                StatusCode::UNAUTHORIZED,
+            )
+            .into())
+        }
+    };
     tracing::debug!("Sending token to `{}`", username);
 
-    Ok(token)
+    Ok(axum::Json(tokens))
 }
 
 #[actix_web::get("/verify")]
-pub async fn verify_token(auth: BearerAuth) -> AppResult<HttpResponse> {
+pub async fn verify_token(jwt: Data<JwtContext>, auth: BearerAuth) -> AppResult<HttpResponse> {
     tracing::debug!("Verifying jwt");
     let token = auth.token();
-    let claims = decode_jwt(token)?;
+    let claims = decode_jwt(&jwt, token)?;
     tracing::debug!("Got claims {:?}", claims);
     Ok(HttpResponse::Ok().json(claims))
 }
@@ -139,10 +236,13 @@ where
 
 /// Verifies a JWT.
 #[tracing::instrument(skip_all)]
-pub async fn verify_token2(auth: BearerAuth2) -> axum::response::Result<axum::Json<Claims>> {
+pub async fn verify_token2(
+    axum::Extension(jwt): axum::Extension<JwtContext>,
+    auth: BearerAuth2,
+) -> axum::response::Result<axum::Json<Claims>> {
     tracing::debug!("Verifying jwt");
     let token = auth.token;
-    let claims = decode_jwt(&token)?;
+    let claims = decode_jwt(&jwt, &token)?;
     tracing::debug!("Got claims {:?}", claims);
     Ok(axum::Json(claims))
 }
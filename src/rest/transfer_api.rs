@@ -1,61 +1,652 @@
 //! An API for transferring money between accounts.
 
 use crate::{
-    infra::error::{DbError, ServiceError},
-    model::transfer_model::NewTransfer,
-    repository::{account_repository, transfer_repository},
+    infra::{
+        configuration::MfaSettings,
+        error::{AppError, DbError, ServiceError},
+        middleware::Idempotency,
+        validation::Validated,
+    },
+    model::{
+        member_model::AccountAccess,
+        money::{Amount, CurrencyCode},
+        pagination::CursorPage,
+        scheduled_transfer_model::{
+            NewScheduledTransfer, ScheduledTransfer, ScheduledTransferQuery, ScheduledTransferRun,
+            ScheduledTransferStatus, ScheduledTransferUpdate,
+        },
+        transfer_batch_model::{
+            NewTransferBatch, TransferBatchItem, TransferBatchItemStatus, TransferBatchMode,
+            TransferBatchReport, TransferBatchStatus,
+        },
+        transfer_model::{NewTransfer, Transfer, TransferQuery},
+    },
+    repository::{
+        account_repository, currency_repository, ledger_repository, scheduled_transfer_repository,
+        transfer_batch_repository, transfer_repository,
+    },
+    rest::account_api::{ensure_access, ensure_open, ensure_within_limits},
     security::jwt::{Claims, Role},
-    AppResult, DbPool,
+    AppResult, DbPool, Tx,
 };
+use actix_http::StatusCode;
 use actix_web::{web, HttpResponse};
-use actix_web_grants::proc_macro::has_roles;
+use actix_web_grants::proc_macro::{has_permissions, has_roles};
+use rust_decimal::Decimal;
+use std::collections::{hash_map::Entry, HashMap};
 
 /// Configure the transfer service.
 pub fn transfer_config(cfg: &mut web::ServiceConfig) {
-    cfg.service(create_transfer);
+    cfg.service(create_transfer)
+        .service(list_transfers)
+        .service(get_transfer)
+        .service(reverse_transfer)
+        .service(create_transfer_batch)
+        .service(get_transfer_batch)
+        .service(create_scheduled_transfer)
+        .service(list_scheduled_transfers)
+        .service(get_scheduled_transfer)
+        .service(patch_scheduled_transfer)
+        .service(cancel_scheduled_transfer)
+        .service(list_scheduled_transfer_runs);
 }
 
-#[actix_web::post("/users/{user_id}/transfers")]
-#[has_roles(
-    "Role::User",
-    type = "Role",
+/// The user whose transfers may be seen, or `None` for admins, who can see all transfers.
+fn visible_to(claims: &Claims, user_id: i32) -> Option<i32> {
+    if claims.has_role(&Role::Admin) {
+        None
+    } else {
+        Some(user_id)
+    }
+}
+
+/// Fails if the scheduled transfer will not run again.
+fn ensure_active(scheduled_transfer: &ScheduledTransfer) -> Result<(), ServiceError> {
+    if scheduled_transfer.status != ScheduledTransferStatus::Active {
+        return Err(ServiceError::ValidationError(format!(
+            "Scheduled transfer {} is {:?}",
+            scheduled_transfer.id, scheduled_transfer.status
+        )));
+    }
+    Ok(())
+}
+
+/// Fails if the amounts, each in minor units of its currency, are above the threshold for
+/// transfers that need a second factor, and the token is not from a sign-in with one. Each
+/// amount counts as its share of the threshold of its currency, and amounts in currencies
+/// without a threshold always need a second factor.
+pub(crate) fn ensure_mfa(
+    claims: &Claims,
+    settings: &MfaSettings,
+    amounts: &[(CurrencyCode, i64)],
+) -> AppResult<()> {
+    if claims.has_mfa() {
+        return Ok(());
+    }
+    let mut share = Some(Decimal::ZERO);
+    for (currency, amount) in amounts {
+        let threshold = settings
+            .transfer_threshold(currency.as_str())
+            .map(Decimal::from);
+        share = share.zip(threshold).and_then(|(share, threshold)| {
+            share.checked_add(Decimal::from(*amount).checked_div(threshold)?)
+        });
+    }
+    if share.is_none_or(|share| share > Decimal::ONE) {
+        return Err(AppError::CustomError(
+            "Transfers above the threshold require multi-factor authentication".to_string(),
+            StatusCode::FORBIDDEN,
+        ));
+    }
+    Ok(())
+}
+
+/// Pairs amounts with the currency of the account they are taken from. Accounts that do not
+/// exist are left out, as the transfers will fail anyway.
+async fn in_currencies(
+    tx: &mut Tx,
+    amounts: impl IntoIterator<Item = (i32, i64)>,
+) -> AppResult<Vec<(CurrencyCode, i64)>> {
+    let mut currencies = HashMap::new();
+    let mut in_currencies = Vec::new();
+    for (account_id, amount) in amounts {
+        let currency = match currencies.entry(account_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                match account_repository::fetch_currency(tx, account_id).await {
+                    Ok(currency) => entry.insert(Some(currency)),
+                    Err(DbError::NotFound) => entry.insert(None),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+        if let Some(currency) = currency {
+            in_currencies.push((currency.clone(), amount));
+        }
+    }
+    Ok(in_currencies)
+}
+
+/// Moves money from an account `user_id` can transact on to any other open account,
+/// converting it if the accounts have different currencies.
+pub(crate) async fn execute_transfer(
+    tx: &mut Tx,
+    user_id: i32,
+    new_transfer: NewTransfer,
+) -> AppResult<Transfer> {
+    let from = new_transfer.from_account;
+    let to = new_transfer.to_account;
+    let amount = new_transfer.amount;
+
+    ensure_access(tx, from, user_id, AccountAccess::Transact).await?;
+    if from == to {
+        return Err(ServiceError::ValidationError(format!(
+            "Cannot transfer from account {} to itself",
+            from
+        ))
+        .into());
+    }
+
+    // Lock both accounts so concurrent transfers see each other's balances
+    let accounts = account_repository::lock_accounts(tx, &[from, to]).await?;
+    let old_account = accounts
+        .iter()
+        .find(|a| a.id() == from)
+        .ok_or(DbError::NotFound)?;
+    let new_account = accounts
+        .iter()
+        .find(|a| a.id() == to)
+        .ok_or(DbError::NotFound)?;
+    ensure_open(old_account)?;
+    ensure_open(new_account)?;
+
+    ensure_within_limits(tx, old_account, amount).await?;
+
+    // Convert the amount if the accounts have different currencies
+    let from_currency = old_account.currency().clone();
+    let to_currency = new_account.currency().clone();
+    let (to_amount, exchange_rate) = if from_currency == to_currency {
+        (amount, None)
+    } else {
+        let rate = match currency_repository::fetch_fx_rate(tx, &from_currency, &to_currency).await
+        {
+            Err(DbError::NotFound) => {
+                return Err(ServiceError::ValidationError(format!(
+                    "No exchange rate from {} to {}",
+                    from_currency, to_currency
+                ))
+                .into())
+            }
+            result => result?.rate,
+        };
+        let base = currency_repository::fetch_currency(tx, &from_currency).await?;
+        let quote = currency_repository::fetch_currency(tx, &to_currency).await?;
+        let to_amount = base
+            .convert(amount, &quote, rate)
+            .map_err(ServiceError::from)?;
+        (to_amount, Some(rate))
+    };
+
+    // Take from account
+    account_repository::withdraw(tx, from, amount).await?;
+
+    // Give to account
+    account_repository::deposit(tx, to, to_amount).await?;
+
+    // Insert transfer
+    let transfer =
+        transfer_repository::insert_transfer(tx, new_transfer, to_amount, exchange_rate).await?;
+    ledger_repository::record_transfer(tx, &transfer, &from_currency, &to_currency).await?;
+
+    Ok(transfer)
+}
+
+/// Executes a transfer inside a savepoint, so that the transaction can still be used if it fails.
+/// Transient errors abort the transaction and are returned as the outer error,
+/// while other failures are rolled back and returned as the inner error.
+pub(crate) async fn try_execute_transfer(
+    tx: &mut Tx,
+    user_id: i32,
+    new_transfer: NewTransfer,
+) -> AppResult<Result<Transfer, AppError>> {
+    sqlx::query!("SAVEPOINT try_execute_transfer")
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+    match execute_transfer(tx, user_id, new_transfer).await {
+        Ok(transfer) => {
+            sqlx::query!("RELEASE SAVEPOINT try_execute_transfer")
+                .execute(&mut *tx)
+                .await
+                .map_err(DbError::from)?;
+            Ok(Ok(transfer))
+        }
+        Err(e) if e.is_transient() => Err(e),
+        Err(e) => {
+            sqlx::query!("ROLLBACK TO SAVEPOINT try_execute_transfer")
+                .execute(&mut *tx)
+                .await
+                .map_err(DbError::from)?;
+            Ok(Err(e))
+        }
+    }
+}
+
+#[actix_web::post("/users/{user_id}/transfers", wrap = "Idempotency")]
+#[has_permissions(
+    "transfers:create",
     secure = "*user_id == claims.id() || claims.has_role(&Role::Admin)"
 )]
 pub async fn create_transfer(
     db: web::Data<DbPool>,
+    mfa: web::Data<MfaSettings>,
     claims: web::ReqData<Claims>,
     user_id: web::Path<i32>,
     new_transfer: web::Json<NewTransfer>,
 ) -> AppResult<HttpResponse> {
     let mut tx = db.get_ref().begin().await.map_err(DbError::from)?;
+    let amounts = [(new_transfer.from_account, new_transfer.amount.minor_units())];
+    ensure_mfa(&claims, &mfa, &in_currencies(&mut tx, amounts).await?)?;
+    let transfer = execute_transfer(&mut tx, *user_id, new_transfer.into_inner()).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Created().json(transfer))
+}
 
-    let user_id = *user_id;
-    let from = new_transfer.from_account;
-    let to = new_transfer.to_account;
-    let amount = new_transfer.amount;
+#[actix_web::get("/users/{user_id}/transfers")]
+#[has_permissions(
+    "transfers:read",
+    secure = "*user_id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn list_transfers(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    user_id: web::Path<i32>,
+    query: web::Query<Validated<TransferQuery>>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let transfers =
+        transfer_repository::fetch_transfers(&mut tx, visible_to(&claims, *user_id), &query)
+            .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    let page = CursorPage::new(transfers, query.limit, |t: &Transfer| t.id);
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[actix_web::get("/users/{user_id}/transfers/{transfer_id}")]
+#[has_permissions(
+    "transfers:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_transfer(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, transfer_id) = *path_params;
+    let transfer =
+        transfer_repository::fetch_transfer(&mut tx, visible_to(&claims, user_id), transfer_id)
+            .await?;
 
-    // Verify old account
-    let old_account = account_repository::fetch_account(&mut tx, user_id, from).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(transfer))
+}
+
+#[actix_web::post("/transfers/{transfer_id}/reversal", wrap = "Idempotency")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn reverse_transfer(
+    db: web::Data<DbPool>,
+    transfer_id: web::Path<i32>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
 
-    if new_transfer.amount as i64 > old_account.balance() {
+    // Lock the transfer so it cannot be reversed twice concurrently
+    let original = transfer_repository::lock_transfer(&mut tx, *transfer_id).await?;
+    if let Some(reversal_of) = original.reversal_of {
         return Err(ServiceError::ValidationError(format!(
-            "Balance is too low, required {} but had {}",
-            new_transfer.amount,
-            old_account.balance()
+            "Transfer {} reverses transfer {} and cannot be reversed",
+            original.id, reversal_of
         ))
         .into());
     }
+    if let Some(reversed_by) = original.reversed_by {
+        return Err(AppError::CustomError(
+            format!(
+                "Transfer {} has already been reversed by transfer {}",
+                original.id, reversed_by
+            ),
+            StatusCode::CONFLICT,
+        ));
+    }
 
-    // Take from account
-    account_repository::withdraw(&mut tx, from, amount).await?;
+    // Money moves back from the destination of the original transfer
+    let accounts =
+        account_repository::lock_accounts(&mut tx, &[original.from_account, original.to_account])
+            .await?;
+    let source = accounts
+        .iter()
+        .find(|a| a.id() == original.to_account)
+        .ok_or(DbError::NotFound)?;
+    let destination = accounts
+        .iter()
+        .find(|a| a.id() == original.from_account)
+        .ok_or(DbError::NotFound)?;
+    ensure_open(source)?;
+    ensure_open(destination)?;
 
-    // Give to account
-    account_repository::deposit(&mut tx, to, amount).await?;
+    // The money must still be there, so a reversal never overdraws the account
+    let funds = source.balance() - source.held();
+    if original.to_amount > funds {
+        return Err(ServiceError::ValidationError(format!(
+            "Balance of account {} is too low to reverse the transfer, required {} but had {} available",
+            source.id(),
+            original.to_amount,
+            funds.max(0)
+        ))
+        .into());
+    }
 
-    // Insert transfer
-    let transfer = transfer_repository::insert_transfer(&mut tx, new_transfer.into_inner()).await?;
+    // Return exactly what was moved, without converting it again
+    let withdrawn = Amount::new(original.to_amount).map_err(ServiceError::from)?;
+    let deposited = Amount::new(original.amount).map_err(ServiceError::from)?;
+    account_repository::withdraw(&mut tx, source.id(), withdrawn).await?;
+    account_repository::deposit(&mut tx, destination.id(), deposited).await?;
+
+    let reversal = transfer_repository::insert_reversal(&mut tx, &original).await?;
+    ledger_repository::record_transfer(
+        &mut tx,
+        &reversal,
+        source.currency(),
+        destination.currency(),
+    )
+    .await?;
 
     tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Created().json(reversal))
+}
 
-    Ok(HttpResponse::Created().json(transfer))
+#[actix_web::post("/users/{user_id}/scheduled-transfers")]
+#[has_permissions(
+    "transfers:create",
+    secure = "*user_id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn create_scheduled_transfer(
+    db: web::Data<DbPool>,
+    mfa: web::Data<MfaSettings>,
+    claims: web::ReqData<Claims>,
+    user_id: web::Path<i32>,
+    new_transfer: web::Json<NewScheduledTransfer>,
+) -> AppResult<HttpResponse> {
+    if let Some(end_at) = new_transfer.end_at {
+        if end_at < new_transfer.start_at {
+            return Err(ServiceError::ValidationError(
+                "End time cannot be before start time".to_string(),
+            )
+            .into());
+        }
+    }
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+
+    // Both accounts must exist, and the user must be able to take money from the first
+    ensure_access(
+        &mut tx,
+        new_transfer.from_account,
+        *user_id,
+        AccountAccess::Transact,
+    )
+    .await?;
+    let from_account =
+        account_repository::fetch_account(&mut tx, *user_id, new_transfer.from_account).await?;
+    ensure_open(&from_account)?;
+    let amount = new_transfer.amount.minor_units();
+    ensure_mfa(&claims, &mfa, &[(from_account.currency().clone(), amount)])?;
+    let to_accounts =
+        account_repository::lock_accounts(&mut tx, &[new_transfer.to_account]).await?;
+    let to_account = to_accounts.first().ok_or(DbError::NotFound)?;
+    ensure_open(to_account)?;
+
+    let scheduled_transfer =
+        scheduled_transfer_repository::insert_scheduled_transfer(&mut tx, *user_id, &new_transfer)
+            .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Created().json(scheduled_transfer))
+}
+
+#[actix_web::get("/users/{user_id}/scheduled-transfers")]
+#[has_permissions(
+    "transfers:read",
+    secure = "*user_id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn list_scheduled_transfers(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    user_id: web::Path<i32>,
+    query: web::Query<Validated<ScheduledTransferQuery>>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let scheduled_transfers =
+        scheduled_transfer_repository::fetch_scheduled_transfers(&mut tx, *user_id, &query).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    let page = CursorPage::new(scheduled_transfers, query.limit, |t: &ScheduledTransfer| {
+        t.id
+    });
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[actix_web::get("/users/{user_id}/scheduled-transfers/{scheduled_transfer_id}")]
+#[has_permissions(
+    "transfers:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_scheduled_transfer(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, id) = *path_params;
+    let scheduled_transfer =
+        scheduled_transfer_repository::fetch_scheduled_transfer(&mut tx, user_id, id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(scheduled_transfer))
+}
+
+#[actix_web::patch("/users/{user_id}/scheduled-transfers/{scheduled_transfer_id}")]
+#[has_permissions(
+    "transfers:create",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn patch_scheduled_transfer(
+    db: web::Data<DbPool>,
+    mfa: web::Data<MfaSettings>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+    update: web::Json<ScheduledTransferUpdate>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, id) = *path_params;
+
+    let scheduled_transfer =
+        scheduled_transfer_repository::fetch_scheduled_transfer(&mut tx, user_id, id).await?;
+    ensure_active(&scheduled_transfer)?;
+    if let Some(amount) = update.amount {
+        let amounts = [(scheduled_transfer.from_account, amount.minor_units())];
+        ensure_mfa(&claims, &mfa, &in_currencies(&mut tx, amounts).await?)?;
+    }
+    if let Some(end_at) = update.end_at {
+        if end_at < scheduled_transfer.start_at {
+            return Err(ServiceError::ValidationError(
+                "End time cannot be before start time".to_string(),
+            )
+            .into());
+        }
+    }
+
+    let scheduled_transfer =
+        scheduled_transfer_repository::update_scheduled_transfer(&mut tx, id, &update).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(scheduled_transfer))
+}
+
+#[actix_web::delete("/users/{user_id}/scheduled-transfers/{scheduled_transfer_id}")]
+#[has_permissions(
+    "transfers:create",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn cancel_scheduled_transfer(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, id) = *path_params;
+
+    let scheduled_transfer =
+        scheduled_transfer_repository::fetch_scheduled_transfer(&mut tx, user_id, id).await?;
+    ensure_active(&scheduled_transfer)?;
+
+    let scheduled_transfer =
+        scheduled_transfer_repository::cancel_scheduled_transfer(&mut tx, id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(scheduled_transfer))
+}
+
+#[actix_web::get("/users/{user_id}/scheduled-transfers/{scheduled_transfer_id}/runs")]
+#[has_permissions(
+    "transfers:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn list_scheduled_transfer_runs(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+    query: web::Query<Validated<ScheduledTransferQuery>>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, id) = *path_params;
+
+    // Make sure the scheduled transfer belongs to the user
+    scheduled_transfer_repository::fetch_scheduled_transfer(&mut tx, user_id, id).await?;
+
+    let runs = scheduled_transfer_repository::fetch_runs(&mut tx, id, &query).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    let page = CursorPage::new(runs, query.limit, |r: &ScheduledTransferRun| r.id);
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[actix_web::post("/users/{user_id}/transfer-batches", wrap = "Idempotency")]
+#[has_permissions(
+    "transfers:create",
+    secure = "*user_id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn create_transfer_batch(
+    db: web::Data<DbPool>,
+    mfa: web::Data<MfaSettings>,
+    claims: web::ReqData<Claims>,
+    user_id: web::Path<i32>,
+    new_batch: web::Json<Validated<NewTransferBatch>>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let amounts = new_batch
+        .transfers
+        .iter()
+        .map(|t| (t.from_account, t.amount.minor_units()));
+    ensure_mfa(&claims, &mfa, &in_currencies(&mut tx, amounts).await?)?;
+    let user_id = *user_id;
+    let mode = new_batch.mode;
+
+    // Everything before this point is kept if an atomic batch fails
+    sqlx::query!("SAVEPOINT transfer_batch")
+        .execute(&mut tx)
+        .await
+        .map_err(DbError::from)?;
+
+    let mut items = Vec::with_capacity(new_batch.transfers.len());
+    let mut failed = false;
+    for (position, new_transfer) in new_batch.transfers.iter().enumerate() {
+        let mut item = TransferBatchItem {
+            position: position as i32,
+            from_account: new_transfer.from_account,
+            to_account: new_transfer.to_account,
+            amount: new_transfer.amount.minor_units(),
+            status: TransferBatchItemStatus::NotExecuted,
+            transfer_id: None,
+            error: None,
+        };
+        if !(failed && mode == TransferBatchMode::Atomic) {
+            match try_execute_transfer(&mut tx, user_id, *new_transfer).await? {
+                Ok(transfer) => {
+                    item.status = TransferBatchItemStatus::Succeeded;
+                    item.transfer_id = Some(transfer.id);
+                }
+                Err(e) => {
+                    failed = true;
+                    item.status = TransferBatchItemStatus::Failed;
+                    item.error = Some(e.to_string());
+                }
+            }
+        }
+        items.push(item);
+    }
+
+    // Undo the transfers of an atomic batch that failed
+    if failed && mode == TransferBatchMode::Atomic {
+        sqlx::query!("ROLLBACK TO SAVEPOINT transfer_batch")
+            .execute(&mut tx)
+            .await
+            .map_err(DbError::from)?;
+        for item in &mut items {
+            if item.status == TransferBatchItemStatus::Succeeded {
+                item.status = TransferBatchItemStatus::NotExecuted;
+                item.transfer_id = None;
+            }
+        }
+    }
+
+    let succeeded = items
+        .iter()
+        .filter(|i| i.status == TransferBatchItemStatus::Succeeded)
+        .count();
+    let status = if succeeded == items.len() {
+        TransferBatchStatus::Succeeded
+    } else if succeeded == 0 {
+        TransferBatchStatus::Failed
+    } else {
+        TransferBatchStatus::PartiallySucceeded
+    };
+
+    let batch =
+        transfer_batch_repository::insert_batch(&mut tx, user_id, mode, status, &items).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Created().json(TransferBatchReport { batch, items }))
+}
+
+#[actix_web::get("/users/{user_id}/transfer-batches/{batch_id}")]
+#[has_permissions(
+    "transfers:read",
+    secure = "path_params.0 == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_transfer_batch(
+    db: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path_params: web::Path<(i32, i32)>,
+) -> AppResult<HttpResponse> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let (user_id, batch_id) = *path_params;
+    let batch = transfer_batch_repository::fetch_batch(&mut tx, user_id, batch_id).await?;
+    let items = transfer_batch_repository::fetch_items(&mut tx, batch_id).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(HttpResponse::Ok().json(TransferBatchReport { batch, items }))
 }
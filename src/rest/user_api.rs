@@ -1,28 +1,71 @@
 //! Routes for user management.
 
-use crate::model::user_model::NewUser;
-use crate::repository::user_repository;
+use crate::infra::configuration::{LoginSettings, UserSettings};
+use crate::infra::error::{DbError, ServiceError};
+use crate::infra::security::lockout;
+use crate::infra::security::password::{self, PasswordPolicy};
+use crate::infra::security::revocation;
+use crate::infra::validation::Validated;
+use crate::model::user_model::{HashedPassword, NewUser, PasswordChange, UserUpdate};
+use crate::repository::{
+    account_repository, login_repository, session_repository, user_repository,
+};
 use crate::security::jwt::{Claims, Role};
 use crate::{infra::error::AppError, DbPool};
+use actix_http::StatusCode;
 use actix_web::{
     web::{self, Data, Json, Path, ReqData},
-    HttpResponse,
+    HttpRequest, HttpResponse,
 };
 use actix_web_grants::proc_macro::has_roles;
 
 /// Configure the user service.
 pub fn user_config(cfg: &mut web::ServiceConfig) {
-    cfg.service(post_user).service(get_user).service(list_users);
+    cfg.service(post_user)
+        .service(get_user)
+        .service(list_users)
+        .service(patch_user)
+        .service(change_password)
+        .service(delete_user)
+        .service(revoke_sessions)
+        .service(unlock_user);
 }
 
 #[actix_web::post("/users")]
 #[has_roles("Role::Admin", type = "Role")]
 pub async fn post_user(
     db: Data<DbPool>,
-    new_user: Json<NewUser>,
+    policy: Data<PasswordPolicy>,
+    new_user: Json<Validated<NewUser>>,
 ) -> Result<HttpResponse, AppError> {
-    let new_user = new_user.into_inner();
-    let user = user_repository::store_user(db.get_ref(), &new_user).await?;
+    policy.check(&new_user.password)?;
+    let password = HashedPassword::new(&new_user.password)?;
+    let user = user_repository::store_user(db.get_ref(), &new_user.name, &password).await?;
+    Ok(HttpResponse::Created().json(user))
+}
+
+/// Lets anyone create a user with the [`Role::User`] role, unless
+/// [`UserSettings::self_registration`] is switched off.
+#[actix_web::post("/register")]
+#[tracing::instrument(skip_all)]
+pub async fn register(
+    db: Data<DbPool>,
+    settings: Data<UserSettings>,
+    policy: Data<PasswordPolicy>,
+    new_user: Json<Validated<NewUser>>,
+) -> Result<HttpResponse, AppError> {
+    if !settings.self_registration {
+        return Err(AppError::CustomError(
+            "Self-registration is disabled".to_string(),
+            StatusCode::FORBIDDEN,
+        ));
+    }
+    policy.check(&new_user.password)?;
+    let password = HashedPassword::new(&new_user.password)?;
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let user = user_repository::store_user(&mut tx, &new_user.name, &password).await?;
+    user_repository::grant_role(&mut tx, user.id, Role::User).await?;
+    tx.commit().await.map_err(DbError::from)?;
     Ok(HttpResponse::Created().json(user))
 }
 
@@ -47,3 +90,114 @@ pub async fn list_users(db: Data<DbPool>) -> Result<HttpResponse, AppError> {
     let users = user_repository::fetch_all_users(db.get_ref()).await?;
     Ok(HttpResponse::Ok().json(users))
 }
+
+#[actix_web::patch("/users/{id}")]
+#[has_roles(
+    "Role::User",
+    type = "Role",
+    secure = "*id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn patch_user(
+    db: Data<DbPool>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+    update: Json<Validated<UserUpdate>>,
+) -> Result<HttpResponse, AppError> {
+    let user = user_repository::update_user(db.get_ref(), *id, &update).await?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+/// Changes the password of the signed in user, who must know the current one. The new password
+/// must follow the [`PasswordPolicy`]. Wrong passwords count as failed sign-ins, and the other
+/// sessions of the user are signed out.
+#[actix_web::put("/users/{id}/password")]
+#[allow(clippy::too_many_arguments)]
+#[has_roles("Role::User", type = "Role", secure = "*id == claims.id()")]
+#[tracing::instrument(skip_all)]
+pub async fn change_password(
+    req: HttpRequest,
+    db: Data<DbPool>,
+    policy: Data<PasswordPolicy>,
+    login: Data<LoginSettings>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+    change: Json<PasswordChange>,
+) -> Result<HttpResponse, AppError> {
+    let change = change.into_inner();
+    let user = user_repository::fetch_user_by_id(db.get_ref(), &id).await?;
+    let address = req.peer_addr().map(|addr| addr.ip());
+    lockout::authenticate(&db, &login, &user.name, &change.current_password, address).await?;
+
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    // Fetched again, as signing in may have hashed the current password again
+    let user = user_repository::fetch_user_by_id(&mut tx, &id).await?;
+    password::change_password(&mut tx, &policy, &user, &change.new_password).await?;
+    let revoked =
+        session_repository::revoke_user_sessions(&mut tx, user.id, claims.session_id()).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    revocation::revoke(&revoked);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Deletes a user who owns no open accounts, keeping the history of their accounts. Accounts
+/// shared with the user stay with their owners.
+#[actix_web::delete("/users/{id}")]
+#[has_roles(
+    "Role::User",
+    type = "Role",
+    secure = "*id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn delete_user(
+    db: Data<DbPool>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let open_accounts = account_repository::count_open_accounts(&mut tx, *id).await?;
+    if open_accounts > 0 {
+        return Err(ServiceError::ValidationError(format!(
+            "User {} still owns {} open accounts",
+            id, open_accounts
+        ))
+        .into());
+    }
+    user_repository::delete_user(&mut tx, *id).await?;
+    let revoked = session_repository::revoke_user_sessions(&mut tx, *id, None).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    revocation::revoke(&revoked);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Signs a user out everywhere, by revoking all their sessions and the access tokens issued in
+/// them.
+#[actix_web::delete("/users/{id}/sessions")]
+#[has_roles(
+    "Role::User",
+    type = "Role",
+    secure = "*id == claims.id() || claims.has_role(&Role::Admin)"
+)]
+#[tracing::instrument(skip_all)]
+pub async fn revoke_sessions(
+    db: Data<DbPool>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    user_repository::fetch_user_by_id(&mut tx, &id).await?;
+    let revoked = session_repository::revoke_user_sessions(&mut tx, *id, None).await?;
+    tx.commit().await.map_err(DbError::from)?;
+    revocation::revoke(&revoked);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Unlocks a user who failed to sign in too many times, before the lock expires.
+#[actix_web::delete("/users/{id}/lockout")]
+#[has_roles("Role::Admin", type = "Role")]
+#[tracing::instrument(skip_all)]
+pub async fn unlock_user(db: Data<DbPool>, id: Path<i32>) -> Result<HttpResponse, AppError> {
+    let user = user_repository::fetch_user_by_id(db.get_ref(), &id).await?;
+    login_repository::unlock_user(db.get_ref(), user.id, &user.name).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,154 @@
+//! A worker that accrues interest every day, and posts interest and fees every month.
+//!
+//! Each day is accrued in its own database transaction, and days missed while the worker
+//! was not running are caught up. Once a month has ended, the interest accrued during it and
+//! the monthly fee are booked to the ledger, one account at a time.
+
+use crate::{
+    infra::{
+        configuration::SchedulerSettings,
+        error::{AppError, DbError},
+    },
+    model::{
+        money::{Amount, CurrencyCode},
+        product_model::{month_start, MonthlyPosting, PostingBasis},
+    },
+    repository::{account_repository, ledger_repository, product_repository},
+    DbPool, Tx,
+};
+use chrono::{Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use tracing::Instrument;
+
+/// Accrues and posts interest every [`SchedulerSettings::poll_seconds`] until the task is aborted.
+pub async fn run_account_products(db: DbPool, settings: SchedulerSettings) -> anyhow::Result<()> {
+    tracing::info!(
+        "Starting account product worker, polling every {} seconds",
+        settings.poll_seconds
+    );
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(settings.poll_seconds));
+    loop {
+        interval.tick().await;
+        let today = Utc::now().date_naive();
+        match accrue_interest(&db, today).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Accrued interest for {} days", n),
+            Err(e) => tracing::error!("Failed to accrue interest: {}", e),
+        }
+        match post_interest_and_fees(&db, today).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Posted interest and fees for {} account months", n),
+            Err(e) => tracing::error!("Failed to post interest and fees: {}", e),
+        }
+    }
+}
+
+/// Accrues interest for every day before `today` that has not been accrued,
+/// and returns how many days were accrued. The first run only accrues yesterday.
+#[tracing::instrument(skip(db))]
+pub async fn accrue_interest(db: &DbPool, today: NaiveDate) -> Result<usize, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let last_run = product_repository::fetch_last_accrual_run(&mut tx).await?;
+    tx.commit().await.map_err(DbError::from)?;
+
+    let mut day = match last_run {
+        Some(last_run) => last_run + Duration::days(1),
+        None => today - Duration::days(1),
+    };
+    let mut accrued = 0;
+    while day < today {
+        if accrue_day(db, day).await? {
+            accrued += 1;
+        }
+        day += Duration::days(1);
+    }
+    Ok(accrued)
+}
+
+/// Accrues interest for a single day on the balance at the end of the day.
+/// Returns false if the day was accrued by someone else.
+async fn accrue_day(db: &DbPool, day: NaiveDate) -> Result<bool, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let end = Utc.from_utc_datetime(&(day + Duration::days(1)).and_time(NaiveTime::default()));
+    let bases = product_repository::fetch_accrual_bases(&mut tx, end).await?;
+    if !product_repository::insert_accrual_run(&mut tx, day, bases.len() as i32).await? {
+        return Ok(false);
+    }
+    for basis in &bases {
+        product_repository::insert_accrual(&mut tx, &basis.accrue(day)).await?;
+    }
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(true)
+}
+
+/// Posts the interest and fees of every month that ended before `today`,
+/// and returns how many account months were posted.
+#[tracing::instrument(skip(db))]
+pub async fn post_interest_and_fees(db: &DbPool, today: NaiveDate) -> Result<usize, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let bases = product_repository::fetch_unposted_months(&mut tx, month_start(today)).await?;
+    tx.commit().await.map_err(DbError::from)?;
+
+    let mut posted = 0;
+    for basis in &bases {
+        if post_month(db, basis).await?.is_some() {
+            posted += 1;
+        }
+    }
+    Ok(posted)
+}
+
+/// Books the interest and fee of an account for a month.
+/// Returns `None` if the month was posted by someone else.
+async fn post_month(db: &DbPool, basis: &PostingBasis) -> Result<Option<MonthlyPosting>, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let account = match account_repository::lock_accounts(&mut tx, &[basis.account_id])
+        .await?
+        .pop()
+    {
+        Some(account) => account,
+        None => return Err(DbError::NotFound.into()),
+    };
+
+    // Closed accounts must stay empty, so they forfeit their interest and are not charged
+    let (interest, fee) = if account.is_open() {
+        let interest = basis.interest();
+        (interest, basis.fee(account.available_balance() + interest))
+    } else {
+        (0, 0)
+    };
+
+    // Run as the owner of the account, so that the postings are audited
+    let span = tracing::info_span!("principal", principal = account.owner_id());
+    let posting = book(&mut tx, basis, account.currency(), interest, fee)
+        .instrument(span)
+        .await?;
+    if posting.is_some() {
+        tx.commit().await.map_err(DbError::from)?;
+    }
+    Ok(posting)
+}
+
+/// Records a monthly posting and moves the money.
+async fn book(
+    tx: &mut Tx,
+    basis: &PostingBasis,
+    currency: &CurrencyCode,
+    interest: i64,
+    fee: i64,
+) -> Result<Option<MonthlyPosting>, AppError> {
+    let posting =
+        product_repository::insert_posting(tx, basis.account_id, basis.month, interest, fee)
+            .await?;
+    if posting.is_none() {
+        return Ok(None);
+    }
+    if let Ok(amount) = Amount::new(interest) {
+        ledger_repository::record_interest(tx, basis.account_id, currency, interest).await?;
+        account_repository::deposit(tx, basis.account_id, amount).await?;
+    }
+    if let Ok(amount) = Amount::new(fee) {
+        ledger_repository::record_fee(tx, basis.account_id, currency, fee).await?;
+        account_repository::withdraw(tx, basis.account_id, amount).await?;
+    }
+    Ok(posting)
+}
@@ -0,0 +1,79 @@
+//! A worker that releases holds once they expire.
+//!
+//! Expired holds are claimed in batches together with their accounts with
+//! `FOR UPDATE SKIP LOCKED`, and each batch frees the reserved money in the same database
+//! transaction as the update to the holds.
+
+use crate::{
+    infra::{
+        configuration::SchedulerSettings,
+        error::{AppError, DbError, ServiceError},
+    },
+    model::{
+        hold_model::{Hold, HoldStatus},
+        money::Amount,
+    },
+    repository::{account_repository, hold_repository},
+    DbPool, Tx,
+};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use tracing::Instrument;
+
+/// The number of holds released in each database transaction.
+const BATCH_SIZE: i64 = 100;
+
+/// Releases expired holds every [`SchedulerSettings::poll_seconds`] until the task is aborted.
+pub async fn run_hold_expiry(db: DbPool, settings: SchedulerSettings) -> anyhow::Result<()> {
+    tracing::info!(
+        "Starting hold expiry worker, polling every {} seconds",
+        settings.poll_seconds
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.poll_seconds));
+    loop {
+        interval.tick().await;
+        match release_expired_holds(&db, Utc::now()).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Released {} expired holds", n),
+            Err(e) => tracing::error!("Failed to release expired holds: {}", e),
+        }
+    }
+}
+
+/// Releases all active holds that expired before `now`, and returns how many were released.
+#[tracing::instrument(skip(db))]
+pub async fn release_expired_holds(db: &DbPool, now: DateTime<Utc>) -> Result<usize, AppError> {
+    let mut released = 0;
+    loop {
+        let mut tx = db.begin().await.map_err(DbError::from)?;
+        let holds = hold_repository::lock_expired_holds(&mut tx, now, BATCH_SIZE).await?;
+        if holds.is_empty() {
+            return Ok(released);
+        }
+        for hold in &holds {
+            expire(&mut tx, hold).await?;
+        }
+        tx.commit().await.map_err(DbError::from)?;
+        released += holds.len();
+    }
+}
+
+/// Frees the money reserved by a hold and marks it as expired.
+async fn expire(tx: &mut Tx, hold: &Hold) -> Result<(), AppError> {
+    // Already locked with the hold
+    let account = account_repository::lock_accounts(tx, &[hold.account_id])
+        .await?
+        .pop()
+        .ok_or(DbError::NotFound)?;
+    let held = Amount::new(hold.amount).map_err(ServiceError::from)?;
+
+    // Run as the owner of the account, so that the release is audited
+    let span = tracing::info_span!("principal", principal = account.owner_id());
+    async {
+        account_repository::unreserve(tx, hold.account_id, held).await?;
+        hold_repository::close_hold(tx, hold.id, HoldStatus::Expired, None).await?;
+        Ok::<_, AppError>(())
+    }
+    .instrument(span)
+    .await
+}
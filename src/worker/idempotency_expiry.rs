@@ -0,0 +1,36 @@
+//! A worker that deletes idempotency keys once they expire.
+//!
+//! Expired keys are only overwritten when a user sends the same key again, so without this
+//! worker the table would keep every key ever used.
+
+use crate::{
+    infra::{configuration::SchedulerSettings, error::AppError},
+    repository::idempotency_repository,
+    DbPool,
+};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// Deletes expired keys every [`SchedulerSettings::poll_seconds`] until the task is aborted.
+pub async fn run_idempotency_expiry(db: DbPool, settings: SchedulerSettings) -> anyhow::Result<()> {
+    tracing::info!(
+        "Starting idempotency key expiry worker, polling every {} seconds",
+        settings.poll_seconds
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.poll_seconds));
+    loop {
+        interval.tick().await;
+        match purge_expired_keys(&db, Utc::now()).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Deleted {} expired idempotency keys", n),
+            Err(e) => tracing::error!("Failed to delete expired idempotency keys: {}", e),
+        }
+    }
+}
+
+/// Deletes all idempotency keys that expired before `now`, and returns how many were deleted.
+#[tracing::instrument(skip(db))]
+pub async fn purge_expired_keys(db: &DbPool, now: DateTime<Utc>) -> Result<u64, AppError> {
+    let deleted = idempotency_repository::delete_expired_keys(db, now).await?;
+    Ok(deleted)
+}
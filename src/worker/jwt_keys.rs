@@ -0,0 +1,31 @@
+//! A worker that retires jwt keys once their overlap ends, and picks up keys rotated by other
+//! instances.
+
+use crate::{
+    infra::{
+        configuration::SchedulerSettings,
+        security::{jwt::JwtContext, keys},
+    },
+    DbPool,
+};
+use chrono::Utc;
+use std::time::Duration;
+
+/// Reloads the keys every [`SchedulerSettings::poll_seconds`] until the task is aborted.
+pub async fn run_jwt_keys(
+    db: DbPool,
+    jwt: JwtContext,
+    settings: SchedulerSettings,
+) -> anyhow::Result<()> {
+    tracing::info!(
+        "Starting jwt key worker, polling every {} seconds",
+        settings.poll_seconds
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.poll_seconds));
+    loop {
+        interval.tick().await;
+        if let Err(e) = keys::sync_keys(&db, &jwt, Utc::now()).await {
+            tracing::error!("Failed to load jwt keys: {}", e);
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! Background jobs that run next to the servers.
+
+pub mod account_product;
+pub mod hold_expiry;
+pub mod idempotency_expiry;
+pub mod jwt_keys;
+pub mod payment_import;
+pub mod scheduled_transfer;
+pub mod token_revocation;
@@ -0,0 +1,138 @@
+//! A worker that executes the payments of imported payment files.
+//!
+//! Each file is claimed with `FOR UPDATE SKIP LOCKED`, and all of its payments are executed
+//! in the same database transaction as the update to its status. A file whose transaction
+//! fails with a transient error stays received and is picked up again on the next poll.
+
+use crate::{
+    infra::{
+        configuration::SchedulerSettings,
+        error::{AppError, DbError, ServiceError},
+    },
+    model::{
+        money::Amount,
+        payment_import_model::{
+            reason, ImportedPayment, ImportedPaymentStatus, PaymentImport, PaymentImportStatus,
+        },
+        transfer_model::NewTransfer,
+    },
+    repository::payment_import_repository,
+    rest::transfer_api::try_execute_transfer,
+    DbPool, Tx,
+};
+use std::time::Duration;
+use tracing::Instrument;
+
+/// Executes received files every [`SchedulerSettings::poll_seconds`] until the task is aborted.
+pub async fn run_payment_imports(db: DbPool, settings: SchedulerSettings) -> anyhow::Result<()> {
+    tracing::info!(
+        "Starting payment import worker, polling every {} seconds",
+        settings.poll_seconds
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.poll_seconds));
+    loop {
+        interval.tick().await;
+        match process_received_imports(&db).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Processed {} payment files", n),
+            Err(e) => tracing::error!("Failed to process payment files: {}", e),
+        }
+    }
+}
+
+/// Executes the payments of all received files, and returns how many files were processed.
+#[tracing::instrument(skip_all)]
+pub async fn process_received_imports(db: &DbPool) -> Result<usize, AppError> {
+    let mut processed = 0;
+    while process_next(db).await?.is_some() {
+        processed += 1;
+    }
+    Ok(processed)
+}
+
+/// Claims and processes the oldest received file, if any.
+async fn process_next(db: &DbPool) -> Result<Option<PaymentImport>, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let import = match payment_import_repository::lock_next_received(&mut tx).await? {
+        Some(import) => import,
+        None => return Ok(None),
+    };
+    let payments = payment_import_repository::fetch_payments(&mut tx, import.id).await?;
+
+    // Run as the uploader of the file, so that the transfers are audited
+    let span = tracing::info_span!("principal", principal = import.owner_id);
+    let mut accepted = 0;
+    for payment in &payments {
+        let status = execute(&mut tx, &import, payment)
+            .instrument(span.clone())
+            .await?;
+        if status == ImportedPaymentStatus::Accepted {
+            accepted += 1;
+        }
+    }
+
+    let status = if accepted == payments.len() {
+        PaymentImportStatus::Accepted
+    } else if accepted == 0 {
+        PaymentImportStatus::Rejected
+    } else {
+        PaymentImportStatus::PartiallyAccepted
+    };
+    let import = payment_import_repository::complete_import(&mut tx, import.id, status)
+        .instrument(span)
+        .await?;
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(Some(import))
+}
+
+/// Executes a pending payment through the same code path as other transfers,
+/// and records the outcome. Payments rejected during validation are left as they are.
+async fn execute(
+    tx: &mut Tx,
+    import: &PaymentImport,
+    payment: &ImportedPayment,
+) -> Result<ImportedPaymentStatus, AppError> {
+    let (from_account, to_account, amount) =
+        match (payment.from_account, payment.to_account, payment.amount) {
+            (Some(from), Some(to), Some(amount))
+                if payment.status == ImportedPaymentStatus::Pending =>
+            {
+                (from, to, amount)
+            }
+            _ => return Ok(payment.status),
+        };
+    let result = match Amount::new(amount) {
+        Ok(amount) => {
+            let new_transfer = NewTransfer {
+                from_account,
+                to_account,
+                amount,
+            };
+            try_execute_transfer(tx, import.owner_id, new_transfer).await?
+        }
+        Err(e) => Err(ServiceError::from(e).into()),
+    };
+    let (status, transfer_id, error) = match result {
+        Ok(transfer) => (ImportedPaymentStatus::Accepted, Some(transfer.id), None),
+        Err(e) => {
+            tracing::info!(
+                "Payment {} of file {} failed: {}",
+                payment.position,
+                import.id,
+                e
+            );
+            (ImportedPaymentStatus::Rejected, None, Some(e.to_string()))
+        }
+    };
+    payment_import_repository::update_payment(
+        tx,
+        import.id,
+        payment.position,
+        status,
+        transfer_id,
+        error.as_ref().map(|_| reason::NARRATIVE),
+        error.as_deref(),
+    )
+    .await?;
+    Ok(status)
+}
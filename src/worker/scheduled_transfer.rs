@@ -0,0 +1,164 @@
+//! A worker that executes scheduled transfers when they are due.
+//!
+//! Each due transfer is claimed with `FOR UPDATE SKIP LOCKED` and executed in the same
+//! database transaction as the update to its schedule, so several workers can run at once
+//! without executing an occurrence twice.
+
+use crate::{
+    infra::{
+        configuration::SchedulerSettings,
+        error::{AppError, DbError, ServiceError},
+    },
+    model::{
+        money::Amount,
+        scheduled_transfer_model::{
+            ScheduledTransfer, ScheduledTransferRun, ScheduledTransferRunStatus,
+            ScheduledTransferStatus,
+        },
+        transfer_model::NewTransfer,
+    },
+    repository::scheduled_transfer_repository,
+    rest::transfer_api::try_execute_transfer,
+    DbPool, Tx,
+};
+use chrono::Utc;
+use std::time::Duration;
+use tracing::Instrument;
+
+/// Executes due transfers every [`SchedulerSettings::poll_seconds`] until the task is aborted.
+pub async fn run_scheduled_transfers(
+    db: DbPool,
+    settings: SchedulerSettings,
+) -> anyhow::Result<()> {
+    tracing::info!(
+        "Starting scheduled transfer worker, polling every {} seconds",
+        settings.poll_seconds
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.poll_seconds));
+    loop {
+        interval.tick().await;
+        match execute_due_transfers(&db, &settings).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Executed {} scheduled transfers", n),
+            Err(e) => tracing::error!("Failed to execute scheduled transfers: {}", e),
+        }
+    }
+}
+
+/// Executes all scheduled transfers that are due, and returns how many were run.
+#[tracing::instrument(skip_all)]
+pub async fn execute_due_transfers(
+    db: &DbPool,
+    settings: &SchedulerSettings,
+) -> Result<usize, AppError> {
+    let mut executed = 0;
+    while execute_next_with_retry(db, settings).await?.is_some() {
+        executed += 1;
+    }
+    Ok(executed)
+}
+
+/// Executes the next due transfer, retrying transient errors with exponential backoff.
+async fn execute_next_with_retry(
+    db: &DbPool,
+    settings: &SchedulerSettings,
+) -> Result<Option<ScheduledTransferRun>, AppError> {
+    let mut attempt = 1;
+    loop {
+        match execute_next(db, attempt).await {
+            Err(e) if e.is_transient() && attempt < settings.max_attempts => {
+                tracing::warn!("Attempt {} failed, retrying: {}", attempt, e);
+                let backoff = settings.retry_millis.saturating_mul(1 << (attempt - 1));
+                tokio::time::sleep(Duration::from_millis(backoff)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Claims and executes the transfer that has been due the longest, if any.
+async fn execute_next(db: &DbPool, attempt: u32) -> Result<Option<ScheduledTransferRun>, AppError> {
+    let mut tx = db.begin().await.map_err(DbError::from)?;
+    let now = Utc::now();
+    let schedule = match scheduled_transfer_repository::lock_next_due(&mut tx, now).await? {
+        Some(schedule) => schedule,
+        None => return Ok(None),
+    };
+
+    // Run as the owner of the schedule, so that the transfer is audited
+    let span = tracing::info_span!("principal", principal = schedule.owner_id);
+    // Transient errors give up on this attempt, so the whole transaction is retried
+    let result = execute(&mut tx, &schedule).instrument(span).await?;
+
+    let (status, transfer_id, error) = match result {
+        Ok(transfer_id) => (
+            ScheduledTransferRunStatus::Succeeded,
+            Some(transfer_id),
+            None,
+        ),
+        Err(e) => {
+            tracing::info!("Scheduled transfer {} failed: {}", schedule.id, e);
+            (
+                ScheduledTransferRunStatus::Failed,
+                None,
+                Some(e.to_string()),
+            )
+        }
+    };
+    let run = scheduled_transfer_repository::insert_run(
+        &mut tx,
+        schedule.id,
+        schedule.next_run_at,
+        status,
+        transfer_id,
+        error.as_deref(),
+        attempt as i32,
+    )
+    .await?;
+
+    // Move on to the next occurrence
+    let (occurrences, next_run_at, status) = match schedule.next_occurrence(now) {
+        Some((n, at)) => (n, at, ScheduledTransferStatus::Active),
+        None if error.is_some() && schedule.recurrence.is_none() => (
+            schedule.occurrences + 1,
+            schedule.next_run_at,
+            ScheduledTransferStatus::Failed,
+        ),
+        None => (
+            schedule.occurrences + 1,
+            schedule.next_run_at,
+            ScheduledTransferStatus::Completed,
+        ),
+    };
+    scheduled_transfer_repository::advance_scheduled_transfer(
+        &mut tx,
+        schedule.id,
+        occurrences,
+        next_run_at,
+        status,
+    )
+    .await?;
+
+    tx.commit().await.map_err(DbError::from)?;
+    Ok(Some(run))
+}
+
+/// Executes a scheduled transfer. Failures other than transient errors
+/// are rolled back and returned in the inner result, so that they can be recorded.
+async fn execute(
+    tx: &mut Tx,
+    schedule: &ScheduledTransfer,
+) -> Result<Result<i32, AppError>, AppError> {
+    let amount = match Amount::new(schedule.amount) {
+        Ok(amount) => amount,
+        Err(e) => return Ok(Err(ServiceError::from(e).into())),
+    };
+    let new_transfer = NewTransfer {
+        from_account: schedule.from_account,
+        to_account: schedule.to_account,
+        amount,
+    };
+    let result = try_execute_transfer(tx, schedule.owner_id, new_transfer).await?;
+    Ok(result.map(|transfer| transfer.id))
+}
@@ -0,0 +1,36 @@
+//! A worker that keeps the in-memory list of revoked access tokens in sync with the database.
+//!
+//! Tokens revoked by this instance are rejected straight away. Tokens revoked by other
+//! instances are picked up within [`SchedulerSettings::poll_seconds`].
+
+use crate::{
+    infra::{configuration::SchedulerSettings, error::AppError, security::revocation},
+    repository::session_repository,
+    DbPool,
+};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// Loads revoked tokens every [`SchedulerSettings::poll_seconds`] until the task is aborted.
+pub async fn run_token_revocation(db: DbPool, settings: SchedulerSettings) -> anyhow::Result<()> {
+    tracing::info!(
+        "Starting token revocation worker, polling every {} seconds",
+        settings.poll_seconds
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.poll_seconds));
+    loop {
+        interval.tick().await;
+        if let Err(e) = sync_revoked_tokens(&db, Utc::now()).await {
+            tracing::error!("Failed to load revoked tokens: {}", e);
+        }
+    }
+}
+
+/// Loads the revoked tokens that have not expired by `now`, and forgets the ones that have.
+#[tracing::instrument(skip(db))]
+pub async fn sync_revoked_tokens(db: &DbPool, now: DateTime<Utc>) -> Result<usize, AppError> {
+    let tokens = session_repository::fetch_revoked_tokens(db, now).await?;
+    revocation::revoke(&tokens);
+    revocation::prune(now);
+    Ok(tokens.len())
+}
@@ -1,6 +1,7 @@
 use actix_web_demo::{
     infra::configuration::{load_configuration, DatabaseSettings},
     infra::logging,
+    infra::security::jwt::JwtContext,
     DbPool,
 };
 use sqlx::Executor;
@@ -67,9 +68,10 @@ pub async fn spawn_test_app() -> TestApp {
 
     let _ = logging::init_logging(&configuration, db.clone()).await;
 
+    let jwt = JwtContext::new(configuration.security.clone()).expect("Failed to load jwt keys");
     let server =
-        actix_web_demo::run_actix(http_listener, db.clone()).expect("Failed to bind address");
-    let _ = tokio::spawn(server);
+        actix_web_demo::run_actix(http_listener, db.clone(), jwt).expect("Failed to bind address");
+    tokio::spawn(server);
 
     TestApp { address, db }
 }
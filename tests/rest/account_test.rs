@@ -1,6 +1,10 @@
 use crate::{common::spawn_test_app, rest};
 use actix_http::StatusCode;
-use actix_web_demo::model::account_model::{Account, Deposit, NewAccount, Withdrawal};
+use actix_web_demo::model::{
+    account_model::{Account, AccountStatus, AccountUpdate, Deposit, NewAccount, Withdrawal},
+    money::Amount,
+    pagination::Page,
+};
 
 #[actix_web::test]
 async fn post_account_gives_201() {
@@ -86,7 +90,7 @@ async fn deposit_increases_balance() {
 
     // Make a deposit
     let deposit_amount = 50;
-    let deposit = Deposit::new(deposit_amount);
+    let deposit = Deposit::new(Amount::new(deposit_amount).unwrap());
     let response = client
         .post(format!("{}/api/users/1/accounts/1/deposits", app.address()))
         .bearer_auth(&user_token)
@@ -110,7 +114,7 @@ async fn deposit_increases_balance() {
     println!("New acc {:?}", old_account);
 
     assert_eq!(
-        old_account.balance() + deposit_amount as i64,
+        old_account.balance() + deposit_amount,
         new_account.balance()
     );
 }
@@ -134,7 +138,7 @@ async fn withdraw_decreases_balance() {
 
     // Make a withdrawal
     let withdrawal_amount = 50;
-    let withdrawal = Withdrawal::new(withdrawal_amount);
+    let withdrawal = Withdrawal::new(Amount::new(withdrawal_amount).unwrap());
     let response = client
         .post(format!(
             "{}/api/users/1/accounts/1/withdrawals",
@@ -158,7 +162,7 @@ async fn withdraw_decreases_balance() {
         .unwrap();
 
     assert_eq!(
-        old_account.balance() - withdrawal_amount as i64,
+        old_account.balance() - withdrawal_amount,
         new_account.balance()
     );
 }
@@ -171,7 +175,7 @@ async fn withdrawing_too_much_fails() {
 
     // Make a withdrawal
     let withdrawal_amount = 500;
-    let withdrawal = Withdrawal::new(withdrawal_amount);
+    let withdrawal = Withdrawal::new(Amount::new(withdrawal_amount).unwrap());
     let response = client
         .post(format!(
             "{}/api/users/1/accounts/1/withdrawals",
@@ -229,3 +233,153 @@ async fn user_cannot_access_other_account() {
         .unwrap();
     assert_eq!(StatusCode::OK, response.status());
 }
+
+#[actix_web::test]
+async fn list_accounts_gives_owned_accounts() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    // Default sorting is by id
+    let response = client
+        .get(format!("{}/api/users/1/accounts", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let page: Page<Account> = response.json().await.unwrap();
+    let ids: Vec<i32> = page.items.iter().map(Account::id).collect();
+    assert_eq!(vec![1, 2], ids);
+    assert_eq!(2, page.total);
+
+    // Sort by balance, largest first, one account per page
+    let page: Page<Account> = client
+        .get(format!(
+            "{}/api/users/1/accounts?sort=balance&order=desc&size=1",
+            app.address()
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let ids: Vec<i32> = page.items.iter().map(Account::id).collect();
+    assert_eq!(vec![2], ids);
+    assert_eq!(2, page.total);
+
+    // Page size is limited
+    let response = client
+        .get(format!("{}/api/users/1/accounts?size=1000", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // Cannot list another user's accounts
+    let response = client
+        .get(format!("{}/api/users/2/accounts", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+}
+
+#[actix_web::test]
+async fn patch_account_renames_it() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let response = client
+        .patch(format!("{}/api/users/1/accounts/1", app.address()))
+        .bearer_auth(&user_token)
+        .json(&AccountUpdate::new("savings".to_string()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let account: Account = response.json().await.unwrap();
+    assert_eq!(Account::new(1, "savings".to_string(), 100, 1), account);
+}
+
+#[actix_web::test]
+async fn closing_account_with_balance_fails() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let response = client
+        .post(format!("{}/api/users/1/accounts/1/close", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[actix_web::test]
+async fn closed_account_rejects_deposits_and_withdrawals() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    // Create an empty account
+    let account: Account = client
+        .post(format!("{}/api/users/1/accounts", app.address()))
+        .bearer_auth(&user_token)
+        .json(&NewAccount::new("temporary".to_string()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    // Close it
+    let response = client
+        .post(format!(
+            "{}/api/users/1/accounts/{}/close",
+            app.address(),
+            account.id()
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let closed_account: Account = response.json().await.unwrap();
+    assert_eq!(AccountStatus::Closed, closed_account.status());
+
+    // Money can no longer move in or out
+    let response = client
+        .post(format!(
+            "{}/api/users/1/accounts/{}/deposits",
+            app.address(),
+            account.id()
+        ))
+        .bearer_auth(&user_token)
+        .json(&Deposit::new(Amount::new(50).unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = client
+        .post(format!(
+            "{}/api/users/1/accounts/{}/withdrawals",
+            app.address(),
+            account.id()
+        ))
+        .bearer_auth(&user_token)
+        .json(&Withdrawal::new(Amount::new(1).unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
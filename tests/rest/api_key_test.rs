@@ -0,0 +1,164 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::model::api_key_model::{ApiKey, CreatedApiKey, NewApiKey};
+use chrono::{Duration, Utc};
+use reqwest::Client;
+
+fn new_key(scopes: &[&str]) -> NewApiKey {
+    NewApiKey {
+        name: "script".to_string(),
+        scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        expires_at: None,
+    }
+}
+
+async fn get(client: &Client, app: &TestApp, path: &str, key: &str) -> StatusCode {
+    client
+        .get(format!("{}{}", app.address(), path))
+        .header("Authorization", format!("ApiKey {}", key))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+#[actix_web::test]
+async fn api_keys_act_for_the_user_within_their_scopes() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let token = rest::authenticate(&app, "user", "user").await;
+
+    let response = client
+        .post(format!("{}/api/users/1/api-keys", app.address()))
+        .bearer_auth(&token)
+        .json(&new_key(&["accounts:read"]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+    let created: CreatedApiKey = response.json().await.unwrap();
+    assert!(created.key.starts_with(&created.api_key.prefix));
+    assert_eq!(None, created.api_key.last_used_at);
+
+    // The key grants only its scopes, and no roles
+    assert_eq!(
+        StatusCode::OK,
+        get(&client, &app, "/api/users/1/accounts", &created.key).await
+    );
+    assert_eq!(
+        StatusCode::FORBIDDEN,
+        get(&client, &app, "/api/users/1/transfers", &created.key).await
+    );
+    assert_eq!(
+        StatusCode::FORBIDDEN,
+        get(&client, &app, "/api/users/1/api-keys", &created.key).await
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        get(&client, &app, "/api/users/1/accounts", "ak_wrong").await
+    );
+
+    // Listing shows when the key was last used, but not the key itself
+    let response = client
+        .get(format!("{}/api/users/1/api-keys", app.address()))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let api_keys: Vec<ApiKey> = response.json().await.unwrap();
+    assert_eq!(1, api_keys.len());
+    assert!(api_keys[0].last_used_at.is_some());
+
+    // Revoked keys no longer work
+    let response = client
+        .delete(format!(
+            "{}/api/users/1/api-keys/{}",
+            app.address(),
+            created.api_key.id
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        get(&client, &app, "/api/users/1/accounts", &created.key).await
+    );
+}
+
+#[actix_web::test]
+async fn api_keys_are_limited_to_the_permissions_of_the_user() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let token = rest::authenticate(&app, "user", "user").await;
+
+    for new_key in [
+        new_key(&["roles:manage"]),
+        new_key(&[]),
+        NewApiKey {
+            expires_at: Some(Utc::now() - Duration::minutes(1)),
+            ..new_key(&["accounts:read"])
+        },
+    ] {
+        let response = client
+            .post(format!("{}/api/users/1/api-keys", app.address()))
+            .bearer_auth(&token)
+            .json(&new_key)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    // Users cannot create keys for others
+    let response = client
+        .post(format!("{}/api/users/2/api-keys", app.address()))
+        .bearer_auth(&token)
+        .json(&new_key(&["accounts:read"]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+}
+
+#[actix_web::test]
+async fn api_keys_can_move_money_within_their_scopes() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let token = rest::authenticate(&app, "user", "user").await;
+
+    let mut keys = Vec::new();
+    for scopes in [&["deposits:create"], &["accounts:read"]] {
+        let response = client
+            .post(format!("{}/api/users/1/api-keys", app.address()))
+            .bearer_auth(&token)
+            .json(&new_key(scopes))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+        let created: CreatedApiKey = response.json().await.unwrap();
+        keys.push(created.key);
+    }
+
+    for (key, status) in keys.iter().zip([StatusCode::OK, StatusCode::FORBIDDEN]) {
+        let response = client
+            .post(format!("{}/api/users/1/accounts/1/deposits", app.address()))
+            .header("Authorization", format!("ApiKey {}", key))
+            .json(&serde_json::json!({ "amount": 10 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status, response.status());
+    }
+    let balance: i64 = sqlx::query_scalar!("SELECT balance FROM accounts WHERE id = 1")
+        .fetch_one(app.db())
+        .await
+        .unwrap();
+    assert_eq!(110, balance);
+}
@@ -1,6 +1,6 @@
 use crate::common::spawn_test_app;
 use actix_http::StatusCode;
-use actix_web_demo::rest::client_context::ClientContext;
+use actix_web_demo::{model::session_model::TokenResponse, rest::client_context::ClientContext};
 
 #[actix_web::test]
 async fn health_check_works() {
@@ -89,11 +89,12 @@ async fn user_creation_to_token_verification() {
         assert_eq!(StatusCode::UNAUTHORIZED, response.status());
     }
 
-    let token: String = response.text().await.unwrap();
+    let tokens: TokenResponse = response.json().await.unwrap();
+    assert_eq!("Bearer", tokens.token_type);
     // Verify token
     let response = client
         .get(format!("{}/verify", &app.address()))
-        .bearer_auth(&token)
+        .bearer_auth(&tokens.access_token)
         .send()
         .await
         .unwrap();
@@ -0,0 +1,161 @@
+use crate::{common::spawn_test_app, rest};
+use actix_http::StatusCode;
+use actix_web_demo::model::{
+    account_model::{Account, NewAccount},
+    fx_model::{FxRate, NewFxRate},
+    ledger_model::BalanceMismatch,
+    money::{Amount, CurrencyCode},
+    transfer_model::{NewTransfer, Transfer},
+};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+#[actix_web::test]
+async fn account_with_unsupported_currency_gives_400() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let new_account =
+        NewAccount::new("bitcoin".to_string()).with_currency(CurrencyCode::new("XBT").unwrap());
+    let response = client
+        .post(format!("{}/api/users/1/accounts", app.address()))
+        .bearer_auth(&user_token)
+        .json(&new_account)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[actix_web::test]
+async fn non_positive_and_oversized_amounts_give_400() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    for amount in [0, -10, i64::MAX] {
+        let response = client
+            .post(format!("{}/api/users/1/accounts/1/deposits", app.address()))
+            .bearer_auth(&user_token)
+            .json(&serde_json::json!({ "amount": amount }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+}
+
+#[actix_web::test]
+async fn cross_currency_transfer_uses_fx_rate() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    // Open a USD account
+    let new_account =
+        NewAccount::new("dollars".to_string()).with_currency(CurrencyCode::new("USD").unwrap());
+    let usd_account: Account = client
+        .post(format!("{}/api/users/1/accounts", app.address()))
+        .bearer_auth(&user_token)
+        .json(&new_account)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!("USD", usd_account.currency().as_str());
+
+    // Without a rate the transfer is rejected
+    let new_transfer = NewTransfer {
+        from_account: 2,
+        to_account: usd_account.id(),
+        amount: Amount::new(455).unwrap(),
+    };
+    let response = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&user_token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // Only admins can set rates
+    let rate = NewFxRate {
+        rate: Decimal::from_str("0.1").unwrap(),
+    };
+    let url = format!("{}/api/fx-rates/NOK/USD", app.address());
+    let response = client
+        .put(&url)
+        .bearer_auth(&user_token)
+        .json(&rate)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let response = client
+        .put(&url)
+        .bearer_auth(&admin_token)
+        .json(&rate)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let rates: Vec<FxRate> = client
+        .get(format!("{}/api/fx-rates", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(1, rates.len());
+    assert_eq!(Some(2), rates[0].updated_by);
+
+    // 4.55 NOK at 0.1 is 0.455 USD, which rounds to 0.46 USD
+    let transfer: Transfer = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&user_token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(455, transfer.amount);
+    assert_eq!(46, transfer.to_amount);
+    assert_eq!(Some(rate.rate), transfer.exchange_rate);
+
+    let account: Account = client
+        .get(format!(
+            "{}/api/users/1/accounts/{}",
+            app.address(),
+            usd_account.id()
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(46, account.balance());
+
+    // The ledger still balances
+    let mismatches: Vec<BalanceMismatch> = client
+        .get(format!("{}/api/ledger/reconciliation", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(mismatches.is_empty());
+}
@@ -0,0 +1,222 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    model::{
+        account_model::{Account, Withdrawal},
+        hold_model::{Hold, HoldCapture, HoldStatus, NewHold},
+        money::Amount,
+        transfer_model::NewTransfer,
+    },
+    worker::hold_expiry::release_expired_holds,
+};
+use chrono::{Duration, Utc};
+use reqwest::{Client, Response};
+
+async fn create_hold(
+    amount: i64,
+    expires_in: Duration,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> Response {
+    let new_hold = NewHold {
+        amount: Amount::new(amount).unwrap(),
+        expires_at: Utc::now() + expires_in,
+    };
+    client
+        .post(format!("{}/api/users/1/accounts/1/holds", app.address()))
+        .bearer_auth(token)
+        .json(&new_hold)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn capture(
+    hold_id: i32,
+    amount: Option<i64>,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> Response {
+    let capture = HoldCapture {
+        amount: amount.map(|amount| Amount::new(amount).unwrap()),
+    };
+    client
+        .post(format!(
+            "{}/api/users/1/accounts/1/holds/{}/capture",
+            app.address(),
+            hold_id
+        ))
+        .bearer_auth(token)
+        .json(&capture)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn fetch_account(token: &str, client: &Client, app: &TestApp) -> Account {
+    client
+        .get(format!("{}/api/users/1/accounts/1", app.address()))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn holds_reserve_money_until_captured_or_released() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let token = rest::authenticate(&app, "user", "user").await;
+
+    let response = create_hold(60, Duration::hours(1), &token, &client, &app).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let hold: Hold = response.json().await.unwrap();
+    assert_eq!(HoldStatus::Active, hold.status);
+    let account = fetch_account(&token, &client, &app).await;
+    assert_eq!(
+        (100, 60, 40),
+        (
+            account.balance(),
+            account.held(),
+            account.available_balance()
+        )
+    );
+
+    // Reserved money cannot be withdrawn or transferred
+    let response = client
+        .post(format!(
+            "{}/api/users/1/accounts/1/withdrawals",
+            app.address()
+        ))
+        .bearer_auth(&token)
+        .json(&Withdrawal::new(Amount::new(50).unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let new_transfer = NewTransfer {
+        from_account: 1,
+        to_account: 2,
+        amount: Amount::new(50).unwrap(),
+    };
+    let response = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = create_hold(50, Duration::hours(1), &token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // A partial capture takes the captured amount and frees the rest
+    let response = capture(hold.id, Some(70), &token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = capture(hold.id, Some(45), &token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let hold: Hold = response.json().await.unwrap();
+    assert_eq!(HoldStatus::Captured, hold.status);
+    assert_eq!(Some(45), hold.captured_amount);
+    let account = fetch_account(&token, &client, &app).await;
+    assert_eq!((55, 0), (account.balance(), account.held()));
+    let response = capture(hold.id, None, &token, &client, &app).await;
+    assert_eq!(StatusCode::CONFLICT, response.status());
+
+    // Releasing frees everything
+    let hold: Hold = create_hold(20, Duration::hours(1), &token, &client, &app)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let response = client
+        .post(format!(
+            "{}/api/users/1/accounts/1/holds/{}/release",
+            app.address(),
+            hold.id
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let hold: Hold = response.json().await.unwrap();
+    assert_eq!(HoldStatus::Released, hold.status);
+    let account = fetch_account(&token, &client, &app).await;
+    assert_eq!((55, 0), (account.balance(), account.held()));
+
+    let response = create_hold(20, Duration::days(31), &token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[actix_web::test]
+async fn expired_holds_are_released() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let token = rest::authenticate(&app, "user", "user").await;
+
+    let hold: Hold = create_hold(30, Duration::hours(1), &token, &client, &app)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        0,
+        release_expired_holds(app.db(), Utc::now()).await.unwrap()
+    );
+    let later = Utc::now() + Duration::hours(2);
+    assert_eq!(1, release_expired_holds(app.db(), later).await.unwrap());
+    assert_eq!(0, release_expired_holds(app.db(), later).await.unwrap());
+
+    let holds: Vec<Hold> = client
+        .get(format!(
+            "{}/api/users/1/accounts/1/holds?status=Expired",
+            app.address()
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![hold.id],
+        holds.iter().map(|h| h.id).collect::<Vec<_>>()
+    );
+    let account = fetch_account(&token, &client, &app).await;
+    assert_eq!((100, 0), (account.balance(), account.held()));
+    let response = capture(hold.id, None, &token, &client, &app).await;
+    assert_eq!(StatusCode::CONFLICT, response.status());
+}
+
+#[actix_web::test]
+async fn holds_cannot_be_captured_from_closed_accounts() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let token = rest::authenticate(&app, "user", "user").await;
+
+    let hold: Hold = create_hold(60, Duration::hours(1), &token, &client, &app)
+        .await
+        .json()
+        .await
+        .unwrap();
+    // Closing is refused while money is held, so close the account behind the API's back
+    sqlx::query!("UPDATE accounts SET status = 'Closed' WHERE id = 1")
+        .execute(app.db())
+        .await
+        .unwrap();
+
+    let response = capture(hold.id, None, &token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let account = fetch_account(&token, &client, &app).await;
+    assert_eq!((100, 60), (account.balance(), account.held()));
+}
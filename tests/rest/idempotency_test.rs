@@ -0,0 +1,141 @@
+use crate::{common::spawn_test_app, rest};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    model::{account_model::Account, money::Amount, transfer_model::NewTransfer},
+    worker::idempotency_expiry::purge_expired_keys,
+};
+use chrono::{Duration, Utc};
+
+#[actix_web::test]
+async fn repeated_transfer_is_only_executed_once() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let new_transfer = NewTransfer {
+        from_account: 1,
+        to_account: 2,
+        amount: Amount::new(50).unwrap(),
+    };
+
+    // Send the same transfer twice
+    let first = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&user_token)
+        .header("Idempotency-Key", "transfer-1")
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, first.status());
+    assert!(first.headers().get("Idempotent-Replayed").is_none());
+    let first_body = first.text().await.unwrap();
+
+    let second = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&user_token)
+        .header("Idempotency-Key", "transfer-1")
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, second.status());
+    assert_eq!("true", second.headers().get("Idempotent-Replayed").unwrap());
+    assert_eq!(first_body, second.text().await.unwrap());
+
+    // The money only moved once
+    let account: Account = client
+        .get(format!("{}/api/users/1/accounts/1", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(50, account.balance());
+}
+
+#[actix_web::test]
+async fn reusing_key_for_different_request_gives_422() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let response = client
+        .post(format!("{}/api/users/1/accounts/1/deposits", app.address()))
+        .bearer_auth(&user_token)
+        .header("Idempotency-Key", "deposit-1")
+        .json(&serde_json::json!({ "amount": 10 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = client
+        .post(format!("{}/api/users/1/accounts/1/deposits", app.address()))
+        .bearer_auth(&user_token)
+        .header("Idempotency-Key", "deposit-1")
+        .json(&serde_json::json!({ "amount": 20 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, response.status());
+}
+
+#[actix_web::test]
+async fn keys_are_scoped_to_the_user() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    for (token, user_id, account_id) in [(&user_token, 1, 1), (&admin_token, 2, 3)] {
+        let response = client
+            .post(format!(
+                "{}/api/users/{}/accounts/{}/deposits",
+                app.address(),
+                user_id,
+                account_id
+            ))
+            .bearer_auth(token)
+            .header("Idempotency-Key", "same-key")
+            .json(&serde_json::json!({ "amount": 10 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert!(response.headers().get("Idempotent-Replayed").is_none());
+    }
+}
+
+#[actix_web::test]
+async fn expired_keys_are_purged() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let response = client
+        .post(format!("{}/api/users/1/accounts/1/deposits", app.address()))
+        .bearer_auth(&user_token)
+        .header("Idempotency-Key", "deposit-1")
+        .json(&serde_json::json!({ "amount": 10 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let count_keys = || async {
+        sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM idempotency_keys"#)
+            .fetch_one(app.db())
+            .await
+            .unwrap()
+    };
+
+    // Keys that have not expired are kept
+    assert_eq!(0, purge_expired_keys(app.db(), Utc::now()).await.unwrap());
+    assert_eq!(1, count_keys().await);
+
+    let after_expiry = Utc::now() + Duration::days(2);
+    assert_eq!(1, purge_expired_keys(app.db(), after_expiry).await.unwrap());
+    assert_eq!(0, count_keys().await);
+}
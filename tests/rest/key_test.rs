@@ -0,0 +1,79 @@
+use crate::{common::spawn_test_app, rest};
+use actix_http::StatusCode;
+use actix_web_demo::infra::security::keys::{JwkSet, JwtKey, KeyStatus};
+
+#[actix_web::test]
+async fn keys_are_published_and_rotated() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let jwks: JwkSet = client
+        .get(format!("{}/.well-known/jwks.json", app.address()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(jwks.keys.iter().any(|key| key.kid == "2022-05-01"));
+    assert!(jwks
+        .keys
+        .iter()
+        .all(|key| key.kty == "EC" && key.alg == "ES256"));
+
+    let response = client
+        .post(format!("{}/api/jwt-keys/rotate", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let response = client
+        .post(format!("{}/api/jwt-keys/rotate", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+    let key: JwtKey = response.json().await.unwrap();
+    assert_eq!(KeyStatus::Active, key.status);
+
+    // New tokens are signed with the new key, and tokens signed before are still accepted
+    let token = rest::authenticate(&app, "user", "user").await;
+    let header = jsonwebtoken::decode_header(&token).unwrap();
+    assert_eq!(Some(key.kid.clone()), header.kid);
+    for token in [&token, &user_token] {
+        let response = client
+            .get(format!("{}/verify", app.address()))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+    }
+    let jwks: JwkSet = client
+        .get(format!("{}/.well-known/jwks.json", app.address()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(jwks.keys.iter().any(|jwk| jwk.kid == key.kid));
+    assert!(jwks.keys.iter().any(|jwk| jwk.kid == "2022-05-01"));
+
+    let keys: Vec<JwtKey> = client
+        .get(format!("{}/api/jwt-keys", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let previous = keys.iter().find(|k| k.kid == "2022-05-01").unwrap();
+    assert_eq!(KeyStatus::Verifying, previous.status);
+    assert!(previous.retires_at.is_some());
+}
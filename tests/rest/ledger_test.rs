@@ -0,0 +1,127 @@
+use crate::{common::spawn_test_app, rest};
+use actix_http::StatusCode;
+use actix_web_demo::model::{
+    account_model::{Deposit, Withdrawal},
+    ledger_model::{BalanceMismatch, LedgerEntry, LedgerEntryKind},
+    money::Amount,
+    pagination::CursorPage,
+    transfer_model::NewTransfer,
+};
+
+#[actix_web::test]
+async fn money_movements_are_recorded_in_the_ledger() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    // Deposit, withdraw and transfer
+    let response = client
+        .post(format!("{}/api/users/1/accounts/1/deposits", app.address()))
+        .bearer_auth(&user_token)
+        .json(&Deposit::new(Amount::new(50).unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let response = client
+        .post(format!(
+            "{}/api/users/1/accounts/1/withdrawals",
+            app.address()
+        ))
+        .bearer_auth(&user_token)
+        .json(&Withdrawal::new(Amount::new(30).unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let response = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&user_token)
+        .json(&NewTransfer {
+            from_account: 1,
+            to_account: 2,
+            amount: Amount::new(20).unwrap(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+
+    // Each movement is an entry, newest first, with the balance after it
+    let response = client
+        .get(format!("{}/api/users/1/accounts/1/entries", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let entries: CursorPage<LedgerEntry> = response.json().await.unwrap();
+    let movements: Vec<(LedgerEntryKind, i64, i64)> = entries
+        .items
+        .iter()
+        .map(|e| (e.kind, e.amount, e.balance))
+        .collect();
+    assert_eq!(
+        vec![
+            (LedgerEntryKind::Transfer, -20, 100),
+            (LedgerEntryKind::Withdrawal, -30, 120),
+            (LedgerEntryKind::Deposit, 50, 150),
+            (LedgerEntryKind::Opening, 100, 100),
+        ],
+        movements
+    );
+
+    // Balances agree with the ledger
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let mismatches: Vec<BalanceMismatch> = client
+        .get(format!("{}/api/ledger/reconciliation", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(mismatches.is_empty());
+}
+
+#[actix_web::test]
+async fn reconciliation_reports_tampered_balances() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    // Change a balance behind the ledger's back
+    sqlx::query!("UPDATE accounts SET balance = 1000 WHERE id = 2")
+        .execute(app.db())
+        .await
+        .unwrap();
+
+    // Only admins can reconcile
+    let response = client
+        .get(format!("{}/api/ledger/reconciliation", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let mismatches: Vec<BalanceMismatch> = client
+        .get(format!("{}/api/ledger/reconciliation", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![BalanceMismatch {
+            account_id: 2,
+            balance: 1000,
+            ledger_balance: 500,
+        }],
+        mismatches
+    );
+}
@@ -0,0 +1,204 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::model::{
+    account_model::{Account, Withdrawal},
+    limit_model::{AccountLimits, NewUserSpendingLimit, UserSpendingLimit},
+    money::Amount,
+    transfer_model::NewTransfer,
+};
+use reqwest::{Client, Response};
+
+async fn withdraw(
+    account_id: i32,
+    amount: i64,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> Response {
+    client
+        .post(format!(
+            "{}/api/users/1/accounts/{}/withdrawals",
+            app.address(),
+            account_id
+        ))
+        .bearer_auth(token)
+        .json(&Withdrawal::new(Amount::new(amount).unwrap()))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn set_account_limits(
+    account_id: i32,
+    limits: &AccountLimits,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> Response {
+    client
+        .put(format!(
+            "{}/api/accounts/{}/limits",
+            app.address(),
+            account_id
+        ))
+        .bearer_auth(token)
+        .json(limits)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn overdraft_allows_negative_balance_down_to_limit() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    let limits = AccountLimits {
+        overdraft_limit: 50,
+        ..AccountLimits::default()
+    };
+
+    // Only admins can change limits
+    let response = set_account_limits(1, &limits, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let response = set_account_limits(1, &limits, &admin_token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let account: Account = response.json().await.unwrap();
+    assert_eq!(50, account.overdraft_limit());
+
+    let response = withdraw(1, 140, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = withdraw(1, 20, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let message = response.text().await.unwrap();
+    assert!(message.contains("10 available"), "{}", message);
+    assert!(message.contains("overdraft limit of 50"), "{}", message);
+
+    // The overdraft cannot be removed while it is in use
+    let response =
+        set_account_limits(1, &AccountLimits::default(), &admin_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // Negative limits are rejected
+    let response = client
+        .put(format!("{}/api/accounts/1/limits", app.address()))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "overdraft_limit": -1 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[actix_web::test]
+async fn daily_account_limit_applies_to_withdrawals_and_transfers() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    let limits = AccountLimits {
+        daily_limit: Some(100),
+        ..AccountLimits::default()
+    };
+    let response = set_account_limits(2, &limits, &admin_token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = withdraw(2, 60, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let new_transfer = NewTransfer {
+        from_account: 2,
+        to_account: 1,
+        amount: Amount::new(50).unwrap(),
+    };
+    let response = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&user_token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let message = response.text().await.unwrap();
+    assert!(
+        message.contains("daily limit of account 2 of 100, 40 remaining"),
+        "{}",
+        message
+    );
+
+    // Spending up to the limit is fine
+    let response = withdraw(2, 40, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[actix_web::test]
+async fn user_limit_applies_across_accounts() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let url = format!("{}/api/users/1/limits/NOK", app.address());
+
+    let new_limit = NewUserSpendingLimit {
+        daily_limit: Some(50),
+        monthly_limit: None,
+    };
+    let response = client
+        .put(&url)
+        .bearer_auth(&user_token)
+        .json(&new_limit)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let response = client
+        .put(&url)
+        .bearer_auth(&admin_token)
+        .json(&new_limit)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    // Users can see their own limits
+    let limits: Vec<UserSpendingLimit> = client
+        .get(format!("{}/api/users/1/limits", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(1, limits.len());
+    assert_eq!(Some(50), limits[0].daily_limit);
+
+    let response = withdraw(1, 30, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let response = withdraw(2, 30, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let message = response.text().await.unwrap();
+    assert!(
+        message.contains("daily NOK limit of user 1 of 50, 20 remaining"),
+        "{}",
+        message
+    );
+
+    let response = client
+        .delete(&url)
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let response = withdraw(2, 30, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+}
@@ -0,0 +1,186 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use reqwest::Client;
+
+async fn sign_in(client: &Client, app: &TestApp, username: &str, password: &str) -> StatusCode {
+    client
+        .post(format!("{}/token", app.address()))
+        .basic_auth(username, Some(password))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+/// Lets the next attempt through, as if the backoff had passed, but not a lockout.
+async fn skip_backoff(app: &TestApp) {
+    sqlx::query!(
+        r#"
+        UPDATE login_failures SET blocked_until = CURRENT_TIMESTAMP
+        WHERE blocked_until < CURRENT_TIMESTAMP + INTERVAL '10 minutes'
+        "#
+    )
+    .execute(app.db())
+    .await
+    .unwrap();
+}
+
+#[actix_web::test]
+async fn failed_sign_ins_back_off_and_lock_the_user() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+
+    // The first failures are free
+    for _ in 0..3 {
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            sign_in(&client, &app, "user", "wrong").await
+        );
+    }
+    assert_eq!(
+        StatusCode::CREATED,
+        sign_in(&client, &app, "user", "user").await
+    );
+
+    // Succeeding starts over, after which failures make the next attempt wait
+    for _ in 0..4 {
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            sign_in(&client, &app, "user", "wrong").await
+        );
+    }
+    assert_eq!(
+        StatusCode::TOO_MANY_REQUESTS,
+        sign_in(&client, &app, "user", "user").await
+    );
+
+    // Until the user is locked
+    for _ in 4..10 {
+        skip_backoff(&app).await;
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            sign_in(&client, &app, "user", "wrong").await
+        );
+    }
+    skip_backoff(&app).await;
+    assert_eq!(
+        StatusCode::TOO_MANY_REQUESTS,
+        sign_in(&client, &app, "user", "user").await
+    );
+
+    // Only admins can unlock users
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let response = client
+        .delete(format!("{}/api/users/1/lockout", app.address()))
+        .basic_auth("user", Some("user"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let response = client
+        .delete(format!("{}/api/users/1/lockout", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    assert_eq!(
+        StatusCode::CREATED,
+        sign_in(&client, &app, "user", "user").await
+    );
+}
+
+#[actix_web::test]
+async fn the_right_password_at_the_threshold_does_not_lock() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+
+    for _ in 0..9 {
+        skip_backoff(&app).await;
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            sign_in(&client, &app, "user", "wrong").await
+        );
+    }
+    skip_backoff(&app).await;
+    assert_eq!(
+        StatusCode::CREATED,
+        sign_in(&client, &app, "user", "user").await
+    );
+    let failures: i64 = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM login_failures WHERE scope = 'Username'"#
+    )
+    .fetch_one(app.db())
+    .await
+    .unwrap();
+    assert_eq!(0, failures);
+    assert_eq!(
+        StatusCode::CREATED,
+        sign_in(&client, &app, "user", "user").await
+    );
+}
+
+#[actix_web::test]
+async fn concurrent_sign_ins_cannot_skip_the_lockout() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+
+    // Only the free attempts and one more get through at once, then one after each backoff,
+    // until the user is locked
+    let mut failed = Vec::new();
+    for _ in 0..8 {
+        let attempts = (0..10).map(|_| sign_in(&client, &app, "user", "wrong"));
+        let statuses = futures::future::join_all(attempts).await;
+        assert!(statuses
+            .iter()
+            .all(|&status| status == StatusCode::UNAUTHORIZED
+                || status == StatusCode::TOO_MANY_REQUESTS));
+        failed.push(
+            statuses
+                .iter()
+                .filter(|&&status| status == StatusCode::UNAUTHORIZED)
+                .count(),
+        );
+        skip_backoff(&app).await;
+    }
+    assert_eq!(vec![4, 1, 1, 1, 1, 1, 1, 0], failed);
+    assert_eq!(
+        StatusCode::TOO_MANY_REQUESTS,
+        sign_in(&client, &app, "user", "user").await
+    );
+}
+
+#[actix_web::test]
+async fn unknown_usernames_fail_like_wrong_passwords() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+
+    for username in ["user", "nobody"] {
+        for _ in 0..4 {
+            let response = client
+                .post(format!("{}/token", app.address()))
+                .basic_auth(username, Some("wrong"))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+            assert_eq!("authentication error", response.text().await.unwrap());
+        }
+        assert_eq!(
+            StatusCode::TOO_MANY_REQUESTS,
+            sign_in(&client, &app, username, "user").await
+        );
+
+        // Basic authentication is throttled too
+        let response = client
+            .get(format!("{}/api/user", app.address()))
+            .basic_auth(username, Some("user"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::model::{
+    account_model::Withdrawal,
+    member_model::{AccountMember, AccountRole, NewAccountMember},
+    money::Amount,
+    transfer_model::{NewTransfer, Transfer},
+};
+use reqwest::{Client, Response};
+
+async fn invite(
+    user_id: i32,
+    role: AccountRole,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> Response {
+    client
+        .post(format!("{}/api/users/1/accounts/1/members", app.address()))
+        .bearer_auth(token)
+        .json(&NewAccountMember { user_id, role })
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn revoke(path: &str, token: &str, client: &Client, app: &TestApp) -> Response {
+    client
+        .delete(format!("{}/api{}", app.address(), path))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn transfer(token: &str, client: &Client, app: &TestApp) -> Response {
+    let new_transfer = NewTransfer {
+        from_account: 1,
+        to_account: 3,
+        amount: Amount::new(10).unwrap(),
+    };
+    client
+        .post(format!("{}/api/users/2/transfers", app.address()))
+        .bearer_auth(token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn viewers_can_only_read_shared_accounts() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let owner_token = rest::authenticate(&app, "user", "user").await;
+    let other_token = rest::authenticate(&app, "admin", "admin").await;
+
+    let response = client
+        .get(format!("{}/api/users/2/accounts/1", app.address()))
+        .bearer_auth(&other_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    let response = invite(2, AccountRole::Viewer, &owner_token, &client, &app).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let member: AccountMember = response.json().await.unwrap();
+    assert_eq!(
+        (1, 2, AccountRole::Viewer),
+        (member.account_id, member.user_id, member.role)
+    );
+    let response = invite(2, AccountRole::CoOwner, &owner_token, &client, &app).await;
+    assert_eq!(StatusCode::CONFLICT, response.status());
+    let response = invite(2, AccountRole::Owner, &owner_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = invite(99, AccountRole::Viewer, &owner_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // The shared account is listed with the viewer's own accounts
+    let page: serde_json::Value = client
+        .get(format!("{}/api/users/2/accounts", app.address()))
+        .bearer_auth(&other_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let ids: Vec<_> = page["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(vec![1, 3], ids);
+    assert_eq!(2, page["total"]);
+
+    // Viewers cannot move money or manage members
+    let response = client
+        .post(format!(
+            "{}/api/users/2/accounts/1/withdrawals",
+            app.address()
+        ))
+        .bearer_auth(&other_token)
+        .json(&Withdrawal::new(Amount::new(10).unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let response = transfer(&other_token, &client, &app).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let response = revoke("/users/2/accounts/1/members/1", &other_token, &client, &app).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let members: Vec<AccountMember> = client
+        .get(format!("{}/api/users/2/accounts/1/members", app.address()))
+        .bearer_auth(&other_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![(1, AccountRole::Owner), (2, AccountRole::Viewer)],
+        members
+            .iter()
+            .map(|m| (m.user_id, m.role))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[actix_web::test]
+async fn co_owners_can_move_money_until_revoked() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let owner_token = rest::authenticate(&app, "user", "user").await;
+    let other_token = rest::authenticate(&app, "admin", "admin").await;
+
+    let response = invite(2, AccountRole::CoOwner, &owner_token, &client, &app).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let response = transfer(&other_token, &client, &app).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let transfer_id = response.json::<Transfer>().await.unwrap().id;
+
+    // Both members see the transfer
+    for (user_id, token) in [(1, &owner_token), (2, &other_token)] {
+        let page: serde_json::Value = client
+            .get(format!(
+                "{}/api/users/{}/transfers?direction=outgoing",
+                app.address(),
+                user_id
+            ))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(transfer_id, page["items"][0]["id"]);
+    }
+
+    // Co-owners cannot close the account, and the owner cannot be removed
+    let response = client
+        .post(format!("{}/api/users/2/accounts/1/close", app.address()))
+        .bearer_auth(&other_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let response = revoke("/users/1/accounts/1/members/1", &owner_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = revoke("/users/1/accounts/1/members/2", &owner_token, &client, &app).await;
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let response = transfer(&other_token, &client, &app).await;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    let response = revoke("/users/1/accounts/1/members/2", &owner_token, &client, &app).await;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    // Members can leave on their own
+    invite(2, AccountRole::Viewer, &owner_token, &client, &app).await;
+    let response = revoke("/users/2/accounts/1/members/2", &other_token, &client, &app).await;
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+}
+
+#[actix_web::test]
+async fn owners_of_shared_accounts_cannot_be_deleted() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let owner_token = rest::authenticate(&app, "user", "user").await;
+    let other_token = rest::authenticate(&app, "admin", "admin").await;
+
+    invite(2, AccountRole::CoOwner, &owner_token, &client, &app).await;
+    sqlx::query!("UPDATE accounts SET status = 'Closed' WHERE id <> 1")
+        .execute(app.db())
+        .await
+        .unwrap();
+
+    // The shared account keeps the owner from being deleted, but not the co-owner
+    let response = revoke("/users/1", &owner_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = revoke("/users/2", &other_token, &client, &app).await;
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let members: Vec<AccountMember> = client
+        .get(format!("{}/api/users/1/accounts/1/members", app.address()))
+        .bearer_auth(&owner_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![(1, AccountRole::Owner)],
+        members
+            .iter()
+            .map(|m| (m.user_id, m.role))
+            .collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,277 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    infra::security::{jwt::Claims, mfa},
+    model::{
+        account_model::{Account, NewAccount},
+        mfa_model::{MfaChallenge, MfaVerification, RecoveryCodes, TotpCode, TotpEnrollment},
+        money::{Amount, CurrencyCode},
+        session_model::TokenResponse,
+        transfer_model::NewTransfer,
+    },
+};
+use chrono::{Duration, Utc};
+use reqwest::{Client, Response};
+
+/// Enrolls the user with id 1, returning the secret and the recovery codes.
+pub(super) async fn enroll(client: &Client, app: &TestApp) -> (String, Vec<String>) {
+    let token = rest::authenticate(app, "user", "user").await;
+    let response = client
+        .post(format!("{}/api/users/1/mfa/totp", app.address()))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+    let enrollment: TotpEnrollment = response.json().await.unwrap();
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+
+    let response = client
+        .post(format!("{}/api/users/1/mfa/totp/confirm", app.address()))
+        .bearer_auth(&token)
+        .json(&TotpCode {
+            code: "000000x".to_string(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let code = mfa::generate_code(&enrollment.secret, Utc::now()).unwrap();
+    let response = client
+        .post(format!("{}/api/users/1/mfa/totp/confirm", app.address()))
+        .bearer_auth(&token)
+        .json(&TotpCode { code })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let codes: RecoveryCodes = response.json().await.unwrap();
+    assert_eq!(mfa::RECOVERY_CODE_COUNT, codes.recovery_codes.len());
+
+    (enrollment.secret, codes.recovery_codes)
+}
+
+/// Signs in with a password, expecting a challenge.
+async fn challenge(client: &Client, app: &TestApp) -> MfaChallenge {
+    let response = client
+        .post(format!("{}/token", app.address()))
+        .basic_auth("user", Some("user"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::ACCEPTED, response.status());
+    response.json().await.unwrap()
+}
+
+async fn verify(verification: &MfaVerification, client: &Client, app: &TestApp) -> Response {
+    client
+        .post(format!("{}/token/mfa", app.address()))
+        .json(verification)
+        .send()
+        .await
+        .unwrap()
+}
+
+pub(super) async fn claims(access_token: &str, client: &Client, app: &TestApp) -> Claims {
+    client
+        .get(format!("{}/verify", app.address()))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn enrolled_users_sign_in_with_a_code() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let (secret, _) = enroll(&client, &app).await;
+
+    let challenge = challenge(&client, &app).await;
+    let response = verify(
+        &MfaVerification {
+            mfa_token: challenge.mfa_token,
+            code: Some("123456".to_string()),
+            recovery_code: None,
+        },
+        &client,
+        &app,
+    )
+    .await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    // The code used for confirming cannot be used again, so take the next one
+    let code = mfa::generate_code(&secret, Utc::now() + Duration::seconds(30)).unwrap();
+    let verification = MfaVerification {
+        mfa_token: challenge.mfa_token,
+        code: Some(code),
+        recovery_code: None,
+    };
+    let response = verify(&verification, &client, &app).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let tokens: TokenResponse = response.json().await.unwrap();
+    let claims = claims(&tokens.access_token, &client, &app).await;
+    assert_eq!(1, claims.id());
+    assert!(claims.has_mfa());
+    assert_eq!(vec!["pwd", "otp", "mfa"], claims.amr().to_vec());
+
+    // Challenges can only be completed once
+    let response = verify(&verification, &client, &app).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    // The second factor cannot be skipped with basic authentication
+    let response = client
+        .get(format!("{}/api/user", app.address()))
+        .basic_auth("user", Some("user"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+}
+
+#[actix_web::test]
+async fn recovery_codes_can_only_be_used_once() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let (_, recovery_codes) = enroll(&client, &app).await;
+
+    let verification = MfaVerification {
+        mfa_token: challenge(&client, &app).await.mfa_token,
+        code: None,
+        recovery_code: Some(recovery_codes[0].clone()),
+    };
+    let response = verify(&verification, &client, &app).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let tokens: TokenResponse = response.json().await.unwrap();
+    let claims = claims(&tokens.access_token, &client, &app).await;
+    assert_eq!(vec!["pwd", "mfa"], claims.amr().to_vec());
+
+    let verification = MfaVerification {
+        mfa_token: challenge(&client, &app).await.mfa_token,
+        ..verification
+    };
+    let response = verify(&verification, &client, &app).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    // Removing the second factor needs a token from a sign-in with it
+    let password_token = rest::authenticate(&app, "admin", "admin").await;
+    let response = client
+        .delete(format!("{}/api/users/1/mfa", app.address()))
+        .bearer_auth(&tokens.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let response = client
+        .delete(format!("{}/api/users/1/mfa", app.address()))
+        .bearer_auth(&password_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    assert!(!rest::authenticate(&app, "user", "user").await.is_empty());
+}
+
+#[actix_web::test]
+async fn large_transfers_require_a_second_factor() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let password_token = rest::authenticate(&app, "user", "user").await;
+    let (_, recovery_codes) = enroll(&client, &app).await;
+
+    let new_transfer = NewTransfer {
+        from_account: 1,
+        to_account: 2,
+        amount: Amount::new(1_000_001).unwrap(),
+    };
+    let response = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&password_token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let verification = MfaVerification {
+        mfa_token: challenge(&client, &app).await.mfa_token,
+        code: None,
+        recovery_code: Some(recovery_codes[0].clone()),
+    };
+    let tokens: TokenResponse = verify(&verification, &client, &app)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let response = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&tokens.access_token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    // Past the second factor check, but the account does not have the money
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[actix_web::test]
+async fn transfer_thresholds_depend_on_the_currency() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let token = rest::authenticate(&app, "user", "user").await;
+
+    // About the same worth, with 0 and 3 decimals
+    for (currency, below, above) in [("JPY", 140_000, 160_000), ("KWD", 290_000, 310_000)] {
+        let mut accounts = Vec::new();
+        for name in ["from", "to"] {
+            let new_account = NewAccount::new(name.to_string())
+                .with_currency(CurrencyCode::new(currency).unwrap());
+            let account: Account = client
+                .post(format!("{}/api/users/1/accounts", app.address()))
+                .bearer_auth(&token)
+                .json(&new_account)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            accounts.push(account.id());
+        }
+        let response = client
+            .post(format!(
+                "{}/api/users/1/accounts/{}/deposits",
+                app.address(),
+                accounts[0]
+            ))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "amount": above }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+
+        for (amount, status) in [(above, StatusCode::FORBIDDEN), (below, StatusCode::CREATED)] {
+            let new_transfer = NewTransfer {
+                from_account: accounts[0],
+                to_account: accounts[1],
+                amount: Amount::new(amount).unwrap(),
+            };
+            let response = client
+                .post(format!("{}/api/users/1/transfers", app.address()))
+                .bearer_auth(&token)
+                .json(&new_transfer)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(status, response.status(), "{} {}", amount, currency);
+        }
+    }
+}
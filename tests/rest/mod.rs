@@ -1,23 +1,46 @@
 use crate::common::TestApp;
+use actix_web_demo::model::session_model::TokenResponse;
 
 mod account_test;
+mod api_key_test;
 mod auth_test;
+mod currency_test;
 mod digest_test;
+mod hold_test;
+mod idempotency_test;
+mod key_test;
+mod ledger_test;
+mod limit_test;
+mod lockout_test;
+mod member_test;
+mod mfa_test;
+mod oauth_test;
+mod password_test;
+mod payment_import_test;
+mod product_test;
+mod role_test;
+mod scheduled_transfer_test;
 mod security_test;
+mod session_test;
 mod signature_test;
+mod statement_test;
+mod transfer_batch_test;
 mod transfer_test;
 mod user_test;
 
 pub async fn authenticate(app: &TestApp, username: &str, password: &str) -> String {
+    sign_in(app, username, password).await.access_token
+}
+
+pub async fn sign_in(app: &TestApp, username: &str, password: &str) -> TokenResponse {
     let client = reqwest::Client::new();
-    let token = client
+    client
         .post(format!("{}/token", app.address()))
         .basic_auth(username, Some(password))
         .send()
         .await
         .expect("failed to login")
-        .text()
+        .json()
         .await
-        .unwrap();
-    token
+        .unwrap()
 }
@@ -0,0 +1,439 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest::{self, mfa_test},
+};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    infra::security::{mfa, oauth::pkce_challenge},
+    model::oauth_model::{
+        CreatedOAuthClient, NewOAuthClient, OAuthTokenResponse, ProviderMetadata,
+    },
+};
+use chrono::{Duration, Utc};
+use reqwest::{redirect::Policy, Client, Response};
+use serde_json::Value;
+use std::collections::HashMap;
+
+const REDIRECT_URI: &str = "http://localhost:3000/callback";
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+async fn register(client: &Client, app: &TestApp, new_client: &NewOAuthClient) -> Response {
+    let token = rest::authenticate(app, "admin", "admin").await;
+    client
+        .post(format!("{}/api/oauth/clients", app.address()))
+        .bearer_auth(&token)
+        .json(new_client)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn register_browser_app(client: &Client, app: &TestApp) -> CreatedOAuthClient {
+    let new_client = NewOAuthClient {
+        name: "<Web app>".to_string(),
+        redirect_uris: vec![REDIRECT_URI.to_string()],
+        scopes: vec!["accounts:read".to_string(), "transfers:read".to_string()],
+        confidential: false,
+        user_id: None,
+    };
+    let response = register(client, app, &new_client).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let created: CreatedOAuthClient = response.json().await.unwrap();
+    assert_eq!(None, created.client_secret);
+    created
+}
+
+fn authorization_params(client_id: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("response_type", "code".to_string()),
+        ("client_id", client_id.to_string()),
+        ("redirect_uri", REDIRECT_URI.to_string()),
+        ("scope", "accounts:read".to_string()),
+        ("state", "xyz".to_string()),
+        ("code_challenge", pkce_challenge(CODE_VERIFIER)),
+        ("code_challenge_method", "S256".to_string()),
+    ]
+}
+
+async fn post_consent(client: &Client, app: &TestApp, form: &[(&str, String)]) -> Response {
+    client
+        .post(format!("{}/oauth/authorize", app.address()))
+        .form(form)
+        .send()
+        .await
+        .unwrap()
+}
+
+/// Returns the query of the URI a response redirects to.
+fn redirect_params(response: &Response) -> HashMap<String, String> {
+    assert_eq!(StatusCode::FOUND, response.status());
+    let location = response.headers()["Location"].to_str().unwrap();
+    assert!(location.starts_with(REDIRECT_URI));
+    url::Url::parse(location)
+        .unwrap()
+        .query_pairs()
+        .into_owned()
+        .collect()
+}
+
+async fn exchange_code(
+    client: &Client,
+    app: &TestApp,
+    client_id: &str,
+    code: &str,
+    code_verifier: &str,
+) -> Response {
+    client
+        .post(format!("{}/oauth/token", app.address()))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", REDIRECT_URI),
+            ("client_id", client_id),
+            ("code_verifier", code_verifier),
+        ])
+        .send()
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn confidential_clients_get_tokens_with_client_credentials() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let new_client = NewOAuthClient {
+        name: "Reporting".to_string(),
+        redirect_uris: vec![],
+        scopes: vec!["accounts:read".to_string()],
+        confidential: true,
+        user_id: Some(1),
+    };
+
+    // Only admins register clients, and public clients cannot act for a user
+    let token = rest::authenticate(&app, "user", "user").await;
+    let response = client
+        .post(format!("{}/api/oauth/clients", app.address()))
+        .bearer_auth(&token)
+        .json(&new_client)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let public_client = NewOAuthClient {
+        confidential: false,
+        ..new_client.clone()
+    };
+    let response = register(&client, &app, &public_client).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = register(&client, &app, &new_client).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let created: CreatedOAuthClient = response.json().await.unwrap();
+    let client_id = created.client.client_id.clone();
+    let client_secret = created.client_secret.unwrap();
+
+    let response = client
+        .post(format!("{}/oauth/token", app.address()))
+        .basic_auth(&client_id, Some(&client_secret))
+        .form(&[("grant_type", "client_credentials")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let tokens: OAuthTokenResponse = response.json().await.unwrap();
+    assert_eq!("Bearer", tokens.token_type);
+    assert_eq!("accounts:read", tokens.scope);
+
+    // The token acts for the user within the scopes of the client
+    let claims = mfa_test::claims(&tokens.access_token, &client, &app).await;
+    assert_eq!(1, claims.id());
+    assert_eq!(Some(client_id.as_str()), claims.client_id());
+    assert!(claims.roles().is_empty());
+    for (path, status) in [
+        ("/api/users/1/accounts", StatusCode::OK),
+        ("/api/users/1/transfers", StatusCode::FORBIDDEN),
+    ] {
+        let response = client
+            .get(format!("{}{}", app.address(), path))
+            .bearer_auth(&tokens.access_token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status, response.status());
+    }
+
+    // The secret can be sent in the body instead, but must be right
+    for (secret, status) in [
+        (client_secret.as_str(), StatusCode::OK),
+        ("wrong", StatusCode::UNAUTHORIZED),
+    ] {
+        let response = client
+            .post(format!("{}/oauth/token", app.address()))
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", &client_id),
+                ("client_secret", secret),
+            ])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status, response.status());
+    }
+    let response = client
+        .post(format!("{}/oauth/token", app.address()))
+        .basic_auth(&client_id, Some(&client_secret))
+        .form(&[
+            ("grant_type", "client_credentials"),
+            ("scope", "roles:manage"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let error: Value = response.json().await.unwrap();
+    assert_eq!("invalid_scope", error["error"]);
+
+    // Signing the user out everywhere revokes the tokens of their clients too
+    let response = client
+        .delete(format!("{}/api/users/1/sessions", app.address()))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let response = client
+        .get(format!("{}/api/users/1/accounts", app.address()))
+        .bearer_auth(&tokens.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    // Revoked clients cannot get tokens
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let response = client
+        .delete(format!(
+            "{}/api/oauth/clients/{}",
+            app.address(),
+            created.client.id
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let response = client
+        .post(format!("{}/oauth/token", app.address()))
+        .basic_auth(&client_id, Some(&client_secret))
+        .form(&[("grant_type", "client_credentials")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let error: Value = response.json().await.unwrap();
+    assert_eq!("invalid_client", error["error"]);
+}
+
+#[actix_web::test]
+async fn browser_apps_get_tokens_with_authorization_codes() {
+    let app = spawn_test_app().await;
+    let client = Client::builder().redirect(Policy::none()).build().unwrap();
+    let created = register_browser_app(&client, &app).await;
+    let client_id = created.client.client_id.as_str();
+    let params = authorization_params(client_id);
+
+    // The consent page shows the client and what it asks for
+    let response = client
+        .get(format!("{}/oauth/authorize", app.address()))
+        .query(&params)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let page = response.text().await.unwrap();
+    assert!(page.contains("&lt;Web app&gt; wants to act for you"));
+    assert!(page.contains("<li>accounts:read</li>"));
+    assert!(!page.contains("<li>transfers:read</li>"));
+
+    // Unregistered redirect URIs are not followed, but other errors go back to the client
+    let mut bad_params = params.clone();
+    bad_params[2].1 = "http://evil.example/callback".to_string();
+    let response = client
+        .get(format!("{}/oauth/authorize", app.address()))
+        .query(&bad_params)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = client
+        .get(format!("{}/oauth/authorize", app.address()))
+        .query(&params[..5])
+        .send()
+        .await
+        .unwrap();
+    let redirect = redirect_params(&response);
+    assert_eq!("invalid_request", redirect["error"]);
+    assert_eq!("xyz", redirect["state"]);
+
+    // Users can deny, or sign in and approve
+    let mut form = params.clone();
+    form.push(("decision", "deny".to_string()));
+    let redirect = redirect_params(&post_consent(&client, &app, &form).await);
+    assert_eq!("access_denied", redirect["error"]);
+
+    let mut form = params.clone();
+    form.push(("decision", "approve".to_string()));
+    form.push(("username", "user".to_string()));
+    form.push(("password", "wrong".to_string()));
+    let response = post_consent(&client, &app, &form).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Wrong username or password"));
+
+    form.last_mut().unwrap().1 = "user".to_string();
+    let redirect = redirect_params(&post_consent(&client, &app, &form).await);
+    assert_eq!("xyz", redirect["state"]);
+    let code = &redirect["code"];
+
+    // The code needs the verifier of the challenge, and works only once
+    let response = exchange_code(&client, &app, client_id, code, &"x".repeat(43)).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = exchange_code(&client, &app, client_id, code, CODE_VERIFIER).await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("no-store", response.headers()["Cache-Control"]);
+    let tokens: OAuthTokenResponse = response.json().await.unwrap();
+    assert_eq!("accounts:read", tokens.scope);
+    let claims = mfa_test::claims(&tokens.access_token, &client, &app).await;
+    assert_eq!(1, claims.id());
+    assert_eq!(vec!["pwd"], claims.amr().to_vec());
+
+    let accounts = |access_token: String| {
+        let request = client
+            .get(format!("{}/api/users/1/accounts", app.address()))
+            .bearer_auth(access_token);
+        async move { request.send().await.unwrap().status() }
+    };
+    assert_eq!(StatusCode::OK, accounts(tokens.access_token.clone()).await);
+
+    // Using the code again also revokes the token it was exchanged for
+    let response = exchange_code(&client, &app, client_id, code, CODE_VERIFIER).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let error: Value = response.json().await.unwrap();
+    assert_eq!("invalid_grant", error["error"]);
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        accounts(tokens.access_token).await
+    );
+}
+
+#[actix_web::test]
+async fn redirect_uris_given_for_the_code_must_be_given_for_the_token() {
+    let app = spawn_test_app().await;
+    let client = Client::builder().redirect(Policy::none()).build().unwrap();
+    let created = register_browser_app(&client, &app).await;
+    let client_id = created.client.client_id.as_str();
+    let exchange = |code: String, redirect_uri: Option<&'static str>| {
+        let client = client.clone();
+        let address = app.address().to_string();
+        let client_id = client_id.to_string();
+        async move {
+            let mut form = vec![
+                ("grant_type", "authorization_code".to_string()),
+                ("code", code),
+                ("client_id", client_id),
+                ("code_verifier", CODE_VERIFIER.to_string()),
+            ];
+            form.extend(redirect_uri.map(|uri| ("redirect_uri", uri.to_string())));
+            client
+                .post(format!("{}/oauth/token", address))
+                .form(&form)
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+
+    let mut form = authorization_params(client_id);
+    form.push(("decision", "approve".to_string()));
+    form.push(("username", "user".to_string()));
+    form.push(("password", "user".to_string()));
+    let code = redirect_params(&post_consent(&client, &app, &form).await)["code"].clone();
+    for redirect_uri in [None, Some("http://localhost/other")] {
+        let response = exchange(code.clone(), redirect_uri).await;
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        let error: Value = response.json().await.unwrap();
+        assert_eq!("invalid_grant", error["error"]);
+    }
+    let response = exchange(code, Some(REDIRECT_URI)).await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    // Codes for the only registered redirect URI can be exchanged without it
+    form.retain(|(name, _)| *name != "redirect_uri");
+    let code = redirect_params(&post_consent(&client, &app, &form).await)["code"].clone();
+    let response = exchange(code, None).await;
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[actix_web::test]
+async fn users_with_a_second_factor_give_it_on_the_consent_page() {
+    let app = spawn_test_app().await;
+    let client = Client::builder().redirect(Policy::none()).build().unwrap();
+    let (secret, _) = mfa_test::enroll(&client, &app).await;
+    let created = register_browser_app(&client, &app).await;
+    let client_id = created.client.client_id.as_str();
+
+    let mut form = authorization_params(client_id);
+    form.push(("decision", "approve".to_string()));
+    let mut password_form = form.clone();
+    password_form.push(("username", "user".to_string()));
+    password_form.push(("password", "user".to_string()));
+    let response = post_consent(&client, &app, &password_form).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let page = response.text().await.unwrap();
+    let mfa_token = page
+        .split("name=\"mfa_token\" value=\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap()
+        .to_string();
+
+    form.push(("mfa_token", mfa_token));
+    form.push(("code", "123456".to_string()));
+    let response = post_consent(&client, &app, &form).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    // The code used for confirming cannot be used again, so take the next one
+    let code = mfa::generate_code(&secret, Utc::now() + Duration::seconds(30)).unwrap();
+    form.last_mut().unwrap().1 = code;
+    let redirect = redirect_params(&post_consent(&client, &app, &form).await);
+    let response = exchange_code(&client, &app, client_id, &redirect["code"], CODE_VERIFIER).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let tokens: OAuthTokenResponse = response.json().await.unwrap();
+    let claims = mfa_test::claims(&tokens.access_token, &client, &app).await;
+    assert!(claims.has_mfa());
+}
+
+#[actix_web::test]
+async fn discovery_document_lists_the_endpoints() {
+    let app = spawn_test_app().await;
+    let response = reqwest::get(format!(
+        "{}/.well-known/openid-configuration",
+        app.address()
+    ))
+    .await
+    .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let metadata: ProviderMetadata = response.json().await.unwrap();
+    assert_eq!(
+        format!("{}/oauth/token", metadata.issuer),
+        metadata.token_endpoint
+    );
+    assert!(metadata.jwks_uri.ends_with("/.well-known/jwks.json"));
+    assert!(metadata
+        .scopes_supported
+        .contains(&"accounts:read".to_string()));
+    assert_eq!(vec!["S256"], metadata.code_challenge_methods_supported);
+}
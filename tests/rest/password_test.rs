@@ -0,0 +1,197 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    model::user_model::{NewUser, PasswordChange, User},
+    repository::user_repository,
+};
+use reqwest::{Client, Response};
+
+async fn register(client: &Client, app: &TestApp, name: &str, password: &str) -> Response {
+    client
+        .post(format!("{}/register", app.address()))
+        .json(&NewUser {
+            name: name.to_string(),
+            password: password.to_string(),
+        })
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn change_password(
+    client: &Client,
+    app: &TestApp,
+    token: &str,
+    user_id: i32,
+    current: &str,
+    new: &str,
+) -> StatusCode {
+    client
+        .put(format!("{}/api/users/{}/password", app.address(), user_id))
+        .bearer_auth(token)
+        .json(&PasswordChange {
+            current_password: current.to_string(),
+            new_password: new.to_string(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn stored_hash(app: &TestApp, name: &str) -> String {
+    sqlx::query_scalar!("SELECT password FROM users WHERE name = $1", name)
+        .fetch_one(app.db())
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn new_passwords_must_follow_the_policy() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+
+    for password in ["short", "Password123", &"x".repeat(129)] {
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            register(&client, &app, "alice", password).await.status(),
+            "{}",
+            password
+        );
+    }
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let response = client
+        .post(format!("{}/api/users", app.address()))
+        .bearer_auth(admin_token)
+        .json(&NewUser {
+            name: "alice".to_string(),
+            password: "iloveyou".to_string(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = register(&client, &app, "alice", "rabbit hole").await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let user: User = response.json().await.unwrap();
+    assert!(stored_hash(&app, "alice")
+        .await
+        .starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+    // Unknown usernames are checked against a hash made the same way
+    assert!(user_repository::unknown_user_password().starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+    let token = rest::authenticate(&app, "alice", "rabbit hole").await;
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        change_password(&client, &app, &token, user.id, "rabbit hole", "welcome1").await
+    );
+}
+
+#[actix_web::test]
+async fn recent_passwords_cannot_be_reused() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let passwords: Vec<String> = (0..6).map(|i| format!("rabbit hole {}", i)).collect();
+    let user: User = register(&client, &app, "alice", &passwords[0])
+        .await
+        .json()
+        .await
+        .unwrap();
+    let token = rest::authenticate(&app, "alice", &passwords[0]).await;
+
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        change_password(&client, &app, &token, user.id, &passwords[0], &passwords[0]).await
+    );
+    for pair in passwords.windows(2) {
+        assert_eq!(
+            StatusCode::NO_CONTENT,
+            change_password(&client, &app, &token, user.id, &pair[0], &pair[1]).await
+        );
+    }
+
+    // Only the last five passwords, including the current one, are remembered
+    assert_eq!(
+        StatusCode::BAD_REQUEST,
+        change_password(&client, &app, &token, user.id, &passwords[5], &passwords[1]).await
+    );
+    let history: i64 = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM password_history WHERE user_id = $1"#,
+        user.id
+    )
+    .fetch_one(app.db())
+    .await
+    .unwrap();
+    assert_eq!(4, history);
+    assert_eq!(
+        StatusCode::NO_CONTENT,
+        change_password(&client, &app, &token, user.id, &passwords[5], &passwords[0]).await
+    );
+}
+
+#[actix_web::test]
+async fn old_hashes_are_upgraded_on_sign_in() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    assert!(stored_hash(&app, "user").await.starts_with("$2"));
+
+    // Wrong passwords leave the hash alone
+    let response = client
+        .post(format!("{}/token", app.address()))
+        .basic_auth("user", Some("wrong"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(stored_hash(&app, "user").await.starts_with("$2"));
+
+    rest::authenticate(&app, "user", "user").await;
+    let upgraded = stored_hash(&app, "user").await;
+    assert!(upgraded.starts_with("$argon2id$"));
+    rest::authenticate(&app, "user", "user").await;
+    assert_eq!(upgraded, stored_hash(&app, "user").await);
+    assert!(stored_hash(&app, "admin").await.starts_with("$2"));
+}
+
+#[actix_web::test]
+async fn password_changes_count_failed_sign_ins_and_sign_out_other_sessions() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let token = rest::authenticate(&app, "user", "user").await;
+    let other_token = rest::authenticate(&app, "user", "user").await;
+
+    // Wrong current passwords count as failed sign-ins
+    for _ in 0..3 {
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            change_password(&client, &app, &token, 1, "wrong", "rabbit hole").await
+        );
+    }
+    let failures = sqlx::query_scalar!(
+        "SELECT failures FROM login_failures WHERE scope = 'Username' AND key = 'user'"
+    )
+    .fetch_one(app.db())
+    .await
+    .unwrap();
+    assert_eq!(3, failures);
+    assert_eq!(
+        StatusCode::NO_CONTENT,
+        change_password(&client, &app, &token, 1, "user", "rabbit hole").await
+    );
+
+    for (token, status) in [
+        (&token, StatusCode::OK),
+        (&other_token, StatusCode::UNAUTHORIZED),
+    ] {
+        let response = client
+            .get(format!("{}/api/users/1/accounts", app.address()))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status, response.status());
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    model::{
+        account_model::Account,
+        payment_import_model::{ImportedPaymentStatus, PaymentImportReport, PaymentImportStatus},
+    },
+    worker::payment_import::process_received_imports,
+};
+use reqwest::{Client, Response};
+
+/// A pain.001 file with one payment per `(debtor, creditor, amount)`.
+fn pain001(message_id: &str, payments: &[(&str, &str, &str)]) -> String {
+    let transactions: String = payments
+        .iter()
+        .enumerate()
+        .map(|(i, (debtor, creditor, amount))| {
+            format!(
+                r#"<PmtInf>
+      <PmtInfId>PMT-{i}</PmtInfId>
+      <PmtMtd>TRF</PmtMtd>
+      <DbtrAcct><Id><Othr><Id>{debtor}</Id></Othr></Id></DbtrAcct>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>E2E-{i}</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="NOK">{amount}</InstdAmt></Amt>
+        <CdtrAcct><Id><Othr><Id>{creditor}</Id></Othr></Id></CdtrAcct>
+      </CdtTrfTxInf>
+    </PmtInf>"#
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.03">
+  <CstmrCdtTrfInitn>
+    <GrpHdr>
+      <MsgId>{message_id}</MsgId>
+      <CreDtTm>2022-10-10T12:00:00</CreDtTm>
+      <NbOfTxs>{}</NbOfTxs>
+    </GrpHdr>
+    {transactions}
+  </CstmrCdtTrfInitn>
+</Document>"#,
+        payments.len()
+    )
+}
+
+async fn upload(body: String, token: &str, client: &Client, app: &TestApp) -> Response {
+    client
+        .post(format!("{}/api/users/1/payment-imports", app.address()))
+        .bearer_auth(token)
+        .header("Content-Type", "application/xml")
+        .body(body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn get_report(id: i32, token: &str, client: &Client, app: &TestApp) -> PaymentImportReport {
+    client
+        .get(format!(
+            "{}/api/users/1/payment-imports/{}",
+            app.address(),
+            id
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn balance(account_id: i32, token: &str, client: &Client, app: &TestApp) -> i64 {
+    let account: Account = client
+        .get(format!(
+            "{}/api/users/1/accounts/{}",
+            app.address(),
+            account_id
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    account.balance()
+}
+
+#[actix_web::test]
+async fn imported_payments_are_executed_and_reported() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let file = pain001(
+        "MSG-1",
+        &[
+            // Executed
+            ("1", "2", "0.30"),
+            // Not the caller's account
+            ("3", "1", "0.10"),
+            // Too many decimals
+            ("1", "2", "0.001"),
+            // Too little money
+            ("1", "2", "10.00"),
+        ],
+    );
+    let response = upload(file.clone(), &user_token, &client, &app).await;
+    assert_eq!(StatusCode::ACCEPTED, response.status());
+    let report: PaymentImportReport = response.json().await.unwrap();
+    assert_eq!(PaymentImportStatus::Received, report.import.status);
+    let statuses: Vec<_> = report.payments.iter().map(|p| p.status).collect();
+    assert_eq!(
+        vec![
+            ImportedPaymentStatus::Pending,
+            ImportedPaymentStatus::Rejected,
+            ImportedPaymentStatus::Rejected,
+            ImportedPaymentStatus::Pending,
+        ],
+        statuses
+    );
+    assert_eq!(Some("AC01"), report.payments[1].reason_code.as_deref());
+    assert_eq!(Some("AM12"), report.payments[2].reason_code.as_deref());
+
+    // The same file cannot be imported twice
+    let response = upload(file, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::CONFLICT, response.status());
+
+    assert_eq!(1, process_received_imports(app.db()).await.unwrap());
+    assert_eq!(0, process_received_imports(app.db()).await.unwrap());
+
+    let report = get_report(report.import.id, &user_token, &client, &app).await;
+    assert_eq!(PaymentImportStatus::PartiallyAccepted, report.import.status);
+    assert!(report.import.processed_at.is_some());
+    assert_eq!(ImportedPaymentStatus::Accepted, report.payments[0].status);
+    assert!(report.payments[0].transfer_id.is_some());
+    assert_eq!(ImportedPaymentStatus::Rejected, report.payments[3].status);
+    assert_eq!(Some("NARR"), report.payments[3].reason_code.as_deref());
+    assert_eq!(70, balance(1, &user_token, &client, &app).await);
+    assert_eq!(530, balance(2, &user_token, &client, &app).await);
+
+    // The status report lists every payment
+    let response = client
+        .get(format!(
+            "{}/api/users/1/payment-imports/{}/status-report",
+            app.address(),
+            report.import.id
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let xml = response.text().await.unwrap();
+    assert!(xml.contains("<OrgnlMsgId>MSG-1</OrgnlMsgId>"));
+    assert!(xml.contains("<GrpSts>PART</GrpSts>"));
+    assert!(xml.contains("<OrgnlEndToEndId>E2E-0</OrgnlEndToEndId><TxSts>ACSC</TxSts>"));
+    assert!(xml.contains("<OrgnlEndToEndId>E2E-1</OrgnlEndToEndId><TxSts>RJCT</TxSts>"));
+}
+
+#[actix_web::test]
+async fn large_imports_require_a_second_factor() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    // Each payment is below the threshold, but together they are above it
+    let file = pain001("MSG-1", &[("1", "2", "6000.00"), ("1", "2", "6000.00")]);
+    let response = upload(file, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let imports: i64 = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM payment_imports"#)
+        .fetch_one(app.db())
+        .await
+        .unwrap();
+    assert_eq!(0, imports);
+}
+
+#[actix_web::test]
+async fn invalid_files_are_rejected() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let response = upload("not xml".to_string(), &user_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // The header promises another transaction
+    let file = pain001("MSG-2", &[("1", "2", "0.30")]).replace("<NbOfTxs>1<", "<NbOfTxs>2<");
+    let response = upload(file, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // Other users' imports cannot be seen
+    let file = pain001("MSG-3", &[("1", "2", "0.30")]);
+    let report: PaymentImportReport = upload(file, &user_token, &client, &app)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let response = client
+        .get(format!(
+            "{}/api/users/2/payment-imports/{}",
+            app.address(),
+            report.import.id
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
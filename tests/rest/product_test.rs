@@ -0,0 +1,197 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    model::{
+        account_model::Account,
+        ledger_model::BalanceMismatch,
+        product_model::{
+            month_start, AccountProduct, AccrualHistory, NewAccountProduct, ProductAssignment,
+        },
+    },
+    worker::account_product::{accrue_interest, post_interest_and_fees},
+};
+use chrono::{Duration, Utc};
+use reqwest::{Client, Response};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+async fn create_product(
+    name: &str,
+    interest_rate: &str,
+    monthly_fee: i64,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> Response {
+    let product = NewAccountProduct {
+        name: name.to_string(),
+        interest_rate: Decimal::from_str(interest_rate).unwrap(),
+        monthly_fee,
+    };
+    client
+        .post(format!("{}/api/products", app.address()))
+        .bearer_auth(token)
+        .json(&product)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn assign_product(
+    account_id: i32,
+    product_id: Option<i32>,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> Response {
+    client
+        .put(format!(
+            "{}/api/accounts/{}/product",
+            app.address(),
+            account_id
+        ))
+        .bearer_auth(token)
+        .json(&ProductAssignment { product_id })
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn balance(account_id: i32, token: &str, client: &Client, app: &TestApp) -> i64 {
+    let account: Account = client
+        .get(format!(
+            "{}/api/users/1/accounts/{}",
+            app.address(),
+            account_id
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    account.balance()
+}
+
+#[actix_web::test]
+async fn products_are_managed_by_admins() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    let response = create_product("Savings", "0.025", 0, &user_token, &client, &app).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let response = create_product("Savings", "0.025", 0, &admin_token, &client, &app).await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let product: AccountProduct = response.json().await.unwrap();
+    assert_eq!(Decimal::from_str("0.025").unwrap(), product.interest_rate);
+
+    let response = create_product("Savings", "0.01", 0, &admin_token, &client, &app).await;
+    assert_eq!(StatusCode::CONFLICT, response.status());
+    let response = create_product("Negative", "-0.01", 0, &admin_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = create_product("Precise", "0.0000001", 0, &admin_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = assign_product(2, Some(product.id + 1), &admin_token, &client, &app).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = assign_product(2, Some(product.id), &admin_token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let account: Account = response.json().await.unwrap();
+    assert_eq!(Some(product.id), account.product_id());
+
+    let products: Vec<AccountProduct> = client
+        .get(format!("{}/api/products", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(vec![product], products);
+}
+
+#[actix_web::test]
+async fn interest_and_fees_are_posted_at_month_end() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    // 500 * 0.73 / 365 is exactly 1 per day
+    let savings: AccountProduct =
+        create_product("Savings", "0.73", 30, &admin_token, &client, &app)
+            .await
+            .json()
+            .await
+            .unwrap();
+    let checking: AccountProduct =
+        create_product("Checking", "0", 500, &admin_token, &client, &app)
+            .await
+            .json()
+            .await
+            .unwrap();
+    assign_product(2, Some(savings.id), &admin_token, &client, &app).await;
+    assign_product(1, Some(checking.id), &admin_token, &client, &app).await;
+
+    // Accrue the current day, and only once
+    let today = Utc::now().date_naive();
+    let tomorrow = today + Duration::days(1);
+    assert_eq!(1, accrue_interest(app.db(), tomorrow).await.unwrap());
+    assert_eq!(0, accrue_interest(app.db(), tomorrow).await.unwrap());
+
+    // Nothing is posted before the month has ended
+    assert_eq!(0, post_interest_and_fees(app.db(), today).await.unwrap());
+    let next_month = month_start(month_start(today) + Duration::days(32));
+    assert_eq!(
+        2,
+        post_interest_and_fees(app.db(), next_month).await.unwrap()
+    );
+    assert_eq!(
+        0,
+        post_interest_and_fees(app.db(), next_month).await.unwrap()
+    );
+
+    // Interest is paid and the fee charged, but the fee never exceeds the balance
+    assert_eq!(500 + 1 - 30, balance(2, &user_token, &client, &app).await);
+    assert_eq!(0, balance(1, &user_token, &client, &app).await);
+
+    let history: AccrualHistory = client
+        .get(format!("{}/api/accounts/2/accruals", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(1, history.accruals.len());
+    assert_eq!(today, history.accruals[0].accrual_date);
+    assert_eq!(500, history.accruals[0].balance);
+    assert_eq!(Decimal::ONE, history.accruals[0].amount);
+    assert_eq!(1, history.postings.len());
+    assert_eq!(month_start(today), history.postings[0].month);
+    assert_eq!(
+        (1, 30),
+        (history.postings[0].interest, history.postings[0].fee)
+    );
+
+    // The postings are in the ledger
+    let mismatches: Vec<BalanceMismatch> = client
+        .get(format!("{}/api/ledger/reconciliation", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(mismatches.is_empty());
+}
@@ -0,0 +1,227 @@
+use crate::{common::spawn_test_app, rest};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    infra::security::jwt::{Claims, Role},
+    model::{
+        money::Amount,
+        permission_model::{NewPermission, Permission, RoleDetails},
+        transfer_model::NewTransfer,
+    },
+};
+
+#[actix_web::test]
+async fn roles_and_permissions_are_data() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let response = client
+        .get(format!("{}/api/roles", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let roles: Vec<RoleDetails> = client
+        .get(format!("{}/api/roles", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(vec![Role::User], roles[1].implies);
+    assert!(roles[0]
+        .permissions
+        .contains(&"transfers:create".to_string()));
+
+    // Permissions flow into the claims, through the role hierarchy
+    let claims: Claims = client
+        .get(format!("{}/verify", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(&[Role::User, Role::Admin], claims.roles());
+    assert!(claims.has_permission("transfers:create"));
+    assert!(claims.has_permission("roles:manage"));
+
+    // New permissions can be defined and granted
+    let response = client
+        .post(format!("{}/api/permissions", app.address()))
+        .bearer_auth(&admin_token)
+        .json(&NewPermission {
+            name: "Reports".to_string(),
+            description: String::new(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = client
+        .post(format!("{}/api/permissions", app.address()))
+        .bearer_auth(&admin_token)
+        .json(&NewPermission {
+            name: "reports:read".to_string(),
+            description: "Read reports".to_string(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+    let permission: Permission = response.json().await.unwrap();
+    assert_eq!("reports:read", permission.name);
+    let response = client
+        .put(format!(
+            "{}/api/roles/User/permissions/reports:read",
+            app.address()
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let response = client
+        .put(format!(
+            "{}/api/roles/User/permissions/reports:write",
+            app.address()
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    let claims: Claims = client
+        .get(format!("{}/verify", app.address()))
+        .bearer_auth(rest::authenticate(&app, "user", "user").await)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(claims.has_permission("reports:read"));
+}
+
+#[actix_web::test]
+async fn handlers_check_permissions() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let transfer = |token: String| {
+        let client = client.clone();
+        let address = app.address().to_string();
+        async move {
+            client
+                .post(format!("{}/api/users/1/transfers", address))
+                .bearer_auth(token)
+                .json(&NewTransfer {
+                    from_account: 1,
+                    to_account: 2,
+                    amount: Amount::new(10).unwrap(),
+                })
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+
+    let response = client
+        .delete(format!(
+            "{}/api/roles/User/permissions/transfers:create",
+            app.address()
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let response = transfer(rest::authenticate(&app, "user", "user").await).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    // Each resource has its own permissions, so reading transfers still works
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let response = client
+        .get(format!("{}/api/users/1/scheduled-transfers", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let deposit = |token: String| {
+        let client = client.clone();
+        let address = app.address().to_string();
+        async move {
+            client
+                .post(format!("{}/api/users/1/accounts/1/deposits", address))
+                .bearer_auth(token)
+                .json(&serde_json::json!({ "amount": 10 }))
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+    assert_eq!(StatusCode::OK, deposit(user_token).await.status());
+    let response = client
+        .delete(format!(
+            "{}/api/roles/User/permissions/deposits:create",
+            app.address()
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let response = deposit(rest::authenticate(&app, "user", "user").await).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    // Roles can be granted to and revoked from users
+    let response = client
+        .put(format!("{}/api/users/1/roles/Admin", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let roles: Vec<Role> = client
+        .get(format!("{}/api/users/1/roles", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(vec![Role::User, Role::Admin], roles);
+    let response = client
+        .delete(format!("{}/api/users/2/roles/Admin", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    for role in ["Admin", "User"] {
+        let response = client
+            .delete(format!("{}/api/users/1/roles/{}", app.address(), role))
+            .bearer_auth(&admin_token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+    }
+
+    // Without roles the user has no permissions left
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let response = client
+        .get(format!("{}/api/users/1/accounts/1", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+}
@@ -0,0 +1,297 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    infra::configuration::{load_configuration, SchedulerSettings},
+    model::{
+        account_model::Account,
+        money::Amount,
+        pagination::CursorPage,
+        scheduled_transfer_model::{
+            NewScheduledTransfer, ScheduledTransfer, ScheduledTransferRun,
+            ScheduledTransferRunStatus, ScheduledTransferStatus, TransferRecurrence,
+        },
+    },
+    worker::scheduled_transfer::execute_due_transfers,
+};
+use chrono::{Duration, Utc};
+use reqwest::Client;
+
+fn settings() -> SchedulerSettings {
+    load_configuration().unwrap().scheduler
+}
+
+fn new_scheduled_transfer(amount: i64) -> NewScheduledTransfer {
+    NewScheduledTransfer {
+        from_account: 1,
+        to_account: 2,
+        amount: Amount::new(amount).unwrap(),
+        start_at: Utc::now(),
+        recurrence: None,
+        end_at: None,
+    }
+}
+
+async fn schedule(
+    new_transfer: &NewScheduledTransfer,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> ScheduledTransfer {
+    let response = client
+        .post(format!("{}/api/users/1/scheduled-transfers", app.address()))
+        .bearer_auth(token)
+        .json(new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+    response.json().await.unwrap()
+}
+
+async fn get_scheduled_transfer(
+    id: i32,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> ScheduledTransfer {
+    client
+        .get(format!(
+            "{}/api/users/1/scheduled-transfers/{}",
+            app.address(),
+            id
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn list_runs(
+    id: i32,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> Vec<ScheduledTransferRun> {
+    let page: CursorPage<ScheduledTransferRun> = client
+        .get(format!(
+            "{}/api/users/1/scheduled-transfers/{}/runs",
+            app.address(),
+            id
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    page.items
+}
+
+async fn balance(account_id: i32, token: &str, client: &Client, app: &TestApp) -> i64 {
+    let account: Account = client
+        .get(format!(
+            "{}/api/users/1/accounts/{}",
+            app.address(),
+            account_id
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    account.balance()
+}
+
+#[actix_web::test]
+async fn one_off_scheduled_transfer_runs_once() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let scheduled = schedule(&new_scheduled_transfer(30), &user_token, &client, &app).await;
+    assert_eq!(ScheduledTransferStatus::Active, scheduled.status);
+
+    assert_eq!(
+        1,
+        execute_due_transfers(app.db(), &settings()).await.unwrap()
+    );
+    assert_eq!(
+        0,
+        execute_due_transfers(app.db(), &settings()).await.unwrap()
+    );
+
+    assert_eq!(70, balance(1, &user_token, &client, &app).await);
+    assert_eq!(530, balance(2, &user_token, &client, &app).await);
+
+    let scheduled = get_scheduled_transfer(scheduled.id, &user_token, &client, &app).await;
+    assert_eq!(ScheduledTransferStatus::Completed, scheduled.status);
+    let runs = list_runs(scheduled.id, &user_token, &client, &app).await;
+    assert_eq!(1, runs.len());
+    assert_eq!(ScheduledTransferRunStatus::Succeeded, runs[0].status);
+    assert!(runs[0].transfer_id.is_some());
+}
+
+#[actix_web::test]
+async fn recurring_transfer_moves_to_next_occurrence() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let new_transfer = NewScheduledTransfer {
+        recurrence: Some(TransferRecurrence::Daily),
+        end_at: Some(Utc::now() + Duration::days(7)),
+        ..new_scheduled_transfer(10)
+    };
+    let scheduled = schedule(&new_transfer, &user_token, &client, &app).await;
+
+    assert_eq!(
+        1,
+        execute_due_transfers(app.db(), &settings()).await.unwrap()
+    );
+    assert_eq!(
+        0,
+        execute_due_transfers(app.db(), &settings()).await.unwrap()
+    );
+
+    let updated = get_scheduled_transfer(scheduled.id, &user_token, &client, &app).await;
+    assert_eq!(ScheduledTransferStatus::Active, updated.status);
+    assert_eq!(1, updated.occurrences);
+    assert_eq!(scheduled.start_at + Duration::days(1), updated.next_run_at);
+    assert_eq!(90, balance(1, &user_token, &client, &app).await);
+}
+
+#[actix_web::test]
+async fn failed_scheduled_transfer_is_recorded() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    // More than the account has
+    let scheduled = schedule(&new_scheduled_transfer(1000), &user_token, &client, &app).await;
+    assert_eq!(
+        1,
+        execute_due_transfers(app.db(), &settings()).await.unwrap()
+    );
+
+    assert_eq!(100, balance(1, &user_token, &client, &app).await);
+    let scheduled = get_scheduled_transfer(scheduled.id, &user_token, &client, &app).await;
+    assert_eq!(ScheduledTransferStatus::Failed, scheduled.status);
+    let runs = list_runs(scheduled.id, &user_token, &client, &app).await;
+    assert_eq!(1, runs.len());
+    assert_eq!(ScheduledTransferRunStatus::Failed, runs[0].status);
+    assert_eq!(None, runs[0].transfer_id);
+    assert!(runs[0].error.is_some());
+}
+
+#[actix_web::test]
+async fn future_and_cancelled_transfers_do_not_run() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let future = NewScheduledTransfer {
+        start_at: Utc::now() + Duration::hours(1),
+        ..new_scheduled_transfer(10)
+    };
+    schedule(&future, &user_token, &client, &app).await;
+
+    let cancelled = schedule(&new_scheduled_transfer(10), &user_token, &client, &app).await;
+    let url = format!(
+        "{}/api/users/1/scheduled-transfers/{}",
+        app.address(),
+        cancelled.id
+    );
+    let response = client
+        .delete(&url)
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    // Cancelled transfers can no longer be changed
+    let response = client
+        .patch(&url)
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({ "amount": 20 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    assert_eq!(
+        0,
+        execute_due_transfers(app.db(), &settings()).await.unwrap()
+    );
+    assert_eq!(100, balance(1, &user_token, &client, &app).await);
+}
+
+#[actix_web::test]
+async fn invalid_scheduled_transfers_are_rejected() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let url = format!("{}/api/users/1/scheduled-transfers", app.address());
+
+    // Ends before it starts
+    let new_transfer = NewScheduledTransfer {
+        recurrence: Some(TransferRecurrence::Weekly),
+        end_at: Some(Utc::now() - Duration::days(1)),
+        ..new_scheduled_transfer(10)
+    };
+    let response = client
+        .post(&url)
+        .bearer_auth(&user_token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // From someone else's account
+    let new_transfer = NewScheduledTransfer {
+        from_account: 3,
+        to_account: 1,
+        ..new_scheduled_transfer(10)
+    };
+    let response = client
+        .post(&url)
+        .bearer_auth(&user_token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
+
+#[actix_web::test]
+async fn concurrent_workers_run_each_transfer_once() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    for _ in 0..10 {
+        schedule(&new_scheduled_transfer(1), &user_token, &client, &app).await;
+    }
+
+    let settings = settings();
+    let workers = (0..3).map(|_| execute_due_transfers(app.db(), &settings));
+    let executed: usize = futures::future::join_all(workers)
+        .await
+        .into_iter()
+        .map(Result::unwrap)
+        .sum();
+
+    assert_eq!(10, executed);
+    assert_eq!(90, balance(1, &user_token, &client, &app).await);
+    assert_eq!(510, balance(2, &user_token, &client, &app).await);
+}
@@ -0,0 +1,156 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::{
+    infra::security::jwt::Claims,
+    model::session_model::{RefreshRequest, TokenResponse},
+    worker::token_revocation::sync_revoked_tokens,
+};
+use chrono::Utc;
+use reqwest::{Client, Response};
+
+async fn refresh(refresh_token: &str, client: &Client, app: &TestApp) -> Response {
+    client
+        .post(format!("{}/token/refresh", app.address()))
+        .json(&RefreshRequest {
+            refresh_token: refresh_token.to_string(),
+        })
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn verify(access_token: &str, client: &Client, app: &TestApp) -> StatusCode {
+    client
+        .get(format!("{}/verify", app.address()))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+#[actix_web::test]
+async fn refresh_tokens_rotate_and_reuse_revokes_the_session() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let first = rest::sign_in(&app, "user", "user").await;
+
+    let response = refresh(&first.refresh_token, &client, &app).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let second: TokenResponse = response.json().await.unwrap();
+    assert_ne!(first.refresh_token, second.refresh_token);
+    assert_ne!(first.access_token, second.access_token);
+    let claims: Claims = client
+        .get(format!("{}/verify", app.address()))
+        .bearer_auth(&second.access_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(1, claims.id());
+    assert!(claims.session_id().is_some());
+
+    // Using the first refresh token again revokes everything issued in the session
+    let response = refresh(&first.refresh_token, &client, &app).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let response = refresh(&second.refresh_token, &client, &app).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        verify(&first.access_token, &client, &app).await
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        verify(&second.access_token, &client, &app).await
+    );
+
+    let response = refresh("not a token", &client, &app).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+}
+
+#[actix_web::test]
+async fn logout_revokes_the_session() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let tokens = rest::sign_in(&app, "user", "user").await;
+    let other = rest::sign_in(&app, "user", "user").await;
+
+    let response = client
+        .post(format!("{}/logout", app.address()))
+        .bearer_auth(&tokens.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let response = client
+        .get(format!("{}/api/users/1/accounts", app.address()))
+        .bearer_auth(&tokens.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let response = refresh(&tokens.refresh_token, &client, &app).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    // Other sessions are not affected
+    assert_eq!(
+        StatusCode::OK,
+        verify(&other.access_token, &client, &app).await
+    );
+}
+
+#[actix_web::test]
+async fn all_sessions_of_a_user_can_be_revoked() {
+    let app = spawn_test_app().await;
+    let client = Client::new();
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let first = rest::sign_in(&app, "user", "user").await;
+    let second = rest::sign_in(&app, "user", "user").await;
+
+    let response = client
+        .delete(format!("{}/api/users/2/sessions", app.address()))
+        .bearer_auth(&first.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let response = client
+        .delete(format!("{}/api/users/1/sessions", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    for tokens in [&first, &second] {
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            verify(&tokens.access_token, &client, &app).await
+        );
+        let response = refresh(&tokens.refresh_token, &client, &app).await;
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    }
+    assert_eq!(StatusCode::OK, verify(&admin_token, &client, &app).await);
+
+    // Tokens revoked elsewhere are picked up from the database
+    let third = rest::sign_in(&app, "user", "user").await;
+    sqlx::query!(
+        "UPDATE access_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE revoked_at IS NULL"
+    )
+    .execute(app.db())
+    .await
+    .unwrap();
+    assert_eq!(
+        StatusCode::OK,
+        verify(&third.access_token, &client, &app).await
+    );
+    sync_revoked_tokens(app.db(), Utc::now()).await.unwrap();
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        verify(&third.access_token, &client, &app).await
+    );
+}
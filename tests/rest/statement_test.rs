@@ -0,0 +1,192 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::model::{
+    account_model::Deposit, ledger_model::LedgerEntryKind, money::Amount,
+    statement_model::Statement, transfer_model::NewTransfer,
+};
+use chrono::{Duration, Utc};
+use reqwest::Client;
+
+async fn move_money(token: &str, client: &Client, app: &TestApp) {
+    let response = client
+        .post(format!("{}/api/users/1/accounts/1/deposits", app.address()))
+        .bearer_auth(token)
+        .json(&Deposit::new(Amount::new(50).unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let response = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(token)
+        .json(&NewTransfer {
+            from_account: 1,
+            to_account: 2,
+            amount: Amount::new(30).unwrap(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+}
+
+#[actix_web::test]
+async fn statement_lists_movements_between_balances() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    move_money(&user_token, &client, &app).await;
+
+    let today = Utc::now().date_naive();
+    let statement: Statement = client
+        .get(format!(
+            "{}/api/users/1/accounts/1/statement?from={}&to={}",
+            app.address(),
+            today,
+            today
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(120, statement.closing_balance);
+    assert_eq!(
+        statement.closing_balance,
+        statement.opening_balance + statement.lines.iter().map(|l| l.amount).sum::<i64>()
+    );
+    let last = &statement.lines[statement.lines.len() - 2..];
+    assert_eq!(LedgerEntryKind::Deposit, last[0].kind);
+    assert_eq!(50, last[0].amount);
+    assert_eq!(LedgerEntryKind::Transfer, last[1].kind);
+    assert_eq!(-30, last[1].amount);
+    assert_eq!(Some(2), last[1].counterparty_account);
+
+    // Nothing happened before the account existed
+    let yesterday = today - Duration::days(1);
+    let statement: Statement = client
+        .get(format!(
+            "{}/api/users/1/accounts/1/statement?from={}&to={}",
+            app.address(),
+            yesterday,
+            yesterday
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(0, statement.opening_balance);
+    assert_eq!(0, statement.closing_balance);
+    assert!(statement.lines.is_empty());
+}
+
+#[actix_web::test]
+async fn statement_format_follows_query_or_accept_header() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    move_money(&user_token, &client, &app).await;
+
+    let today = Utc::now().date_naive();
+    let url = format!(
+        "{}/api/users/1/accounts/1/statement?from={}&to={}",
+        app.address(),
+        today,
+        today
+    );
+
+    let response = client
+        .get(&url)
+        .bearer_auth(&user_token)
+        .header("Accept", "text/csv")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        "text/csv; charset=utf-8",
+        response.headers()["Content-Type"].to_str().unwrap()
+    );
+    let csv = response.text().await.unwrap();
+    let rows: Vec<&str> = csv.lines().collect();
+    assert!(rows[0].starts_with("booked_at,kind,"));
+    assert!(rows[1].starts_with(&format!("{},OpeningBalance,", today)));
+    assert!(rows[rows.len() - 2].contains("Transfer to account 2"));
+    assert_eq!(
+        format!("{},ClosingBalance,,,,,1.20,NOK", today),
+        rows[rows.len() - 1]
+    );
+
+    // The query wins over the header
+    let response = client
+        .get(format!("{}&format=camt053", url))
+        .bearer_auth(&user_token)
+        .header("Accept", "text/csv")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        "application/xml",
+        response.headers()["Content-Type"].to_str().unwrap()
+    );
+    let xml = response.text().await.unwrap();
+    assert!(xml.contains("urn:iso:std:iso:20022:tech:xsd:camt.053.001.02"));
+    assert!(xml.contains(
+        r#"<Cd>CLBD</Cd></CdOrPrtry></Tp><Amt Ccy="NOK">1.20</Amt><CdtDbtInd>CRDT</CdtDbtInd>"#
+    ));
+
+    let response = client
+        .get(&url)
+        .bearer_auth(&user_token)
+        .header("Accept", "application/x-ofx")
+        .send()
+        .await
+        .unwrap();
+    let ofx = response.text().await.unwrap();
+    assert!(ofx.contains("<LEDGERBAL><BALAMT>1.20</BALAMT>"));
+}
+
+#[actix_web::test]
+async fn invalid_statement_requests_are_rejected() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let today = Utc::now().date_naive();
+
+    // Ends before it starts
+    let response = client
+        .get(format!(
+            "{}/api/users/1/accounts/1/statement?from={}&to={}",
+            app.address(),
+            today,
+            today - Duration::days(1)
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // Someone else's account
+    let response = client
+        .get(format!(
+            "{}/api/users/1/accounts/3/statement?from={}&to={}",
+            app.address(),
+            today,
+            today
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
@@ -0,0 +1,178 @@
+use crate::{
+    common::{spawn_test_app, TestApp},
+    rest,
+};
+use actix_http::StatusCode;
+use actix_web_demo::model::{
+    account_model::Account,
+    money::Amount,
+    transfer_batch_model::{
+        NewTransferBatch, TransferBatchItemStatus, TransferBatchMode, TransferBatchReport,
+        TransferBatchStatus,
+    },
+    transfer_model::NewTransfer,
+};
+use reqwest::Client;
+
+fn transfer(from_account: i32, to_account: i32, amount: i64) -> NewTransfer {
+    NewTransfer {
+        from_account,
+        to_account,
+        amount: Amount::new(amount).unwrap(),
+    }
+}
+
+async fn submit(
+    batch: &NewTransferBatch,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> TransferBatchReport {
+    let response = client
+        .post(format!("{}/api/users/1/transfer-batches", app.address()))
+        .bearer_auth(token)
+        .json(batch)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+    response.json().await.unwrap()
+}
+
+async fn balance(account_id: i32, token: &str, client: &Client, app: &TestApp) -> i64 {
+    let account: Account = client
+        .get(format!(
+            "{}/api/users/1/accounts/{}",
+            app.address(),
+            account_id
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    account.balance()
+}
+
+fn statuses(report: &TransferBatchReport) -> Vec<TransferBatchItemStatus> {
+    report.items.iter().map(|i| i.status).collect()
+}
+
+#[actix_web::test]
+async fn atomic_batch_executes_all_transfers() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let batch = NewTransferBatch {
+        mode: TransferBatchMode::Atomic,
+        transfers: vec![transfer(1, 2, 30), transfer(2, 3, 40)],
+    };
+    let report = submit(&batch, &user_token, &client, &app).await;
+    assert_eq!(TransferBatchStatus::Succeeded, report.batch.status);
+    assert_eq!(
+        vec![TransferBatchItemStatus::Succeeded; 2],
+        statuses(&report)
+    );
+    assert!(report.items.iter().all(|i| i.transfer_id.is_some()));
+    assert_eq!(70, balance(1, &user_token, &client, &app).await);
+    assert_eq!(490, balance(2, &user_token, &client, &app).await);
+
+    // The batch can be looked up later
+    let fetched: TransferBatchReport = client
+        .get(format!(
+            "{}/api/users/1/transfer-batches/{}",
+            app.address(),
+            report.batch.id
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report, fetched);
+
+    // But not by other users
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let response = client
+        .get(format!(
+            "{}/api/users/2/transfer-batches/{}",
+            app.address(),
+            report.batch.id
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
+
+#[actix_web::test]
+async fn atomic_batch_rolls_back_on_failure() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let batch = NewTransferBatch {
+        mode: TransferBatchMode::Atomic,
+        transfers: vec![transfer(1, 2, 30), transfer(1, 2, 1000), transfer(1, 2, 10)],
+    };
+    let report = submit(&batch, &user_token, &client, &app).await;
+    assert_eq!(TransferBatchStatus::Failed, report.batch.status);
+    assert_eq!(
+        vec![
+            TransferBatchItemStatus::NotExecuted,
+            TransferBatchItemStatus::Failed,
+            TransferBatchItemStatus::NotExecuted,
+        ],
+        statuses(&report)
+    );
+    assert!(report.items[1].error.is_some());
+    assert!(report.items.iter().all(|i| i.transfer_id.is_none()));
+    assert_eq!(100, balance(1, &user_token, &client, &app).await);
+    assert_eq!(500, balance(2, &user_token, &client, &app).await);
+}
+
+#[actix_web::test]
+async fn best_effort_batch_reports_each_transfer() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let batch = NewTransferBatch {
+        mode: TransferBatchMode::BestEffort,
+        transfers: vec![transfer(1, 2, 30), transfer(1, 2, 1000), transfer(1, 2, 10)],
+    };
+    let report = submit(&batch, &user_token, &client, &app).await;
+    assert_eq!(TransferBatchStatus::PartiallySucceeded, report.batch.status);
+    assert_eq!(
+        vec![
+            TransferBatchItemStatus::Succeeded,
+            TransferBatchItemStatus::Failed,
+            TransferBatchItemStatus::Succeeded,
+        ],
+        statuses(&report)
+    );
+    assert_eq!(60, balance(1, &user_token, &client, &app).await);
+    assert_eq!(540, balance(2, &user_token, &client, &app).await);
+}
+
+#[actix_web::test]
+async fn empty_batch_gives_400() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let response = client
+        .post(format!("{}/api/users/1/transfer-batches", app.address()))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({ "transfers": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
@@ -1,5 +1,11 @@
 use actix_http::StatusCode;
-use actix_web_demo::model::{account_model::Account, transfer_model::NewTransfer};
+use actix_web_demo::model::{
+    account_model::{Account, NewAccount},
+    limit_model::AccountLimits,
+    money::Amount,
+    pagination::CursorPage,
+    transfer_model::{NewTransfer, Transfer},
+};
 use reqwest::Client;
 
 use crate::{
@@ -16,7 +22,7 @@ async fn user_can_transfer_between_own_accounts() {
     let new_transfer = NewTransfer {
         from_account: 1,
         to_account: 2,
-        amount: 50,
+        amount: Amount::new(50).unwrap(),
     };
 
     let old_from = get_account(1, new_transfer.from_account, &user_token, &client, &app).await;
@@ -35,11 +41,11 @@ async fn user_can_transfer_between_own_accounts() {
     let new_to = get_account(1, new_transfer.to_account, &user_token, &client, &app).await;
 
     assert_eq!(
-        old_from.balance() - new_transfer.amount as i64,
+        old_from.balance() - new_transfer.amount.minor_units(),
         new_from.balance()
     );
     assert_eq!(
-        old_to.balance() + new_transfer.amount as i64,
+        old_to.balance() + new_transfer.amount.minor_units(),
         new_to.balance()
     );
 }
@@ -54,7 +60,7 @@ async fn user_can_transfer_to_non_owned_account() {
     let new_transfer = NewTransfer {
         from_account: 1,
         to_account: 3,
-        amount: 50,
+        amount: Amount::new(50).unwrap(),
     };
 
     let old_from = get_account(1, new_transfer.from_account, &user_token, &client, &app).await;
@@ -73,11 +79,11 @@ async fn user_can_transfer_to_non_owned_account() {
     let new_to = get_account(2, new_transfer.to_account, &admin_token, &client, &app).await;
 
     assert_eq!(
-        old_from.balance() - new_transfer.amount as i64,
+        old_from.balance() - new_transfer.amount.minor_units(),
         new_from.balance()
     );
     assert_eq!(
-        old_to.balance() + new_transfer.amount as i64,
+        old_to.balance() + new_transfer.amount.minor_units(),
         new_to.balance()
     );
 }
@@ -91,7 +97,7 @@ async fn user_cannot_transfer_from_non_owned_account() {
     let new_transfer = NewTransfer {
         from_account: 3,
         to_account: 1,
-        amount: 50,
+        amount: Amount::new(50).unwrap(),
     };
 
     let response = client
@@ -113,7 +119,55 @@ async fn admin_can_transfer_from_non_owned_account() {
     let new_transfer = NewTransfer {
         from_account: 1,
         to_account: 3,
-        amount: 50,
+        amount: Amount::new(50).unwrap(),
+    };
+
+    let response = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&user_token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+}
+
+#[actix_web::test]
+async fn transfer_to_missing_account_gives_404() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let new_transfer = NewTransfer {
+        from_account: 1,
+        to_account: 0,
+        amount: Amount::new(50).unwrap(),
+    };
+
+    let response = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&user_token)
+        .json(&new_transfer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    // Nothing was withdrawn
+    let account = get_account(1, 1, &user_token, &client, &app).await;
+    assert_eq!(100, account.balance());
+}
+
+#[actix_web::test]
+async fn transfer_to_the_same_account_gives_400() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let new_transfer = NewTransfer {
+        from_account: 1,
+        to_account: 1,
+        amount: Amount::new(50).unwrap(),
     };
 
     let response = client
@@ -123,7 +177,380 @@ async fn admin_can_transfer_from_non_owned_account() {
         .send()
         .await
         .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let account = get_account(1, 1, &user_token, &client, &app).await;
+    assert_eq!(100, account.balance());
+}
+
+#[actix_web::test]
+async fn concurrent_transfers_keep_balances_consistent() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    let old_first = get_account(1, 1, &user_token, &client, &app).await;
+    let old_second = get_account(1, 2, &user_token, &client, &app).await;
+
+    // Send transfers both ways at the same time, more than the first account can cover
+    let amount = 30;
+    let requests = (0..20).map(|i| {
+        let (from_account, to_account) = if i % 4 == 0 { (2, 1) } else { (1, 2) };
+        client
+            .post(format!("{}/api/users/1/transfers", app.address()))
+            .bearer_auth(&user_token)
+            .json(&NewTransfer {
+                from_account,
+                to_account,
+                amount: Amount::new(amount).unwrap(),
+            })
+            .send()
+    });
+    let responses = futures::future::join_all(requests).await;
+
+    // Every transfer either succeeds or is rejected, none fail with deadlocks
+    let mut moved_to_second = 0;
+    for (i, response) in responses.into_iter().enumerate() {
+        let status = response.unwrap().status();
+        assert!(
+            status == StatusCode::CREATED || status == StatusCode::BAD_REQUEST,
+            "unexpected status {}",
+            status
+        );
+        if status == StatusCode::CREATED {
+            let delta = amount;
+            moved_to_second += if i % 4 == 0 { -delta } else { delta };
+        }
+    }
+
+    let new_first = get_account(1, 1, &user_token, &client, &app).await;
+    let new_second = get_account(1, 2, &user_token, &client, &app).await;
+    assert!(new_first.balance() >= 0);
+    assert!(new_second.balance() >= 0);
+    assert_eq!(old_first.balance() - moved_to_second, new_first.balance());
+    assert_eq!(old_second.balance() + moved_to_second, new_second.balance());
+}
+
+#[actix_web::test]
+async fn list_transfers_gives_transfers_touching_own_accounts() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+
+    // Both seeded transfers touch the user's accounts, newest first
+    let transfers = list_transfers(1, "", &user_token, &client, &app).await;
+    assert_eq!(vec![2, 1], ids(&transfers));
+    assert_eq!(None, transfers.next_cursor);
+
+    // Filter by account and direction
+    let transfers = list_transfers(
+        1,
+        "account=2&direction=outgoing",
+        &user_token,
+        &client,
+        &app,
+    )
+    .await;
+    assert_eq!(vec![2], ids(&transfers));
+    let transfers = list_transfers(
+        1,
+        "account=2&direction=incoming",
+        &user_token,
+        &client,
+        &app,
+    )
+    .await;
+    assert_eq!(vec![1], ids(&transfers));
+
+    // Filter by amount and time
+    let transfers = list_transfers(1, "min_amount=150", &user_token, &client, &app).await;
+    assert_eq!(vec![1], ids(&transfers));
+    let transfers = list_transfers(
+        1,
+        "created_before=2000-01-01T00:00:00Z",
+        &user_token,
+        &client,
+        &app,
+    )
+    .await;
+    assert!(transfers.items.is_empty());
+
+    // Page through the transfers
+    let transfers = list_transfers(1, "limit=1", &user_token, &client, &app).await;
+    assert_eq!(vec![2], ids(&transfers));
+    assert_eq!(Some(2), transfers.next_cursor);
+    let transfers = list_transfers(1, "limit=1&cursor=2", &user_token, &client, &app).await;
+    assert_eq!(vec![1], ids(&transfers));
+
+    // The admin only owns the destination of one of them, but can see both
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+    let transfers = list_transfers(2, "", &admin_token, &client, &app).await;
+    assert_eq!(vec![2, 1], ids(&transfers));
+    let transfers = list_transfers(2, "direction=incoming", &admin_token, &client, &app).await;
+    assert_eq!(vec![2, 1], ids(&transfers));
+}
+
+#[actix_web::test]
+async fn user_cannot_see_transfers_between_other_accounts() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    // Make a transfer between two of the admin's accounts
+    let account: Account = client
+        .post(format!("{}/api/users/2/accounts", app.address()))
+        .bearer_auth(&admin_token)
+        .json(&NewAccount::new("other".to_string()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let transfer: Transfer = client
+        .post(format!("{}/api/users/2/transfers", app.address()))
+        .bearer_auth(&admin_token)
+        .json(&NewTransfer {
+            from_account: 3,
+            to_account: account.id(),
+            amount: Amount::new(10).unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    // The user can see their own transfers, but not the admin's
+    let response = client
+        .get(format!("{}/api/users/1/transfers/1", app.address()))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let response = client
+        .get(format!(
+            "{}/api/users/1/transfers/{}",
+            app.address(),
+            transfer.id
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    // Admins can see everything
+    let response = client
+        .get(format!(
+            "{}/api/users/1/transfers/{}",
+            app.address(),
+            transfer.id
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let transfers = list_transfers(1, "", &user_token, &client, &app).await;
+    assert!(!ids(&transfers).contains(&transfer.id));
+    let transfers = list_transfers(1, "", &admin_token, &client, &app).await;
+    assert!(ids(&transfers).contains(&transfer.id));
+}
+
+#[actix_web::test]
+async fn admin_can_reverse_transfer_once() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    let old_from = get_account(1, 1, &user_token, &client, &app).await;
+    let old_to = get_account(1, 2, &user_token, &client, &app).await;
+    let transfer: Transfer = client
+        .post(format!("{}/api/users/1/transfers", app.address()))
+        .bearer_auth(&user_token)
+        .json(&NewTransfer {
+            from_account: 1,
+            to_account: 2,
+            amount: Amount::new(50).unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let url = format!("{}/api/transfers/{}/reversal", app.address(), transfer.id);
+
+    // Users cannot reverse transfers
+    let response = client
+        .post(&url)
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let response = client
+        .post(&url)
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
     assert_eq!(StatusCode::CREATED, response.status());
+    let reversal: Transfer = response.json().await.unwrap();
+    assert_eq!(Some(transfer.id), reversal.reversal_of);
+    assert_eq!(
+        (2, 1, 50),
+        (reversal.from_account, reversal.to_account, reversal.amount)
+    );
+
+    // Balances are restored and the original links to its reversal
+    let new_from = get_account(1, 1, &user_token, &client, &app).await;
+    let new_to = get_account(1, 2, &user_token, &client, &app).await;
+    assert_eq!(old_from.balance(), new_from.balance());
+    assert_eq!(old_to.balance(), new_to.balance());
+    let original: Transfer = client
+        .get(format!(
+            "{}/api/users/1/transfers/{}",
+            app.address(),
+            transfer.id
+        ))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(Some(reversal.id), original.reversed_by);
+
+    // Neither can be reversed again
+    let response = client
+        .post(&url)
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CONFLICT, response.status());
+    let response = client
+        .post(format!(
+            "{}/api/transfers/{}/reversal",
+            app.address(),
+            reversal.id
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[actix_web::test]
+async fn reversal_fails_if_destination_lacks_funds() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+    let user_token = rest::authenticate(&app, "user", "user").await;
+    let admin_token = rest::authenticate(&app, "admin", "admin").await;
+
+    // Spend most of what the seeded transfer of 200 sent to account 2
+    let response = client
+        .post(format!(
+            "{}/api/users/1/accounts/2/withdrawals",
+            app.address()
+        ))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({ "amount": 450 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = client
+        .post(format!("{}/api/transfers/1/reversal", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    assert_eq!(
+        100,
+        get_account(1, 1, &user_token, &client, &app)
+            .await
+            .balance()
+    );
+    assert_eq!(
+        50,
+        get_account(1, 2, &user_token, &client, &app)
+            .await
+            .balance()
+    );
+
+    // An overdraft limit does not make up for the missing money
+    let response = client
+        .put(format!("{}/api/accounts/2/limits", app.address()))
+        .bearer_auth(&admin_token)
+        .json(&AccountLimits {
+            overdraft_limit: 1000,
+            ..AccountLimits::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let response = client
+        .post(format!("{}/api/transfers/1/reversal", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    assert_eq!(
+        50,
+        get_account(1, 2, &user_token, &client, &app)
+            .await
+            .balance()
+    );
+
+    // Missing transfers cannot be reversed
+    let response = client
+        .post(format!("{}/api/transfers/1000/reversal", app.address()))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
+
+async fn list_transfers(
+    user_id: i32,
+    query: &str,
+    token: &str,
+    client: &Client,
+    app: &TestApp,
+) -> CursorPage<Transfer> {
+    client
+        .get(format!(
+            "{}/api/users/{}/transfers?{}",
+            app.address(),
+            user_id,
+            query
+        ))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+fn ids(transfers: &CursorPage<Transfer>) -> Vec<i32> {
+    transfers.items.iter().map(|t| t.id).collect()
 }
 
 async fn get_account(
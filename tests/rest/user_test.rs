@@ -1,6 +1,9 @@
 use crate::{common::spawn_test_app, rest};
 use actix_http::StatusCode;
-use actix_web_demo::model::user_model::{NewUser, User};
+use actix_web_demo::model::{
+    account_model::NewAccount,
+    user_model::{NewUser, PasswordChange, User, UserUpdate},
+};
 
 #[actix_web::test]
 async fn creating_user_adds_it_to_db() {
@@ -11,7 +14,7 @@ async fn creating_user_adds_it_to_db() {
 
     let new_user = NewUser {
         name: "foo".to_string(),
-        password: "bar".to_string(),
+        password: "bar baz qux".to_string(),
     };
 
     let response = client
@@ -35,10 +38,157 @@ async fn creating_user_adds_it_to_db() {
     // Log in
     let response = client
         .post(format!("{}/token", app.address()))
-        .basic_auth("foo", Some("bar"))
+        .basic_auth("foo", Some("bar baz qux"))
         .json(&new_user)
         .send()
         .await
         .unwrap();
     assert_eq!(StatusCode::CREATED, response.status());
 }
+
+#[actix_web::test]
+async fn users_can_register_and_manage_themselves() {
+    let app = spawn_test_app().await;
+    let client = reqwest::Client::new();
+
+    let new_user = NewUser {
+        name: "alice".to_string(),
+        password: "rabbit hole".to_string(),
+    };
+    let response = client
+        .post(format!("{}/register", app.address()))
+        .json(&new_user)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body.get("password").is_none());
+    let user_id = body["id"].as_i64().unwrap();
+    let token = rest::authenticate(&app, "alice", "rabbit hole").await;
+
+    // Registered users can use the API as themselves
+    let response = client
+        .patch(format!("{}/api/users/{}", app.address(), user_id))
+        .bearer_auth(&token)
+        .json(&UserUpdate {
+            full_name: Some("Alice Liddell".to_string()),
+            email: Some("alice@example.com".to_string()),
+            ..UserUpdate::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let user: User = response.json().await.unwrap();
+    assert_eq!(
+        (Some("Alice Liddell"), Some("alice@example.com")),
+        (user.full_name.as_deref(), user.email.as_deref())
+    );
+    let response = client
+        .patch(format!("{}/api/users/1", app.address()))
+        .bearer_auth(&token)
+        .json(&UserUpdate::default())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let response = client
+        .patch(format!("{}/api/users/{}", app.address(), user_id))
+        .bearer_auth(&token)
+        .json(&UserUpdate {
+            email: Some("not an email".to_string()),
+            ..UserUpdate::default()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    // The current password is required to change it
+    let change = |current: &str| PasswordChange {
+        current_password: current.to_string(),
+        new_password: "looking glass".to_string(),
+    };
+    let response = client
+        .put(format!("{}/api/users/{}/password", app.address(), user_id))
+        .bearer_auth(&token)
+        .json(&change("wrong"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let response = client
+        .put(format!("{}/api/users/{}/password", app.address(), user_id))
+        .bearer_auth(&token)
+        .json(&change("rabbit hole"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let token = rest::authenticate(&app, "alice", "looking glass").await;
+
+    // Open accounts must be closed before the user can be deleted
+    let account: serde_json::Value = client
+        .post(format!("{}/api/users/{}/accounts", app.address(), user_id))
+        .bearer_auth(&token)
+        .json(&NewAccount::new("savings".to_string()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let response = client
+        .delete(format!("{}/api/users/{}", app.address(), user_id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    client
+        .post(format!(
+            "{}/api/users/{}/accounts/{}/close",
+            app.address(),
+            user_id,
+            account["id"]
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    let response = client
+        .delete(format!("{}/api/users/{}", app.address(), user_id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+
+    // The user is anonymised, but their account is kept
+    let row = sqlx::query!(
+        "SELECT name, password, email, deleted_at FROM users WHERE id = $1",
+        user_id as i32
+    )
+    .fetch_one(app.db())
+    .await
+    .unwrap();
+    assert_eq!(format!("deleted-{}", user_id), row.name);
+    assert_eq!(("", None), (row.password.as_str(), row.email));
+    assert!(row.deleted_at.is_some());
+    let owner_id: i32 = sqlx::query_scalar!(
+        "SELECT owner_id FROM accounts WHERE id = $1",
+        account["id"].as_i64().unwrap() as i32
+    )
+    .fetch_one(app.db())
+    .await
+    .unwrap();
+    assert_eq!(user_id as i32, owner_id);
+    let response = client
+        .post(format!("{}/token", app.address()))
+        .basic_auth("alice", Some("looking glass"))
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(StatusCode::CREATED, response.status());
+}